@@ -0,0 +1,100 @@
+//! Reference server for the rain remote cache, small enough to run locally as a stand in for a shared deployment.
+//!
+//! The protocol is plain HTTP with two namespaces, both addressed by lowercase hex sha256 digests:
+//! - `/cas/{digest}` is a content addressed store, the digest is of the body and `PUT` checks it matches
+//! - `/kv/{digest}` is an index from the digest of a cache key to an opaque entry
+//!
+//! Both support `GET`, `HEAD` and `PUT`. Missing entries give `404`.
+
+use std::{
+    io::{ErrorKind, Write as _},
+    path::{Path, PathBuf},
+};
+
+use sha2::Digest as _;
+use tiny_http::{Method, Request, Response, ResponseBox};
+
+pub struct CacheServer {
+    root: PathBuf,
+}
+
+impl CacheServer {
+    pub fn new(root: impl Into<PathBuf>) -> std::io::Result<Self> {
+        let root = root.into();
+        std::fs::create_dir_all(root.join("cas"))?;
+        std::fs::create_dir_all(root.join("kv"))?;
+        Ok(Self { root })
+    }
+
+    /// Handle requests one at a time until the server is unblocked or closed
+    pub fn serve(&self, server: &tiny_http::Server) {
+        for request in server.incoming_requests() {
+            self.handle(request);
+        }
+    }
+
+    pub fn handle(&self, mut request: Request) {
+        log::debug!("{} {}", request.method(), request.url());
+        let response = match self.respond(&mut request) {
+            Ok(response) => response,
+            Err(err) => {
+                log::error!("{} {}: {err}", request.method(), request.url());
+                Response::from_string(err.to_string())
+                    .with_status_code(500)
+                    .boxed()
+            }
+        };
+        if let Err(err) = request.respond(response) {
+            log::error!("could not respond: {err}");
+        }
+    }
+
+    fn respond(&self, request: &mut Request) -> std::io::Result<ResponseBox> {
+        let url = request.url().to_owned();
+        let Some((namespace, digest)) = url.strip_prefix('/').and_then(|url| url.split_once('/'))
+        else {
+            return Ok(status(404));
+        };
+        if !matches!(namespace, "cas" | "kv") || !is_digest(digest) {
+            return Ok(status(404));
+        }
+        let path = self.root.join(namespace).join(digest);
+        match request.method() {
+            Method::Get => match std::fs::File::open(&path) {
+                Ok(f) => Ok(Response::from_file(f).boxed()),
+                Err(err) if err.kind() == ErrorKind::NotFound => Ok(status(404)),
+                Err(err) => Err(err),
+            },
+            Method::Head => Ok(status(if path.exists() { 200 } else { 404 })),
+            Method::Put => {
+                let mut body = Vec::new();
+                request.as_reader().read_to_end(&mut body)?;
+                if namespace == "cas"
+                    && base16::encode_lower(&sha2::Sha256::digest(&body)) != digest
+                {
+                    return Ok(status(400));
+                }
+                write_atomic(&path, &body)?;
+                Ok(status(201))
+            }
+            _ => Ok(status(405)),
+        }
+    }
+}
+
+fn status(code: u16) -> ResponseBox {
+    Response::empty(code).boxed()
+}
+
+fn is_digest(s: &str) -> bool {
+    s.len() == 64 && s.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+/// Write to a temporary file first so a reader never sees a partial entry
+fn write_atomic(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let tmp_path = path.with_extension(format!("tmp-{}", std::process::id()));
+    let mut f = std::fs::File::create(&tmp_path)?;
+    f.write_all(contents)?;
+    f.sync_all()?;
+    std::fs::rename(tmp_path, path)
+}
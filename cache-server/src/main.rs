@@ -0,0 +1,38 @@
+use std::{path::PathBuf, process::ExitCode};
+
+use clap::Parser;
+use env_logger::Env;
+use rain_cache_server::CacheServer;
+
+/// Reference remote cache server for rain
+#[derive(Parser)]
+struct Cli {
+    /// Address to listen on
+    #[arg(long, default_value = "127.0.0.1:7171")]
+    addr: String,
+    /// Directory to store cache entries in
+    #[arg(long)]
+    dir: PathBuf,
+}
+
+fn main() -> ExitCode {
+    env_logger::init_from_env(Env::new().filter_or("RAIN_LOG", "info"));
+    let cli = Cli::parse();
+    let cache_server = match CacheServer::new(&cli.dir) {
+        Ok(cache_server) => cache_server,
+        Err(err) => {
+            log::error!("could not create cache directory {:?}: {err}", cli.dir);
+            return ExitCode::FAILURE;
+        }
+    };
+    let server = match tiny_http::Server::http(&cli.addr) {
+        Ok(server) => server,
+        Err(err) => {
+            log::error!("could not listen on {}: {err}", cli.addr);
+            return ExitCode::FAILURE;
+        }
+    };
+    log::info!("serving cache from {:?} on {}", cli.dir, cli.addr);
+    cache_server.serve(&server);
+    ExitCode::SUCCESS
+}
@@ -39,7 +39,7 @@ impl Runner {
         let root = File::new_checked(driver, root_entry).unwrap();
         let src = driver.read_file(&root).unwrap();
         let module = rain_lang::ast::parser::parse_module(&src);
-        let mut ir = rain_lang::ir::Rir::new();
+        let ir = rain_lang::ir::Rir::new();
         let mid = match ir.insert_module(Some(root), src, module) {
             Ok(mid) => mid,
             Err(err) => {
@@ -55,13 +55,15 @@ impl Runner {
         let mut persistent_cache = self.persistent_cache.plock();
         let cache_core = persistent_cache
             .take()
-            .map(|c| c.depersist(&self.config, &self.cache_stats, &mut ir))
+            .map(|c| c.depersist(&self.config, &self.cache_stats, &ir))
             .unwrap_or_default();
         let cache = Cache {
             core: Arc::new(Mutex::new(cache_core)),
             stats: Arc::clone(&self.cache_stats),
+            remote: None,
+            secrets: Arc::clone(&driver.secrets),
         };
-        let mut runner = rain_lang::runner::Runner::new(&mut ir, &cache, driver);
+        let mut runner = rain_lang::runner::Runner::new(&ir, &cache, driver);
         runner.seal = self.seal;
         info!("Running");
         let res = runner.evaluate_and_call(main, &[]);
@@ -94,8 +96,8 @@ impl Runner {
         let Some(pcache) = persistent_cache.take() else {
             return;
         };
-        let mut ir = rain_lang::ir::Rir::new();
-        let cache = pcache.depersist(&self.config, &self.cache_stats, &mut ir);
+        let ir = rain_lang::ir::Rir::new();
+        let cache = pcache.depersist(&self.config, &self.cache_stats, &ir);
         if let Err(err) = cache.prune_generated_areas(&self.config) {
             error!("prune error: {err:#}");
         }
@@ -5,19 +5,27 @@ use std::fmt::Write as _;
 mod exe;
 mod remote;
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::{
     ffi::OsStr,
     io::{Write as _, stderr, stdin},
+    num::NonZeroUsize,
     process::ExitCode,
+    sync::Mutex,
 };
 
 use clap::{Parser, Subcommand};
 use env_logger::Env;
-use rain_core::{CoreError, config::Config};
+use poison_panic::MutexExt as _;
+use rain_core::{
+    CoreError,
+    config::Config,
+    rain_lang::ir::lint::{Lint, LintConfig, LintLevel},
+};
 use remote::{
     client::{ClientMode, make_request_or_start},
     msg::{
+        cancel::{CancelRequest, Cancelled},
         clean::CleanRequest,
         info::InfoRequest,
         inspect::{InspectRequest, InspectResponse},
@@ -49,13 +57,21 @@ fn fallible_main() -> Result<(), ()> {
 
 #[expect(clippy::unwrap_used)]
 fn rain_ctl_command(config: &Config) -> Result<(), ()> {
-    ctrlc::set_handler(|| {
-        println!("\nCTRL+C pressed");
-        std::process::exit(1);
+    let mode = ClientMode::BackgroundThread;
+    let handler_config = config.clone();
+    ctrlc::set_handler(move || {
+        // The first CTRL+C cancels the current run, if there isn't one or it is pressed again exit straight away
+        let Some(id) = CURRENT_RUN.plock().take() else {
+            println!("\nCTRL+C pressed");
+            std::process::exit(1);
+        };
+        eprintln!("\nCTRL+C pressed, cancelling");
+        if !cancel(&handler_config, id, mode) {
+            std::process::exit(1);
+        }
     })
     .expect("init signal handler");
     let cli = Cli::parse();
-    let mode = ClientMode::BackgroundThread;
     match cli.command {
         RainCtlCommand::Init => {
             let mut f = std::fs::File::create_new("main.rain").unwrap();
@@ -63,12 +79,31 @@ fn rain_ctl_command(config: &Config) -> Result<(), ()> {
             f.flush().unwrap();
             Ok(())
         }
-        RainCtlCommand::Check => run(config, "check", vec![], &cli.options, mode),
-        RainCtlCommand::Build => run(config, "build", vec![], &cli.options, mode),
-        RainCtlCommand::Exec { target, args } => run(
+        RainCtlCommand::Check { jobs } => {
+            run(config, "check", vec![], jobs.get(), &cli.options, mode)
+        }
+        RainCtlCommand::Build { jobs } => {
+            run(config, "build", vec![], jobs.get(), &cli.options, mode)
+        }
+        RainCtlCommand::CheckTypes => check_types(config, &cli.options),
+        RainCtlCommand::Lint { allow, warn, deny } => {
+            let mut levels = LintConfig::default();
+            for (lints, level) in [
+                (allow, LintLevel::Allow),
+                (warn, LintLevel::Warn),
+                (deny, LintLevel::Deny),
+            ] {
+                for lint in lints {
+                    levels.set(lint, level);
+                }
+            }
+            lint(config, &cli.options, &levels)
+        }
+        RainCtlCommand::Exec { target, args, jobs } => run(
             config,
             &target.unwrap_or_default(),
             args,
+            jobs.get(),
             &cli.options,
             mode,
         ),
@@ -118,6 +153,72 @@ fn rain_ctl_command(config: &Config) -> Result<(), ()> {
         }
         RainCtlCommand::Clean => clean(config, mode),
         RainCtlCommand::Prune => prune(config, mode),
+        RainCtlCommand::EncryptSecrets { input, output } => encrypt_secrets(&input, &output),
+    }
+}
+
+/// Run in progress that CTRL+C should cancel
+static CURRENT_RUN: Mutex<Option<uuid::Uuid>> = Mutex::new(None);
+
+fn cancel(config: &Config, id: uuid::Uuid, mode: ClientMode) -> bool {
+    match mode {
+        ClientMode::BackgroundThread => remote::server::cancel_run(id),
+        ClientMode::ForkProcess => {
+            match make_request_or_start(config, CancelRequest { id }, |()| {}, mode) {
+                Ok(Cancelled { found }) => found,
+                Err(err) => {
+                    eprintln!("{err}");
+                    false
+                }
+            }
+        }
+    }
+}
+
+fn report_progress(report: ReportMode, stack: &mut Vec<String>, progress: RunProgress) {
+    match report {
+        ReportMode::Basic => {
+            match progress {
+                RunProgress::Print(s) => eprintln!("{s}"),
+                RunProgress::EnterCall(s) => {
+                    if !s.starts_with("internal.") {
+                        stack.push(s);
+                    }
+                }
+                RunProgress::ExitCall(s) => {
+                    if !s.starts_with("internal.") {
+                        stack.pop();
+                    }
+                }
+                RunProgress::Output { .. } => return,
+            }
+            if let Some(last) = stack.last() {
+                eprintln!("{last}");
+            }
+            let _ = stderr().flush();
+        }
+        ReportMode::Verbose => {
+            match progress {
+                RunProgress::Print(s) => eprintln!("{s}"),
+                RunProgress::EnterCall(s) => {
+                    stack.push(s);
+                }
+                RunProgress::ExitCall(_) => {
+                    stack.pop();
+                }
+                RunProgress::Output { chunk, .. } => {
+                    let mut stderr = stderr();
+                    let _ = stderr.write_all(&chunk);
+                    let _ = stderr.flush();
+                    return;
+                }
+            }
+            if let Some(last) = stack.last() {
+                eprintln!("{last}");
+            }
+            let _ = stderr().flush();
+        }
+        ReportMode::None => {}
     }
 }
 
@@ -125,6 +226,7 @@ fn run(
     config: &Config,
     target: &str,
     args: Vec<String>,
+    jobs: usize,
     options: &GlobalOptions,
     mode: ClientMode,
 ) -> Result<(), ()> {
@@ -136,57 +238,27 @@ fn run(
             .map_err(|()| eprintln!("no main.rain found"))?
     };
     let mut stack = Vec::new();
+    let id = uuid::Uuid::new_v4();
+    *CURRENT_RUN.plock() = Some(id);
     let run_response = make_request_or_start(
         config,
         RunRequest {
+            id,
             root,
             target: target.to_owned(),
             args,
             resolve: options.resolve,
             offline: options.offline,
             seal: options.seal,
+            sandbox: options.sandbox,
             host_override: options.host.clone(),
+            jobs,
         },
-        |im| match options.report {
-            ReportMode::Basic => {
-                match im {
-                    RunProgress::Print(s) => eprintln!("{s}"),
-                    RunProgress::EnterCall(s) => {
-                        if !s.starts_with("internal.") {
-                            stack.push(s);
-                        }
-                    }
-                    RunProgress::ExitCall(s) => {
-                        if !s.starts_with("internal.") {
-                            stack.pop();
-                        }
-                    }
-                }
-                if let Some(last) = stack.last() {
-                    eprintln!("{last}");
-                }
-                let _ = stderr().flush();
-            }
-            ReportMode::Verbose => {
-                match im {
-                    RunProgress::Print(s) => eprintln!("{s}"),
-                    RunProgress::EnterCall(s) => {
-                        stack.push(s);
-                    }
-                    RunProgress::ExitCall(_) => {
-                        stack.pop();
-                    }
-                }
-                if let Some(last) = stack.last() {
-                    eprintln!("{last}");
-                }
-                let _ = stderr().flush();
-            }
-            ReportMode::None => {}
-        },
+        |progress| report_progress(options.report, &mut stack, progress),
         mode,
-    )
-    .map_err(|err| {
+    );
+    CURRENT_RUN.plock().take();
+    let run_response = run_response.map_err(|err| {
         eprintln!("{err}");
     })?;
     let RunResponse {
@@ -229,6 +301,58 @@ fn run(
     }
 }
 
+fn check_types(config: &Config, options: &GlobalOptions) -> Result<(), ()> {
+    let root = if let Some(entrypoint) = &options.entrypoint {
+        entrypoint.clone()
+    } else {
+        rain_core::find_main_rain()
+            .ok_or(())
+            .map_err(|()| eprintln!("no main.rain found"))?
+    };
+    let driver = rain_core::driver::DriverImpl::new(config.clone());
+    let errors = rain_core::check_types(&root, &driver).map_err(|err| {
+        eprintln!("{err}");
+    })?;
+    if errors.is_empty() {
+        eprintln!("✔  No type errors");
+        return Ok(());
+    }
+    let mut stderr = termcolor::StandardStream::stderr(termcolor::ColorChoice::Auto);
+    for err in &errors {
+        err.write_color(&mut stderr).expect("write stderr");
+    }
+    eprintln!("❗ {} type errors", errors.len());
+    Err(())
+}
+
+fn lint(config: &Config, options: &GlobalOptions, levels: &LintConfig) -> Result<(), ()> {
+    let root = if let Some(entrypoint) = &options.entrypoint {
+        entrypoint.clone()
+    } else {
+        rain_core::find_main_rain()
+            .ok_or(())
+            .map_err(|()| eprintln!("no main.rain found"))?
+    };
+    let driver = rain_core::driver::DriverImpl::new(config.clone());
+    let lints = rain_core::lint(&root, &driver, levels).map_err(|err| {
+        eprintln!("{err}");
+    })?;
+    if lints.is_empty() {
+        eprintln!("✔  No lints");
+        return Ok(());
+    }
+    let mut stderr = termcolor::StandardStream::stderr(termcolor::ColorChoice::Auto);
+    for (_, err) in &lints {
+        err.write_color(&mut stderr).expect("write stderr");
+    }
+    let denied = lints
+        .iter()
+        .filter(|(level, _)| *level == LintLevel::Deny)
+        .count();
+    eprintln!("❗ {} warnings, {denied} errors", lints.len() - denied);
+    if denied > 0 { Err(()) } else { Ok(()) }
+}
+
 fn clean(config: &Config, mode: ClientMode) -> Result<(), ()> {
     println!("Will delete:");
     for p in config.clean_directories() {
@@ -277,6 +401,31 @@ fn prune(config: &Config, mode: ClientMode) -> Result<(), ()> {
     Ok(())
 }
 
+fn encrypt_secrets(input: &Path, output: &Path) -> Result<(), ()> {
+    let plaintext = std::fs::read_to_string(input).map_err(|err| {
+        eprintln!("read {}: {err}", input.display());
+    })?;
+    let passphrase = match std::env::var(rain_core::secrets::PASSPHRASE_ENV_VAR) {
+        Ok(passphrase) => passphrase,
+        Err(_) => inquire::Password::new("Passphrase:")
+            .prompt()
+            .map_err(|err| {
+                eprintln!("{err}");
+            })?,
+    };
+    let encrypted = rain_core::secrets::encrypt(&plaintext, &passphrase).map_err(|err| {
+        eprintln!("{err}");
+    })?;
+    std::fs::write(output, encrypted).map_err(|err| {
+        eprintln!("write {}: {err}", output.display());
+    })?;
+    println!(
+        "Encrypted secrets written to {}, set RAIN_SECRETS_ENCRYPTED_FILE to use them",
+        output.display()
+    );
+    Ok(())
+}
+
 #[derive(Debug, Clone, Parser)]
 struct GlobalOptions {
     /// Disable performing actions that require an internet connection and try to use cache more often
@@ -291,6 +440,9 @@ struct GlobalOptions {
     /// Disable escape commands (not a security sandbox)
     #[arg(long, global = true, env = "RAIN_SEAL")]
     seal: bool,
+    /// Run commands in a Linux namespace sandbox that can only read their inputs, only write their output area and has no network unless asked for
+    #[arg(long, global = true, env = "RAIN_SANDBOX")]
+    sandbox: bool,
     /// The reporting mode to use
     #[arg(long, global = true, default_value = "basic")]
     report: ReportMode,
@@ -316,14 +468,39 @@ enum RainCtlCommand {
     Info,
     /// Run checks
     /// Equivalent to `rain exec check`
-    Check,
+    Check {
+        /// Number of threads to evaluate independent expressions on
+        #[arg(long, short, default_value = "1")]
+        jobs: NonZeroUsize,
+    },
     /// Build!
     /// Equivalent to `rain exec build`
-    Build,
+    Build {
+        /// Number of threads to evaluate independent expressions on
+        #[arg(long, short, default_value = "1")]
+        jobs: NonZeroUsize,
+    },
+    /// Type check the entrypoint without running it
+    CheckTypes,
+    /// Lint the entrypoint and the modules it imports
+    Lint {
+        /// Lints to not report
+        #[arg(long, short = 'A')]
+        allow: Vec<Lint>,
+        /// Lints to report as warnings
+        #[arg(long, short = 'W')]
+        warn: Vec<Lint>,
+        /// Lints to report as errors, `rain lint` fails if any are found
+        #[arg(long, short = 'D')]
+        deny: Vec<Lint>,
+    },
     /// Execute a rain function
     Exec {
         target: Option<String>,
         args: Vec<String>,
+        /// Number of threads to evaluate independent expressions on
+        #[arg(long, short, default_value = "1")]
+        jobs: NonZeroUsize,
     },
     /// Stop the rain server process
     Shutdown,
@@ -337,6 +514,8 @@ enum RainCtlCommand {
     Clean,
     /// Prune the rain cache
     Prune,
+    /// Encrypt a TOML file of secrets with a passphrase, the passphrase is read from `RAIN_SECRETS_PASSPHRASE` or prompted for
+    EncryptSecrets { input: PathBuf, output: PathBuf },
 }
 
 #[test]
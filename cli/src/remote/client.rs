@@ -156,6 +156,7 @@ where
                         stream,
                     };
                     let result = client_handler.handle_client();
+                    server.flush_remote_cache();
                     match result {
                         Ok(()) | Err(super::server::Error::GracefulExit) => (),
                         Err(err) => eprintln!("server error: {err:#}"),
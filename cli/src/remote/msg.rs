@@ -39,6 +39,7 @@ pub enum Request {
     Shutdown(shutdown::ShutdownRequest),
     Clean(clean::CleanRequest),
     Prune(prune::PruneRequest),
+    Cancel(cancel::CancelRequest),
 }
 
 pub trait RequestTrait: Into<Request> + private::Sealed {
@@ -53,17 +54,21 @@ mod private {
 pub mod run {
     use std::{path::PathBuf, time::Duration};
 
-    use rain_core::CoreError;
+    use rain_core::{CoreError, rain_lang::driver::OutputStream};
 
     #[derive(Debug, serde::Serialize, serde::Deserialize)]
     pub struct RunRequest {
+        /// Identifies the run so it can be cancelled
+        pub id: uuid::Uuid,
         pub root: PathBuf,
         pub target: String,
         pub args: Vec<String>,
         pub resolve: bool,
         pub offline: bool,
         pub seal: bool,
+        pub sandbox: bool,
         pub host_override: Option<String>,
+        pub jobs: usize,
     }
 
     impl From<RunRequest> for super::Request {
@@ -84,6 +89,12 @@ pub mod run {
         Print(String),
         EnterCall(String),
         ExitCall(String),
+        /// Output of a command run by the call as it is produced
+        Output {
+            call: String,
+            stream: OutputStream,
+            chunk: Vec<u8>,
+        },
     }
 
     #[derive(Debug, serde::Serialize, serde::Deserialize)]
@@ -217,3 +228,29 @@ pub mod prune {
         pub errors: u32,
     }
 }
+
+pub mod cancel {
+    #[derive(Debug, serde::Serialize, serde::Deserialize)]
+    pub struct CancelRequest {
+        pub id: uuid::Uuid,
+    }
+
+    impl From<CancelRequest> for super::Request {
+        fn from(req: CancelRequest) -> Self {
+            Self::Cancel(req)
+        }
+    }
+
+    impl super::private::Sealed for CancelRequest {}
+
+    impl super::RequestTrait for CancelRequest {
+        type Intermediate = ();
+        type Response = Cancelled;
+    }
+
+    #[derive(Debug, serde::Serialize, serde::Deserialize)]
+    pub struct Cancelled {
+        /// Whether the run was still going
+        pub found: bool,
+    }
+}
@@ -4,8 +4,8 @@ use std::{
     collections::HashMap,
     path::Path,
     sync::{
-        Mutex,
-        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
         mpsc::{Receiver, SyncSender, sync_channel},
     },
     time::{Instant, SystemTime},
@@ -24,8 +24,9 @@ use rain_core::{
         afs::{entry::FSEntryTrait as _, file::File},
         driver::FSTrait as _,
         ir::Rir,
-        runner::{Runner, cache::CacheTrait as _, value::Value},
+        runner::{Runner, cache::CacheTrait as _, parallel::WorkerPool, value::Value},
     },
+    secrets::SecretAllowlist,
 };
 
 use crate::remote::msg::{RequestWrapper, RestartReason, prune::Pruned};
@@ -77,30 +78,57 @@ pub fn rain_server(config: Config) -> Result<(), Error> {
     let socket_path = s.config.server_socket_path();
     std::fs::create_dir_all(socket_path.parent().expect("path parent"))?;
     let mut l = ruipc::Listener::bind(socket_path)?;
-    for stream in l.incoming() {
-        match stream {
-            Ok(connection) => {
-                log::info!("got a stream {connection:?}");
-                let result = ClientHandler {
-                    server: &s,
-                    stream: IpcMsgConnection { connection },
+    // Clients are handled concurrently so runs can be cancelled while they are in progress
+    std::thread::scope(|scope| {
+        for stream in l.incoming() {
+            match stream {
+                Ok(connection) => {
+                    log::info!("got a stream {connection:?}");
+                    let s = &s;
+                    scope.spawn(move || {
+                        let result = ClientHandler {
+                            server: s,
+                            stream: IpcMsgConnection { connection },
+                        }
+                        .handle_client();
+                        match result {
+                            Ok(()) => (),
+                            Err(Error::GracefulExit) => {
+                                // Wait for any run that started in the meantime to finish before exiting
+                                let _request_guard = s.request_lock.plock();
+                                s.flush_remote_cache();
+                                std::process::exit(0)
+                            }
+                            Err(err) => log::error!("client error: {err}"),
+                        }
+                    });
                 }
-                .handle_client();
-                match result {
-                    Ok(()) => (),
-                    Err(Error::GracefulExit) => std::process::exit(0),
-                    Err(err) => return Err(err),
+                Err(err) => {
+                    log::error!("unix listener error: {err}");
                 }
             }
-            Err(err) => {
-                log::error!("unix listener error: {err}");
-            }
         }
-    }
+    });
     log::error!("server ended unexpectedly");
     Ok(())
 }
 
+/// Cancellation flags of the runs in progress
+///
+/// This is shared by every server in the process so runs can also be cancelled when the server is running in a background thread of the client
+static RUNNING: Mutex<Vec<(uuid::Uuid, Arc<AtomicBool>)>> = Mutex::new(Vec::new());
+
+/// Cancel a run in progress killing the commands it is running, returns false if there is no such run
+pub fn cancel_run(id: uuid::Uuid) -> bool {
+    let running = RUNNING.plock();
+    let Some((_, cancelled)) = running.iter().find(|(run_id, _)| *run_id == id) else {
+        return false;
+    };
+    log::info!("cancelling run {id}");
+    cancelled.store(true, Ordering::Relaxed);
+    true
+}
+
 pub struct Server {
     config: Config,
     /// Time the rain binary was modified, used to check if we should restart the server if the file on disk is newer
@@ -110,6 +138,8 @@ pub struct Server {
     cache: rain_core::cache::Cache,
     stats: Stats,
     ir: Mutex<Rir>,
+    /// Held while handling a run, clean, prune or shutdown and until the server exits after a graceful exit
+    request_lock: Mutex<()>,
 }
 
 impl Server {
@@ -125,8 +155,13 @@ impl Server {
             cache,
             stats: Stats::default(),
             ir: Mutex::new(ir),
+            request_lock: Mutex::new(()),
         })
     }
+    /// Wait for entries queued for the remote cache to be pushed, call before the process exits
+    pub fn flush_remote_cache(&self) {
+        self.cache.flush_remote();
+    }
 }
 
 #[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
@@ -188,6 +223,18 @@ pub struct ClientHandler<'a, C> {
 impl<C: MsgConnection> ClientHandler<'_, C> {
     pub fn handle_client(mut self) -> Result<(), Error> {
         let RequestWrapper { header, request } = self.stream.receive()?;
+        let request: Result<Request, _> = ciborium::from_reader(std::io::Cursor::new(request));
+        // Cancellations must not wait for the run they are cancelling
+        if let Ok(Request::Cancel(req)) = request {
+            log::info!("Request {req:?}");
+            return self.cancel(req);
+        }
+        // Requests that change the cache are handled one at a time so cleaning or shutting down can't happen in the middle of a run, read only requests don't wait for them
+        let _request_guard = matches!(
+            request,
+            Ok(Request::Run(_) | Request::Clean(_) | Request::Prune(_) | Request::Shutdown(_))
+        )
+        .then(|| self.server.request_lock.plock());
         if header.exe != crate::exe::current_exe().ok_or(Error::CurrentExe)? {
             log::info!("Restarting because exe symlink changed");
             return self.restart();
@@ -201,7 +248,7 @@ impl<C: MsgConnection> ClientHandler<'_, C> {
             return self.restart();
         }
         log::info!("Header {header:?}");
-        let request: Request = ciborium::from_reader(std::io::Cursor::new(request))?;
+        let request = request?;
         log::info!("Request {request:?}");
         self.server
             .stats
@@ -222,12 +269,15 @@ impl<C: MsgConnection> ClientHandler<'_, C> {
             Ok(Err(err)) => Err(err),
             Ok(Ok(())) => {
                 log::info!("cache size {}", self.server.cache.len());
+                // Hold the ir lock while saving so concurrent clients don't write the cache at the same time
+                let ir = self.server.ir.plock();
                 let persistent_cache = PersistCache::persist(
                     &self.server.cache.core.plock(),
                     &self.server.cache.stats,
-                    &self.server.ir.plock(),
+                    &ir,
                 );
                 persistent_cache.save(&self.server.config.cache_json_path())?;
+                drop(ir);
                 log::info!("cache stats {:#?}", self.server.cache.stats);
                 Ok(())
             }
@@ -281,16 +331,23 @@ impl<C: MsgConnection> ClientHandler<'_, C> {
             }
             Request::Clean(req) => self.clean(req),
             Request::Prune(req) => self.prune(req),
+            Request::Cancel(req) => self.cancel(req),
         }
     }
 
+    fn cancel(&mut self, req: super::msg::cancel::CancelRequest) -> Result<(), Error> {
+        let found = cancel_run(req.id);
+        self.send_response(req, &super::msg::cancel::Cancelled { found })?;
+        Ok(())
+    }
+
     fn run(&mut self, req: super::msg::run::RunRequest) -> Result<(), Error> {
         let config = self.server.config.clone();
         let cache = &self.server.cache;
-        let mut ir = self.server.ir.plock();
+        let ir = self.server.ir.plock();
         let s = Mutex::new(self);
         let start = Instant::now();
-        let result = run_inner(&req, config, cache, &s, &mut ir);
+        let result = run_inner(&req, config, cache, &s, &ir);
         let s = s.pinto_inner();
         s.send_response(
             req,
@@ -336,11 +393,17 @@ impl<C: MsgConnection> ClientHandler<'_, C> {
     fn prune(&mut self, req: super::msg::prune::PruneRequest) -> Result<(), Error> {
         let guard = self.server.cache.core.plock();
         let pruned = guard.prune_generated_areas(&self.server.config)?;
+        let mirrors = rain_core::git_mirror::prune(
+            &self.server.config,
+            rain_core::git_mirror::MIRROR_MAX_AGE,
+        )?;
+        let downloads =
+            rain_core::download::prune(&self.server.config, rain_core::download::DOWNLOAD_MAX_AGE)?;
         self.send_response(
             req,
             &Pruned {
-                size: pruned.size,
-                errors: pruned.errors,
+                size: pruned.size + mirrors.size + downloads.size,
+                errors: pruned.errors + mirrors.errors + downloads.errors,
             },
         )?;
         Ok(())
@@ -387,7 +450,7 @@ fn run_inner<C: MsgConnection>(
     config: Config,
     cache: &Cache,
     s: &Mutex<&mut ClientHandler<'_, C>>,
-    ir: &mut Rir,
+    ir: &Rir,
 ) -> Result<String, CoreError> {
     let mut driver = DriverImpl {
         print_handler: Some(Box::new(|m| {
@@ -414,13 +477,39 @@ fn run_inner<C: MsgConnection>(
                 log::error!("send intermediate exit call: {err}");
             }
         })),
+        output_handler: Some(Box::new(|call, stream, chunk| {
+            let send_result = s.plock().send_intermediate(
+                req,
+                &RunProgress::Output {
+                    call: call.to_owned(),
+                    stream,
+                    chunk: chunk.to_vec(),
+                },
+            );
+            if let Err(err) = send_result {
+                log::error!("send intermediate output: {err}");
+            }
+        })),
+        secrets: Arc::clone(&cache.secrets),
         ..DriverImpl::new(config)
     };
+    if let Some(project_dir) = req.root.parent() {
+        driver.secret_allowlist =
+            SecretAllowlist::load(project_dir).map_err(|err| CoreError::Other(err.to_string()))?;
+    }
     if let Some(host_override) = &req.host_override {
         driver.host_triple = host_override.to_owned().into();
     }
-
-    run_core(req, cache, &driver, ir).map(|v| match v {
+    driver.sandbox = req.sandbox;
+
+    // Secrets are looked up again each run in case they have changed
+    driver.secrets.forget_lookups();
+    RUNNING
+        .plock()
+        .push((req.id, Arc::clone(&driver.cancelled)));
+    let result = run_core(req, cache, &driver, ir).map_err(|err| err.redact(&driver.secrets));
+    RUNNING.plock().retain(|(id, _)| *id != req.id);
+    result.map(|v| match v {
         Value::Unit => String::new(),
         Value::Dir(d) if req.resolve => driver.resolve_fs_entry(d.inner()).display().to_string(),
         Value::File(f) if req.resolve => driver.resolve_fs_entry(f.inner()).display().to_string(),
@@ -430,17 +519,20 @@ fn run_inner<C: MsgConnection>(
 
 fn run_core(
     super::msg::run::RunRequest {
+        id: _,
         root,
         target,
         args,
         resolve: _,
         offline,
         seal,
+        sandbox: _,
         host_override: _,
+        jobs,
     }: &super::msg::run::RunRequest,
     cache: &Cache,
     driver: &DriverImpl<'_>,
-    ir: &mut Rir,
+    ir: &Rir,
 ) -> Result<Value, CoreError> {
     let path = root;
     let file = File::new_local(path.as_ref()).map_err(|err| CoreError::Other(err.to_string()))?;
@@ -449,10 +541,11 @@ fn run_core(
     let module = rain_core::rain_lang::ast::parser::parse_module(&src);
     let mut mid = ir
         .insert_module(Some(file), src, module)
-        .map_err(|err| CoreError::LangError(Box::new(err.resolve_ir(ir).into_owned())))?;
+        .map_err(|err| CoreError::LangError(Box::new(err.resolve_ir(ir))))?;
     let mut runner = Runner::new(ir, cache, driver);
     runner.offline = *offline;
     runner.seal = *seal;
+    runner.workers = Arc::new(WorkerPool::new(*jobs));
     let declarations = target.split('.');
     let mut value: Option<Value> = None;
     for declaration in declarations {
@@ -482,9 +575,11 @@ fn run_core(
             }
             return Err(CoreError::UnknownDeclaration(declarations));
         };
-        value = Some(runner.evaluate_and_call(main, args).map_err(|err| {
-            CoreError::LangError(Box::new(err.resolve_ir(runner.ir).into_owned()))
-        })?);
+        value = Some(
+            runner
+                .evaluate_and_call(main, args)
+                .map_err(|err| CoreError::LangError(Box::new(err.resolve_ir(runner.ir))))?,
+        );
     }
     Ok(value.unwrap())
 }
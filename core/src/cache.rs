@@ -1,4 +1,5 @@
 pub mod persistent;
+pub mod remote;
 
 use std::{
     collections::HashSet,
@@ -15,8 +16,14 @@ use lru::LruCache;
 use poison_panic::MutexExt as _;
 use rain_lang::{
     afs::area::{FileArea, GeneratedFileArea},
-    runner::cache::{CacheEntry, CacheKey},
+    runner::{
+        cache::{CacheEntry, CacheKey},
+        dep::Dep,
+    },
 };
+use remote::RemoteCache;
+
+use crate::secrets::SecretStore;
 
 const CACHE_SIZE: NonZeroUsize = NonZeroUsize::new(1024).expect("cache size must be non zero");
 /// Minimum execution time to be stored in the cache
@@ -26,6 +33,10 @@ const EXECUTION_TIME_THRESHOLD: Duration = Duration::from_millis(1);
 pub struct Cache {
     pub core: Arc<Mutex<CacheCore>>,
     pub stats: Arc<CacheStats>,
+    /// Shared cache consulted on a miss and sent entries whose deps can be checked on any machine
+    pub remote: Option<Arc<RemoteCache>>,
+    /// Entries that would leak a secret are never persisted or pushed to the remote
+    pub secrets: Arc<SecretStore>,
 }
 
 impl Cache {
@@ -33,6 +44,76 @@ impl Cache {
         Self {
             core: Arc::new(Mutex::new(core)),
             stats: Arc::default(),
+            remote: None,
+            secrets: Arc::default(),
+        }
+    }
+
+    #[must_use]
+    pub fn with_remote(mut self, remote: Option<RemoteCache>) -> Self {
+        self.remote = remote.map(Arc::new);
+        self
+    }
+
+    #[must_use]
+    pub fn with_secrets(mut self, secrets: Arc<SecretStore>) -> Self {
+        self.secrets = secrets;
+        self
+    }
+
+    fn get_local(&self, key: &CacheKey) -> Option<CacheEntry> {
+        let mut guard = self.core.plock();
+        let entry = guard.storage.get(key)?.clone();
+        if !entry.deps.iter().all(|dep| self.is_dep_current(dep)) {
+            log::debug!("cache entry {key:?} is stale");
+            self.stats.stale.inc();
+            guard.storage.pop(key);
+            return None;
+        }
+        Some(entry)
+    }
+
+    fn get_remote(&self, key: &CacheKey) -> Option<CacheEntry> {
+        let remote = self.remote.as_ref()?;
+        match remote.pull(key) {
+            Ok(Some(entry)) if entry.deps.iter().all(|dep| self.is_dep_current(dep)) => {
+                log::debug!("remote cache hit {key:?}");
+                self.stats.remote_hits.inc();
+                self.core.plock().storage.put(key.clone(), entry.clone());
+                Some(entry)
+            }
+            Ok(_) => None,
+            Err(err) => {
+                log::warn!("remote cache pull failed for {key}: {err}");
+                self.stats.remote_fails.inc();
+                None
+            }
+        }
+    }
+
+    /// Whether a dep recorded in an earlier run still matches the current environment
+    fn is_dep_current(&self, dep: &Dep) -> bool {
+        match dep {
+            Dep::LocalEntry { entry, fingerprint } => {
+                crate::fingerprint::is_current(entry, fingerprint)
+            }
+            Dep::EnvVar { name, value_hash } => {
+                std::env::var(name).ok().as_deref().map(Dep::value_hash) == *value_hash
+            }
+            Dep::Secret { name, value_hash } => self
+                .secrets
+                .lookup(name)
+                .is_ok_and(|secret| secret.is_some_and(|s| Dep::value_hash(&s) == *value_hash)),
+            Dep::Uncacheable | Dep::LocalArea | Dep::Escape | Dep::CallingModule | Dep::Print => {
+                true
+            }
+        }
+    }
+
+    /// Wait for entries queued for the remote cache to be pushed
+    pub fn flush_remote(&self) {
+        if let Some(remote) = &self.remote {
+            remote.flush();
         }
     }
 
@@ -47,8 +128,7 @@ impl Cache {
 
 impl rain_lang::runner::cache::CacheTrait for Cache {
     fn get(&self, key: &CacheKey) -> Option<CacheEntry> {
-        let mut guard = self.core.plock();
-        let res = guard.storage.get(key).cloned();
+        let res = self.get_local(key).or_else(|| self.get_remote(key));
         if let Some(entry) = &res {
             self.stats.hits.inc();
             log::trace!("cache get hit {key:?} {:?}", entry.deps);
@@ -68,9 +148,35 @@ impl rain_lang::runner::cache::CacheTrait for Cache {
             self.stats.put_fails.inc();
             return;
         }
+        let leaks_secret = if entry
+            .deps
+            .iter()
+            .any(|d| matches!(d, Dep::Secret { name, .. } if self.secrets.is_untracked(name)))
+        {
+            log::warn!(
+                "cache entry depends on a secret too short to track, it will not be persisted"
+            );
+            true
+        } else {
+            self.secrets.leaks(key.find_strings()) || self.secrets.leaks(entry.value.find_strings())
+        };
+        if leaks_secret {
+            log::debug!("cache entry leaks a secret, it will not be persisted");
+        }
+        if let Some(remote) = &self.remote {
+            if !leaks_secret && entry.deps.iter().all(Dep::is_portable) {
+                remote.push_in_background(key.clone(), entry.clone(), Arc::clone(&self.stats));
+            }
+        }
         log::trace!("caching {key:?}");
         self.stats.puts.inc();
-        self.core.plock().storage.put(key, entry);
+        let mut core = self.core.plock();
+        if leaks_secret {
+            core.secret_keys.insert(key.clone());
+        } else {
+            core.secret_keys.remove(&key);
+        }
+        core.storage.put(key, entry);
     }
 
     fn put_if_slow(&self, key: CacheKey, entry: CacheEntry) {
@@ -90,7 +196,8 @@ impl rain_lang::runner::cache::CacheTrait for Cache {
             .storage
             .iter()
             .map(|(k, v)| {
-                let mut s = format!("{k} => {:?} {:?}", v.value, v.execution_time);
+                let s = format!("{k} => {:?} {:?}", v.value, v.execution_time);
+                let mut s = self.secrets.redact(&s).into_owned();
                 if s.len() > 200 {
                     s.truncate(197);
                     s.push_str("...");
@@ -101,13 +208,17 @@ impl rain_lang::runner::cache::CacheTrait for Cache {
     }
 
     fn clean(&self) {
-        self.core.plock().storage.clear();
+        let mut core = self.core.plock();
+        core.storage.clear();
+        core.secret_keys.clear();
     }
 }
 
 #[derive(Clone)]
 pub struct CacheCore {
     storage: LruCache<CacheKey, CacheEntry>,
+    /// Keys of entries that contain a secret and must not be persisted
+    secret_keys: HashSet<CacheKey>,
 }
 
 impl Default for CacheCore {
@@ -120,6 +231,7 @@ impl CacheCore {
     pub fn new(cap: NonZeroUsize) -> Self {
         Self {
             storage: LruCache::new(cap),
+            secret_keys: HashSet::new(),
         }
     }
 
@@ -186,12 +298,17 @@ impl CacheCore {
 pub struct CacheStats {
     pub hits: Counter,
     pub misses: Counter,
+    /// Entries dropped because a local file, environment variable or secret they depend on changed
+    pub stale: Counter,
     pub puts: Counter,
     pub put_fails: Counter,
     pub depersists: Counter,
     pub depersist_fails: Counter,
     pub persists: Counter,
     pub persist_fails: Counter,
+    pub remote_hits: Counter,
+    pub remote_pushes: Counter,
+    pub remote_fails: Counter,
 }
 
 #[derive(Default)]
@@ -209,7 +326,7 @@ impl std::fmt::Debug for Counter {
     }
 }
 
-fn remove_recursive(path: &Path) -> std::io::Result<u64> {
+pub(crate) fn remove_recursive(path: &Path) -> std::io::Result<u64> {
     let metadata = std::fs::symlink_metadata(path)?;
     let filetype = metadata.file_type();
     if filetype.is_symlink() {
@@ -9,18 +9,19 @@ use rain_lang::{
         entry::{FSEntry, FSEntryTrait as _},
         file::File,
     },
+    driver::DownloadAuth,
     ir::Rir,
     runner::{
         cache::{CacheEntry, CacheKey},
         dep_list::DepList,
         internal::InternalFunction,
-        value::{RainInteger, RainList, RainRecord, RainTypeId, Value},
+        value::{RainFloat, RainInteger, RainList, RainRecord, RainTypeId, Value},
     },
 };
 
 use crate::config::Config;
 
-pub const FORMAT_VERSION: u64 = 3;
+pub const FORMAT_VERSION: u64 = 7;
 
 #[derive(Debug, thiserror::Error)]
 pub enum PersistCacheError {
@@ -83,12 +84,17 @@ impl PersistCache {
             .storage
             .iter()
             .filter_map(|(k, e)| {
-                let Some(k) = PersistCacheKey::persist(k, rir) else {
+                if cache.secret_keys.contains(k) {
+                    log::debug!("not persisting cache entry that contains a secret");
+                    stats.persist_fails.inc();
+                    return None;
+                }
+                let Some(k) = PersistCacheKey::persist(k, Some(rir)) else {
                     log::debug!("could not persist cache key {k:?}");
                     stats.persist_fails.inc();
                     return None;
                 };
-                let Some(e) = PersistCacheEntry::persist(e, rir) else {
+                let Some(e) = PersistCacheEntry::persist(e, Some(rir)) else {
                     log::debug!("could not persist cache entry {e:?}");
                     stats.persist_fails.inc();
                     return None;
@@ -107,7 +113,7 @@ impl PersistCache {
         self,
         config: &Config,
         stats: &super::CacheStats,
-        rir: &mut Rir,
+        rir: &Rir,
     ) -> super::CacheCore {
         if self.rain_version != env!("CARGO_PKG_VERSION") {
             log::warn!("persist cache miss matched rain version");
@@ -115,12 +121,12 @@ impl PersistCache {
         }
         let mut lru = lru::LruCache::new(super::CACHE_SIZE);
         for (k, e) in self.entries {
-            let Some(k) = k.depersist(config, rir) else {
+            let Some(k) = k.depersist(config, Some(rir)) else {
                 log::warn!("could not depersist cache key for {e:?}");
                 stats.depersist_fails.inc();
                 continue;
             };
-            let Some(e) = e.depersist(config, rir) else {
+            let Some(e) = e.depersist(config, Some(rir)) else {
                 log::warn!("could not depersist cache entry");
                 stats.depersist_fails.inc();
                 continue;
@@ -128,7 +134,10 @@ impl PersistCache {
             stats.depersists.inc();
             lru.put(k, e);
         }
-        super::CacheCore { storage: lru }
+        super::CacheCore {
+            storage: lru,
+            secret_keys: std::collections::HashSet::new(),
+        }
     }
 }
 
@@ -148,7 +157,7 @@ pub struct PersistCacheEntry {
 }
 
 impl PersistCacheEntry {
-    fn persist(entry: &CacheEntry, rir: &Rir) -> Option<Self> {
+    pub(super) fn persist(entry: &CacheEntry, rir: Option<&Rir>) -> Option<Self> {
         if entry.deps.iter().any(|d| !d.is_inter_run_stable()) {
             // Don't cache because a dep is inter run unstable
             return None;
@@ -162,7 +171,7 @@ impl PersistCacheEntry {
         })
     }
 
-    fn depersist(self, config: &Config, rir: &mut Rir) -> Option<CacheEntry> {
+    pub(super) fn depersist(self, config: &Config, rir: Option<&Rir>) -> Option<CacheEntry> {
         let value = self.value.depersist(config, rir)?;
         Some(CacheEntry {
             execution_time: self.execution_time,
@@ -179,6 +188,7 @@ pub enum PersistValue {
     Unit,
     Boolean(bool),
     Integer(RainInteger),
+    Float(RainFloat),
     String(String),
     FileArea(FileArea),
     File(FSEntry),
@@ -192,14 +202,16 @@ pub enum PersistValue {
 }
 
 impl PersistValue {
-    fn persist(value: &Value, rir: &Rir) -> Option<Self> {
+    /// Modules can only be persisted given the [`Rir`] they are in
+    pub(super) fn persist(value: &Value, rir: Option<&Rir>) -> Option<Self> {
         match value {
             Value::Unit => Some(Self::Unit),
             Value::Boolean(b) => Some(Self::Boolean(*b)),
             Value::Integer(rain_integer) => Some(Self::Integer((**rain_integer).clone())),
+            Value::Float(rain_float) => Some(Self::Float(*rain_float)),
             Value::String(s) => Some(Self::String((**s).clone())),
             Value::Module(mid) => {
-                let module = rir.get_module(*mid);
+                let module = rir?.get_module(*mid);
                 Some(Self::Module {
                     file: module.file.as_ref()?.inner().clone(),
                     src: module.src.clone().into_owned(),
@@ -212,13 +224,8 @@ impl PersistValue {
                     Some(Self::FileArea((**file_area).clone()))
                 }
             }
-            Value::File(file) => {
-                if file.inner().area.is_local() {
-                    None
-                } else {
-                    Some(Self::File(file.inner().clone()))
-                }
-            }
+            // Local files are fine to persist because internal functions record the fingerprint of any local files passed to them
+            Value::File(file) => Some(Self::File(file.inner().clone())),
             Value::Dir(dir) => Some(Self::Dir(dir.inner().clone())),
             Value::Internal => Some(Self::Internal),
             Value::InternalFunction(internal_function) => {
@@ -244,11 +251,12 @@ impl PersistValue {
         }
     }
 
-    fn depersist(self, config: &Config, rir: &mut Rir) -> Option<Value> {
+    pub(super) fn depersist(self, config: &Config, rir: Option<&Rir>) -> Option<Value> {
         match self {
             Self::Unit => Some(Value::Unit),
             Self::Boolean(b) => Some(Value::Boolean(b)),
             Self::Integer(rain_integer) => Some(Value::Integer(Arc::new(rain_integer))),
+            Self::Float(rain_float) => Some(Value::Float(rain_float)),
             Self::String(s) => Some(Value::String(Arc::new(s))),
             Self::FileArea(file_area) => Some(Value::FileArea(Arc::new(file_area))),
             Self::File(fsentry) => Some(Value::File(Arc::new(File::new_checked(config, fsentry)?))),
@@ -270,7 +278,7 @@ impl PersistValue {
             )))),
             Self::Module { file, src } => {
                 let ast = rain_lang::ast::parser::parse_module(&src);
-                match rir.insert_module(Some(File::new_checked(config, file)?), src, ast) {
+                match rir?.insert_module(Some(File::new_checked(config, file)?), src, ast) {
                     Ok(mid) => Some(Value::Module(mid)),
                     Err(err) => {
                         log::error!("error loading cached module: {err:?}");
@@ -291,16 +299,25 @@ pub enum PersistCacheKey {
     },
     Download {
         url: String,
+        sha256: Option<String>,
+        headers: Vec<(String, String)>,
+        auth: Option<DownloadAuth>,
+    },
+    GitRef {
+        url: String,
+        git_ref: String,
+        submodules: bool,
     },
 }
 
 impl PersistCacheKey {
-    fn persist(key: &CacheKey, rir: &Rir) -> Option<Self> {
+    pub(super) fn persist(key: &CacheKey, rir: Option<&Rir>) -> Option<Self> {
         match key {
             // TODO: It is possible to persist declarations in the cache if we resolve the function/module id to a stable value and embed the File it was imported from
             // TODO: It is possible to persist embed in the cache if we key it by the rain binary version
             CacheKey::Declaration { .. }
             | CacheKey::CallClosure { .. }
+            | CacheKey::Comprehension { .. }
             | CacheKey::Embed
             | CacheKey::Import { .. } => None,
             CacheKey::InternalFunction { func, args } => Some(Self::InternalFunction {
@@ -310,11 +327,30 @@ impl PersistCacheKey {
                     .map(|v| PersistValue::persist(v, rir))
                     .collect::<Option<_>>()?,
             }),
-            CacheKey::Download { url } => Some(Self::Download { url: url.clone() }),
+            CacheKey::Download {
+                url,
+                sha256,
+                headers,
+                auth,
+            } => Some(Self::Download {
+                url: url.clone(),
+                sha256: sha256.clone(),
+                headers: headers.clone(),
+                auth: auth.clone(),
+            }),
+            CacheKey::GitRef {
+                url,
+                git_ref,
+                submodules,
+            } => Some(Self::GitRef {
+                url: url.clone(),
+                git_ref: git_ref.clone(),
+                submodules: *submodules,
+            }),
         }
     }
 
-    fn depersist(self, config: &Config, rir: &mut Rir) -> Option<CacheKey> {
+    pub(super) fn depersist(self, config: &Config, rir: Option<&Rir>) -> Option<CacheKey> {
         match self {
             Self::InternalFunction { func, args } => Some(CacheKey::InternalFunction {
                 func,
@@ -323,7 +359,26 @@ impl PersistCacheKey {
                     .map(|a| a.depersist(config, rir))
                     .collect::<Option<Vec<Value>>>()?,
             }),
-            Self::Download { url } => Some(CacheKey::Download { url }),
+            Self::Download {
+                url,
+                sha256,
+                headers,
+                auth,
+            } => Some(CacheKey::Download {
+                url,
+                sha256,
+                headers,
+                auth,
+            }),
+            Self::GitRef {
+                url,
+                git_ref,
+                submodules,
+            } => Some(CacheKey::GitRef {
+                url,
+                git_ref,
+                submodules,
+            }),
         }
     }
 }
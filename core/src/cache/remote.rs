@@ -0,0 +1,346 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io::Write as _,
+    path::{Path, PathBuf},
+    sync::{Arc, Condvar, Mutex, PoisonError},
+    time::Duration,
+};
+
+use poison_panic::MutexExt as _;
+use rain_lang::{
+    afs::{
+        area::{FileArea, GeneratedFileArea},
+        entry::FSEntry,
+        path::SealedFilePath,
+    },
+    driver::FSTrait as _,
+    runner::cache::{CacheEntry, CacheKey},
+};
+use sha2::Digest as _;
+
+use super::{
+    CacheStats,
+    persistent::{FORMAT_VERSION, PersistCacheEntry, PersistCacheKey, PersistValue},
+};
+use crate::config::Config;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(30);
+/// Areas can be large so allow much longer to receive a body than a response
+const BODY_TIMEOUT: Duration = Duration::from_secs(600);
+
+#[derive(Debug, thiserror::Error)]
+pub enum RemoteCacheError {
+    #[error("http: {0}")]
+    Http(#[from] ureq::Error),
+    #[error("io: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("de: {0}")]
+    De(#[from] ciborium::de::Error<std::io::Error>),
+    #[error("ser: {0}")]
+    Ser(#[from] ciborium::ser::Error<std::io::Error>),
+    #[error("blob {0} did not match its digest")]
+    DigestMissmatch(String),
+}
+
+/// What is stored in the remote index for each cache key
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct RemoteCacheEntry {
+    entry: PersistCacheEntry,
+    /// Generated areas the entry refers to with the digest of their archive in the content addressed store
+    areas: Vec<(GeneratedFileArea, String)>,
+}
+
+/// Client for a cache shared between machines over HTTP, see `rain-cache-server` for the protocol
+///
+/// Generated areas are uploaded as zstd compressed tarballs and keep their id so entries that refer to them stay valid on every machine
+pub struct RemoteCache {
+    config: Config,
+    base_url: String,
+    agent: ureq::Agent,
+    /// Digests of areas already uploaded by this client
+    uploaded: Mutex<HashMap<GeneratedFileArea, String>>,
+    /// Entries waiting to be pushed by the background pusher
+    queue: Mutex<PushQueue>,
+    /// Notified when the background pusher has emptied the queue
+    queue_drained: Condvar,
+}
+
+#[derive(Default)]
+struct PushQueue {
+    entries: VecDeque<(CacheKey, CacheEntry, Arc<CacheStats>)>,
+    /// Whether a thread is currently pushing the queued entries
+    pushing: bool,
+}
+
+impl RemoteCache {
+    pub fn new(config: Config, base_url: &str) -> Self {
+        Self {
+            config,
+            base_url: base_url.trim_end_matches('/').to_owned(),
+            agent: ureq::Agent::new_with_config(
+                ureq::config::Config::builder()
+                    .http_status_as_error(false)
+                    .timeout_connect(Some(CONNECT_TIMEOUT))
+                    .timeout_recv_response(Some(RESPONSE_TIMEOUT))
+                    .timeout_recv_body(Some(BODY_TIMEOUT))
+                    .build(),
+            ),
+            uploaded: Mutex::default(),
+            queue: Mutex::default(),
+            queue_drained: Condvar::new(),
+        }
+    }
+
+    /// Queue an entry to be pushed from a background thread so packing and uploading areas does not hold up the run
+    pub fn push_in_background(
+        self: &Arc<Self>,
+        key: CacheKey,
+        entry: CacheEntry,
+        stats: Arc<CacheStats>,
+    ) {
+        let mut queue = self.queue.plock();
+        queue.entries.push_back((key, entry, stats));
+        if queue.pushing {
+            return;
+        }
+        queue.pushing = true;
+        drop(queue);
+        let remote = Arc::clone(self);
+        if let Err(err) = std::thread::Builder::new()
+            .name(String::from("remote cache push"))
+            .spawn(move || remote.drain_queue())
+        {
+            log::warn!("failed to spawn remote cache push thread, pushing inline: {err}");
+            self.drain_queue();
+        }
+    }
+
+    fn drain_queue(&self) {
+        loop {
+            let mut queue = self.queue.plock();
+            let Some((key, entry, stats)) = queue.entries.pop_front() else {
+                queue.pushing = false;
+                self.queue_drained.notify_all();
+                return;
+            };
+            drop(queue);
+            match self.push(&key, &entry) {
+                Ok(true) => stats.remote_pushes.inc(),
+                Ok(false) => {}
+                Err(err) => {
+                    log::warn!("remote cache push failed for {key}: {err}");
+                    stats.remote_fails.inc();
+                }
+            }
+        }
+    }
+
+    /// Wait until every queued entry has been pushed
+    pub fn flush(&self) {
+        let mut queue = self.queue.plock();
+        while queue.pushing {
+            queue = self
+                .queue_drained
+                .wait(queue)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+
+    /// Look up an entry fetching any generated areas it refers to, keys that can't be persisted are never found
+    pub fn pull(&self, key: &CacheKey) -> Result<Option<CacheEntry>, RemoteCacheError> {
+        let Some(key) = PersistCacheKey::persist(key, None) else {
+            return Ok(None);
+        };
+        let mut response = self
+            .agent
+            .get(format!("{}/kv/{}", self.base_url, key_digest(&key)?))
+            .call()?;
+        if !response.status().is_success() {
+            return Ok(None);
+        }
+        let remote_entry: RemoteCacheEntry =
+            ciborium::from_reader(response.body_mut().as_reader())?;
+        for (area, digest) in &remote_entry.areas {
+            self.download_area(area, digest)?;
+        }
+        Ok(remote_entry.entry.depersist(&self.config, None))
+    }
+
+    /// Upload an entry and the generated areas it refers to, returns false if the entry can't be persisted
+    pub fn push(&self, key: &CacheKey, entry: &CacheEntry) -> Result<bool, RemoteCacheError> {
+        let Some(key) = PersistCacheKey::persist(key, None) else {
+            return Ok(false);
+        };
+        let Some(entry) = PersistCacheEntry::persist(entry, None) else {
+            return Ok(false);
+        };
+        let mut areas = Vec::new();
+        generated_areas(&entry.value, &mut areas);
+        let areas = areas
+            .into_iter()
+            .map(|area| {
+                let digest = self.upload_area(&area)?;
+                Ok((area, digest))
+            })
+            .collect::<Result<_, RemoteCacheError>>()?;
+        let mut body = Vec::new();
+        ciborium::into_writer(&RemoteCacheEntry { entry, areas }, &mut body)?;
+        self.put(&format!("kv/{}", key_digest(&key)?), body)?;
+        Ok(true)
+    }
+
+    fn put(&self, path: &str, body: impl ureq::AsSendBody) -> Result<(), RemoteCacheError> {
+        let response = self
+            .agent
+            .put(format!("{}/{path}", self.base_url))
+            .send(body)?;
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(ureq::Error::StatusCode(response.status().as_u16()).into())
+        }
+    }
+
+    fn area_path(&self, area: &GeneratedFileArea) -> PathBuf {
+        self.config.resolve_fs_entry(&FSEntry::new(
+            FileArea::Generated(area.clone()),
+            SealedFilePath::root(),
+        ))
+    }
+
+    fn scratch_path(&self, area: &GeneratedFileArea) -> PathBuf {
+        self.config
+            .base_cache_dir
+            .join("remote")
+            .join(format!("{}.tar.zst", area.id))
+    }
+
+    fn upload_area(&self, area: &GeneratedFileArea) -> Result<String, RemoteCacheError> {
+        if let Some(digest) = self.uploaded.plock().get(area) {
+            return Ok(digest.clone());
+        }
+        let archive_path = self.scratch_path(area);
+        pack_area(&self.area_path(area), &archive_path)?;
+        let digest = file_digest(&archive_path)?;
+        let exists = self
+            .agent
+            .head(format!("{}/cas/{digest}", self.base_url))
+            .call()?
+            .status()
+            .is_success();
+        if !exists {
+            log::debug!("uploading area {} as {digest}", area.id);
+            self.put(
+                &format!("cas/{digest}"),
+                std::fs::File::open(&archive_path)?,
+            )?;
+        }
+        std::fs::remove_file(&archive_path)?;
+        self.uploaded.plock().insert(area.clone(), digest.clone());
+        Ok(digest)
+    }
+
+    fn download_area(
+        &self,
+        area: &GeneratedFileArea,
+        digest: &str,
+    ) -> Result<(), RemoteCacheError> {
+        let area_path = self.area_path(area);
+        if area_path.exists() {
+            return Ok(());
+        }
+        log::debug!("downloading area {} from {digest}", area.id);
+        let mut response = self
+            .agent
+            .get(format!("{}/cas/{digest}", self.base_url))
+            .call()?;
+        if !response.status().is_success() {
+            return Err(ureq::Error::StatusCode(response.status().as_u16()).into());
+        }
+        let archive_path = self.scratch_path(area);
+        if let Some(parent) = archive_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut archive = std::fs::File::create(&archive_path)?;
+        std::io::copy(&mut response.body_mut().as_reader(), &mut archive)?;
+        archive.flush()?;
+        if file_digest(&archive_path)? != digest {
+            std::fs::remove_file(&archive_path)?;
+            return Err(RemoteCacheError::DigestMissmatch(digest.to_owned()));
+        }
+        // Unpack next to the area and rename so a partially unpacked area is never used
+        let partial_path = area_path.with_extension("partial");
+        if partial_path.exists() {
+            std::fs::remove_dir_all(&partial_path)?;
+        }
+        let decoder = zstd::Decoder::new(std::fs::File::open(&archive_path)?)?;
+        tar::Archive::new(decoder).unpack(&partial_path)?;
+        std::fs::rename(&partial_path, &area_path)?;
+        std::fs::remove_file(&archive_path)?;
+        Ok(())
+    }
+}
+
+/// Digest of the key along with everything that changes how entries are interpreted
+fn key_digest(key: &PersistCacheKey) -> Result<String, RemoteCacheError> {
+    let mut serialized = Vec::new();
+    ciborium::into_writer(
+        &(env!("CARGO_PKG_VERSION"), FORMAT_VERSION, key),
+        &mut serialized,
+    )?;
+    Ok(base16::encode_lower(&sha2::Sha256::digest(&serialized)))
+}
+
+fn file_digest(path: &Path) -> std::io::Result<String> {
+    let mut hasher = sha2::Sha256::new();
+    std::io::copy(&mut std::fs::File::open(path)?, &mut hasher)?;
+    Ok(base16::encode_lower(&hasher.finalize()))
+}
+
+fn pack_area(area_path: &Path, archive_path: &Path) -> std::io::Result<()> {
+    if let Some(parent) = archive_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let encoder = zstd::Encoder::new(std::fs::File::create(archive_path)?, 0)?;
+    let mut builder = tar::Builder::new(encoder);
+    builder.follow_symlinks(false);
+    builder.append_dir_all(".", area_path)?;
+    builder.into_inner()?.finish()?.flush()
+}
+
+fn generated_areas(value: &PersistValue, areas: &mut Vec<GeneratedFileArea>) {
+    let mut add = |area: &FileArea| {
+        if let FileArea::Generated(area) = area {
+            if !areas.contains(area) {
+                areas.push(area.clone());
+            }
+        }
+    };
+    match value {
+        PersistValue::FileArea(area) => add(area),
+        PersistValue::File(entry)
+        | PersistValue::Dir(entry)
+        | PersistValue::Module { file: entry, .. } => {
+            add(&entry.area);
+        }
+        PersistValue::List(values) => {
+            for v in values {
+                generated_areas(v, areas);
+            }
+        }
+        PersistValue::Record(values) => {
+            for v in values.values() {
+                generated_areas(v, areas);
+            }
+        }
+        PersistValue::Unit
+        | PersistValue::Boolean(_)
+        | PersistValue::Integer(_)
+        | PersistValue::Float(_)
+        | PersistValue::String(_)
+        | PersistValue::Internal
+        | PersistValue::InternalFunction(_)
+        | PersistValue::Type(_) => {}
+    }
+}
@@ -6,15 +6,22 @@ use rain_lang::{
         entry::FSEntry,
     },
     driver::{FSEntryQueryResult, FSTrait},
+    runner::dep::{ContentsFilter, Fingerprint},
 };
 use serde::{Deserialize, Serialize};
 
+use crate::secrets::SecretsConfig;
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct Config {
     pub base_cache_dir: PathBuf,
     pub base_generated_dir: PathBuf,
     pub base_data_dir: PathBuf,
     pub base_run_dir: PathBuf,
+    /// Base url of a shared remote cache, set with `RAIN_REMOTE_CACHE`
+    pub remote_cache_url: Option<String>,
+    /// Where secrets are looked up, see [`crate::secrets`]
+    pub secrets: SecretsConfig,
 }
 
 impl Default for Config {
@@ -41,6 +48,8 @@ impl Config {
             base_generated_dir,
             base_data_dir,
             base_run_dir,
+            remote_cache_url: std::env::var("RAIN_REMOTE_CACHE").ok(),
+            secrets: SecretsConfig::from_env(),
         }
     }
 
@@ -48,6 +57,16 @@ impl Config {
         self.base_cache_dir.join("cache.json")
     }
 
+    /// Content addressed store of downloads with a known hash, see [`crate::download`]
+    pub fn download_store_dir(&self) -> PathBuf {
+        self.base_cache_dir.join("downloads")
+    }
+
+    /// Directory of the bare git mirrors, see [`crate::git_mirror`]
+    pub fn git_mirror_dir(&self) -> PathBuf {
+        self.base_cache_dir.join("git")
+    }
+
     #[cfg(target_family = "unix")]
     pub fn server_socket_path(&self) -> PathBuf {
         self.base_run_dir.join("server.socket")
@@ -103,6 +122,14 @@ impl FSTrait for Config {
             Err(err) => Err(err),
         }
     }
+
+    fn fingerprint(
+        &self,
+        entry: &FSEntry,
+        contents: Option<&ContentsFilter>,
+    ) -> Result<Fingerprint, std::io::Error> {
+        crate::fingerprint::fingerprint(&self.resolve_fs_entry(entry), contents)
+    }
 }
 
 fn unique_directories<'a>(dirs: &[&'a Path]) -> Vec<&'a Path> {
@@ -0,0 +1,376 @@
+//! Streaming downloads that are checked against their expected hash as they arrive
+//!
+//! Interrupted downloads are resumed with range requests. Downloads with a known hash go through a content addressed store so the same file is only fetched once whatever url it comes from.
+
+use std::{
+    io::{Read as _, Seek as _},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, SystemTime},
+};
+
+use poison_panic::MutexExt as _;
+use sha2::Digest as _;
+
+use crate::{cache::PruneStats, config::Config};
+
+/// Times a url is tried before moving on to the next mirror
+const MAX_ATTEMPTS: usize = 3;
+/// Downloads in the store that haven't been used for this long are removed by prune
+pub const DOWNLOAD_MAX_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Hashes currently being downloaded into the store
+static IN_PROGRESS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+#[derive(Debug, thiserror::Error)]
+pub enum DownloadError {
+    #[error("http: {0}")]
+    Http(#[from] ureq::Error),
+    #[error("io: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("{url} has sha256 {actual} but expected {expected}")]
+    HashMismatch {
+        url: String,
+        expected: String,
+        actual: String,
+    },
+}
+
+/// Response to a download that completed
+pub struct Fetched {
+    pub status: u16,
+    pub etag: Option<Vec<u8>>,
+    /// Body of unsuccessful responses, successful bodies are written to the destination instead
+    pub error_body: Vec<u8>,
+}
+
+impl Fetched {
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+}
+
+/// Headers sent with each request of a download
+#[derive(Default)]
+pub struct RequestHeaders<'a> {
+    pub headers: &'a [(String, String)],
+    /// Value of the `Authorization` header, only sent to urls with the same origin as the first url so mirrors never see it
+    pub authorization: Option<String>,
+}
+
+impl RequestHeaders<'_> {
+    fn for_url(&self, first_url: &str, url: &str) -> Vec<(&str, &str)> {
+        let mut headers: Vec<(&str, &str)> = self
+            .headers
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect();
+        if let Some(authorization) = &self.authorization {
+            if same_origin(first_url, url) {
+                headers.push(("Authorization", authorization));
+            } else {
+                log::debug!("not sending authorization to {url}");
+            }
+        }
+        headers
+    }
+}
+
+fn same_origin(a: &str, b: &str) -> bool {
+    let (Ok(a), Ok(b)) = (a.parse::<ureq::http::Uri>(), b.parse::<ureq::http::Uri>()) else {
+        return false;
+    };
+    a.scheme() == b.scheme() && a.authority() == b.authority()
+}
+
+/// Path of the download with `sha256` in the content addressed store
+pub fn store_path(config: &Config, sha256: &str) -> PathBuf {
+    config.download_store_dir().join(sha256)
+}
+
+/// Download into the store if it isn't already there, trying each url in turn
+///
+/// Partial downloads are kept in the store so they can be resumed by later calls even from a different url
+pub fn fetch_into_store(
+    agent: &ureq::Agent,
+    config: &Config,
+    urls: &[&str],
+    sha256: &str,
+    headers: &RequestHeaders<'_>,
+) -> Result<Result<PathBuf, Fetched>, DownloadError> {
+    let stored = store_path(config, sha256);
+    if stored.exists() {
+        log::debug!("download {sha256} already in store");
+        // The modified time of a stored download is when it was last used
+        if let Err(err) = std::fs::File::options()
+            .append(true)
+            .open(&stored)
+            .and_then(|f| f.set_modified(SystemTime::now()))
+        {
+            log::warn!("could not mark download used: {err}");
+        }
+        return Ok(Ok(stored));
+    }
+    let partial_dir = config.download_store_dir().join("partial");
+    std::fs::create_dir_all(&partial_dir)?;
+    let in_progress = InProgress::claim(sha256);
+    let partial = if in_progress.is_some() {
+        partial_dir.join(sha256)
+    } else {
+        // Someone else is downloading this already, don't trample their partial file
+        partial_dir.join(format!("{sha256}-{}", uuid::Uuid::new_v4()))
+    };
+    match fetch_any(agent, urls, &partial, None, Some(sha256), headers) {
+        Ok(fetched) if fetched.is_success() => {
+            std::fs::rename(&partial, &stored)?;
+            Ok(Ok(stored))
+        }
+        result => {
+            if in_progress.is_none() {
+                // Nothing will resume a uniquely named partial file so don't leave it behind
+                if let Err(err) = std::fs::remove_file(&partial) {
+                    if err.kind() != std::io::ErrorKind::NotFound {
+                        log::warn!("could not remove {}: {err}", partial.display());
+                    }
+                }
+            }
+            result.map(Err)
+        }
+    }
+}
+
+/// Try each url in turn until one succeeds, returns the last failed response if none do
+pub fn fetch_any(
+    agent: &ureq::Agent,
+    urls: &[&str],
+    dest: &Path,
+    etag: Option<&[u8]>,
+    sha256: Option<&str>,
+    headers: &RequestHeaders<'_>,
+) -> Result<Fetched, DownloadError> {
+    let mut last = None;
+    for (i, url) in urls.iter().enumerate() {
+        // Only the primary url's response is cached so only it can be revalidated
+        let etag = if i == 0 { etag } else { None };
+        let headers = headers.for_url(urls[0], url);
+        match fetch(agent, url, dest, etag, sha256, &headers) {
+            Ok(fetched) if fetched.is_success() || fetched.status == 304 => return Ok(fetched),
+            Ok(fetched) => {
+                log::warn!("download {url} failed with status {}", fetched.status);
+                last = Some(Ok(fetched));
+            }
+            Err(err) => {
+                log::warn!("download {url} failed: {err}");
+                last = Some(Err(err));
+            }
+        }
+    }
+    last.unwrap_or_else(|| {
+        Err(DownloadError::Io(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "no urls to download",
+        )))
+    })
+}
+
+/// Download `url` to `dest` resuming from whatever is already in `dest`
+///
+/// Without an expected hash a partial download is only resumed if the server can promise it hasn't changed
+pub fn fetch(
+    agent: &ureq::Agent,
+    url: &str,
+    dest: &Path,
+    etag: Option<&[u8]>,
+    sha256: Option<&str>,
+    headers: &[(&str, &str)],
+) -> Result<Fetched, DownloadError> {
+    let mut validator: Option<Vec<u8>> = None;
+    let mut last_err = None;
+    for attempt in 1..=MAX_ATTEMPTS {
+        let mut existing = std::fs::metadata(dest).map_or(0, |m| m.len());
+        if existing > 0 && sha256.is_none() && validator.is_none() {
+            // Nothing to tell us the partial download is of the same file
+            existing = 0;
+        }
+        let mut request = agent.get(url);
+        for (key, value) in headers {
+            request = request.header(*key, *value);
+        }
+        if existing > 0 {
+            log::debug!("resuming download of {url} from {existing}");
+            request = request.header(ureq::http::header::RANGE, format!("bytes={existing}-"));
+            if let Some(validator) = &validator {
+                request = request.header(ureq::http::header::IF_RANGE, validator.as_slice());
+            }
+        } else if let Some(etag) = etag {
+            request = request.header(ureq::http::header::IF_NONE_MATCH, etag);
+        }
+        let mut response = match request.call() {
+            Ok(response) => response,
+            Err(err) => {
+                log::debug!("download {url} attempt {attempt} failed: {err}");
+                last_err = Some(err.into());
+                continue;
+            }
+        };
+        let status = response.status().as_u16();
+        let response_etag: Option<Vec<u8>> = response
+            .headers()
+            .get(ureq::http::header::ETAG)
+            .map(|h| h.as_bytes().to_vec());
+        if status == 416 && existing > 0 {
+            // The partial download might already be complete
+            if let Some(sha256) = sha256 {
+                if file_sha256(dest)? == sha256 {
+                    return Ok(Fetched {
+                        status: 200,
+                        etag: response_etag,
+                        error_body: Vec::new(),
+                    });
+                }
+            }
+            std::fs::remove_file(dest)?;
+            continue;
+        }
+        if !response.status().is_success() {
+            let error_body = response.body_mut().read_to_vec().unwrap_or_default();
+            return Ok(Fetched {
+                status,
+                etag: response_etag,
+                error_body,
+            });
+        }
+        let resume = status == 206 && existing > 0;
+        let mut f = std::fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(false)
+            .open(dest)?;
+        let mut hasher = sha2::Sha256::new();
+        if resume {
+            std::io::copy(&mut std::fs::File::open(dest)?.take(existing), &mut hasher)?;
+            f.seek(std::io::SeekFrom::Start(existing))?;
+            f.set_len(existing)?;
+        } else {
+            f.set_len(0)?;
+        }
+        validator.clone_from(&response_etag);
+        if let Err(err) = copy_hashing(&mut response.body_mut().as_reader(), &mut f, &mut hasher) {
+            log::debug!("download {url} attempt {attempt} interrupted: {err}");
+            last_err = Some(err.into());
+            continue;
+        }
+        if let Some(expected) = sha256 {
+            let actual = base16::encode_lower(&hasher.finalize());
+            if actual != expected {
+                std::fs::remove_file(dest)?;
+                return Err(DownloadError::HashMismatch {
+                    url: url.to_owned(),
+                    expected: expected.to_owned(),
+                    actual,
+                });
+            }
+        }
+        return Ok(Fetched {
+            status: if resume { 200 } else { status },
+            etag: response_etag,
+            error_body: Vec::new(),
+        });
+    }
+    Err(last_err
+        .unwrap_or_else(|| DownloadError::Io(std::io::Error::other("download kept restarting"))))
+}
+
+fn copy_hashing(
+    reader: &mut impl std::io::Read,
+    writer: &mut impl std::io::Write,
+    hasher: &mut sha2::Sha256,
+) -> std::io::Result<()> {
+    let mut buf = [0u8; 8192];
+    loop {
+        let n = match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        hasher.update(&buf[..n]);
+        writer.write_all(&buf[..n])?;
+    }
+    writer.flush()
+}
+
+fn file_sha256(path: &Path) -> std::io::Result<String> {
+    let mut hasher = sha2::Sha256::new();
+    std::io::copy(&mut std::fs::File::open(path)?, &mut hasher)?;
+    Ok(base16::encode_lower(&hasher.finalize()))
+}
+
+/// Marks a hash as being downloaded into the store until dropped
+/// Remove downloads and partial downloads from the store that have not been used for `max_age`
+pub fn prune(config: &Config, max_age: Duration) -> std::io::Result<PruneStats> {
+    let mut stats = PruneStats { size: 0, errors: 0 };
+    let dir = config.download_store_dir();
+    prune_dir(&dir, max_age, &mut stats)?;
+    prune_dir(&dir.join("partial"), max_age, &mut stats)?;
+    Ok(stats)
+}
+
+fn prune_dir(dir: &Path, max_age: Duration, stats: &mut PruneStats) -> std::io::Result<()> {
+    if !dir.exists() {
+        return Ok(());
+    }
+    // Hold the lock so nothing starts downloading into the store while it is pruned
+    let in_progress = IN_PROGRESS.plock();
+    let now = SystemTime::now();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_file() {
+            continue;
+        }
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if in_progress.iter().any(|h| name.starts_with(h.as_str())) {
+            log::info!("Not Pruning download in progress {}", path.display());
+            continue;
+        }
+        let metadata = entry.metadata()?;
+        if metadata
+            .modified()
+            .ok()
+            .and_then(|t| now.duration_since(t).ok())
+            .is_some_and(|age| age < max_age)
+        {
+            log::info!("Not Pruning download {}", path.display());
+            continue;
+        }
+        log::info!("Pruning download {}", path.display());
+        match std::fs::remove_file(&path) {
+            Ok(()) => stats.size += metadata.len(),
+            Err(err) => {
+                log::error!("Failed to prune download {} because {err}", path.display());
+                stats.errors += 1;
+            }
+        }
+    }
+    Ok(())
+}
+
+struct InProgress(String);
+
+impl InProgress {
+    fn claim(sha256: &str) -> Option<Self> {
+        let mut in_progress = IN_PROGRESS.plock();
+        if in_progress.iter().any(|h| h == sha256) {
+            return None;
+        }
+        in_progress.push(sha256.to_owned());
+        Some(Self(sha256.to_owned()))
+    }
+}
+
+impl Drop for InProgress {
+    fn drop(&mut self) {
+        IN_PROGRESS.plock().retain(|h| h != &self.0);
+    }
+}
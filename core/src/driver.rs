@@ -1,10 +1,15 @@
 use std::{
     borrow::Cow,
+    io::{Read as _, Seek as _, Write as _},
     path::{Path, PathBuf},
-    sync::Mutex,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant},
 };
 
-use git2::{Cred, Oid};
+use git2::Oid;
 use poison_panic::MutexExt as _;
 use rain_lang::{
     afs::{
@@ -16,17 +21,33 @@ use rain_lang::{
         path::SealedFilePath,
     },
     driver::{
-        DownloadStatus, DriverTrait, EscapeRunStatus, FSEntryQueryResult, FSTrait, FileMetadata,
-        MonitoringTrait, RunOptions, RunStatus,
+        DownloadAuth, DownloadOptions, DownloadStatus, DriverTrait, EscapeRunStatus,
+        FSEntryQueryResult, FSTrait, FileMetadata, GlobMatch, GlobOptions, LfsOptions,
+        MonitoringTrait, OutputStream, ResourceLimits, RunOptions, RunStatus,
+    },
+    runner::{
+        dep::{ContentsFilter, Fingerprint},
+        error::{KillReason, RunnerError},
+        internal::InternalFunction,
     },
-    runner::{error::RunnerError, internal::InternalFunction},
 };
 
 use sha2::Digest as _;
 
-use crate::config::Config;
+use crate::{
+    config::Config,
+    secrets::{SecretAllowlist, SecretStore},
+};
+
+/// Most output kept in a string value of a run, anything earlier is only in the output file
+const OUTPUT_STRING_LIMIT: u64 = 1024 * 1024;
+/// Most output included in the error for a killed command
+const KILLED_OUTPUT_LIMIT: u64 = 64 * 1024;
+/// Most output held back waiting for the end of a line before it is streamed anyway
+const MAX_PENDING_OUTPUT: usize = 64 * 1024;
 
-pub type PrintHandler<'a> = Box<dyn Fn(&str) + 'a + Send>;
+pub type PrintHandler<'a> = Box<dyn Fn(&str) + 'a + Send + Sync>;
+pub type OutputHandler<'a> = Box<dyn Fn(&str, OutputStream, &[u8]) + 'a + Send + Sync>;
 
 pub struct DriverImpl<'a> {
     pub config: Config,
@@ -34,8 +55,26 @@ pub struct DriverImpl<'a> {
     pub print_handler: Option<PrintHandler<'a>>,
     pub enter_handler: Option<PrintHandler<'a>>,
     pub exit_handler: Option<PrintHandler<'a>>,
+    pub output_handler: Option<OutputHandler<'a>>,
     pub embed: Option<Cow<'static, str>>,
     pub host_triple: Cow<'static, str>,
+    /// Run commands in a sandbox that can only see their input areas
+    pub sandbox: bool,
+    /// Set to kill running commands and fail any that are started afterwards
+    pub cancelled: Arc<AtomicBool>,
+    /// Shared with the cache so everything it stores can be checked for secrets
+    pub secrets: Arc<SecretStore>,
+    /// Secrets the project being run may read, loaded from its root directory
+    pub secret_allowlist: SecretAllowlist,
+}
+
+/// Output of a command that has exited
+struct CapturedOutput {
+    /// None if the command could not be run, the reason is written to stderr
+    status: Option<std::process::ExitStatus>,
+    killed: Option<KillReason>,
+    stdout: File,
+    stderr: File,
 }
 
 pub const fn default_host_triple() -> &'static str {
@@ -45,16 +84,237 @@ pub const fn default_host_triple() -> &'static str {
 impl DriverImpl<'_> {
     pub fn new(config: Config) -> Self {
         Self {
-            config,
             prints: Mutex::default(),
             print_handler: None,
             enter_handler: None,
             exit_handler: None,
+            output_handler: None,
             embed: Some(include_str!("../../lib/embed/embed.rain").into()),
             host_triple: default_host_triple().into(),
+            sandbox: false,
+            cancelled: Arc::default(),
+            secrets: Arc::new(SecretStore::from_config(&config.secrets)),
+            secret_allowlist: SecretAllowlist::default(),
+            config,
+        }
+    }
+
+    /// Run `cmd` to completion writing its output to files in a new area while reporting it as it is produced
+    ///
+    /// The command is killed if it runs for longer than `timeout` or the run is cancelled
+    fn captured_output(
+        &self,
+        call: &str,
+        cmd: &mut std::process::Command,
+        timeout: Option<Duration>,
+    ) -> Result<CapturedOutput, RunnerError> {
+        let area = self.create_empty_area()?;
+        let (stdout_file, stdout_writer) = self.create_output_file(&area, "stdout")?;
+        let (stderr_file, mut stderr_writer) = self.create_output_file(&area, "stderr")?;
+        // Put the command in its own process group so everything it spawns can be killed with it
+        #[cfg(target_family = "unix")]
+        std::os::unix::process::CommandExt::process_group(cmd, 0);
+        let status = cmd
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .spawn()
+            .and_then(|mut child| {
+                let stdout = child.stdout.take();
+                let stderr = child.stderr.take();
+                std::thread::scope(|s| {
+                    let stdout = s.spawn(|| {
+                        self.forward_output(call, OutputStream::Stdout, stdout, stdout_writer)
+                    });
+                    let stderr = s.spawn(|| {
+                        self.forward_output(call, OutputStream::Stderr, stderr, &mut stderr_writer)
+                    });
+                    let status = self.wait_or_kill(&mut child, timeout);
+                    for forward in [stdout, stderr] {
+                        forward
+                            .join()
+                            .unwrap_or_else(|err| std::panic::resume_unwind(err))?;
+                    }
+                    status
+                })
+            });
+        let (status, killed) = match status {
+            Ok((status, killed)) => (Some(status), killed),
+            Err(err) => {
+                log::debug!("running command failed: {err}");
+                stderr_writer
+                    .write_all(err.to_string().as_bytes())
+                    .map_err(RunnerError::AreaIOError)?;
+                (None, None)
+            }
+        };
+        Ok(CapturedOutput {
+            status,
+            killed,
+            stdout: stdout_file,
+            stderr: stderr_file,
+        })
+    }
+
+    /// Wait for `child` to exit killing it and its process group if it times out or the run is cancelled
+    fn wait_or_kill(
+        &self,
+        child: &mut std::process::Child,
+        timeout: Option<Duration>,
+    ) -> std::io::Result<(std::process::ExitStatus, Option<KillReason>)> {
+        const POLL_INTERVAL: Duration = Duration::from_millis(10);
+        let start = Instant::now();
+        loop {
+            if let Some(status) = child.try_wait()? {
+                return Ok((status, None));
+            }
+            let reason = if self.cancelled.load(Ordering::Relaxed) {
+                Some(KillReason::Cancelled)
+            } else {
+                timeout
+                    .filter(|timeout| start.elapsed() >= *timeout)
+                    .map(KillReason::Timeout)
+            };
+            if let Some(reason) = reason {
+                log::debug!("killing {} because it {reason}", child.id());
+                kill_process_group(child)?;
+                return Ok((child.wait()?, Some(reason)));
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    fn create_output_file(
+        &self,
+        area: &FileArea,
+        name: &str,
+    ) -> Result<(File, std::fs::File), RunnerError> {
+        let entry = FSEntry::new(area.clone(), SealedFilePath::new(name)?);
+        let writer = std::fs::File::create_new(self.resolve_fs_entry(&entry))
+            .map_err(RunnerError::AreaIOError)?;
+        // Safety: We just created the file
+        let file = unsafe { File::new(entry) };
+        Ok((file, writer))
+    }
+
+    fn forward_output(
+        &self,
+        call: &str,
+        stream: OutputStream,
+        reader: Option<impl std::io::Read>,
+        mut writer: impl std::io::Write,
+    ) -> std::io::Result<()> {
+        let Some(mut reader) = reader else {
+            return Ok(());
+        };
+        let mut buf = [0u8; 8192];
+        // Output is streamed a line at a time so secrets split across reads are still redacted
+        let mut pending = Vec::new();
+        loop {
+            let n = match reader.read(&mut buf) {
+                Ok(0) => {
+                    if !pending.is_empty() {
+                        self.output(call, stream, &pending);
+                    }
+                    return writer.flush();
+                }
+                Ok(n) => n,
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            };
+            writer.write_all(&buf[..n])?;
+            pending.extend_from_slice(&buf[..n]);
+            if let Some(end) = pending.iter().rposition(|&b| b == b'\n' || b == b'\r') {
+                self.output(call, stream, &pending[..=end]);
+                pending.drain(..=end);
+            } else if pending.len() >= MAX_PENDING_OUTPUT {
+                self.output(call, stream, &pending);
+                pending.clear();
+            }
+        }
+    }
+
+    fn check_killed(&self, output: &CapturedOutput) -> Result<(), RunnerError> {
+        match output.killed {
+            Some(reason) => Err(RunnerError::RunKilled {
+                reason,
+                stdout: self.read_lossy_tail(&output.stdout, KILLED_OUTPUT_LIMIT)?,
+                stderr: self.read_lossy_tail(&output.stderr, KILLED_OUTPUT_LIMIT)?,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// The last `limit` bytes of a file decoded as UTF-8 replacing invalid sequences, noting how much was left out
+    fn read_lossy_tail(&self, file: &File, limit: u64) -> Result<String, RunnerError> {
+        let mut f = std::fs::File::open(self.resolve_fs_entry(file.inner()))
+            .map_err(RunnerError::AreaIOError)?;
+        let len = f.metadata().map_err(RunnerError::AreaIOError)?.len();
+        let skipped = len.saturating_sub(limit);
+        f.seek(std::io::SeekFrom::Start(skipped))
+            .map_err(RunnerError::AreaIOError)?;
+        let mut bytes = Vec::new();
+        f.read_to_end(&mut bytes)
+            .map_err(RunnerError::AreaIOError)?;
+        let tail = String::from_utf8_lossy(&bytes);
+        if skipped == 0 {
+            Ok(tail.into_owned())
+        } else {
+            Ok(format!("[{skipped} bytes truncated]\n{tail}"))
         }
     }
 
+    /// Make `cmd` enter a sandbox when spawned, returns the directory the sandbox root is mounted on which should be removed after the command exits
+    #[cfg(target_os = "linux")]
+    fn sandbox_command(
+        &self,
+        cmd: &mut std::process::Command,
+        bin: &Path,
+        input_areas: &[FileArea],
+        output_dir_path: PathBuf,
+        network: bool,
+    ) -> Result<PathBuf, RunnerError> {
+        let mut read_only: Vec<PathBuf> = input_areas
+            .iter()
+            .map(|area| self.resolve_fs_entry(Dir::root(area.clone()).inner()))
+            .collect();
+        if !read_only.iter().any(|p| bin.starts_with(p)) {
+            if let Some(bin_dir) = bin.parent() {
+                read_only.push(bin_dir.to_path_buf());
+            }
+        }
+        let root = self
+            .config
+            .base_run_dir
+            .join("sandbox")
+            .join(uuid::Uuid::new_v4().to_string());
+        std::fs::create_dir_all(&root)
+            .map_err(|err| RunnerError::MakeshiftIO("create sandbox root".into(), err))?;
+        crate::sandbox::Sandbox {
+            root: root.clone(),
+            read_only,
+            writable: output_dir_path,
+            network,
+        }
+        .apply(cmd)
+        .map_err(|err| RunnerError::MakeshiftIO("prepare sandbox".into(), err))?;
+        Ok(root)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn sandbox_command(
+        &self,
+        _cmd: &mut std::process::Command,
+        _bin: &Path,
+        _input_areas: &[FileArea],
+        _output_dir_path: PathBuf,
+        _network: bool,
+    ) -> Result<PathBuf, RunnerError> {
+        Err(RunnerError::Makeshift(
+            "sandboxing is only supported on linux".into(),
+        ))
+    }
+
     fn create_empty_area(&self) -> Result<FileArea, RunnerError> {
         self.create_overlay_area(std::iter::empty(), false, true)
     }
@@ -87,9 +347,7 @@ impl DriverImpl<'_> {
                 std::fs::copy(path, dest_path)
                     .map_err(|err| RunnerError::MakeshiftIO("copy file".into(), err))?;
             } else if metadata.is_dir() {
-                let walker = ignore::WalkBuilder::new(&path)
-                    .hidden(!include_hidden)
-                    .build();
+                let walker = area_walker(&path, include_hidden);
                 let dir_name = path
                     .file_name()
                     .ok_or_else(|| RunnerError::Makeshift("no dir name".into()))?;
@@ -136,6 +394,14 @@ impl FSTrait for DriverImpl<'_> {
     fn query_fs(&self, entry: &FSEntry) -> Result<FSEntryQueryResult, std::io::Error> {
         self.config.query_fs(entry)
     }
+
+    fn fingerprint(
+        &self,
+        entry: &FSEntry,
+        contents: Option<&ContentsFilter>,
+    ) -> Result<Fingerprint, std::io::Error> {
+        self.config.fingerprint(entry, contents)
+    }
 }
 
 impl DriverTrait for DriverImpl<'_> {
@@ -171,6 +437,7 @@ impl DriverTrait for DriverImpl<'_> {
     }
 
     fn print(&self, message: String) {
+        let message = self.secrets.redact(&message).into_owned();
         if let Some(ph) = &self.print_handler {
             ph(&message);
         }
@@ -270,7 +537,15 @@ impl DriverTrait for DriverImpl<'_> {
         overlay_area: Option<&FileArea>,
         bin: &Path,
         args: Vec<String>,
-        RunOptions { inherit_env, env }: RunOptions,
+        RunOptions {
+            call,
+            inherit_env,
+            env,
+            network,
+            input_areas,
+            timeout,
+            limits,
+        }: RunOptions,
     ) -> Result<RunStatus, RunnerError> {
         let output_area = if let Some(overlay_area) = overlay_area {
             self.create_overlay_area(
@@ -284,33 +559,34 @@ impl DriverTrait for DriverImpl<'_> {
         let output_dir = Dir::root(output_area.clone());
         let output_dir_path = self.resolve_fs_entry(output_dir.inner());
         let mut cmd = std::process::Command::new(bin);
-        cmd.current_dir(output_dir_path);
+        cmd.current_dir(&output_dir_path);
         cmd.args(args);
         if !inherit_env {
             cmd.env_clear();
         }
         cmd.envs(env);
-        log::debug!("Running {cmd:?}");
-        let output = match cmd.output() {
-            Ok(output) => output,
-            Err(err) => {
-                return Ok(RunStatus {
-                    success: false,
-                    exit_code: None,
-                    area: output_area,
-                    stdout: String::new(),
-                    stderr: err.to_string(),
-                });
-            }
+        apply_limits(&mut cmd, limits)?;
+        let sandbox_root = if self.sandbox {
+            Some(self.sandbox_command(&mut cmd, bin, &input_areas, output_dir_path, network)?)
+        } else {
+            None
         };
-        let success = output.status.success();
-        let exit_code = output.status.code();
+        log::debug!("Running {cmd:?}");
+        let output = self.captured_output(&call, &mut cmd, timeout);
+        if let Some(sandbox_root) = sandbox_root {
+            std::fs::remove_dir(sandbox_root)
+                .map_err(|err| RunnerError::MakeshiftIO("remove sandbox root".into(), err))?;
+        }
+        let output = output?;
+        self.check_killed(&output)?;
         Ok(RunStatus {
-            success,
-            exit_code,
+            success: output.status.is_some_and(|s| s.success()),
+            exit_code: output.status.and_then(|s| s.code()),
             area: output_area,
-            stdout: String::from_utf8(output.stdout)?,
-            stderr: String::from_utf8(output.stderr)?,
+            stdout: self.read_lossy_tail(&output.stdout, OUTPUT_STRING_LIMIT)?,
+            stderr: self.read_lossy_tail(&output.stderr, OUTPUT_STRING_LIMIT)?,
+            stdout_file: output.stdout,
+            stderr_file: output.stderr,
         })
     }
 
@@ -319,7 +595,12 @@ impl DriverTrait for DriverImpl<'_> {
         current_dir: &Dir,
         bin: &Path,
         args: Vec<String>,
-        RunOptions { inherit_env, env }: RunOptions,
+        RunOptions {
+            call,
+            inherit_env,
+            env,
+            ..
+        }: RunOptions,
     ) -> Result<EscapeRunStatus, RunnerError> {
         let current_dir_path = self.resolve_fs_entry(current_dir.inner());
         let mut cmd = std::process::Command::new(bin);
@@ -330,24 +611,15 @@ impl DriverTrait for DriverImpl<'_> {
         }
         cmd.envs(env);
         log::debug!("Running {cmd:?}");
-        let output = match cmd.output() {
-            Ok(output) => output,
-            Err(err) => {
-                return Ok(EscapeRunStatus {
-                    success: false,
-                    exit_code: None,
-                    stdout: String::new(),
-                    stderr: err.to_string(),
-                });
-            }
-        };
-        let success = output.status.success();
-        let exit_code = output.status.code();
+        let output = self.captured_output(&call, &mut cmd, None)?;
+        self.check_killed(&output)?;
         Ok(EscapeRunStatus {
-            success,
-            exit_code,
-            stdout: String::from_utf8(output.stdout)?,
-            stderr: String::from_utf8(output.stderr)?,
+            success: output.status.is_some_and(|s| s.success()),
+            exit_code: output.status.and_then(|s| s.code()),
+            stdout: self.read_lossy_tail(&output.stdout, OUTPUT_STRING_LIMIT)?,
+            stderr: self.read_lossy_tail(&output.stderr, OUTPUT_STRING_LIMIT)?,
+            stdout_file: output.stdout,
+            stderr_file: output.stderr,
         })
     }
 
@@ -356,41 +628,61 @@ impl DriverTrait for DriverImpl<'_> {
         url: &str,
         name: &str,
         etag: Option<&[u8]>,
+        options: &DownloadOptions,
     ) -> Result<DownloadStatus, RunnerError> {
         let agent = ureq::Agent::new_with_config(
             ureq::config::Config::builder()
                 .http_status_as_error(false)
                 .build(),
         );
-        let mut request = agent.get(url);
-        if let Some(etag) = etag {
-            request = request.header(ureq::http::header::IF_NONE_MATCH, etag);
-        }
-        log::debug!("Download {url}");
-        let mut response = request
-            .call()
-            .map_err(|err| RunnerError::MakeshiftIO("download request".into(), err.into_io()))?;
-        log::debug!("Download complete {url} {}", response.status());
-        let etag: Option<Vec<u8>> = response
-            .headers()
-            .get(ureq::http::header::ETAG)
-            .map(|h| h.as_bytes().to_vec());
+        let urls: Vec<&str> = std::iter::once(url)
+            .chain(options.mirrors.iter().map(String::as_str))
+            .collect();
+        let headers = crate::download::RequestHeaders {
+            headers: &options.headers,
+            authorization: authorization(options.auth.as_ref(), options.auth_secret.as_deref())?,
+        };
         let area = self.create_empty_area()?;
         let path = SealedFilePath::new(name)?;
         let entry = FSEntry::new(area, path);
         let output_path = self.resolve_fs_entry(&entry);
-        let mut out = std::fs::File::create_new(output_path)
-            .map_err(|err| RunnerError::MakeshiftIO("create download file".into(), err))?;
-        let body = response.body_mut();
-        std::io::copy(&mut body.as_reader(), &mut out)
-            .map_err(|err| RunnerError::MakeshiftIO("download file".into(), err))?;
-        // Safety: We just created the file and checked for errors so it is present
+        log::debug!("Download {url}");
+        let fetched = if let Some(sha256) = &options.sha256 {
+            match crate::download::fetch_into_store(&agent, &self.config, &urls, sha256, &headers)
+                .map_err(|err| RunnerError::Makeshift(format!("download: {err}").into()))?
+            {
+                Ok(stored) => {
+                    // Copy rather than link so changes to the area can't corrupt the store
+                    std::fs::copy(&stored, &output_path).map_err(|err| {
+                        RunnerError::MakeshiftIO("copy download from store".into(), err)
+                    })?;
+                    // Safety: We just created the file and checked for errors so it is present
+                    let output = unsafe { File::new(entry) };
+                    return Ok(DownloadStatus {
+                        ok: true,
+                        status_code: None,
+                        file: Some(output),
+                        etag: None,
+                    });
+                }
+                Err(fetched) => fetched,
+            }
+        } else {
+            crate::download::fetch_any(&agent, &urls, &output_path, etag, None, &headers)
+                .map_err(|err| RunnerError::Makeshift(format!("download: {err}").into()))?
+        };
+        log::debug!("Download complete {url} {}", fetched.status);
+        if !fetched.is_success() {
+            std::fs::write(&output_path, &fetched.error_body)
+                .map_err(|err| RunnerError::MakeshiftIO("create download file".into(), err))?;
+        }
+        // Safety: The download or its error body was written to the file and we checked for errors so it is present
         let output = unsafe { File::new(entry) };
         Ok(DownloadStatus {
-            ok: response.status().is_success(),
-            status_code: Some(response.status().as_u16()),
+            ok: fetched.is_success(),
+            status_code: Some(fetched.status),
             file: Some(output),
-            etag,
+            etag: fetched.etag,
         })
     }
 
@@ -465,24 +757,17 @@ impl DriverTrait for DriverImpl<'_> {
         })
     }
 
-    #[expect(clippy::unwrap_used)]
-    fn glob(&self, dir: &Dir, _pattern: &str) -> Result<Vec<File>, RunnerError> {
+    fn glob(&self, dir: &Dir, options: &GlobOptions) -> Result<Vec<GlobMatch>, RunnerError> {
         let base_path = self.resolve_fs_entry(dir.inner());
-        // TODO: Implement proper globbing instead of ignoring the pattern
         let mut out = Vec::new();
-        for entry in ignore::Walk::new(&base_path) {
-            let entry = entry.unwrap();
-            let file_type = entry.file_type().unwrap();
-            if file_type.is_symlink() || file_type.is_dir() {
-                continue;
-            }
-            if file_type.is_file() {
-                let p = entry.path();
-                let p = p.strip_prefix(&base_path).unwrap().to_str().unwrap();
-                let p = dir.path().join(p).unwrap();
+        for (rel, file_type) in glob_matches(&base_path, options)? {
+            let entry = FSEntry::new(dir.area().clone(), dir.path().join(&rel)?);
+            if file_type.is_dir() {
+                // Safety: We know this dir exists, we just checked
+                out.push(GlobMatch::Dir(unsafe { Dir::new(entry) }));
+            } else if file_type.is_file() {
                 // Safety: We know this file exists, we just checked
-                let file = unsafe { File::new(FSEntry::new(dir.area().clone(), p)) };
-                out.push(file);
+                out.push(GlobMatch::File(unsafe { File::new(entry) }));
             }
         }
         Ok(out)
@@ -605,77 +890,103 @@ impl DriverTrait for DriverImpl<'_> {
     }
 
     fn get_secret(&self, name: &str) -> Result<String, RunnerError> {
-        // TODO: Ask before accessing
-        match std::env::var(name) {
-            Ok(secret) => return Ok(secret),
-            Err(std::env::VarError::NotPresent) => {}
-            Err(std::env::VarError::NotUnicode(_)) => {
-                return Err(RunnerError::Makeshift("secret not utf8".into()));
-            }
-        }
-        match std::fs::read_to_string("secrets.toml") {
-            Ok(secrets_contents) => {
-                let toml: toml::Value = toml::from_str(&secrets_contents)
-                    .map_err(|_err| RunnerError::Makeshift("parse secrets.toml".into()))?;
-                return Ok(toml
-                    .get(name)
-                    .ok_or_else(|| RunnerError::Makeshift("secret is not present".into()))?
-                    .as_str()
-                    .ok_or_else(|| RunnerError::Makeshift("secret is not a string".into()))?
-                    .to_owned());
-            }
-            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
-            Err(err) => return Err(RunnerError::MakeshiftIO("open secrets.toml".into(), err)),
-        }
-        Err(RunnerError::Makeshift(
-            format!("secret {name:?} not found").into(),
-        ))
+        self.secrets
+            .get(&self.secret_allowlist, name)
+            .map_err(|err| RunnerError::Makeshift(err.to_string().into()))
     }
 
-    fn git_contents(&self, url: &str, commit: &str) -> Result<FileArea, RunnerError> {
+    fn git_contents(
+        &self,
+        url: &str,
+        commit: &str,
+        submodules: bool,
+    ) -> Result<FileArea, RunnerError> {
         let area = self.create_empty_area()?;
         let dir = Dir::root(area);
         let commit = Oid::from_str(commit)
             .map_err(|err| RunnerError::Makeshift(format!("parse commit hash: {err}").into()))?;
-        let mut fo = git2::FetchOptions::new();
-        let mut rcb = git2::RemoteCallbacks::new();
-        rcb.credentials(|_url, username_from_url, allowed_types| {
-            let username = username_from_url.unwrap_or("git");
-            if allowed_types.contains(git2::CredentialType::USERNAME) {
-                return git2::Cred::username(username);
-            }
-            Cred::ssh_key_from_agent(username)
-        });
-        fo.remote_callbacks(rcb);
-        let repo = git2::build::RepoBuilder::new()
-            .fetch_options(fo)
-            .with_checkout(git2::build::CheckoutBuilder::new())
-            .clone(url, &self.resolve_fs_entry(dir.inner()))
-            .map_err(|err| RunnerError::Makeshift(format!("clone repo: {err}").into()))?;
-        repo.set_head_detached(commit)
-            .map_err(|err| RunnerError::Makeshift(format!("set head: {err}").into()))?;
+        let mirror = crate::git_mirror::ensure_commit(&self.config, url, commit)
+            .map_err(|err| RunnerError::Makeshift(format!("update git mirror: {err}").into()))?;
+        let path = self.resolve_fs_entry(dir.inner());
+        crate::git_mirror::checkout(&mirror, url, commit, &path)
+            .map_err(|err| RunnerError::Makeshift(format!("checkout: {err}").into()))?;
+        if submodules {
+            let repo = git2::Repository::open(&path)
+                .map_err(|err| RunnerError::Makeshift(format!("open repo: {err}").into()))?;
+            update_submodules(&repo)?;
+        }
         Ok(dir.area().clone())
     }
 
-    fn git_lfs_smudge(&self, _area: &FileArea) -> Result<FileArea, RunnerError> {
-        // let dir = Dir::root(area.clone());
-        // let path = self.resolve_fs_entry(dir.inner());
-        // for entry in ignore::Walk::new(&path) {
-        //     let entry = entry.unwrap();
-        //     if !entry.file_type().unwrap().is_file() {
-        //         continue;
-        //     }
-        //     let Ok(lfs_entry) = git_lfs_rs::Pointer::from_path(entry.path()) else {
-        //         continue;
-        //     };
-        //     dbg!(lfs_entry);
-        // }
-
+    fn git_resolve_ref(&self, url: &str, git_ref: &str) -> Result<String, RunnerError> {
+        let mut remote = git2::Remote::create_detached(url)
+            .map_err(|err| RunnerError::Makeshift(format!("create remote: {err}").into()))?;
+        let connection = remote
+            .connect_auth(
+                git2::Direction::Fetch,
+                Some(crate::git_mirror::callbacks()),
+                None,
+            )
+            .map_err(|err| RunnerError::Makeshift(format!("connect to remote: {err}").into()))?;
+        let heads = connection
+            .list()
+            .map_err(|err| RunnerError::Makeshift(format!("list remote refs: {err}").into()))?;
+        let candidates = [
+            git_ref.to_owned(),
+            format!("refs/tags/{git_ref}"),
+            format!("refs/heads/{git_ref}"),
+        ];
+        for name in candidates {
+            // Annotated tags point at the tag object, the peeled entry points at the commit
+            let peeled = format!("{name}^{{}}");
+            let head = heads
+                .iter()
+                .find(|h| h.name() == peeled)
+                .or_else(|| heads.iter().find(|h| h.name() == name));
+            if let Some(head) = head {
+                return Ok(head.oid().to_string());
+            }
+        }
         Err(RunnerError::Makeshift(
-            "git lfs smudge unimplemented".into(),
+            format!("git ref {git_ref} not found in {url}").into(),
         ))
     }
 
+    fn git_lfs_smudge(
+        &self,
+        area: &FileArea,
+        options: &LfsOptions,
+    ) -> Result<FileArea, RunnerError> {
+        let src_path = self.resolve_fs_entry(Dir::root(area.clone()).inner());
+        let endpoint = match &options.endpoint {
+            Some(endpoint) => endpoint.clone(),
+            None => lfs_endpoint(&src_path)?,
+        };
+        let authorization = authorization(options.auth.as_ref(), options.auth_secret.as_deref())?;
+        let new_area =
+            self.create_overlay_area(std::iter::once(Dir::root(area.clone()).inner()), true, true)?;
+        let new_path = self.resolve_fs_entry(Dir::root(new_area.clone()).inner());
+        let mut pointers = Vec::new();
+        let walker = ignore::WalkBuilder::new(&new_path)
+            .standard_filters(false)
+            .filter_entry(|e| e.file_name() != ".git")
+            .build();
+        for entry in walker {
+            let entry = entry.map_err(|err| RunnerError::Makeshift(err.to_string().into()))?;
+            if !entry.file_type().is_some_and(|t| t.is_file()) {
+                continue;
+            }
+            let pointer = crate::git_lfs::Pointer::from_path(entry.path())
+                .map_err(|err| RunnerError::MakeshiftIO("read lfs pointer".into(), err))?;
+            if let Some(pointer) = pointer {
+                pointers.push((entry.into_path(), pointer));
+            }
+        }
+        crate::git_lfs::smudge(&endpoint, authorization.as_deref(), &pointers)
+            .map_err(|err| RunnerError::Makeshift(format!("git lfs smudge: {err}").into()))?;
+        Ok(new_area)
+    }
+
     fn env_var(&self, key: &str) -> Result<Option<String>, RunnerError> {
         Ok(std::env::var(key).ok())
     }
@@ -746,13 +1057,13 @@ impl DriverTrait for DriverImpl<'_> {
 impl MonitoringTrait for DriverImpl<'_> {
     fn enter_call(&self, s: &str) {
         if let Some(ph) = &self.enter_handler {
-            ph(s);
+            ph(&self.secrets.redact(s));
         }
     }
 
     fn exit_call(&self, s: &str) {
         if let Some(ph) = &self.exit_handler {
-            ph(s);
+            ph(&self.secrets.redact(s));
         }
     }
 
@@ -767,6 +1078,146 @@ impl MonitoringTrait for DriverImpl<'_> {
             ph(&format!("internal.{f:?}"));
         }
     }
+
+    fn output(&self, call: &str, stream: OutputStream, chunk: &[u8]) {
+        if let Some(oh) = &self.output_handler {
+            oh(
+                &self.secrets.redact(call),
+                stream,
+                &self.secrets.redact_bytes(chunk),
+            );
+        }
+    }
+}
+
+fn update_submodules(repo: &git2::Repository) -> Result<(), RunnerError> {
+    let submodules = repo
+        .submodules()
+        .map_err(|err| RunnerError::Makeshift(format!("list submodules: {err}").into()))?;
+    for mut submodule in submodules {
+        let mut options = git2::SubmoduleUpdateOptions::new();
+        options.fetch(crate::git_mirror::fetch_options());
+        submodule
+            .update(true, Some(&mut options))
+            .map_err(|err| RunnerError::Makeshift(format!("update submodule: {err}").into()))?;
+        let sub_repo = submodule
+            .open()
+            .map_err(|err| RunnerError::Makeshift(format!("open submodule: {err}").into()))?;
+        update_submodules(&sub_repo)?;
+    }
+    Ok(())
+}
+
+/// `Authorization` header value for `auth` using the secret it names
+fn authorization(
+    auth: Option<&DownloadAuth>,
+    secret: Option<&str>,
+) -> Result<Option<String>, RunnerError> {
+    let Some(auth) = auth else {
+        return Ok(None);
+    };
+    let Some(secret) = secret else {
+        return Err(RunnerError::Makeshift(
+            format!("secret {} for auth was not resolved", auth.secret_name()).into(),
+        ));
+    };
+    Ok(Some(match auth {
+        DownloadAuth::Bearer { .. } => format!("Bearer {secret}"),
+        DownloadAuth::Basic { username, .. } => {
+            use base64::Engine as _;
+            let credentials =
+                base64::engine::general_purpose::STANDARD.encode(format!("{username}:{secret}"));
+            format!("Basic {credentials}")
+        }
+    }))
+}
+
+/// The lfs endpoint of a checkout from its `.lfsconfig` or otherwise its origin remote
+fn lfs_endpoint(checkout: &Path) -> Result<String, RunnerError> {
+    let lfsconfig_path = checkout.join(".lfsconfig");
+    if lfsconfig_path.exists() {
+        let lfsconfig = git2::Config::open(&lfsconfig_path)
+            .map_err(|err| RunnerError::Makeshift(format!("open .lfsconfig: {err}").into()))?;
+        if let Ok(url) = lfsconfig.get_string("lfs.url") {
+            return Ok(url);
+        }
+    }
+    let repo = git2::Repository::open(checkout)
+        .map_err(|err| RunnerError::Makeshift(format!("open repo: {err}").into()))?;
+    let remote = repo
+        .find_remote("origin")
+        .map_err(|err| RunnerError::Makeshift(format!("find origin remote: {err}").into()))?;
+    let url = remote
+        .url()
+        .ok_or_else(|| RunnerError::Makeshift("origin remote url is not utf-8".into()))?;
+    crate::git_lfs::endpoint_from_remote(url)
+        .map_err(|err| RunnerError::Makeshift(err.to_string().into()))
+}
+
+#[cfg(target_family = "unix")]
+fn kill_process_group(child: &std::process::Child) -> std::io::Result<()> {
+    let pgid = libc::pid_t::try_from(child.id())
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, err))?;
+    // Safety: Sending a signal has no memory safety requirements
+    if unsafe { libc::kill(-pgid, libc::SIGKILL) } == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(target_family = "unix"))]
+fn kill_process_group(child: &mut std::process::Child) -> std::io::Result<()> {
+    child.kill()
+}
+
+#[cfg(target_family = "unix")]
+#[expect(clippy::unnecessary_wraps)]
+fn apply_limits(
+    cmd: &mut std::process::Command,
+    limits: ResourceLimits,
+) -> Result<(), RunnerError> {
+    use std::os::unix::process::CommandExt as _;
+
+    if limits.memory.is_none() && limits.cpu.is_none() {
+        return Ok(());
+    }
+    // Safety: The closure only makes system calls
+    unsafe {
+        cmd.pre_exec(move || {
+            for (resource, limit) in [
+                (libc::RLIMIT_AS, limits.memory),
+                (libc::RLIMIT_CPU, limits.cpu),
+            ] {
+                let Some(limit) = limit else {
+                    continue;
+                };
+                let rlimit = libc::rlimit {
+                    rlim_cur: limit,
+                    rlim_max: limit,
+                };
+                if libc::setrlimit(resource, &raw const rlimit) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+            Ok(())
+        });
+    }
+    Ok(())
+}
+
+#[cfg(not(target_family = "unix"))]
+fn apply_limits(
+    _cmd: &mut std::process::Command,
+    limits: ResourceLimits,
+) -> Result<(), RunnerError> {
+    if limits.memory.is_none() && limits.cpu.is_none() {
+        Ok(())
+    } else {
+        Err(RunnerError::Makeshift(
+            "resource limits are only supported on unix".into(),
+        ))
+    }
 }
 
 #[cfg(target_family = "unix")]
@@ -798,3 +1249,80 @@ fn find_bin_in_dir(dir: &Path, name: &str) -> Option<AbsolutePathBuf> {
         }
     })
 }
+
+/// Paths relative to `base_path` matched by a glob along with their file type, sorted so results are deterministic
+pub(crate) fn glob_matches(
+    base_path: &Path,
+    options: &GlobOptions,
+) -> Result<Vec<(String, std::fs::FileType)>, RunnerError> {
+    let mut include = globset::GlobSetBuilder::new();
+    let mut exclude = globset::GlobSetBuilder::new();
+    // Patterns that are all negations subtract from everything rather than from nothing
+    let include_all = options.patterns.iter().all(|p| p.starts_with('!'));
+    for pattern in &options.patterns {
+        let (builder, pattern) = match pattern.strip_prefix('!') {
+            Some(pattern) => (&mut exclude, pattern),
+            None => (&mut include, pattern.as_str()),
+        };
+        let glob = globset::GlobBuilder::new(pattern)
+            .literal_separator(true)
+            .build()
+            .map_err(|err| RunnerError::InvalidGlobPattern(Box::new(err)))?;
+        builder.add(glob);
+    }
+    let include = include
+        .build()
+        .map_err(|err| RunnerError::InvalidGlobPattern(Box::new(err)))?;
+    let exclude = exclude
+        .build()
+        .map_err(|err| RunnerError::InvalidGlobPattern(Box::new(err)))?;
+    let walker = ignore::WalkBuilder::new(base_path)
+        .standard_filters(options.respect_gitignore)
+        .build();
+    let mut out = Vec::new();
+    for entry in walker {
+        let entry = entry.map_err(|err| {
+            RunnerError::MakeshiftIO("glob walk".into(), std::io::Error::other(err))
+        })?;
+        if entry.depth() == 0 {
+            continue;
+        }
+        let Some(file_type) = entry.file_type() else {
+            continue;
+        };
+        if file_type.is_symlink() || (file_type.is_dir() && !options.include_dirs) {
+            continue;
+        }
+        let rel = relative_glob_path(base_path, entry.path())?;
+        if !(include_all || include.is_match(&rel)) || exclude.is_match(&rel) {
+            continue;
+        }
+        out.push((rel, file_type));
+    }
+    // Walk order depends on the filesystem so sort to keep results deterministic
+    out.sort_by(|(a, _), (b, _)| a.cmp(b));
+    Ok(out)
+}
+
+/// Walks the files of a directory that are copied into an area
+pub(crate) fn area_walker(path: &Path, include_hidden: bool) -> ignore::Walk {
+    ignore::WalkBuilder::new(path)
+        .hidden(!include_hidden)
+        .build()
+}
+
+/// Path of `path` relative to `base` joined with `/` so patterns match the same on every platform
+fn relative_glob_path(base: &Path, path: &Path) -> Result<String, RunnerError> {
+    let rel = path
+        .strip_prefix(base)
+        .map_err(|err| RunnerError::Makeshift(err.to_string().into()))?;
+    let components = rel
+        .components()
+        .map(|c| {
+            c.as_os_str()
+                .to_str()
+                .ok_or_else(|| RunnerError::Makeshift("glob path is not valid utf8".into()))
+        })
+        .collect::<Result<Vec<&str>, RunnerError>>()?;
+    Ok(components.join("/"))
+}
@@ -0,0 +1,107 @@
+use std::{
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use rain_lang::{
+    afs::{area::FileArea, entry::FSEntry},
+    runner::dep::{ContentsFilter, Fingerprint},
+};
+use sha2::Digest as _;
+
+/// Fingerprint whatever is at `path`, directory contents are only hashed if `contents` is set and then only the entries it selects
+pub fn fingerprint(path: &Path, contents: Option<&ContentsFilter>) -> std::io::Result<Fingerprint> {
+    let metadata = match std::fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Fingerprint::Missing),
+        Err(err) => return Err(err),
+    };
+    if metadata.is_file() {
+        Ok(Fingerprint::File {
+            size: metadata.len(),
+            modified: metadata.modified()?,
+        })
+    } else if let Some(filter) = contents {
+        let mut hasher = sha2::Sha256::new();
+        match filter {
+            ContentsFilter::Area => {
+                for entry in crate::driver::area_walker(path, true) {
+                    let entry = entry.map_err(std::io::Error::other)?;
+                    if !entry.file_type().is_some_and(|t| t.is_file()) {
+                        continue;
+                    }
+                    let rel = entry
+                        .path()
+                        .strip_prefix(path)
+                        .map_err(std::io::Error::other)?;
+                    hash_entry(
+                        &mut hasher,
+                        &rel.to_string_lossy(),
+                        &entry.metadata().map_err(std::io::Error::other)?,
+                    )?;
+                }
+            }
+            ContentsFilter::Glob(options) => {
+                let matches =
+                    crate::driver::glob_matches(path, options).map_err(std::io::Error::other)?;
+                for (rel, _) in matches {
+                    hash_entry(
+                        &mut hasher,
+                        &rel,
+                        &std::fs::symlink_metadata(path.join(&rel))?,
+                    )?;
+                }
+            }
+        }
+        Ok(Fingerprint::DirContents {
+            filter: filter.clone(),
+            digest: base16::encode_lower(&hasher.finalize()),
+        })
+    } else {
+        Ok(Fingerprint::Dir)
+    }
+}
+
+fn hash_entry(
+    hasher: &mut sha2::Sha256,
+    relative_path: &str,
+    metadata: &std::fs::Metadata,
+) -> std::io::Result<()> {
+    hasher.update(relative_path.as_bytes());
+    hasher.update([0]);
+    if metadata.is_dir() {
+        hasher.update(b"d");
+    } else {
+        hasher.update(b"f");
+        hasher.update(metadata.len().to_le_bytes());
+        let modified = metadata
+            .modified()?
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default();
+        hasher.update(modified.as_nanos().to_le_bytes());
+    }
+    hasher.update([0]);
+    Ok(())
+}
+
+fn local_path(entry: &FSEntry) -> Option<PathBuf> {
+    let FileArea::Local(root) = &entry.area else {
+        return None;
+    };
+    let rel_path = entry.path.path().strip_prefix('/')?;
+    Some(root.join(rel_path))
+}
+
+/// Whether a local entry still has the fingerprint it was recorded with
+pub fn is_current(entry: &FSEntry, fingerprint: &Fingerprint) -> bool {
+    let Some(path) = local_path(entry) else {
+        return false;
+    };
+    match self::fingerprint(&path, fingerprint.contents_filter()) {
+        Ok(current) => &current == fingerprint,
+        Err(err) => {
+            log::debug!("could not fingerprint {entry}: {err}");
+            false
+        }
+    }
+}
@@ -0,0 +1,344 @@
+//! Replace git lfs pointer files with the objects they point to using the lfs batch api
+//!
+//! See <https://github.com/git-lfs/git-lfs/blob/main/docs/api/batch.md>
+
+use std::{
+    collections::HashMap,
+    io::Write as _,
+    path::{Path, PathBuf},
+};
+
+use sha2::Digest as _;
+
+const POINTER_VERSION: &str = "version https://git-lfs.github.com/spec/v1";
+/// Pointer files are always smaller than this so larger files don't need to be read
+pub const MAX_POINTER_SIZE: u64 = 1024;
+const LFS_CONTENT_TYPE: &str = "application/vnd.git-lfs+json";
+
+#[derive(Debug, thiserror::Error)]
+pub enum LfsError {
+    #[error("http: {0}")]
+    Http(#[from] ureq::Error),
+    #[error("io: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("batch request failed with status {0}")]
+    BatchStatus(u16),
+    #[error("object {oid}: {message}")]
+    Object { oid: String, message: String },
+    #[error("object {oid} did not match its pointer")]
+    ObjectMismatch { oid: String },
+    #[error("can't work out the lfs endpoint for {0}")]
+    UnknownEndpoint(String),
+}
+
+/// Contents of a git lfs pointer file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pointer {
+    /// Hex encoded sha256 of the object
+    pub oid: String,
+    pub size: u64,
+}
+
+impl Pointer {
+    pub fn parse(contents: &str) -> Option<Self> {
+        let mut lines = contents.lines();
+        if lines.next()? != POINTER_VERSION {
+            return None;
+        }
+        let mut oid = None;
+        let mut size = None;
+        for line in lines {
+            let (key, value) = line.split_once(' ')?;
+            match key {
+                "oid" => oid = Some(value.strip_prefix("sha256:")?),
+                "size" => size = Some(value.parse().ok()?),
+                // Pointers may have extension keys we don't need
+                _ => {}
+            }
+        }
+        let oid = oid?;
+        if oid.len() != 64 || !oid.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return None;
+        }
+        Some(Self {
+            oid: oid.to_ascii_lowercase(),
+            size: size?,
+        })
+    }
+
+    /// Read a pointer from a file, returns none if the file is not a pointer
+    pub fn from_path(path: &Path) -> std::io::Result<Option<Self>> {
+        if std::fs::metadata(path)?.len() >= MAX_POINTER_SIZE {
+            return Ok(None);
+        }
+        let contents = std::fs::read(path)?;
+        Ok(std::str::from_utf8(&contents).ok().and_then(Self::parse))
+    }
+}
+
+/// Work out the lfs endpoint of a git remote following the same rules as git lfs
+pub fn endpoint_from_remote(url: &str) -> Result<String, LfsError> {
+    let base = if url.starts_with("https://") || url.starts_with("http://") {
+        url.to_owned()
+    } else if let Some(rest) = url.strip_prefix("ssh://") {
+        let rest = rest.split_once('@').map_or(rest, |(_, rest)| rest);
+        format!("https://{rest}")
+    } else if let Some((user_host, path)) = url.split_once(':') {
+        // scp like syntax git@github.com:owner/repo.git
+        let host = user_host
+            .split_once('@')
+            .map_or(user_host, |(_, host)| host);
+        format!("https://{host}/{path}")
+    } else {
+        return Err(LfsError::UnknownEndpoint(url.to_owned()));
+    };
+    let base = base.trim_end_matches('/');
+    let base = base.strip_suffix(".git").unwrap_or(base);
+    Ok(format!("{base}.git/info/lfs"))
+}
+
+#[derive(Debug, serde::Serialize)]
+struct BatchRequest<'a> {
+    operation: &'static str,
+    transfers: &'static [&'static str],
+    objects: Vec<BatchObject<'a>>,
+    hash_algo: &'static str,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct BatchObject<'a> {
+    oid: &'a str,
+    size: u64,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct BatchResponse {
+    objects: Vec<BatchResponseObject>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct BatchResponseObject {
+    oid: String,
+    #[serde(default)]
+    actions: Option<BatchActions>,
+    #[serde(default)]
+    error: Option<BatchObjectError>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct BatchActions {
+    download: Option<BatchAction>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct BatchAction {
+    href: String,
+    #[serde(default)]
+    header: HashMap<String, String>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct BatchObjectError {
+    message: String,
+}
+
+/// Replace each pointer file with its object, every object is checked against its pointer before it replaces the pointer
+///
+/// `authorization` is only sent to the batch api, object downloads get the headers it returns. Each object is downloaded once however many pointers there are to it.
+pub fn smudge(
+    endpoint: &str,
+    authorization: Option<&str>,
+    pointers: &[(PathBuf, Pointer)],
+) -> Result<(), LfsError> {
+    if pointers.is_empty() {
+        return Ok(());
+    }
+    let agent = ureq::Agent::new_with_config(
+        ureq::config::Config::builder()
+            .http_status_as_error(false)
+            .build(),
+    );
+    let mut objects: Vec<BatchObject<'_>> = Vec::new();
+    for (_, pointer) in pointers {
+        if !objects.iter().any(|o| o.oid == pointer.oid) {
+            objects.push(BatchObject {
+                oid: &pointer.oid,
+                size: pointer.size,
+            });
+        }
+    }
+    log::debug!("requesting {} lfs objects from {endpoint}", objects.len());
+    let mut request = agent
+        .post(format!("{}/objects/batch", endpoint.trim_end_matches('/')))
+        .header("Accept", LFS_CONTENT_TYPE);
+    if let Some(authorization) = authorization {
+        request = request.header("Authorization", authorization);
+    }
+    let mut response = request
+        .content_type(LFS_CONTENT_TYPE)
+        .send_json(BatchRequest {
+            operation: "download",
+            transfers: &["basic"],
+            objects,
+            hash_algo: "sha256",
+        })?;
+    if !response.status().is_success() {
+        return Err(LfsError::BatchStatus(response.status().as_u16()));
+    }
+    let batch: BatchResponse = response.body_mut().read_json()?;
+    let downloads: HashMap<&str, &BatchResponseObject> =
+        batch.objects.iter().map(|o| (o.oid.as_str(), o)).collect();
+    // Where each object has already been written so later pointers to it are copied from there
+    let mut smudged: HashMap<&str, &Path> = HashMap::new();
+    for (path, pointer) in pointers {
+        if let Some(first) = smudged.get(pointer.oid.as_str()) {
+            copy_object(first, pointer, path)?;
+            continue;
+        }
+        let object = downloads
+            .get(pointer.oid.as_str())
+            .ok_or_else(|| LfsError::Object {
+                oid: pointer.oid.clone(),
+                message: String::from("missing from batch response"),
+            })?;
+        if let Some(err) = &object.error {
+            return Err(LfsError::Object {
+                oid: pointer.oid.clone(),
+                message: err.message.clone(),
+            });
+        }
+        let action = object
+            .actions
+            .as_ref()
+            .and_then(|a| a.download.as_ref())
+            .ok_or_else(|| LfsError::Object {
+                oid: pointer.oid.clone(),
+                message: String::from("no download action"),
+            })?;
+        download_object(&agent, action, pointer, path)?;
+        smudged.insert(&pointer.oid, path);
+    }
+    Ok(())
+}
+
+/// Replace a pointer with a copy of its object already written to `from`
+fn copy_object(from: &Path, pointer: &Pointer, path: &Path) -> Result<(), LfsError> {
+    let partial_path = partial_path(pointer, path)?;
+    let result =
+        std::fs::copy(from, &partial_path).and_then(|_| std::fs::rename(&partial_path, path));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&partial_path);
+    }
+    Ok(result?)
+}
+
+/// Objects are written next to their pointer and renamed so a partial object never replaces it, the oid keeps the name unique in the directory
+fn partial_path(pointer: &Pointer, path: &Path) -> Result<PathBuf, LfsError> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| LfsError::Object {
+            oid: pointer.oid.clone(),
+            message: String::from("pointer path has no file name"),
+        })?
+        .to_string_lossy();
+    Ok(path.with_file_name(format!("{file_name}.{}.lfs-partial", pointer.oid)))
+}
+
+fn download_object(
+    agent: &ureq::Agent,
+    action: &BatchAction,
+    pointer: &Pointer,
+    path: &Path,
+) -> Result<(), LfsError> {
+    let mut request = agent.get(&action.href);
+    for (key, value) in &action.header {
+        request = request.header(key, value);
+    }
+    let mut response = request.call()?;
+    if !response.status().is_success() {
+        return Err(ureq::Error::StatusCode(response.status().as_u16()).into());
+    }
+    let partial_path = partial_path(pointer, path)?;
+    let result = write_object(&mut response, pointer, &partial_path)
+        .and_then(|()| Ok(std::fs::rename(&partial_path, path)?));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&partial_path);
+    }
+    result
+}
+
+fn write_object(
+    response: &mut ureq::http::Response<ureq::Body>,
+    pointer: &Pointer,
+    partial_path: &Path,
+) -> Result<(), LfsError> {
+    let mut hasher = sha2::Sha256::new();
+    let mut size = 0;
+    let mut f = std::fs::File::create(partial_path)?;
+    let mut reader = response.body_mut().as_reader();
+    let mut buf = [0u8; 8192];
+    loop {
+        let n = match std::io::Read::read(&mut reader, &mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err.into()),
+        };
+        hasher.update(&buf[..n]);
+        size += n as u64;
+        f.write_all(&buf[..n])?;
+    }
+    f.flush()?;
+    if size != pointer.size || base16::encode_lower(&hasher.finalize()) != pointer.oid {
+        return Err(LfsError::ObjectMismatch {
+            oid: pointer.oid.clone(),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_pointer() {
+        let pointer = Pointer::parse(
+            "version https://git-lfs.github.com/spec/v1\noid sha256:4d7a214614ab2935c943f9e0ff69d22eadbb8f32b1258daaa5e2ca24d17e2393\nsize 12345\n",
+        );
+        assert_eq!(
+            pointer,
+            Some(Pointer {
+                oid: String::from(
+                    "4d7a214614ab2935c943f9e0ff69d22eadbb8f32b1258daaa5e2ca24d17e2393"
+                ),
+                size: 12345,
+            })
+        );
+        assert_eq!(Pointer::parse("hello world\n"), None);
+        assert_eq!(
+            Pointer::parse("version https://git-lfs.github.com/spec/v1\noid sha256:abc\nsize 1\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn endpoints() {
+        assert_eq!(
+            endpoint_from_remote("https://github.com/owner/repo").unwrap(),
+            "https://github.com/owner/repo.git/info/lfs"
+        );
+        assert_eq!(
+            endpoint_from_remote("https://github.com/owner/repo.git").unwrap(),
+            "https://github.com/owner/repo.git/info/lfs"
+        );
+        assert_eq!(
+            endpoint_from_remote("git@github.com:owner/repo.git").unwrap(),
+            "https://github.com/owner/repo.git/info/lfs"
+        );
+        assert_eq!(
+            endpoint_from_remote("ssh://git@github.com/owner/repo").unwrap(),
+            "https://github.com/owner/repo.git/info/lfs"
+        );
+    }
+}
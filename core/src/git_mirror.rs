@@ -0,0 +1,151 @@
+//! Store of bare git mirrors under the cache directory
+//!
+//! Each remote is mirrored once and later fetches only download what is new, checkouts of specific commits are cloned from the mirror
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, LazyLock, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use git2::{Cred, Oid, Repository};
+use poison_panic::MutexExt as _;
+use sha2::Digest as _;
+
+use crate::{cache::PruneStats, config::Config};
+
+/// Mirrors that haven't been used for this long are removed by prune
+pub const MIRROR_MAX_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+/// File in each mirror touched whenever it is used
+const LAST_USED_FILE: &str = "rain-last-used";
+const MIRROR_REFSPECS: &[&str] = &["+refs/heads/*:refs/heads/*", "+refs/tags/*:refs/tags/*"];
+
+/// Mirrors are shared by every run in the server so updates to the same mirror must not overlap, different mirrors can be fetched at the same time
+static MIRROR_LOCKS: LazyLock<Mutex<HashMap<PathBuf, Arc<Mutex<()>>>>> =
+    LazyLock::new(Mutex::default);
+
+fn mirror_lock(path: &Path) -> Arc<Mutex<()>> {
+    Arc::clone(MIRROR_LOCKS.plock().entry(path.to_path_buf()).or_default())
+}
+
+pub fn mirror_path(config: &Config, url: &str) -> PathBuf {
+    config
+        .git_mirror_dir()
+        .join(base16::encode_lower(&sha2::Sha256::digest(url)))
+}
+
+/// Make sure the mirror of `url` contains `commit`, fetching from the remote only if it doesn't already
+pub fn ensure_commit(config: &Config, url: &str, commit: Oid) -> Result<PathBuf, git2::Error> {
+    let path = mirror_path(config, url);
+    let lock = mirror_lock(&path);
+    let _guard = lock.plock();
+    let repo = open_or_init(&path, url)?;
+    if repo.find_commit(commit).is_err() {
+        log::debug!("updating git mirror of {url} at {}", path.display());
+        let mut remote = repo.find_remote("origin")?;
+        // Keep a ref to the commit so it is kept by the mirror
+        let by_hash = [format!("+{commit}:refs/rain/{commit}")];
+        let mut fo = fetch_options();
+        fo.depth(1);
+        if let Err(err) = remote.fetch(&by_hash, Some(&mut fo), None) {
+            // Not every server allows fetching by hash, fall back to fetching all branches and tags
+            log::debug!("shallow fetch of {commit} failed, fetching all refs: {err}");
+            remote.fetch(MIRROR_REFSPECS, Some(&mut fetch_options()), None)?;
+            if repo.find_commit(commit).is_err() {
+                log::debug!("{commit} not reachable from refs, fetching it by hash");
+                remote.fetch(&by_hash, Some(&mut fetch_options()), None)?;
+            }
+        }
+    }
+    if let Err(err) = std::fs::write(path.join(LAST_USED_FILE), []) {
+        log::warn!("could not mark git mirror used: {err}");
+    }
+    Ok(path)
+}
+
+fn open_or_init(path: &Path, url: &str) -> Result<Repository, git2::Error> {
+    if let Ok(repo) = Repository::open_bare(path) {
+        return Ok(repo);
+    }
+    let repo = Repository::init_bare(path)?;
+    repo.remote("origin", url)?;
+    Ok(repo)
+}
+
+/// Check out `commit` from the mirror at `mirror` into `dest`, `dest` gets its own repo with `url` as its origin
+pub fn checkout(mirror: &Path, url: &str, commit: Oid, dest: &Path) -> Result<(), git2::Error> {
+    // Local clones hard link the mirror's objects where they can so are cheap
+    let repo = git2::build::RepoBuilder::new()
+        .clone_local(git2::build::CloneLocal::Local)
+        .with_checkout({
+            let mut checkout = git2::build::CheckoutBuilder::new();
+            checkout.dry_run();
+            checkout
+        })
+        .clone(&mirror.to_string_lossy(), dest)?;
+    repo.remote_set_url("origin", url)?;
+    let object = repo.find_object(commit, None)?;
+    repo.checkout_tree(&object, Some(git2::build::CheckoutBuilder::new().force()))?;
+    repo.set_head_detached(commit)?;
+    Ok(())
+}
+
+pub fn callbacks() -> git2::RemoteCallbacks<'static> {
+    let mut rcb = git2::RemoteCallbacks::new();
+    rcb.credentials(|_url, username_from_url, allowed_types| {
+        let username = username_from_url.unwrap_or("git");
+        if allowed_types.contains(git2::CredentialType::USERNAME) {
+            return Cred::username(username);
+        }
+        Cred::ssh_key_from_agent(username)
+    });
+    rcb
+}
+
+pub fn fetch_options() -> git2::FetchOptions<'static> {
+    let mut fo = git2::FetchOptions::new();
+    fo.remote_callbacks(callbacks());
+    fo
+}
+
+/// Remove mirrors that have not been used for `max_age`
+pub fn prune(config: &Config, max_age: Duration) -> std::io::Result<PruneStats> {
+    let mut stats = PruneStats { size: 0, errors: 0 };
+    let dir = config.git_mirror_dir();
+    if !dir.exists() {
+        return Ok(stats);
+    }
+    let now = SystemTime::now();
+    for entry in std::fs::read_dir(&dir)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+        let path = entry.path();
+        let lock = mirror_lock(&path);
+        let _guard = lock.plock();
+        let last_used = std::fs::metadata(path.join(LAST_USED_FILE))
+            .and_then(|m| m.modified())
+            .ok();
+        if last_used
+            .and_then(|t| now.duration_since(t).ok())
+            .is_some_and(|age| age < max_age)
+        {
+            log::info!("Not Pruning git mirror {}", path.display());
+            continue;
+        }
+        log::info!("Pruning git mirror {}", path.display());
+        match crate::cache::remove_recursive(&path) {
+            Ok(s) => stats.size += s,
+            Err(err) => {
+                log::error!(
+                    "Failed to prune git mirror {} because {err}",
+                    path.display()
+                );
+                stats.errors += 1;
+            }
+        }
+    }
+    Ok(stats)
+}
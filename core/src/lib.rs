@@ -1,8 +1,16 @@
 pub mod cache;
 pub mod config;
+pub mod download;
 pub mod driver;
+pub mod fingerprint;
+pub mod git_lfs;
+pub mod git_mirror;
+#[cfg(target_os = "linux")]
+pub mod sandbox;
+pub mod secrets;
 
 use std::{
+    collections::HashMap,
     path::Path,
     sync::{Arc, Mutex},
 };
@@ -11,15 +19,28 @@ pub use rain_lang;
 
 use driver::DriverImpl;
 use rain_lang::{
-    afs::entry::FSEntryTrait as _, driver::FSTrait as _, error::OwnedResolvedError,
+    afs::{
+        entry::{FSEntry, FSEntryTrait as _},
+        file::File,
+        path::SealedFilePath,
+    },
+    driver::FSTrait as _,
+    error::OwnedResolvedError,
+    ir::lint::{LintConfig, LintLevel},
     runner::value::Value,
 };
 use serde::{Deserialize, Serialize};
 
 #[expect(clippy::result_unit_err, clippy::print_stderr)]
 pub fn run_stderr(path: impl AsRef<Path>, declaration: &str) -> Result<Value, ()> {
-    let driver = DriverImpl::new(config::Config::default());
-    let cache = cache::Cache::default();
+    let mut driver = DriverImpl::new(config::Config::default());
+    if let Some(project_dir) = path.as_ref().parent() {
+        match secrets::SecretAllowlist::load(project_dir) {
+            Ok(allowlist) => driver.secret_allowlist = allowlist,
+            Err(err) => log::warn!("{err}"),
+        }
+    }
+    let cache = cache::Cache::default().with_secrets(Arc::clone(&driver.secrets));
     run(path, declaration, &cache, &driver).map_err(|err| {
         eprintln!("{err}");
     })
@@ -36,20 +57,77 @@ pub fn run(
     let path = driver.resolve_fs_entry(file.inner());
     let src = std::fs::read_to_string(&path).map_err(|err| CoreError::Other(err.to_string()))?;
     let module = rain_lang::ast::parser::parse_module(&src);
-    let mut ir = rain_lang::ir::Rir::new();
+    let ir = rain_lang::ir::Rir::new();
     let mid = ir
         .insert_module(Some(file), src, module)
-        .map_err(|err| CoreError::LangError(Box::new(err.resolve_ir(&ir).into_owned())))?;
+        .map_err(|err| CoreError::LangError(Box::new(err.resolve_ir(&ir))))?;
     let main = ir
         .resolve_global_declaration(mid, declaration)
         .ok_or_else(|| CoreError::Other(String::from("declaration does not exist")))?;
-    let mut runner = rain_lang::runner::Runner::new(&mut ir, cache, driver);
+    cache.secrets.forget_lookups();
+    driver.secrets.forget_lookups();
+    let mut runner = rain_lang::runner::Runner::new(&ir, cache, driver);
     let value = runner
         .evaluate_and_call(main, &[])
-        .map_err(|err| CoreError::LangError(Box::new(err.resolve_ir(runner.ir).into_owned())))?;
+        .map_err(|err| CoreError::LangError(Box::new(err.resolve_ir(runner.ir))))
+        .map_err(|err| err.redact(&driver.secrets))?;
     Ok(value)
 }
 
+/// Statically type check the module at `path` without running anything
+pub fn check_types(
+    path: impl AsRef<Path>,
+    driver: &DriverImpl,
+) -> Result<Vec<OwnedResolvedError>, CoreError> {
+    let file = rain_lang::afs::file::File::new_local(path.as_ref())
+        .map_err(|err| CoreError::Other(err.to_string()))?;
+    let path = driver.resolve_fs_entry(file.inner());
+    let src = std::fs::read_to_string(&path).map_err(|err| CoreError::Other(err.to_string()))?;
+    let module = rain_lang::ast::parser::parse_module(&src);
+    let ir = rain_lang::ir::Rir::new();
+    let mid = ir
+        .insert_module(Some(file), src, module)
+        .map_err(|err| CoreError::LangError(Box::new(err.resolve_ir(&ir))))?;
+    Ok(rain_lang::ir::typecheck::check_module(&ir.get_module(mid))
+        .into_iter()
+        .map(|err| err.upgrade(mid).resolve_ir(&ir))
+        .collect())
+}
+
+/// Lint the module at `path` and the local modules it statically imports, returning the lints with their level
+pub fn lint(
+    path: impl AsRef<Path>,
+    driver: &DriverImpl,
+    config: &LintConfig,
+) -> Result<Vec<(LintLevel, OwnedResolvedError)>, CoreError> {
+    let file = File::new_local(path.as_ref()).map_err(|err| CoreError::Other(err.to_string()))?;
+    let path = driver.resolve_fs_entry(file.inner());
+    let src = std::fs::read_to_string(&path).map_err(|err| CoreError::Other(err.to_string()))?;
+    let module = rain_lang::ast::parser::parse_module(&src);
+    let ir = rain_lang::ir::Rir::new();
+    let mid = ir
+        .insert_module(Some(file.clone()), src, module)
+        .map_err(|err| CoreError::LangError(Box::new(err.resolve_ir(&ir))))?;
+    let mut loaded = HashMap::from([(file, mid)]);
+    let errors = rain_lang::ir::lint::lint(&ir, mid, config, |module, import_path| {
+        let area = module.file.as_ref()?.area().clone();
+        let entry = FSEntry::new(area, SealedFilePath::new(import_path).ok()?);
+        let file = File::new_checked(driver, entry)?;
+        if let Some(mid) = loaded.get(&file) {
+            return Some(*mid);
+        }
+        let src = std::fs::read_to_string(driver.resolve_fs_entry(file.inner())).ok()?;
+        let module = rain_lang::ast::parser::parse_module(&src);
+        let mid = ir.insert_module(Some(file.clone()), src, module).ok()?;
+        loaded.insert(file, mid);
+        Some(mid)
+    });
+    Ok(errors
+        .into_iter()
+        .map(|err| (err.err.level, err.resolve_ir(&ir)))
+        .collect())
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub enum CoreError {
     LangError(Box<OwnedResolvedError>),
@@ -57,6 +135,21 @@ pub enum CoreError {
     Other(String),
 }
 
+impl CoreError {
+    /// Remove any secrets that have been handed out from the error
+    #[must_use]
+    pub fn redact(self, secrets: &secrets::SecretStore) -> Self {
+        match self {
+            Self::LangError(mut err) => {
+                err.redact(|s| secrets.redact(s).into_owned());
+                Self::LangError(err)
+            }
+            Self::Other(s) => Self::Other(secrets.redact(&s).into_owned()),
+            Self::UnknownDeclaration(_) => self,
+        }
+    }
+}
+
 impl std::fmt::Display for CoreError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -84,21 +177,33 @@ pub fn find_main_rain() -> Option<std::path::PathBuf> {
 
 pub fn load_cache_or_default(config: &config::Config) -> (cache::Cache, rain_lang::ir::Rir) {
     let stats = cache::CacheStats::default();
-    let mut ir = rain_lang::ir::Rir::new();
+    let ir = rain_lang::ir::Rir::new();
+    let remote = config
+        .remote_cache_url
+        .as_deref()
+        .map(|url| cache::remote::RemoteCache::new(config.clone(), url));
     match cache::persistent::PersistCache::load(&config.cache_json_path()) {
         Ok(p) => {
-            let core = p.depersist(config, &stats, &mut ir);
+            let core = p.depersist(config, &stats, &ir);
             (
                 cache::Cache {
                     core: Arc::new(Mutex::new(core)),
                     stats: Arc::new(stats),
+                    remote: remote.map(Arc::new),
+                    secrets: Arc::new(secrets::SecretStore::from_config(&config.secrets)),
                 },
                 ir,
             )
         }
         Err(err) => {
             log::info!("failed to load persist cache: {err}");
-            (cache::Cache::default(), ir)
+            let secrets = Arc::new(secrets::SecretStore::from_config(&config.secrets));
+            (
+                cache::Cache::default()
+                    .with_remote(remote)
+                    .with_secrets(secrets),
+                ir,
+            )
         }
     }
 }
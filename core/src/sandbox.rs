@@ -0,0 +1,392 @@
+//! Run commands inside Linux user, mount, PID and network namespaces so they can only see their inputs
+//!
+//! A fresh tmpfs becomes the root filesystem of the command, the declared inputs and the host's system directories are bind mounted read only at their usual paths and only the output area is writable.
+//! The command is the init process of its own PID namespace with a fresh `/proc` and `/dev` only holds a few harmless devices.
+
+use std::{
+    ffi::CString,
+    io::ErrorKind,
+    os::unix::{ffi::OsStrExt as _, process::CommandExt as _},
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// Host directories visible read only so dynamically linked programs can still run
+const SYSTEM_DIRS: &[&str] = &["/usr", "/bin", "/sbin", "/lib", "/lib32", "/lib64", "/etc"];
+
+/// Host devices bind mounted read only into an otherwise empty `/dev`
+const DEVICES: &[&str] = &[
+    "/dev/null",
+    "/dev/zero",
+    "/dev/random",
+    "/dev/urandom",
+    "/dev/tty",
+];
+
+/// Symlinks created in `/dev` that programs commonly expect
+const DEV_SYMLINKS: &[(&std::ffi::CStr, &str)] = &[
+    (c"/proc/self/fd", "fd"),
+    (c"/proc/self/fd/0", "stdin"),
+    (c"/proc/self/fd/1", "stdout"),
+    (c"/proc/self/fd/2", "stderr"),
+];
+
+pub struct Sandbox {
+    /// Empty directory the sandbox root is mounted on, it must exist until the command exits
+    pub root: PathBuf,
+    /// Paths visible read only
+    pub read_only: Vec<PathBuf>,
+    /// Path visible and writable, the command is started in this directory
+    pub writable: PathBuf,
+    pub network: bool,
+}
+
+/// How a path is visible in the sandbox, when the same path is listed twice the first access wins
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Access {
+    ReadOnly,
+    Writable,
+    /// An empty tmpfs
+    Scratch,
+    /// A host device node, visible read only
+    Device,
+    /// A procfs for the sandbox's PID namespace
+    Proc,
+}
+
+enum MountKind {
+    Bind {
+        source: CString,
+        /// Flags to remount with to make the mount read only, this has to include flags locked by the original mount
+        read_only_flags: Option<libc::c_ulong>,
+    },
+    Tmpfs,
+    Proc,
+}
+
+struct Mount {
+    /// Directories to create before mounting ending with the target
+    dirs: Vec<CString>,
+    /// The target is created as an empty file rather than a directory, used for device nodes
+    file: bool,
+    kind: MountKind,
+}
+
+/// Everything the child needs precomputed because it can't allocate between fork and exec
+struct Plan {
+    namespaces: libc::c_int,
+    uid_map: Vec<u8>,
+    gid_map: Vec<u8>,
+    root: CString,
+    mounts: Vec<Mount>,
+    /// Symlinks to create after mounting as pairs of target and link path
+    symlinks: Vec<(&'static std::ffi::CStr, CString)>,
+    cwd: CString,
+}
+
+impl Sandbox {
+    /// Set up `cmd` to enter the sandbox after it is spawned
+    pub fn apply(&self, cmd: &mut Command) -> std::io::Result<()> {
+        let plan = self.plan()?;
+        // Safety: The closure only makes system calls and uses memory allocated before the fork
+        unsafe {
+            cmd.pre_exec(move || plan.enter());
+        }
+        Ok(())
+    }
+
+    fn plan(&self) -> std::io::Result<Plan> {
+        let mut targets: Vec<(&Path, Access)> = SYSTEM_DIRS
+            .iter()
+            .map(Path::new)
+            .filter(|p| p.exists())
+            .map(|p| (p, Access::ReadOnly))
+            .collect();
+        targets.extend(
+            self.read_only
+                .iter()
+                .map(|p| (p.as_path(), Access::ReadOnly)),
+        );
+        targets.push((&self.writable, Access::Writable));
+        targets.push((Path::new("/dev"), Access::Scratch));
+        targets.extend(
+            DEVICES
+                .iter()
+                .map(Path::new)
+                .filter(|p| p.exists())
+                .map(|p| (p, Access::Device)),
+        );
+        targets.push((Path::new("/proc"), Access::Proc));
+        targets.push((Path::new("/tmp"), Access::Scratch));
+        // Parents must be mounted before their children so they don't hide them
+        targets.sort_by(|(a, a_access), (b, b_access)| {
+            a.components()
+                .count()
+                .cmp(&b.components().count())
+                .then_with(|| a.cmp(b))
+                .then_with(|| a_access.cmp(b_access))
+        });
+        targets.dedup_by_key(|(p, _)| *p);
+        let mounts = targets
+            .into_iter()
+            .map(|(path, access)| {
+                let kind = match access {
+                    Access::ReadOnly | Access::Device => MountKind::Bind {
+                        source: cstring(path)?,
+                        read_only_flags: Some(
+                            locked_flags(path)?
+                                | libc::MS_BIND
+                                | libc::MS_REMOUNT
+                                | libc::MS_RDONLY,
+                        ),
+                    },
+                    Access::Writable => MountKind::Bind {
+                        source: cstring(path)?,
+                        read_only_flags: None,
+                    },
+                    Access::Scratch => MountKind::Tmpfs,
+                    Access::Proc => MountKind::Proc,
+                };
+                Ok(Mount {
+                    dirs: self.target_dirs(path)?,
+                    file: access == Access::Device,
+                    kind,
+                })
+            })
+            .collect::<std::io::Result<Vec<Mount>>>()?;
+        let symlinks = DEV_SYMLINKS
+            .iter()
+            .map(|(target, name)| Ok((*target, cstring(&self.root.join("dev").join(name))?)))
+            .collect::<std::io::Result<_>>()?;
+        let mut namespaces = libc::CLONE_NEWUSER | libc::CLONE_NEWNS | libc::CLONE_NEWPID;
+        if !self.network {
+            namespaces |= libc::CLONE_NEWNET;
+        }
+        // Safety: These calls can't fail
+        let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+        Ok(Plan {
+            namespaces,
+            uid_map: format!("{uid} {uid} 1").into_bytes(),
+            gid_map: format!("{gid} {gid} 1").into_bytes(),
+            root: cstring(&self.root)?,
+            mounts,
+            symlinks,
+            cwd: cstring(&self.writable)?,
+        })
+    }
+
+    /// Every directory from the sandbox root down to where `path` is mounted
+    fn target_dirs(&self, path: &Path) -> std::io::Result<Vec<CString>> {
+        let mut target = self.root.clone();
+        let mut dirs = Vec::new();
+        for component in path.components().skip(1) {
+            target.push(component);
+            dirs.push(cstring(&target)?);
+        }
+        Ok(dirs)
+    }
+}
+
+impl Plan {
+    fn enter(&self) -> std::io::Result<()> {
+        // Safety: All pointers passed are to nul terminated strings that outlive the calls
+        unsafe {
+            check(libc::unshare(self.namespaces))?;
+            write_file(c"/proc/self/setgroups", b"deny")?;
+            write_file(c"/proc/self/uid_map", &self.uid_map)?;
+            write_file(c"/proc/self/gid_map", &self.gid_map)?;
+            // Only children join the new PID namespace so fork and have the child become its init
+            let pid = libc::fork();
+            if pid < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            if pid > 0 {
+                wait_and_exit(pid);
+            }
+            // Make sure the sandbox does not outlive the process the driver is waiting on
+            check(libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL))?;
+            check(libc::mount(
+                std::ptr::null(),
+                c"/".as_ptr(),
+                std::ptr::null(),
+                libc::MS_REC | libc::MS_PRIVATE,
+                std::ptr::null(),
+            ))?;
+            check(libc::mount(
+                c"tmpfs".as_ptr(),
+                self.root.as_ptr(),
+                c"tmpfs".as_ptr(),
+                0,
+                std::ptr::null(),
+            ))?;
+            for mount in &self.mounts {
+                let Some((target, parents)) = mount.dirs.split_last() else {
+                    continue;
+                };
+                for dir in parents {
+                    make_dir(dir)?;
+                }
+                if mount.file {
+                    let fd = libc::open(
+                        target.as_ptr(),
+                        libc::O_WRONLY | libc::O_CREAT | libc::O_CLOEXEC,
+                        0o644,
+                    );
+                    if fd < 0 {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                    libc::close(fd);
+                } else {
+                    make_dir(target)?;
+                }
+                match &mount.kind {
+                    MountKind::Bind {
+                        source,
+                        read_only_flags,
+                    } => {
+                        check(libc::mount(
+                            source.as_ptr(),
+                            target.as_ptr(),
+                            std::ptr::null(),
+                            libc::MS_BIND | libc::MS_REC,
+                            std::ptr::null(),
+                        ))?;
+                        if let Some(flags) = read_only_flags {
+                            check(libc::mount(
+                                std::ptr::null(),
+                                target.as_ptr(),
+                                std::ptr::null(),
+                                *flags,
+                                std::ptr::null(),
+                            ))?;
+                        }
+                    }
+                    MountKind::Tmpfs => check(libc::mount(
+                        c"tmpfs".as_ptr(),
+                        target.as_ptr(),
+                        c"tmpfs".as_ptr(),
+                        0,
+                        std::ptr::null(),
+                    ))?,
+                    MountKind::Proc => check(libc::mount(
+                        c"proc".as_ptr(),
+                        target.as_ptr(),
+                        c"proc".as_ptr(),
+                        libc::MS_NOSUID | libc::MS_NODEV | libc::MS_NOEXEC,
+                        std::ptr::null(),
+                    ))?,
+                }
+            }
+            for (target, link) in &self.symlinks {
+                check(libc::symlink(target.as_ptr(), link.as_ptr()))?;
+            }
+            check(libc::chdir(self.root.as_ptr()))?;
+            check(
+                libc::syscall(libc::SYS_pivot_root, c".".as_ptr(), c".".as_ptr())
+                    .try_into()
+                    .unwrap_or(-1),
+            )?;
+            check(libc::umount2(c".".as_ptr(), libc::MNT_DETACH))?;
+            check(libc::chdir(self.cwd.as_ptr()))?;
+        }
+        Ok(())
+    }
+}
+
+/// Wait for the sandboxed child and exit the same way it did
+///
+/// # Safety
+/// Only makes system calls so it is safe to call between fork and exec
+unsafe fn wait_and_exit(pid: libc::pid_t) -> ! {
+    // Safety: Only system calls are made and status is a valid pointer
+    unsafe {
+        // Close everything but stdio, in particular the pipe std uses to report exec failures so spawning returns once the child has exec'd
+        if libc::syscall(libc::SYS_close_range, 3, libc::c_uint::MAX, 0) != 0 {
+            for fd in 3..1024 {
+                libc::close(fd);
+            }
+        }
+        let mut status = 0;
+        while libc::waitpid(pid, &raw mut status, 0) < 0 {
+            if std::io::Error::last_os_error().kind() != ErrorKind::Interrupted {
+                libc::_exit(1);
+            }
+        }
+        if libc::WIFSIGNALED(status) {
+            let signal = libc::WTERMSIG(status);
+            libc::signal(signal, libc::SIG_DFL);
+            libc::kill(libc::getpid(), signal);
+            libc::_exit(128 + signal);
+        }
+        libc::_exit(libc::WEXITSTATUS(status))
+    }
+}
+
+/// # Safety
+/// Only makes system calls so it is safe to call between fork and exec
+unsafe fn make_dir(dir: &std::ffi::CStr) -> std::io::Result<()> {
+    // Safety: dir is nul terminated
+    if unsafe { libc::mkdir(dir.as_ptr(), 0o755) } != 0 {
+        let err = std::io::Error::last_os_error();
+        if err.kind() != ErrorKind::AlreadyExists {
+            return Err(err);
+        }
+    }
+    Ok(())
+}
+
+fn check(ret: libc::c_int) -> std::io::Result<()> {
+    if ret == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
+/// # Safety
+/// Only makes system calls so it is safe to call between fork and exec
+unsafe fn write_file(path: &std::ffi::CStr, contents: &[u8]) -> std::io::Result<()> {
+    // Safety: path is nul terminated and contents is valid for its length
+    unsafe {
+        let fd = libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC);
+        if fd < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let written = libc::write(fd, contents.as_ptr().cast(), contents.len());
+        let write_err = std::io::Error::last_os_error();
+        libc::close(fd);
+        if usize::try_from(written).ok() == Some(contents.len()) {
+            Ok(())
+        } else {
+            Err(write_err)
+        }
+    }
+}
+
+fn cstring(path: &Path) -> std::io::Result<CString> {
+    CString::new(path.as_os_str().as_bytes())
+        .map_err(|err| std::io::Error::new(ErrorKind::InvalidInput, err))
+}
+
+/// Flags of the mount containing `path` that a user namespace is not allowed to clear when remounting
+fn locked_flags(path: &Path) -> std::io::Result<libc::c_ulong> {
+    let c_path = cstring(path)?;
+    // Safety: statvfs is plain old data so zeroed is a valid value
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // Safety: c_path is nul terminated and stat is a valid pointer
+    check(unsafe { libc::statvfs(c_path.as_ptr(), &raw mut stat) })?;
+    let mut flags = 0;
+    for (st_flag, ms_flag) in [
+        (libc::ST_NOSUID, libc::MS_NOSUID),
+        (libc::ST_NODEV, libc::MS_NODEV),
+        (libc::ST_NOEXEC, libc::MS_NOEXEC),
+        (libc::ST_NOATIME, libc::MS_NOATIME),
+        (libc::ST_NODIRATIME, libc::MS_NODIRATIME),
+        (libc::ST_RELATIME, libc::MS_RELATIME),
+    ] {
+        if stat.f_flag & st_flag != 0 {
+            flags |= ms_flag;
+        }
+    }
+    Ok(flags)
+}
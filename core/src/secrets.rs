@@ -0,0 +1,522 @@
+//! Where secrets come from and keeping them out of output
+//!
+//! Secrets are looked up from each configured [`SecretProvider`] in turn. A project can only read the secrets named in its allowlist and every secret handed out is remembered so it can be redacted from prints, run output, errors and anything persisted.
+
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    io::Write as _,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use chacha20poly1305::{
+    KeyInit as _, XChaCha20Poly1305, XNonce,
+    aead::{Aead as _, OsRng, rand_core::RngCore as _},
+};
+use poison_panic::MutexExt as _;
+use serde::{Deserialize, Serialize};
+
+/// Name of the allowlist file in a project's root directory
+pub const ALLOWLIST_FILE_NAME: &str = "rain-secrets.toml";
+/// Environment variable holding the passphrase of the encrypted secrets file
+pub const PASSPHRASE_ENV_VAR: &str = "RAIN_SECRETS_PASSPHRASE";
+/// Shown in place of a secret
+pub const REDACTED: &str = "<redacted>";
+/// Secrets shorter than this are not redacted, redacting them would mangle too much unrelated output, and can't be spotted in cache entries so anything depending on them is never persisted
+const MIN_REDACT_LEN: usize = 4;
+const ENCRYPTED_MAGIC: &[u8] = b"RAINSEC1";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SecretError {
+    #[error("secret {name:?} is not in the allowlist, add it to {allowlist}")]
+    NotAllowed { name: String, allowlist: String },
+    #[error("secret {0:?} not found")]
+    NotFound(String),
+    #[error("{provider}: {message}")]
+    Provider {
+        provider: &'static str,
+        message: String,
+    },
+}
+
+impl SecretError {
+    fn provider(provider: &'static str, message: impl std::fmt::Display) -> Self {
+        Self::Provider {
+            provider,
+            message: message.to_string(),
+        }
+    }
+}
+
+/// A source of secrets
+pub trait SecretProvider: Send + Sync {
+    fn name(&self) -> &'static str;
+    /// Look up a secret, returns none if this provider does not have it
+    fn get(&self, name: &str) -> Result<Option<String>, SecretError>;
+}
+
+/// Secrets from environment variables of the same name
+pub struct EnvProvider;
+
+impl SecretProvider for EnvProvider {
+    fn name(&self) -> &'static str {
+        "env"
+    }
+
+    fn get(&self, name: &str) -> Result<Option<String>, SecretError> {
+        match std::env::var(name) {
+            Ok(secret) => Ok(Some(secret)),
+            Err(std::env::VarError::NotPresent) => Ok(None),
+            Err(std::env::VarError::NotUnicode(_)) => {
+                Err(SecretError::provider(self.name(), "secret not utf8"))
+            }
+        }
+    }
+}
+
+/// Secrets from a TOML file of `NAME = "value"` pairs
+pub struct TomlFileProvider {
+    pub path: PathBuf,
+}
+
+impl SecretProvider for TomlFileProvider {
+    fn name(&self) -> &'static str {
+        "toml file"
+    }
+
+    fn get(&self, name: &str) -> Result<Option<String>, SecretError> {
+        let contents = match std::fs::read_to_string(&self.path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(SecretError::provider(self.name(), err)),
+        };
+        lookup_toml(self.name(), &contents, name)
+    }
+}
+
+/// Secrets from a TOML file encrypted with a passphrase, see [`encrypt`]
+pub struct EncryptedFileProvider {
+    pub path: PathBuf,
+}
+
+impl SecretProvider for EncryptedFileProvider {
+    fn name(&self) -> &'static str {
+        "encrypted file"
+    }
+
+    fn get(&self, name: &str) -> Result<Option<String>, SecretError> {
+        let contents = match std::fs::read(&self.path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(SecretError::provider(self.name(), err)),
+        };
+        let passphrase = std::env::var(PASSPHRASE_ENV_VAR).map_err(|_| {
+            SecretError::provider(self.name(), format!("{PASSPHRASE_ENV_VAR} is not set"))
+        })?;
+        let plaintext = decrypt(&contents, &passphrase)?;
+        lookup_toml(self.name(), &plaintext, name)
+    }
+}
+
+/// Secrets from an external helper, like git credential helpers
+///
+/// The helper is run with the argument `get` and is sent `name=<name>` followed by a blank line on stdin. It replies with `value=<secret>` on stdout or nothing if it doesn't have the secret.
+pub struct CommandProvider {
+    pub program: String,
+    pub args: Vec<String>,
+}
+
+impl SecretProvider for CommandProvider {
+    fn name(&self) -> &'static str {
+        "command"
+    }
+
+    fn get(&self, name: &str) -> Result<Option<String>, SecretError> {
+        let mut child = std::process::Command::new(&self.program)
+            .args(&self.args)
+            .arg("get")
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::inherit())
+            .spawn()
+            .map_err(|err| SecretError::provider(self.name(), err))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            write!(stdin, "name={name}\n\n")
+                .map_err(|err| SecretError::provider(self.name(), err))?;
+        }
+        let output = child
+            .wait_with_output()
+            .map_err(|err| SecretError::provider(self.name(), err))?;
+        if !output.status.success() {
+            return Err(SecretError::provider(
+                self.name(),
+                format!("{} exited with {}", self.program, output.status),
+            ));
+        }
+        let stdout = String::from_utf8(output.stdout)
+            .map_err(|_| SecretError::provider(self.name(), "secret not utf8"))?;
+        Ok(stdout
+            .lines()
+            .find_map(|line| line.strip_prefix("value="))
+            .map(ToOwned::to_owned))
+    }
+}
+
+fn lookup_toml(
+    provider: &'static str,
+    contents: &str,
+    name: &str,
+) -> Result<Option<String>, SecretError> {
+    let toml: toml::Table =
+        toml::from_str(contents).map_err(|err| SecretError::provider(provider, err))?;
+    match toml.get(name) {
+        None => Ok(None),
+        Some(toml::Value::String(s)) => Ok(Some(s.clone())),
+        Some(_) => Err(SecretError::provider(provider, "secret is not a string")),
+    }
+}
+
+/// Which secret providers to use, secrets are looked up from the command, then the encrypted file, then the toml file and finally the environment
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SecretsConfig {
+    /// TOML file of secrets, set with `RAIN_SECRETS_FILE`
+    pub file: Option<PathBuf>,
+    /// Encrypted TOML file of secrets, set with `RAIN_SECRETS_ENCRYPTED_FILE`
+    pub encrypted_file: Option<PathBuf>,
+    /// Helper command and its arguments separated by spaces, set with `RAIN_SECRETS_COMMAND`
+    pub command: Option<String>,
+}
+
+impl SecretsConfig {
+    pub fn from_env() -> Self {
+        Self {
+            file: std::env::var_os("RAIN_SECRETS_FILE").map(PathBuf::from),
+            encrypted_file: std::env::var_os("RAIN_SECRETS_ENCRYPTED_FILE").map(PathBuf::from),
+            command: std::env::var("RAIN_SECRETS_COMMAND").ok(),
+        }
+    }
+
+    pub fn providers(&self) -> Vec<Box<dyn SecretProvider>> {
+        let mut providers: Vec<Box<dyn SecretProvider>> = Vec::new();
+        if let Some(command) = &self.command {
+            let mut parts = command.split_whitespace().map(ToOwned::to_owned);
+            if let Some(program) = parts.next() {
+                providers.push(Box::new(CommandProvider {
+                    program,
+                    args: parts.collect(),
+                }));
+            }
+        }
+        if let Some(path) = &self.encrypted_file {
+            providers.push(Box::new(EncryptedFileProvider { path: path.clone() }));
+        }
+        if let Some(path) = &self.file {
+            providers.push(Box::new(TomlFileProvider { path: path.clone() }));
+        }
+        providers.push(Box::new(EnvProvider));
+        providers
+    }
+}
+
+/// Looks up secrets and remembers every value handed out so it can be redacted
+pub struct SecretStore {
+    providers: Vec<Box<dyn SecretProvider>>,
+    revealed: Mutex<Vec<String>>,
+    /// Names of secrets handed out that are too short to be redacted
+    untracked: Mutex<HashSet<String>>,
+    /// Values looked up during the current run so providers aren't asked again every time a dep is checked
+    looked_up: Mutex<HashMap<String, Option<String>>>,
+}
+
+impl std::fmt::Debug for SecretStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SecretStore")
+            .field(
+                "providers",
+                &self.providers.iter().map(|p| p.name()).collect::<Vec<_>>(),
+            )
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for SecretStore {
+    fn default() -> Self {
+        Self::from_config(&SecretsConfig::from_env())
+    }
+}
+
+impl SecretStore {
+    pub fn new(providers: Vec<Box<dyn SecretProvider>>) -> Self {
+        Self {
+            providers,
+            revealed: Mutex::default(),
+            untracked: Mutex::default(),
+            looked_up: Mutex::default(),
+        }
+    }
+
+    pub fn from_config(config: &SecretsConfig) -> Self {
+        Self::new(config.providers())
+    }
+
+    /// Look up a secret for a project, only secrets in its allowlist can be read
+    pub fn get(&self, allowlist: &SecretAllowlist, name: &str) -> Result<String, SecretError> {
+        if !allowlist.allows(name) {
+            return Err(SecretError::NotAllowed {
+                name: name.to_owned(),
+                allowlist: allowlist.describe(),
+            });
+        }
+        let secret = self
+            .lookup(name)?
+            .ok_or_else(|| SecretError::NotFound(name.to_owned()))?;
+        self.reveal(name, &secret);
+        Ok(secret)
+    }
+
+    /// Look up a secret without checking any allowlist or remembering it, for checking whether a secret has changed
+    ///
+    /// Values are reused until [`Self::forget_lookups`] so each provider is asked at most once per run
+    pub fn lookup(&self, name: &str) -> Result<Option<String>, SecretError> {
+        if let Some(secret) = self.looked_up.plock().get(name) {
+            return Ok(secret.clone());
+        }
+        let secret = self.lookup_providers(name)?;
+        self.looked_up
+            .plock()
+            .insert(name.to_owned(), secret.clone());
+        Ok(secret)
+    }
+
+    fn lookup_providers(&self, name: &str) -> Result<Option<String>, SecretError> {
+        for provider in &self.providers {
+            if let Some(secret) = provider.get(name)? {
+                log::debug!("found secret {name:?} in {}", provider.name());
+                return Ok(Some(secret));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Forget looked up values so the next run sees any changes to the providers
+    pub fn forget_lookups(&self) {
+        self.looked_up.plock().clear();
+    }
+
+    fn reveal(&self, name: &str, secret: &str) {
+        if secret.len() < MIN_REDACT_LEN {
+            log::warn!("secret {name:?} is too short to be redacted from output");
+            self.untracked.plock().insert(name.to_owned());
+            return;
+        }
+        let mut revealed = self.revealed.plock();
+        if !revealed.iter().any(|s| s == secret) {
+            revealed.push(secret.to_owned());
+        }
+    }
+
+    /// Replace any secrets that have been handed out with [`REDACTED`]
+    pub fn redact<'a>(&self, s: &'a str) -> Cow<'a, str> {
+        let revealed = self.revealed.plock();
+        let mut out = Cow::Borrowed(s);
+        for secret in revealed.iter() {
+            if out.contains(secret.as_str()) {
+                out = Cow::Owned(out.replace(secret.as_str(), REDACTED));
+            }
+        }
+        out
+    }
+
+    /// Replace any secrets that have been handed out with [`REDACTED`] in output that might not be UTF-8
+    pub fn redact_bytes<'a>(&self, b: &'a [u8]) -> Cow<'a, [u8]> {
+        let revealed = self.revealed.plock();
+        let mut out = Cow::Borrowed(b);
+        for secret in revealed.iter() {
+            if let Some(replaced) = replace_bytes(&out, secret.as_bytes(), REDACTED.as_bytes()) {
+                out = Cow::Owned(replaced);
+            }
+        }
+        out
+    }
+
+    pub fn contains_secret(&self, s: &str) -> bool {
+        self.revealed
+            .plock()
+            .iter()
+            .any(|secret| s.contains(secret.as_str()))
+    }
+
+    /// Whether a secret appears in any of `strings`, cache keys and values that leak secrets must not be persisted
+    pub fn leaks<'a>(&self, strings: impl IntoIterator<Item = &'a str>) -> bool {
+        let revealed = self.revealed.plock();
+        if revealed.is_empty() {
+            return false;
+        }
+        strings
+            .into_iter()
+            .any(|s| revealed.iter().any(|secret| s.contains(secret.as_str())))
+    }
+
+    /// Whether the secret called `name` was handed out but is too short to be redacted or spotted by [`Self::leaks`]
+    pub fn is_untracked(&self, name: &str) -> bool {
+        self.untracked.plock().contains(name)
+    }
+}
+
+/// `haystack` with every `needle` replaced by `with`, or `None` if there aren't any
+fn replace_bytes(haystack: &[u8], needle: &[u8], with: &[u8]) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    let mut rest = haystack;
+    let mut found = false;
+    while let Some(i) = rest.windows(needle.len()).position(|w| w == needle) {
+        out.extend_from_slice(&rest[..i]);
+        out.extend_from_slice(with);
+        rest = &rest[i + needle.len()..];
+        found = true;
+    }
+    found.then(|| {
+        out.extend_from_slice(rest);
+        out
+    })
+}
+
+/// Names of the secrets a project may read, from [`ALLOWLIST_FILE_NAME`] in its root directory
+///
+/// ```toml
+/// allow = ["GITHUB_TOKEN"]
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct SecretAllowlist {
+    path: Option<PathBuf>,
+    names: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct AllowlistFile {
+    allow: Vec<String>,
+}
+
+impl SecretAllowlist {
+    /// Load the allowlist of the project in `project_dir`, a project without one can't read any secrets
+    pub fn load(project_dir: &Path) -> Result<Self, SecretError> {
+        let path = project_dir.join(ALLOWLIST_FILE_NAME);
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(Self {
+                    path: Some(path),
+                    names: Vec::new(),
+                });
+            }
+            Err(err) => return Err(SecretError::provider("allowlist", err)),
+        };
+        let AllowlistFile { allow } = toml::from_str(&contents).map_err(|err| {
+            SecretError::provider("allowlist", format!("{}: {err}", path.display()))
+        })?;
+        Ok(Self {
+            path: Some(path),
+            names: allow,
+        })
+    }
+
+    pub fn new(names: Vec<String>) -> Self {
+        Self { path: None, names }
+    }
+
+    pub fn allows(&self, name: &str) -> bool {
+        self.names.iter().any(|n| n == name)
+    }
+
+    fn describe(&self) -> String {
+        self.path.as_ref().map_or_else(
+            || String::from("the project allowlist"),
+            |p| p.display().to_string(),
+        )
+    }
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<chacha20poly1305::Key, SecretError> {
+    let mut key = chacha20poly1305::Key::default();
+    argon2::Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|err| SecretError::provider("encrypted file", err))?;
+    Ok(key)
+}
+
+/// Encrypt the contents of a secrets TOML file with a passphrase for [`EncryptedFileProvider`]
+pub fn encrypt(plaintext: &str, passphrase: &str) -> Result<Vec<u8>, SecretError> {
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let mut nonce = XNonce::default();
+    OsRng.fill_bytes(&mut nonce);
+    let cipher = XChaCha20Poly1305::new(&derive_key(passphrase, &salt)?);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext.as_bytes())
+        .map_err(|err| SecretError::provider("encrypted file", err))?;
+    let mut out =
+        Vec::with_capacity(ENCRYPTED_MAGIC.len() + SALT_LEN + NONCE_LEN + ciphertext.len());
+    out.extend_from_slice(ENCRYPTED_MAGIC);
+    out.extend_from_slice(&salt);
+    out.extend_from_slice(&nonce);
+    out.extend_from_slice(&ciphertext);
+    Ok(out)
+}
+
+pub fn decrypt(contents: &[u8], passphrase: &str) -> Result<String, SecretError> {
+    let invalid = || SecretError::provider("encrypted file", "not a rain encrypted secrets file");
+    let rest = contents.strip_prefix(ENCRYPTED_MAGIC).ok_or_else(invalid)?;
+    let (salt, rest) = rest.split_first_chunk::<SALT_LEN>().ok_or_else(invalid)?;
+    let (nonce, ciphertext) = rest.split_first_chunk::<NONCE_LEN>().ok_or_else(invalid)?;
+    let cipher = XChaCha20Poly1305::new(&derive_key(passphrase, salt)?);
+    let plaintext = cipher
+        .decrypt(&XNonce::from(*nonce), ciphertext)
+        .map_err(|_| {
+            SecretError::provider("encrypted file", "wrong passphrase or corrupted file")
+        })?;
+    String::from_utf8(plaintext)
+        .map_err(|_| SecretError::provider("encrypted file", "secrets not utf8"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encrypt_roundtrip() {
+        let encrypted = encrypt("TOKEN = \"hunter22\"\n", "passphrase").unwrap();
+        assert_eq!(
+            lookup_toml("test", &decrypt(&encrypted, "passphrase").unwrap(), "TOKEN").unwrap(),
+            Some(String::from("hunter22"))
+        );
+        decrypt(&encrypted, "wrong").unwrap_err();
+    }
+
+    #[test]
+    fn redacts_revealed_secrets() {
+        let store = SecretStore::new(vec![Box::new(TomlFileProvider {
+            path: PathBuf::from("/does/not/exist"),
+        })]);
+        store.reveal("TOKEN", "hunter22");
+        assert_eq!(
+            store.redact("password is hunter22"),
+            "password is <redacted>"
+        );
+        assert!(store.leaks(["xhunter22x"]));
+        assert_eq!(store.redact("nothing to see"), "nothing to see");
+        assert_eq!(
+            store.redact_bytes(b"\xffhunter22\xff"),
+            b"\xff<redacted>\xff".as_slice()
+        );
+
+        store.reveal("OTHER", "hunter\"22");
+        assert!(store.leaks(["xhunter\"22x"]));
+
+        store.reveal("SHORT", "abc");
+        assert!(store.is_untracked("SHORT"));
+        assert!(!store.is_untracked("TOKEN"));
+        assert!(!store.leaks(["xabcx"]));
+    }
+}
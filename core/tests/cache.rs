@@ -4,7 +4,7 @@ use std::{
     fs::{self},
     io::{Seek as _, Write as _},
     path::Path,
-    sync::Arc,
+    sync::{Arc, atomic::Ordering},
 };
 
 use poison_panic::MutexExt as _;
@@ -21,6 +21,7 @@ struct CacheTester {
     driver: rain_core::driver::DriverImpl<'static>,
     persist_cache: Option<PersistCache>,
     cache_stats: rain_core::cache::CacheStats,
+    last_run_stats: Arc<rain_core::cache::CacheStats>,
 }
 
 impl CacheTester {
@@ -34,6 +35,27 @@ impl CacheTester {
             driver,
             persist_cache,
             cache_stats: stats,
+            last_run_stats: Arc::default(),
+        }
+    }
+
+    /// A tester with its own directories as if it were on another machine
+    fn new_machine(dir: &Path, remote_cache_url: &str) -> Self {
+        let config = rain_core::config::Config {
+            base_cache_dir: dir.join("cache"),
+            base_generated_dir: dir.join("generated"),
+            base_data_dir: dir.join("data"),
+            base_run_dir: dir.join("run"),
+            remote_cache_url: Some(remote_cache_url.to_owned()),
+            secrets: rain_core::secrets::SecretsConfig::default(),
+        };
+        let driver = rain_core::driver::DriverImpl::new(config.clone());
+        Self {
+            config,
+            driver,
+            persist_cache: None,
+            cache_stats: rain_core::cache::CacheStats::default(),
+            last_run_stats: Arc::default(),
         }
     }
 
@@ -42,22 +64,29 @@ impl CacheTester {
         let path = self.driver.resolve_fs_entry(file.inner());
         let src = std::fs::read_to_string(&path).unwrap();
         let module = rain_lang::ast::parser::parse_module(&src);
-        let mut ir = rain_lang::ir::Rir::new();
+        let ir = rain_lang::ir::Rir::new();
         let cache_core = self.persist_cache.take().unwrap_or_default().depersist(
             &self.config,
             &self.cache_stats,
-            &mut ir,
+            &ir,
         );
-        let cache = rain_core::cache::Cache::new(cache_core);
+        let remote = self
+            .config
+            .remote_cache_url
+            .as_deref()
+            .map(|url| rain_core::cache::remote::RemoteCache::new(self.config.clone(), url));
+        let cache = rain_core::cache::Cache::new(cache_core).with_remote(remote);
         let mid = ir.insert_module(Some(file), src, module).unwrap();
         let main = ir.resolve_global_declaration(mid, declaration).unwrap();
-        let mut runner = rain_lang::runner::Runner::new(&mut ir, &cache, &self.driver);
+        let mut runner = rain_lang::runner::Runner::new(&ir, &cache, &self.driver);
         let value = runner.evaluate_and_call(main, &[]).unwrap();
+        cache.flush_remote();
         self.persist_cache = Some(PersistCache::persist(
             &cache.core.plock(),
             &cache.stats,
             &ir,
         ));
+        self.last_run_stats = Arc::clone(&cache.stats);
         value
     }
 }
@@ -103,3 +132,157 @@ fn modify_local_import() {
     let value = cache_tester.run(&root, "main");
     assert_eq!(value, Value::Integer(Arc::new(RainInteger::from(5))));
 }
+
+#[test]
+fn local_file_read_survives_restart() {
+    let mut cache_tester = CacheTester::new();
+
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().join("main.rain");
+    fs::write(
+        &root,
+        "let main = internal._read_file(internal._get_file(\"data.txt\"))",
+    )
+    .unwrap();
+    let data = dir.path().join("data.txt");
+    fs::write(&data, "hello").unwrap();
+
+    let value = cache_tester.run(&root, "main");
+    assert_eq!(value, Value::String(Arc::new(String::from("hello"))));
+
+    let value = cache_tester.run(&root, "main");
+    assert_eq!(value, Value::String(Arc::new(String::from("hello"))));
+    assert_eq!(
+        cache_tester.last_run_stats.hits.0.load(Ordering::Relaxed),
+        1
+    );
+
+    fs::write(&data, "goodbye").unwrap();
+    let value = cache_tester.run(&root, "main");
+    assert_eq!(value, Value::String(Arc::new(String::from("goodbye"))));
+    assert_eq!(
+        cache_tester.last_run_stats.stale.0.load(Ordering::Relaxed),
+        1
+    );
+}
+
+#[test]
+fn remote_cache_shared_between_machines() {
+    let storage = tempfile::tempdir().unwrap();
+    let cache_server = rain_cache_server::CacheServer::new(storage.path()).unwrap();
+    let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", server.server_addr().to_ip().unwrap());
+    std::thread::spawn(move || cache_server.serve(&server));
+
+    let src = tempfile::tempdir().unwrap();
+    let root = src.path().join("main.rain");
+    fs::write(
+        &root,
+        "let main = internal._read_file(internal._create_file(\"hello\", \"greeting.txt\", false))",
+    )
+    .unwrap();
+
+    let machine_a = tempfile::tempdir().unwrap();
+    let mut cache_tester = CacheTester::new_machine(machine_a.path(), &url);
+    let value = cache_tester.run(&root, "main");
+    assert_eq!(value, Value::String(Arc::new(String::from("hello"))));
+    assert_eq!(
+        cache_tester
+            .last_run_stats
+            .remote_pushes
+            .0
+            .load(Ordering::Relaxed),
+        2
+    );
+
+    let machine_b = tempfile::tempdir().unwrap();
+    let mut cache_tester = CacheTester::new_machine(machine_b.path(), &url);
+    let value = cache_tester.run(&root, "main");
+    assert_eq!(value, Value::String(Arc::new(String::from("hello"))));
+    assert_eq!(
+        cache_tester
+            .last_run_stats
+            .remote_hits
+            .0
+            .load(Ordering::Relaxed),
+        2
+    );
+    assert_eq!(
+        cache_tester
+            .last_run_stats
+            .remote_fails
+            .0
+            .load(Ordering::Relaxed),
+        0
+    );
+}
+
+#[test]
+fn local_entries_not_pushed_to_remote() {
+    let storage = tempfile::tempdir().unwrap();
+    let cache_server = rain_cache_server::CacheServer::new(storage.path()).unwrap();
+    let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", server.server_addr().to_ip().unwrap());
+    std::thread::spawn(move || cache_server.serve(&server));
+
+    let src = tempfile::tempdir().unwrap();
+    let root = src.path().join("main.rain");
+    fs::write(
+        &root,
+        "let main = internal._read_file(internal._get_file(\"data.txt\"))",
+    )
+    .unwrap();
+    fs::write(src.path().join("data.txt"), "hello").unwrap();
+
+    let machine = tempfile::tempdir().unwrap();
+    let mut cache_tester = CacheTester::new_machine(machine.path(), &url);
+    let value = cache_tester.run(&root, "main");
+    assert_eq!(value, Value::String(Arc::new(String::from("hello"))));
+    assert_ne!(
+        cache_tester.last_run_stats.puts.0.load(Ordering::Relaxed),
+        0
+    );
+    assert_eq!(
+        cache_tester
+            .last_run_stats
+            .remote_pushes
+            .0
+            .load(Ordering::Relaxed),
+        0
+    );
+}
+
+#[test]
+fn glob_only_tracks_matched_files() {
+    let mut cache_tester = CacheTester::new();
+
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().join("main.rain");
+    fs::write(
+        &root,
+        "let main = internal._list_length(internal._glob(internal._get_dir(\"src\"), \"*.txt\"))",
+    )
+    .unwrap();
+    let src = dir.path().join("src");
+    fs::create_dir_all(&src).unwrap();
+    fs::write(src.join("a.txt"), "a").unwrap();
+
+    let value = cache_tester.run(&root, "main");
+    assert_eq!(value, Value::Integer(Arc::new(RainInteger::from(1))));
+
+    fs::write(src.join("build.log"), "not matched").unwrap();
+    let value = cache_tester.run(&root, "main");
+    assert_eq!(value, Value::Integer(Arc::new(RainInteger::from(1))));
+    assert_ne!(
+        cache_tester.last_run_stats.hits.0.load(Ordering::Relaxed),
+        0
+    );
+    assert_eq!(
+        cache_tester.last_run_stats.stale.0.load(Ordering::Relaxed),
+        0
+    );
+
+    fs::write(src.join("b.txt"), "b").unwrap();
+    let value = cache_tester.run(&root, "main");
+    assert_eq!(value, Value::Integer(Arc::new(RainInteger::from(2))));
+}
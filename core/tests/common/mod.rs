@@ -0,0 +1,33 @@
+//! Helpers shared by the integration tests
+
+use std::path::{Path, PathBuf};
+
+/// Rain files in the repo that run fine, relative to the core crate
+///
+/// The root main.rain is left out as it keeps targets that no longer run
+pub fn working_code() -> Vec<PathBuf> {
+    let mut paths = [
+        "../hermetic.rain",
+        "../publish.rain",
+        "../test.rain",
+        "../cli/src/template_main.rain",
+        "../tree-sitter-rain/_.rain",
+    ]
+    .map(PathBuf::from)
+    .to_vec();
+    rain_files(Path::new("../ci"), &mut paths);
+    rain_files(Path::new("tests/scripts"), &mut paths);
+    rain_files(Path::new("../lib"), &mut paths);
+    paths
+}
+
+fn rain_files(dir: &Path, out: &mut Vec<PathBuf>) {
+    for entry in std::fs::read_dir(dir).expect("read dir") {
+        let path = entry.expect("read dir entry").path();
+        if path.is_dir() {
+            rain_files(&path, out);
+        } else if path.extension().is_some_and(|e| e == "rain") {
+            out.push(path);
+        }
+    }
+}
@@ -0,0 +1,370 @@
+#![cfg(test)]
+
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use poison_panic::MutexExt as _;
+use rain_core::{
+    driver::DriverImpl,
+    secrets::{SecretAllowlist, SecretStore, TomlFileProvider},
+};
+use rain_lang::{
+    afs::entry::FSEntryTrait as _,
+    driver::{DownloadOptions, DriverTrait as _, FSTrait as _},
+    runner::{
+        error::{ErrorTrace, Throwing},
+        value::Value,
+    },
+};
+use sha2::Digest as _;
+use test_log::test;
+
+const CONTENTS: &[u8] = b"the quick brown fox jumps over the lazy dog";
+
+/// Requests the server received as (url, range header)
+type RequestLog = Arc<Mutex<Vec<(String, Option<String>)>>>;
+
+/// Serves [`CONTENTS`] at `/file` and `/copy` with range support and at `/private` to bearer `s3cret`, everything else is not found
+fn serve(server: Arc<tiny_http::Server>, log: RequestLog) -> std::thread::JoinHandle<()> {
+    std::thread::spawn(move || {
+        for request in server.incoming_requests() {
+            let range = request
+                .headers()
+                .iter()
+                .find(|h| h.field.equiv("Range"))
+                .map(|h| h.value.to_string());
+            log.plock().push((request.url().to_owned(), range.clone()));
+            let authorization = request
+                .headers()
+                .iter()
+                .find(|h| h.field.equiv("Authorization"))
+                .map(|h| h.value.to_string());
+            let response = match request.url() {
+                "/private" if authorization.as_deref() == Some("Bearer s3cret") => {
+                    tiny_http::Response::from_data(CONTENTS)
+                }
+                "/private" => {
+                    tiny_http::Response::from_data(b"unauthorized".as_slice()).with_status_code(401)
+                }
+                "/file" | "/copy" => {
+                    let start: Option<usize> = range
+                        .as_deref()
+                        .and_then(|r| r.strip_prefix("bytes="))
+                        .and_then(|r| r.strip_suffix('-'))
+                        .and_then(|r| r.parse().ok());
+                    match start {
+                        Some(start) => tiny_http::Response::from_data(&CONTENTS[start..])
+                            .with_status_code(206)
+                            .with_header(
+                                tiny_http::Header::from_bytes(
+                                    "Content-Range",
+                                    format!(
+                                        "bytes {start}-{}/{}",
+                                        CONTENTS.len() - 1,
+                                        CONTENTS.len()
+                                    ),
+                                )
+                                .unwrap(),
+                            ),
+                        None => tiny_http::Response::from_data(CONTENTS),
+                    }
+                }
+                _ => tiny_http::Response::from_data(b"not found".as_slice()).with_status_code(404),
+            };
+            request.respond(response).unwrap();
+        }
+    })
+}
+
+struct TestServer {
+    server: Arc<tiny_http::Server>,
+    handle: Option<std::thread::JoinHandle<()>>,
+    log: RequestLog,
+}
+
+impl TestServer {
+    fn new() -> Self {
+        let server = Arc::new(tiny_http::Server::http("127.0.0.1:0").unwrap());
+        let log = RequestLog::default();
+        let handle = serve(Arc::clone(&server), Arc::clone(&log));
+        Self {
+            server,
+            handle: Some(handle),
+            log,
+        }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!(
+            "http://{}{path}",
+            self.server.server_addr().to_ip().unwrap()
+        )
+    }
+
+    fn requests(&self) -> Vec<(String, Option<String>)> {
+        self.log.plock().clone()
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.server.unblock();
+        if let Some(handle) = self.handle.take() {
+            handle.join().unwrap();
+        }
+    }
+}
+
+fn new_driver(dir: &Path) -> DriverImpl<'static> {
+    DriverImpl::new(rain_core::config::Config {
+        base_cache_dir: dir.join("cache"),
+        base_generated_dir: dir.join("generated"),
+        base_data_dir: dir.join("data"),
+        base_run_dir: dir.join("run"),
+        remote_cache_url: None,
+        secrets: rain_core::secrets::SecretsConfig::default(),
+    })
+}
+
+fn contents_sha256() -> String {
+    base16::encode_lower(&sha2::Sha256::digest(CONTENTS))
+}
+
+fn run_main(driver: &DriverImpl<'_>, cache: &rain_core::cache::Cache, src: String) -> Value {
+    try_run_main(driver, cache, src).unwrap()
+}
+
+fn try_run_main(
+    driver: &DriverImpl<'_>,
+    cache: &rain_core::cache::Cache,
+    src: String,
+) -> Result<Value, ErrorTrace<Throwing>> {
+    let module = rain_lang::ast::parser::parse_module(&src);
+    let ir = rain_lang::ir::Rir::new();
+    let mid = ir.insert_module(None, src, module).unwrap();
+    let main = ir.resolve_global_declaration(mid, "main").unwrap();
+    let mut runner = rain_lang::runner::Runner::new(&ir, cache, driver);
+    runner.evaluate_and_call(main, &[])
+}
+
+fn download(driver: &DriverImpl<'_>, url: &str, options: &DownloadOptions) -> Vec<u8> {
+    let status = driver.download(url, "download", None, options).unwrap();
+    assert!(status.ok);
+    std::fs::read(driver.resolve_fs_entry(status.file.unwrap().inner())).unwrap()
+}
+
+#[test]
+fn download_checks_hash() {
+    let dir = tempfile::tempdir().unwrap();
+    let driver = new_driver(dir.path());
+    let server = TestServer::new();
+    let options = DownloadOptions {
+        sha256: Some(contents_sha256()),
+        mirrors: Vec::new(),
+        ..DownloadOptions::default()
+    };
+    assert_eq!(download(&driver, &server.url("/file"), &options), CONTENTS);
+
+    let bad_options = DownloadOptions {
+        sha256: Some("0".repeat(64)),
+        mirrors: Vec::new(),
+        ..DownloadOptions::default()
+    };
+    let err = driver
+        .download(&server.url("/file"), "download", None, &bad_options)
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("expected"), "{err}");
+}
+
+#[test]
+fn download_tries_mirrors() {
+    let dir = tempfile::tempdir().unwrap();
+    let driver = new_driver(dir.path());
+    let server = TestServer::new();
+    let options = DownloadOptions {
+        sha256: None,
+        mirrors: vec![server.url("/missing"), server.url("/file")],
+        ..DownloadOptions::default()
+    };
+    assert_eq!(
+        download(&driver, &server.url("/missing"), &options),
+        CONTENTS
+    );
+    let urls: Vec<String> = server.requests().into_iter().map(|(url, _)| url).collect();
+    assert_eq!(urls, ["/missing", "/missing", "/file"]);
+}
+
+#[test]
+fn download_resumes_partial() {
+    let dir = tempfile::tempdir().unwrap();
+    let driver = new_driver(dir.path());
+    let server = TestServer::new();
+    let sha256 = contents_sha256();
+    let partial_dir = driver.config.download_store_dir().join("partial");
+    std::fs::create_dir_all(&partial_dir).unwrap();
+    std::fs::write(partial_dir.join(&sha256), &CONTENTS[..10]).unwrap();
+    let options = DownloadOptions {
+        sha256: Some(sha256),
+        mirrors: Vec::new(),
+        ..DownloadOptions::default()
+    };
+    assert_eq!(download(&driver, &server.url("/file"), &options), CONTENTS);
+    assert_eq!(
+        server.requests(),
+        [(String::from("/file"), Some(String::from("bytes=10-")))]
+    );
+}
+
+#[test]
+fn download_store_not_changed_through_area() {
+    let dir = tempfile::tempdir().unwrap();
+    let driver = new_driver(dir.path());
+    let server = TestServer::new();
+    let options = DownloadOptions {
+        sha256: Some(contents_sha256()),
+        mirrors: Vec::new(),
+        ..DownloadOptions::default()
+    };
+    let status = driver
+        .download(&server.url("/file"), "download", None, &options)
+        .unwrap();
+    std::fs::write(
+        driver.resolve_fs_entry(status.file.unwrap().inner()),
+        b"tampered",
+    )
+    .unwrap();
+    assert_eq!(download(&driver, &server.url("/file"), &options), CONTENTS);
+}
+
+#[test]
+fn download_store_shared_between_urls() {
+    let dir = tempfile::tempdir().unwrap();
+    let driver = new_driver(dir.path());
+    let server = TestServer::new();
+    let cache = rain_core::cache::Cache::default();
+    let src = format!(
+        "let main = fn() {{
+            a = internal._download(\"{}\", {{sha256 = \"{sha256}\"}})
+            b = internal._download(\"{}\", {{sha256 = \"{sha256}\"}})
+            internal._read_file(b.file)
+        }}",
+        server.url("/file"),
+        server.url("/copy"),
+        sha256 = contents_sha256(),
+    );
+    let value = run_main(&driver, &cache, src);
+    let Value::String(s) = value else {
+        panic!("expected string got {value}");
+    };
+    assert_eq!(s.as_bytes(), CONTENTS);
+    let urls: Vec<String> = server.requests().into_iter().map(|(url, _)| url).collect();
+    assert_eq!(urls, ["/file"]);
+}
+
+#[test]
+fn prune_download_store() {
+    let dir = tempfile::tempdir().unwrap();
+    let driver = new_driver(dir.path());
+    let server = TestServer::new();
+    let sha256 = contents_sha256();
+    let options = DownloadOptions {
+        sha256: Some(sha256.clone()),
+        mirrors: Vec::new(),
+        ..DownloadOptions::default()
+    };
+    download(&driver, &server.url("/file"), &options);
+    let partial = driver.config.download_store_dir().join("partial/abandoned");
+    std::fs::write(&partial, &CONTENTS[..10]).unwrap();
+    let stored = rain_core::download::store_path(&driver.config, &sha256);
+    let day = std::time::Duration::from_secs(24 * 60 * 60);
+
+    let pruned = rain_core::download::prune(&driver.config, day).unwrap();
+    assert_eq!(pruned.size, 0);
+    assert!(stored.exists());
+    assert!(partial.exists());
+
+    // Using a download from the store marks it used again
+    let old = std::time::SystemTime::now() - 2 * day;
+    for path in [&stored, &partial] {
+        std::fs::File::options()
+            .append(true)
+            .open(path)
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+    }
+    download(&driver, &server.url("/file"), &options);
+    let pruned = rain_core::download::prune(&driver.config, day).unwrap();
+    assert_eq!(pruned.errors, 0);
+    assert_eq!(pruned.size, 10);
+    assert!(stored.exists());
+    assert!(!partial.exists());
+
+    let pruned = rain_core::download::prune(&driver.config, std::time::Duration::ZERO).unwrap();
+    assert_eq!(pruned.size, CONTENTS.len() as u64);
+    assert!(!stored.exists());
+}
+
+const TOKEN_NAME: &str = "DOWNLOAD_TOKEN";
+
+#[test]
+fn download_with_secret_auth() {
+    let dir = tempfile::tempdir().unwrap();
+    let secrets_file = dir.path().join("secrets.toml");
+    std::fs::write(&secrets_file, format!("{TOKEN_NAME} = \"s3cret\"\n")).unwrap();
+    let mut driver = new_driver(dir.path());
+    driver.secrets = Arc::new(SecretStore::new(vec![Box::new(TomlFileProvider {
+        path: secrets_file,
+    })]));
+    driver.secret_allowlist = SecretAllowlist::new(vec![TOKEN_NAME.to_owned()]);
+    let server = TestServer::new();
+    let other_server = TestServer::new();
+    let cache = rain_core::cache::Cache::default().with_secrets(Arc::clone(&driver.secrets));
+    let src = format!(
+        "let main = fn() {{
+            auth = {{bearer = \"{TOKEN_NAME}\"}}
+            private = internal._download(\"{}\", {{auth = auth, headers = {{Accept = \"application/octet-stream\"}}}})
+            mirrored = internal._download(\"{}\", {{auth = auth, mirrors = [\"{}\"]}})
+            {{private = internal._read_file(private.file), mirrored = mirrored.status_code}}
+        }}",
+        server.url("/private"),
+        server.url("/missing"),
+        other_server.url("/private"),
+    );
+    let value = run_main(&driver, &cache, src);
+    assert_eq!(
+        value.to_string(),
+        format!(
+            "{{private: \"{}\", mirrored: 401}}",
+            std::str::from_utf8(CONTENTS).unwrap()
+        )
+    );
+    // Mirrors on another origin never get the credentials
+    assert_eq!(other_server.requests().len(), 1);
+    for entry in rain_lang::runner::cache::CacheTrait::inspect_all(&cache) {
+        assert!(!entry.contains("s3cret"), "{entry}");
+    }
+}
+
+#[test]
+fn download_rejects_credential_headers() {
+    let dir = tempfile::tempdir().unwrap();
+    let driver = new_driver(dir.path());
+    let server = TestServer::new();
+    let cache = rain_core::cache::Cache::default();
+    for header in ["Authorization", "proxy-authorization", "Cookie"] {
+        let src = format!(
+            "let main = fn() {{
+                internal._download(\"{}\", {{headers = {{[\"{header}\"] = \"s3cret\"}}}})
+            }}",
+            server.url("/private"),
+        );
+        let err = try_run_main(&driver, &cache, src).unwrap_err();
+        let err = err.err_span.err.to_string();
+        assert!(err.contains("use auth"), "{err}");
+    }
+    assert!(server.requests().is_empty());
+}
@@ -0,0 +1,110 @@
+#![cfg(test)]
+//! Tests that change environment variables
+//!
+//! Changing the environment while another thread reads it is undefined behaviour and most tests read it, through secret providers or env var deps, so these get a test binary to themselves and run one at a time.
+
+use std::{
+    path::Path,
+    sync::{Arc, Mutex, atomic::Ordering},
+};
+
+use poison_panic::MutexExt as _;
+use rain_core::{
+    cache::{Cache, CacheStats, persistent::PersistCache},
+    driver::DriverImpl,
+};
+use rain_lang::{afs::entry::FSEntryTrait as _, driver::FSTrait as _, runner::value::Value};
+use test_log::test;
+
+/// Held by every test in this binary while it uses the environment
+static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+/// Run `declaration` in a new cache loaded from `persist` as if it were a new run, persisting the cache afterwards
+fn run(
+    driver: &DriverImpl<'_>,
+    persist: &mut Option<PersistCache>,
+    path: &Path,
+    declaration: &str,
+) -> (Value, Arc<CacheStats>) {
+    let file = rain_lang::afs::file::File::new_local(path).unwrap();
+    let src = std::fs::read_to_string(driver.resolve_fs_entry(file.inner())).unwrap();
+    let module = rain_lang::ast::parser::parse_module(&src);
+    let ir = rain_lang::ir::Rir::new();
+    let core =
+        persist
+            .take()
+            .unwrap_or_default()
+            .depersist(&driver.config, &CacheStats::default(), &ir);
+    let cache = Cache::new(core);
+    let mid = ir.insert_module(Some(file), src, module).unwrap();
+    let main = ir.resolve_global_declaration(mid, declaration).unwrap();
+    let mut runner = rain_lang::runner::Runner::new(&ir, &cache, driver);
+    let value = runner.evaluate_and_call(main, &[]).unwrap();
+    *persist = Some(PersistCache::persist(
+        &cache.core.plock(),
+        &cache.stats,
+        &ir,
+    ));
+    (value, Arc::clone(&cache.stats))
+}
+
+const REVALIDATED_VAR: &str = "RAIN_TEST_ENV_VAR_REVALIDATED_ACROSS_RUNS";
+
+#[test]
+fn env_var_revalidated_across_runs() {
+    let _guard = ENV_LOCK.plock();
+    let dir = tempfile::tempdir().unwrap();
+    let driver = DriverImpl::new(rain_core::config::Config::new());
+    let mut persist = None;
+    let root = dir.path().join("main.rain");
+    std::fs::write(
+        &root,
+        format!("let main = internal._env_var(\"{REVALIDATED_VAR}\")"),
+    )
+    .unwrap();
+
+    // Safety: Tests in this binary hold ENV_LOCK so nothing else is reading the environment
+    unsafe { std::env::set_var(REVALIDATED_VAR, "a") };
+    let (value, _) = run(&driver, &mut persist, &root, "main");
+    assert_eq!(value, Value::String(Arc::new(String::from("a"))));
+
+    let (value, stats) = run(&driver, &mut persist, &root, "main");
+    assert_eq!(value, Value::String(Arc::new(String::from("a"))));
+    assert_eq!(stats.hits.0.load(Ordering::Relaxed), 1);
+
+    // Safety: Tests in this binary hold ENV_LOCK so nothing else is reading the environment
+    unsafe { std::env::set_var(REVALIDATED_VAR, "b") };
+    let (value, stats) = run(&driver, &mut persist, &root, "main");
+    assert_eq!(value, Value::String(Arc::new(String::from("b"))));
+    assert_eq!(stats.stale.0.load(Ordering::Relaxed), 1);
+
+    // Safety: Tests in this binary hold ENV_LOCK so nothing else is reading the environment
+    unsafe { std::env::remove_var(REVALIDATED_VAR) };
+    let (value, _) = run(&driver, &mut persist, &root, "main");
+    assert_eq!(value, Value::Unit);
+}
+
+#[test]
+fn encrypted_secrets_file() {
+    let _guard = ENV_LOCK.plock();
+    let dir = tempfile::tempdir().unwrap();
+    let encrypted_file = dir.path().join("secrets.enc");
+    std::fs::write(
+        &encrypted_file,
+        rain_core::secrets::encrypt("API_TOKEN = \"hunter22\"\n", "open sesame").unwrap(),
+    )
+    .unwrap();
+    let provider = rain_core::secrets::EncryptedFileProvider {
+        path: encrypted_file,
+    };
+    // Safety: Tests in this binary hold ENV_LOCK so nothing else is reading the environment
+    unsafe { std::env::set_var(rain_core::secrets::PASSPHRASE_ENV_VAR, "open sesame") };
+    assert_eq!(
+        rain_core::secrets::SecretProvider::get(&provider, "API_TOKEN").unwrap(),
+        Some(String::from("hunter22"))
+    );
+    assert_eq!(
+        rain_core::secrets::SecretProvider::get(&provider, "MISSING").unwrap(),
+        None
+    );
+}
@@ -50,4 +50,12 @@ tests! {
     record_type_check,
     private_declaration,
     throw,
+    format_string_type,
+    string_slice_out_of_bounds,
+    list_sort_incomparable,
+    list_sort_nan,
+    record_computed_key_type,
+    try_unrecoverable,
+    comprehension_iterable_type,
+    match_no_arm,
 }
@@ -0,0 +1,244 @@
+#![cfg(test)]
+
+use rain_lang::{
+    afs::{dir::Dir, entry::FSEntryTrait as _},
+    driver::{DriverTrait as _, FSTrait as _},
+};
+use std::{
+    io::Write as _,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use test_log::test;
+
+/// Commit `contents` to `hello.txt` on top of the current head
+fn commit_file(repo: &git2::Repository, contents: &str) -> git2::Oid {
+    let workdir = repo.workdir().unwrap();
+    std::fs::write(workdir.join("hello.txt"), contents).unwrap();
+    let mut index = repo.index().unwrap();
+    index.add_path(Path::new("hello.txt")).unwrap();
+    index.write().unwrap();
+    commit_index(repo, contents)
+}
+
+/// Commit whatever is in the index on top of the current head
+fn commit_index(repo: &git2::Repository, message: &str) -> git2::Oid {
+    let tree = repo
+        .find_tree(repo.index().unwrap().write_tree().unwrap())
+        .unwrap();
+    let sig = git2::Signature::now("rain", "rain@example.com").unwrap();
+    let parent = repo.head().ok().and_then(|h| h.peel_to_commit().ok());
+    let parents: Vec<&git2::Commit<'_>> = parent.iter().collect();
+    repo.commit(Some("HEAD"), &sig, &sig, message, &tree, &parents)
+        .unwrap()
+}
+
+fn new_config(dir: &Path) -> rain_core::config::Config {
+    rain_core::config::Config {
+        base_cache_dir: dir.join("cache"),
+        base_generated_dir: dir.join("generated"),
+        base_data_dir: dir.join("data"),
+        base_run_dir: dir.join("run"),
+        remote_cache_url: None,
+        secrets: rain_core::secrets::SecretsConfig::default(),
+    }
+}
+
+fn header(request: &tiny_http::Request, name: &'static str) -> Option<String> {
+    request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv(name))
+        .map(|h| h.value.to_string())
+}
+
+/// Serve the repos in `root` over smart http with `git http-backend`, unlike file:// this supports shallow fetches
+fn serve_git_http(root: &Path) -> String {
+    let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", server.server_addr().to_ip().unwrap());
+    let root = root.to_path_buf();
+    std::thread::spawn(move || {
+        for mut request in server.incoming_requests() {
+            let url = request.url().to_owned();
+            let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+            let mut cmd = Command::new("git");
+            cmd.arg("http-backend")
+                .env("GIT_PROJECT_ROOT", &root)
+                .env("GIT_HTTP_EXPORT_ALL", "1")
+                .env("PATH_INFO", path)
+                .env("QUERY_STRING", query)
+                .env("REQUEST_METHOD", request.method().as_str())
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped());
+            for (name, var) in [
+                ("Content-Type", "CONTENT_TYPE"),
+                ("Content-Encoding", "HTTP_CONTENT_ENCODING"),
+                ("Git-Protocol", "HTTP_GIT_PROTOCOL"),
+            ] {
+                if let Some(value) = header(&request, name) {
+                    cmd.env(var, value);
+                }
+            }
+            let mut body = Vec::new();
+            request.as_reader().read_to_end(&mut body).unwrap();
+            cmd.env("CONTENT_LENGTH", body.len().to_string());
+            let mut child = cmd.spawn().unwrap();
+            child.stdin.take().unwrap().write_all(&body).unwrap();
+            let output = child.wait_with_output().unwrap();
+            let split = output
+                .stdout
+                .windows(4)
+                .position(|w| w == b"\r\n\r\n")
+                .unwrap();
+            let head = std::str::from_utf8(&output.stdout[..split]).unwrap();
+            let mut response = tiny_http::Response::from_data(output.stdout[split + 4..].to_vec());
+            for line in head.lines() {
+                let (name, value) = line.split_once(": ").unwrap();
+                if name == "Status" {
+                    let code = value.split_once(' ').map_or(value, |(code, _)| code);
+                    response = response.with_status_code(code.parse::<u16>().unwrap());
+                } else {
+                    response.add_header(tiny_http::Header::from_bytes(name, value).unwrap());
+                }
+            }
+            request.respond(response).unwrap();
+        }
+    });
+    url
+}
+
+#[test]
+fn git_contents_resolves_refs() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = new_config(dir.path());
+    let driver = rain_core::driver::DriverImpl::new(config.clone());
+    let repo_path = dir.path().join("repo");
+    let repo = git2::Repository::init(&repo_path).unwrap();
+    let first = commit_file(&repo, "first");
+    let sig = git2::Signature::now("rain", "rain@example.com").unwrap();
+    repo.tag(
+        "v1",
+        &repo.find_object(first, None).unwrap(),
+        &sig,
+        "v1",
+        false,
+    )
+    .unwrap();
+    let second = commit_file(&repo, "second");
+    let branch = repo.head().unwrap().shorthand().unwrap().to_owned();
+    let url = format!("file://{}", repo_path.display());
+
+    assert_eq!(
+        driver.git_resolve_ref(&url, &branch).unwrap(),
+        second.to_string()
+    );
+    assert_eq!(
+        driver.git_resolve_ref(&url, "v1").unwrap(),
+        first.to_string()
+    );
+    driver.git_resolve_ref(&url, "missing").unwrap_err();
+
+    let area = driver
+        .git_contents(&url, &first.to_string(), false)
+        .unwrap();
+    let path = driver.resolve_fs_entry(Dir::root(area).inner());
+    assert_eq!(
+        std::fs::read_to_string(path.join("hello.txt")).unwrap(),
+        "first"
+    );
+
+    // Checkouts are made from the mirror so commits already in it don't need the remote
+    std::fs::remove_dir_all(&repo_path).unwrap();
+    let area = driver
+        .git_contents(&url, &second.to_string(), false)
+        .unwrap();
+    let path = driver.resolve_fs_entry(Dir::root(area).inner());
+    assert_eq!(
+        std::fs::read_to_string(path.join("hello.txt")).unwrap(),
+        "second"
+    );
+
+    let mirror = rain_core::git_mirror::mirror_path(&config, &url);
+    assert!(mirror.exists());
+    let pruned = rain_core::git_mirror::prune(&config, std::time::Duration::ZERO).unwrap();
+    assert_eq!(pruned.errors, 0);
+    assert!(!mirror.exists());
+}
+
+#[test]
+fn git_mirror_fetches_shallow_over_http() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = new_config(dir.path());
+    let driver = rain_core::driver::DriverImpl::new(config.clone());
+    let repo = git2::Repository::init(dir.path().join("served/repo")).unwrap();
+    // Fetching by hash needs the server to allow wanting commits that aren't advertised
+    repo.config()
+        .unwrap()
+        .set_bool("uploadpack.allowAnySHA1InWant", true)
+        .unwrap();
+    let first = commit_file(&repo, "first");
+    let second = commit_file(&repo, "second");
+    let url = format!("{}/repo", serve_git_http(&dir.path().join("served")));
+
+    let area = driver
+        .git_contents(&url, &first.to_string(), false)
+        .unwrap();
+    let path = driver.resolve_fs_entry(Dir::root(area).inner());
+    assert_eq!(
+        std::fs::read_to_string(path.join("hello.txt")).unwrap(),
+        "first"
+    );
+    let mirror = rain_core::git_mirror::mirror_path(&config, &url);
+    assert_eq!(
+        std::fs::read_to_string(mirror.join("shallow")).unwrap(),
+        format!("{first}\n")
+    );
+    let mirror_repo = git2::Repository::open_bare(&mirror).unwrap();
+    mirror_repo.find_commit(second).unwrap_err();
+
+    // The child of a shallow commit is fetched on its own too
+    let area = driver
+        .git_contents(&url, &second.to_string(), false)
+        .unwrap();
+    let path = driver.resolve_fs_entry(Dir::root(area).inner());
+    assert_eq!(
+        std::fs::read_to_string(path.join("hello.txt")).unwrap(),
+        "second"
+    );
+}
+
+#[test]
+fn git_contents_checks_out_submodules() {
+    let dir = tempfile::tempdir().unwrap();
+    let driver = rain_core::driver::DriverImpl::new(new_config(dir.path()));
+    let child_path = dir.path().join("child");
+    let child = git2::Repository::init(&child_path).unwrap();
+    commit_file(&child, "child");
+    let child_url = format!("file://{}", child_path.display());
+    let parent_path = dir.path().join("parent");
+    let parent = git2::Repository::init(&parent_path).unwrap();
+    let mut submodule = parent
+        .submodule(&child_url, Path::new("child"), true)
+        .unwrap();
+    submodule.clone(None).unwrap();
+    submodule.add_finalize().unwrap();
+    let commit = commit_index(&parent, "add child");
+    let url = format!("file://{}", parent_path.display());
+
+    let area = driver
+        .git_contents(&url, &commit.to_string(), true)
+        .unwrap();
+    let path = driver.resolve_fs_entry(Dir::root(area).inner());
+    assert_eq!(
+        std::fs::read_to_string(path.join("child/hello.txt")).unwrap(),
+        "child"
+    );
+
+    let area = driver
+        .git_contents(&url, &commit.to_string(), false)
+        .unwrap();
+    let path = driver.resolve_fs_entry(Dir::root(area).inner());
+    assert!(path.join("child").is_dir());
+    assert!(!path.join("child/hello.txt").exists());
+}
@@ -0,0 +1,132 @@
+#![cfg(test)]
+
+use rain_lang::{
+    afs::{dir::Dir, entry::FSEntryTrait as _},
+    driver::{DownloadAuth, DriverTrait as _, FSTrait as _, LfsOptions},
+};
+use sha2::Digest as _;
+use test_log::test;
+
+const OBJECT: &[u8] = b"pretend this is a very large binary file";
+
+/// Stand in for an lfs server that serves a single object to bearer `s3cret` until it is unblocked, returns the urls requested
+fn serve_lfs(server: &tiny_http::Server, oid: &str) -> Vec<String> {
+    let addr = server.server_addr().to_ip().unwrap();
+    let mut urls = Vec::new();
+    while let Ok(mut request) = server.recv() {
+        urls.push(request.url().to_owned());
+        let mut body = String::new();
+        request.as_reader().read_to_string(&mut body).unwrap();
+        let authorized = request
+            .headers()
+            .iter()
+            .any(|h| h.field.equiv("Authorization") && h.value.as_str() == "Bearer s3cret");
+        let response = match (request.method(), request.url()) {
+            (tiny_http::Method::Post, "/repo.git/info/lfs/objects/batch") if !authorized => {
+                tiny_http::Response::from_string("unauthorized").with_status_code(401)
+            }
+            (tiny_http::Method::Post, "/repo.git/info/lfs/objects/batch") => {
+                assert!(body.contains(oid));
+                tiny_http::Response::from_string(format!(
+                    r#"{{"transfer":"basic","objects":[{{"oid":"{oid}","size":{},"actions":{{"download":{{"href":"http://{addr}/objects/{oid}"}}}}}}]}}"#,
+                    OBJECT.len()
+                ))
+            }
+            (tiny_http::Method::Get, url) if url == format!("/objects/{oid}") => {
+                tiny_http::Response::from_data(OBJECT)
+            }
+            _ => tiny_http::Response::from_string("not found").with_status_code(404),
+        };
+        request.respond(response).unwrap();
+    }
+    urls
+}
+
+#[test]
+fn smudge_lfs_pointers() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = rain_core::config::Config {
+        base_cache_dir: dir.path().join("cache"),
+        base_generated_dir: dir.path().join("generated"),
+        base_data_dir: dir.path().join("data"),
+        base_run_dir: dir.path().join("run"),
+        remote_cache_url: None,
+        secrets: rain_core::secrets::SecretsConfig::default(),
+    };
+    let driver = rain_core::driver::DriverImpl::new(config);
+    let oid = base16::encode_lower(&sha2::Sha256::digest(OBJECT));
+    let area = driver
+        .create_overlay_area(std::iter::empty(), false, true)
+        .unwrap();
+    let area_path = driver.resolve_fs_entry(Dir::root(area.clone()).inner());
+    std::fs::create_dir_all(area_path.join("assets")).unwrap();
+    let pointer = format!(
+        "version https://git-lfs.github.com/spec/v1\noid sha256:{oid}\nsize {}\n",
+        OBJECT.len()
+    );
+    std::fs::write(area_path.join("assets/big.bin"), &pointer).unwrap();
+    std::fs::write(area_path.join("copy.bin"), &pointer).unwrap();
+    std::fs::write(area_path.join("small.txt"), "not a pointer").unwrap();
+    // Shares a stem with the pointer so must not be mistaken for its partial download
+    std::fs::write(area_path.join("assets/big.lfs-partial"), "keep me").unwrap();
+
+    let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+    let endpoint = format!(
+        "http://{}/repo.git/info/lfs",
+        server.server_addr().to_ip().unwrap()
+    );
+    let mut options = LfsOptions {
+        endpoint: Some(endpoint),
+        auth: Some(DownloadAuth::Bearer {
+            secret: String::from("LFS_TOKEN"),
+        }),
+        auth_secret: Some(String::from("wrong")),
+    };
+    let (smudged, urls) = std::thread::scope(|s| {
+        let serving = s.spawn(|| serve_lfs(&server, &oid));
+        let err = driver.git_lfs_smudge(&area, &options).unwrap_err();
+        assert!(err.to_string().contains("401"), "{err}");
+        options.auth_secret = Some(String::from("s3cret"));
+        let smudged = driver.git_lfs_smudge(&area, &options).unwrap();
+        server.unblock();
+        (smudged, serving.join().unwrap())
+    });
+    // Both pointers to the object share one download
+    assert_eq!(
+        urls,
+        [
+            String::from("/repo.git/info/lfs/objects/batch"),
+            String::from("/repo.git/info/lfs/objects/batch"),
+            format!("/objects/{oid}"),
+        ]
+    );
+
+    let smudged_path = driver.resolve_fs_entry(Dir::root(smudged).inner());
+    assert_eq!(
+        std::fs::read(smudged_path.join("assets/big.bin")).unwrap(),
+        OBJECT
+    );
+    assert_eq!(
+        std::fs::read(smudged_path.join("copy.bin")).unwrap(),
+        OBJECT
+    );
+    assert_eq!(
+        std::fs::read_to_string(smudged_path.join("small.txt")).unwrap(),
+        "not a pointer"
+    );
+    assert_eq!(
+        std::fs::read_to_string(smudged_path.join("assets/big.lfs-partial")).unwrap(),
+        "keep me"
+    );
+    assert_eq!(
+        std::fs::read_dir(smudged_path.join("assets"))
+            .unwrap()
+            .count(),
+        2
+    );
+    // The original area is left alone
+    assert_ne!(
+        std::fs::read(area_path.join("assets/big.bin")).unwrap(),
+        OBJECT
+    );
+}
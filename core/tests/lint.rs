@@ -0,0 +1,86 @@
+mod common;
+
+use std::{fmt::Write as _, path::Path};
+
+use rain_core::{
+    config::Config,
+    driver::DriverImpl,
+    rain_lang::{
+        afs::file::File,
+        ir::lint::{Lint, LintConfig, LintLevel},
+    },
+};
+
+fn lint(path: impl AsRef<Path>, config: &LintConfig) -> String {
+    let driver = DriverImpl::new(Config::default());
+    let mut out = String::new();
+    for (level, mut err) in rain_core::lint(path, &driver, config).expect("lint") {
+        err.file_name = String::from("<hidden>");
+        write!(out, "{level:?} {err}").expect("write string");
+    }
+    out
+}
+
+/// Code that runs fine can't refer to unknown identifiers
+#[test]
+fn no_unknown_idents_in_working_code() {
+    let mut config = LintConfig::default();
+    for lint in Lint::ALL {
+        config.set(lint, LintLevel::Allow);
+    }
+    config.set(Lint::UnknownIdent, LintLevel::Deny);
+    let driver = DriverImpl::new(Config::default());
+    for path in common::working_code() {
+        // Imported modules are checked on their own if they are working code
+        let file_name = File::new_local(&path).expect("local file").to_string();
+        let lints: String = rain_core::lint(&path, &driver, &config)
+            .expect("lint")
+            .into_iter()
+            .filter(|(_, err)| err.file_name == file_name)
+            .map(|(_, err)| err.to_string())
+            .collect();
+        assert!(lints.is_empty(), "{}\n{lints}", path.display());
+    }
+}
+
+#[test]
+fn unused_pub() {
+    let mut config = LintConfig::default();
+    config.set(Lint::UnusedPub, LintLevel::Warn);
+    insta::assert_snapshot!(lint("tests/lint/unused_pub.rain", &config));
+}
+
+/// Only pub declarations of the entrypoint count as targets, private ones nothing refers to are dead
+#[test]
+fn unused_private_in_entrypoint() {
+    let lints = lint("tests/lint/entrypoint.rain", &LintConfig::default());
+    assert!(lints.contains("`test_zig` is never used"), "{lints}");
+    assert!(!lints.contains("`build`"), "{lints}");
+}
+
+#[test]
+fn configured_levels() {
+    let mut config = LintConfig::default();
+    config.set(Lint::UnusedDeclaration, LintLevel::Deny);
+    config.set(Lint::UnusedImport, LintLevel::Allow);
+    config.set(Lint::UnusedDestructure, LintLevel::Allow);
+    insta::assert_snapshot!(lint("tests/lint/unused.rain", &config));
+}
+
+macro_rules! tests {
+    ($($name:ident,)*) => {
+        $(
+        #[test]
+        fn $name() {
+            insta::assert_snapshot!(lint(concat!("tests/lint/", stringify!($name), ".rain"), &LintConfig::default()));
+        }
+        )*
+    };
+}
+
+tests! {
+    unused,
+    shadowing,
+    unknown_ident,
+    unreachable,
+}
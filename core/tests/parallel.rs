@@ -0,0 +1,47 @@
+#![cfg(test)]
+
+use std::sync::Arc;
+
+use poison_panic::MutexExt as _;
+use rain_lang::runner::{parallel::WorkerPool, value::Value};
+use test_log::test;
+
+fn run_with_jobs(path: &str, jobs: usize) -> (Result<Value, String>, Vec<String>) {
+    let config = rain_core::config::Config::new();
+    let driver = rain_core::driver::DriverImpl::new(config);
+    let cache = rain_core::cache::Cache::default();
+    let file = rain_lang::afs::file::File::new_local(std::path::Path::new(path)).unwrap();
+    let src = std::fs::read_to_string(path).unwrap();
+    let module = rain_lang::ast::parser::parse_module(&src);
+    let ir = rain_lang::ir::Rir::new();
+    let mid = ir.insert_module(Some(file), src, module).unwrap();
+    let main = ir.resolve_global_declaration(mid, "main").unwrap();
+    let mut runner = rain_lang::runner::Runner::new(&ir, &cache, &driver);
+    runner.workers = Arc::new(WorkerPool::new(jobs));
+    let value = runner
+        .evaluate_and_call(main, &[])
+        .map_err(|err| err.resolve_ir(&ir).to_string());
+    let prints = driver.prints.plock().clone();
+    (value, prints)
+}
+
+#[test]
+fn parallel_matches_sequential() {
+    let (sequential_value, sequential_prints) = run_with_jobs("tests/scripts/parallel.rain", 1);
+    for jobs in [2, 4, 16] {
+        let (value, prints) = run_with_jobs("tests/scripts/parallel.rain", jobs);
+        assert_eq!(value, sequential_value);
+        assert_eq!(prints, sequential_prints);
+    }
+}
+
+#[test]
+fn parallel_error_is_not_cancellation() {
+    let (sequential_value, _) = run_with_jobs("tests/scripts/parallel_error.rain", 1);
+    let sequential_err = sequential_value.unwrap_err();
+    assert!(sequential_err.contains("boom"), "{sequential_err}");
+    for jobs in [2, 4, 16] {
+        let (value, _) = run_with_jobs("tests/scripts/parallel_error.rain", jobs);
+        assert_eq!(value.unwrap_err(), sequential_err);
+    }
+}
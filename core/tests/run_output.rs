@@ -0,0 +1,171 @@
+#![cfg(test)]
+#![cfg(target_family = "unix")]
+
+use std::{
+    path::Path,
+    sync::{Arc, Mutex, atomic::Ordering},
+    time::{Duration, Instant},
+};
+
+use poison_panic::MutexExt as _;
+use rain_core::driver::{DriverImpl, OutputHandler};
+use rain_lang::{
+    afs::entry::FSEntryTrait as _,
+    driver::{FSTrait as _, OutputStream},
+    runner::{
+        error::{ErrorTrace, KillReason, RunnerError, Throwing},
+        value::Value,
+    },
+};
+use test_log::test;
+
+fn new_driver<'a>(dir: &Path, output_handler: Option<OutputHandler<'a>>) -> DriverImpl<'a> {
+    let config = rain_core::config::Config {
+        base_cache_dir: dir.join("cache"),
+        base_generated_dir: dir.join("generated"),
+        base_data_dir: dir.join("data"),
+        base_run_dir: dir.join("run"),
+        remote_cache_url: None,
+        secrets: rain_core::secrets::SecretsConfig::default(),
+    };
+    DriverImpl {
+        output_handler,
+        ..DriverImpl::new(config)
+    }
+}
+
+fn run_script(
+    driver: &DriverImpl<'_>,
+    script: &str,
+    options: &str,
+) -> Result<Value, ErrorTrace<Throwing>> {
+    let cache = rain_core::cache::Cache::default();
+    let src = format!(
+        "let main = fn() {{
+            internal._run(internal._unit(), internal._escape_bin(\"sh\"), [\"-c\", \"{script}\"], {{}}, {options})
+        }}"
+    );
+    let module = rain_lang::ast::parser::parse_module(&src);
+    let ir = rain_lang::ir::Rir::new();
+    let mid = ir.insert_module(None, src, module).unwrap();
+    let main = ir.resolve_global_declaration(mid, "main").unwrap();
+    let mut runner = rain_lang::runner::Runner::new(&ir, &cache, driver);
+    runner.evaluate_and_call(main, &[])
+}
+
+fn field(value: &Value, key: &str) -> Value {
+    let Value::Record(record) = value else {
+        panic!("expected record got {value}");
+    };
+    record.0[key].clone()
+}
+
+#[test]
+fn run_output_streamed() {
+    let dir = tempfile::tempdir().unwrap();
+    let streamed = Mutex::new(Vec::new());
+    let driver = new_driver(
+        dir.path(),
+        Some(Box::new(|call, stream, chunk| {
+            streamed
+                .plock()
+                .push((call.to_owned(), stream, chunk.to_vec()));
+        })),
+    );
+    let value = run_script(&driver, "echo out; echo err >&2", "{}").unwrap();
+    drop(driver);
+
+    assert_eq!(
+        field(&value, "stdout"),
+        Value::String(String::from("out\n").into())
+    );
+    let streamed = streamed.into_inner().unwrap();
+    assert!(streamed.iter().all(|(call, _, _)| call.starts_with("Run ")));
+    let collect = |stream| {
+        streamed
+            .iter()
+            .filter(|(_, s, _)| *s == stream)
+            .flat_map(|(_, _, chunk)| chunk.iter().copied())
+            .collect::<Vec<u8>>()
+    };
+    assert_eq!(collect(OutputStream::Stdout), b"out\n");
+    assert_eq!(collect(OutputStream::Stderr), b"err\n");
+}
+
+#[test]
+fn run_output_not_utf8() {
+    let dir = tempfile::tempdir().unwrap();
+    let driver = new_driver(dir.path(), None);
+    let value = run_script(&driver, "printf 'a\\\\377b'", "{}").unwrap();
+
+    assert_eq!(field(&value, "success"), Value::Boolean(true));
+    assert_eq!(
+        field(&value, "stdout"),
+        Value::String(String::from("a\u{FFFD}b").into())
+    );
+    let Value::File(stdout_file) = field(&value, "stdout_file") else {
+        panic!("expected stdout_file to be a file");
+    };
+    let stdout = std::fs::read(driver.resolve_fs_entry(stdout_file.inner())).unwrap();
+    assert_eq!(stdout, b"a\xffb");
+}
+
+#[test]
+fn run_large_output_truncated() {
+    let dir = tempfile::tempdir().unwrap();
+    let driver = new_driver(dir.path(), None);
+    let value = run_script(
+        &driver,
+        "head -c 2097152 /dev/zero | tr '\\\\0' a; echo end",
+        "{}",
+    )
+    .unwrap();
+
+    let Value::String(stdout) = field(&value, "stdout") else {
+        panic!("expected stdout to be a string");
+    };
+    assert!(stdout.starts_with("[1048580 bytes truncated]\naaaa"));
+    assert!(stdout.ends_with("aaaaend\n"));
+    let Value::File(stdout_file) = field(&value, "stdout_file") else {
+        panic!("expected stdout_file to be a file");
+    };
+    let stdout = std::fs::read(driver.resolve_fs_entry(stdout_file.inner())).unwrap();
+    assert_eq!(stdout.len(), 2 * 1024 * 1024 + 4);
+}
+
+#[test]
+fn run_timeout_killed() {
+    let dir = tempfile::tempdir().unwrap();
+    let driver = new_driver(dir.path(), None);
+    let start = Instant::now();
+    let err = run_script(&driver, "echo started; sleep 60", "{timeout = 1}").unwrap_err();
+
+    assert!(start.elapsed() < Duration::from_secs(30));
+    let Throwing::Unrecoverable(RunnerError::RunKilled { reason, stdout, .. }) = err.err_span.err
+    else {
+        panic!("expected run to be killed got {:?}", err.err_span.err);
+    };
+    assert_eq!(reason, KillReason::Timeout(Duration::from_secs(1)));
+    assert_eq!(stdout, "started\n");
+}
+
+#[test]
+fn run_cancelled() {
+    let dir = tempfile::tempdir().unwrap();
+    let driver = new_driver(dir.path(), None);
+    let cancelled = Arc::clone(&driver.cancelled);
+    let cancel_thread = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(200));
+        cancelled.store(true, Ordering::Relaxed);
+    });
+    let err = run_script(&driver, "sleep 60", "{}").unwrap_err();
+    cancel_thread.join().unwrap();
+
+    assert!(matches!(
+        err.err_span.err,
+        Throwing::Unrecoverable(RunnerError::RunKilled {
+            reason: KillReason::Cancelled,
+            ..
+        })
+    ));
+}
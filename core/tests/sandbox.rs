@@ -0,0 +1,105 @@
+#![cfg(test)]
+#![cfg(target_os = "linux")]
+
+use std::path::Path;
+
+use rain_lang::runner::value::Value;
+use test_log::test;
+
+fn run_sandboxed(dir: &Path, src: &str) -> Value {
+    let config = rain_core::config::Config {
+        base_cache_dir: dir.join("cache"),
+        base_generated_dir: dir.join("generated"),
+        base_data_dir: dir.join("data"),
+        base_run_dir: dir.join("run"),
+        remote_cache_url: None,
+        secrets: rain_core::secrets::SecretsConfig::default(),
+    };
+    let mut driver = rain_core::driver::DriverImpl::new(config);
+    driver.sandbox = true;
+    let cache = rain_core::cache::Cache::default();
+    let path = dir.join("main.rain");
+    std::fs::write(&path, src).unwrap();
+    let file = rain_lang::afs::file::File::new_local(&path).unwrap();
+    let module = rain_lang::ast::parser::parse_module(src);
+    let ir = rain_lang::ir::Rir::new();
+    let mid = ir
+        .insert_module(Some(file), src.to_owned(), module)
+        .unwrap();
+    let main = ir.resolve_global_declaration(mid, "main").unwrap();
+    let mut runner = rain_lang::runner::Runner::new(&ir, &cache, &driver);
+    runner.evaluate_and_call(main, &[]).unwrap()
+}
+
+fn field(value: &Value, key: &str) -> Value {
+    let Value::Record(record) = value else {
+        panic!("expected record got {value}");
+    };
+    record.0[key].clone()
+}
+
+#[test]
+fn sandbox_only_sees_inputs() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = format!(
+        "let sh = internal._escape_bin(\"sh\")
+        let input = internal._create_file(\"hello\", \"input.txt\", false)
+        let sh_run = fn(script, arg) {{
+            internal._run(internal._unit(), sh, [\"-c\", script, \"sh\", arg], {{}})
+        }}
+        let main = fn() {{
+            copy = sh_run(\"cat $1 > out.txt\", input)
+            {{
+                copied = internal._read_file(internal._get_file(copy.area, \"out.txt\")),
+                write_input = sh_run(\"echo bye > $1\", input).success,
+                read_undeclared = sh_run(\"cat $1\", \"{}\").success,
+                network_interfaces = sh_run(\"grep -c : $1\", \"/proc/net/dev\").stdout,
+            }}
+        }}",
+        dir.path().join("main.rain").display()
+    );
+    let value = run_sandboxed(dir.path(), &src);
+    assert_eq!(
+        field(&value, "copied"),
+        Value::String(String::from("hello").into())
+    );
+    assert_eq!(field(&value, "write_input"), Value::Boolean(false));
+    assert_eq!(field(&value, "read_undeclared"), Value::Boolean(false));
+    assert_eq!(
+        field(&value, "network_interfaces"),
+        Value::String(String::from("1\n").into())
+    );
+}
+
+#[test]
+fn sandbox_has_own_pid_namespace_and_minimal_dev() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = "let sh = internal._escape_bin(\"sh\")
+        let sh_run = fn(script) {
+            internal._run(internal._unit(), sh, [\"-c\", script], {})
+        }
+        let main = fn() {
+            {
+                pid = sh_run(\"echo $$\").stdout,
+                init = sh_run(\"cat /proc/1/comm\").stdout,
+                dev = sh_run(\"ls /dev\").stdout,
+                write_null = sh_run(\"echo hi > /dev/null\").success,
+            }
+        }";
+    let value = run_sandboxed(dir.path(), src);
+    assert_eq!(
+        field(&value, "pid"),
+        Value::String(String::from("1\n").into())
+    );
+    assert_eq!(
+        field(&value, "init"),
+        Value::String(String::from("sh\n").into())
+    );
+    assert_eq!(
+        field(&value, "dev"),
+        Value::String(
+            String::from("fd\nnull\nrandom\nstderr\nstdin\nstdout\ntty\nurandom\nzero\n").into()
+        )
+    );
+    assert_eq!(field(&value, "write_null"), Value::Boolean(true));
+}
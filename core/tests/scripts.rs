@@ -46,4 +46,13 @@ tests! {
     any_type,
     record_type_check,
     generated_vs_local,
+    glob,
+    floats,
+    format_strings,
+    string_std,
+    list_std,
+    record_std,
+    try_catch,
+    comprehensions,
+    match_patterns,
 }
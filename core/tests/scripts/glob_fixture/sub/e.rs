@@ -0,0 +1 @@
+e
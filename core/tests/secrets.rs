@@ -0,0 +1,257 @@
+#![cfg(test)]
+
+use std::{path::Path, sync::Arc};
+
+use poison_panic::MutexExt as _;
+use rain_core::{
+    CoreError,
+    cache::{Cache, persistent::PersistCache},
+    driver::DriverImpl,
+    secrets::{SecretAllowlist, SecretsConfig},
+};
+use rain_lang::runner::{
+    cache::{CacheEntry, CacheKey, CacheTrait as _},
+    dep::Dep,
+    dep_list::DepList,
+    value::Value,
+};
+use test_log::test;
+
+const SECRETS_TOML: &str = "API_TOKEN = \"hunter22\"\nOTHER_TOKEN = \"correct-horse\"\n";
+
+fn new_driver(dir: &Path, secrets: SecretsConfig) -> DriverImpl<'static> {
+    DriverImpl::new(rain_core::config::Config {
+        base_cache_dir: dir.join("cache"),
+        base_generated_dir: dir.join("generated"),
+        base_data_dir: dir.join("data"),
+        base_run_dir: dir.join("run"),
+        remote_cache_url: None,
+        secrets,
+    })
+}
+
+/// Write a project that allows `API_TOKEN` and return the path of its main.rain
+fn write_project(dir: &Path, src: &str) -> std::path::PathBuf {
+    let project = dir.join("project");
+    std::fs::create_dir_all(&project).unwrap();
+    std::fs::write(
+        project.join(rain_core::secrets::ALLOWLIST_FILE_NAME),
+        "allow = [\"API_TOKEN\"]\n",
+    )
+    .unwrap();
+    let main = project.join("main.rain");
+    std::fs::write(&main, src).unwrap();
+    main
+}
+
+fn run(driver: &mut DriverImpl<'_>, main: &Path) -> Result<Value, CoreError> {
+    driver.secret_allowlist = SecretAllowlist::load(main.parent().unwrap()).unwrap();
+    let cache = Cache::default().with_secrets(Arc::clone(&driver.secrets));
+    rain_core::run(main, "main", &cache, driver)
+}
+
+#[test]
+fn secret_from_toml_file_is_redacted() {
+    let dir = tempfile::tempdir().unwrap();
+    let secrets_file = dir.path().join("secrets.toml");
+    std::fs::write(&secrets_file, SECRETS_TOML).unwrap();
+    let mut driver = new_driver(
+        dir.path(),
+        SecretsConfig {
+            file: Some(secrets_file),
+            ..SecretsConfig::default()
+        },
+    );
+    let main = write_project(
+        dir.path(),
+        "let main = fn() {
+            token = internal._get_secret(\"API_TOKEN\")
+            internal._print(\"token is\", token)
+            internal._throw(token)
+        }",
+    );
+    let err = run(&mut driver, &main).unwrap_err();
+    assert_eq!(*driver.prints.plock(), ["token is <redacted>"]);
+    let err = err.to_string();
+    assert!(!err.contains("hunter22"), "{err}");
+    assert!(err.contains("<redacted>"), "{err}");
+}
+
+#[test]
+fn secret_not_in_allowlist() {
+    let dir = tempfile::tempdir().unwrap();
+    let secrets_file = dir.path().join("secrets.toml");
+    std::fs::write(&secrets_file, SECRETS_TOML).unwrap();
+    let mut driver = new_driver(
+        dir.path(),
+        SecretsConfig {
+            file: Some(secrets_file),
+            ..SecretsConfig::default()
+        },
+    );
+    let main = write_project(
+        dir.path(),
+        "let main = fn() { internal._get_secret(\"OTHER_TOKEN\") }",
+    );
+    let err = run(&mut driver, &main).unwrap_err().to_string();
+    assert!(err.contains("not in the allowlist"), "{err}");
+    assert!(
+        err.contains(rain_core::secrets::ALLOWLIST_FILE_NAME),
+        "{err}"
+    );
+}
+
+#[cfg(target_family = "unix")]
+#[test]
+fn secret_from_command() {
+    let dir = tempfile::tempdir().unwrap();
+    let helper = dir.path().join("helper.sh");
+    std::fs::write(
+        &helper,
+        "#!/bin/sh\n[ \"$1\" = get ] || exit 1\nread line\n[ \"$line\" = name=API_TOKEN ] && echo value=from-helper\nexit 0\n",
+    )
+    .unwrap();
+    std::fs::set_permissions(&helper, std::os::unix::fs::PermissionsExt::from_mode(0o755)).unwrap();
+    let mut driver = new_driver(
+        dir.path(),
+        SecretsConfig {
+            command: Some(helper.display().to_string()),
+            ..SecretsConfig::default()
+        },
+    );
+    let main = write_project(
+        dir.path(),
+        "let main = fn() { internal._get_secret(\"API_TOKEN\") }",
+    );
+    let value = run(&mut driver, &main).unwrap();
+    assert_eq!(value.to_string(), "\"from-helper\"");
+}
+
+#[test]
+fn secret_values_not_persisted() {
+    let dir = tempfile::tempdir().unwrap();
+    let secrets_file = dir.path().join("secrets.toml");
+    std::fs::write(&secrets_file, SECRETS_TOML).unwrap();
+    let driver = new_driver(
+        dir.path(),
+        SecretsConfig {
+            file: Some(secrets_file),
+            ..SecretsConfig::default()
+        },
+    );
+    let cache = Cache::default().with_secrets(Arc::clone(&driver.secrets));
+    let secret = driver
+        .secrets
+        .get(
+            &SecretAllowlist::new(vec![String::from("API_TOKEN")]),
+            "API_TOKEN",
+        )
+        .unwrap();
+    let entry = |value: String| CacheEntry {
+        execution_time: std::time::Duration::from_secs(1),
+        expires: None,
+        etag: None,
+        deps: DepList::new(),
+        value: Value::String(Arc::new(value)),
+    };
+    let key = |url: &str| CacheKey::Download {
+        url: url.to_owned(),
+        sha256: None,
+        headers: Vec::new(),
+        auth: None,
+    };
+    cache.put(
+        key("https://example.com/secret"),
+        entry(format!("x{secret}x")),
+    );
+    cache.put(
+        key("https://example.com/public"),
+        entry(String::from("public")),
+    );
+    assert_eq!(cache.len(), 2);
+    let persisted = PersistCache::persist(
+        &cache.core.plock(),
+        &cache.stats,
+        &rain_lang::ir::Rir::new(),
+    );
+    assert_eq!(persisted.entries.len(), 1);
+    for entry in cache.inspect_all() {
+        assert!(!entry.contains(&secret), "{entry}");
+    }
+}
+
+#[test]
+fn secret_deps_not_persisted() {
+    let dir = tempfile::tempdir().unwrap();
+    let driver = new_driver(dir.path(), SecretsConfig::default());
+    let cache = Cache::default().with_secrets(Arc::clone(&driver.secrets));
+    let mut deps = DepList::new();
+    deps.push(Dep::Secret {
+        name: String::from("API_TOKEN"),
+        value_hash: Dep::value_hash("hunter22"),
+    });
+    cache.put(
+        CacheKey::Download {
+            url: String::from("https://example.com/private"),
+            sha256: None,
+            headers: Vec::new(),
+            auth: None,
+        },
+        CacheEntry {
+            execution_time: std::time::Duration::from_secs(1),
+            expires: None,
+            etag: None,
+            deps,
+            value: Value::String(Arc::new(String::from("downloaded"))),
+        },
+    );
+    assert_eq!(cache.len(), 1);
+    let persisted = PersistCache::persist(
+        &cache.core.plock(),
+        &cache.stats,
+        &rain_lang::ir::Rir::new(),
+    );
+    assert_eq!(persisted.entries.len(), 0);
+}
+
+#[cfg(target_family = "unix")]
+#[test]
+fn secret_redacted_from_streamed_output() {
+    let dir = tempfile::tempdir().unwrap();
+    let secrets_file = dir.path().join("secrets.toml");
+    std::fs::write(&secrets_file, SECRETS_TOML).unwrap();
+    let streamed = std::sync::Mutex::new(Vec::new());
+    let calls = std::sync::Mutex::new(Vec::new());
+    let mut driver = DriverImpl {
+        output_handler: Some(Box::new(|call, _stream, chunk| {
+            streamed.plock().push(call.to_owned());
+            streamed
+                .plock()
+                .push(String::from_utf8_lossy(chunk).into_owned());
+        })),
+        enter_handler: Some(Box::new(|s| calls.plock().push(s.to_owned()))),
+        ..new_driver(
+            dir.path(),
+            SecretsConfig {
+                file: Some(secrets_file),
+                ..SecretsConfig::default()
+            },
+        )
+    };
+    let main = write_project(
+        dir.path(),
+        "let main = fn() {
+            token = internal._get_secret(\"API_TOKEN\")
+            internal._run(internal._unit(), internal._escape_bin(\"sh\"), [\"-c\", \"printf hunter; printf '22 is the token'\"], {}, {})
+            internal._run(internal._unit(), internal._escape_bin(\"sh\"), [\"-c\", \"echo $0\", token], {}, {})
+        }",
+    );
+    run(&mut driver, &main).unwrap();
+    drop(driver);
+    let streamed = streamed.into_inner().unwrap().concat();
+    assert!(!streamed.contains("hunter22"), "{streamed}");
+    assert!(streamed.contains("<redacted> is the token"), "{streamed}");
+    let calls = calls.into_inner().unwrap().concat();
+    assert!(!calls.contains("hunter22"), "{calls}");
+    assert!(calls.contains("<redacted>"), "{calls}");
+}
@@ -0,0 +1,44 @@
+mod common;
+
+use std::path::Path;
+
+use rain_core::{config::Config, driver::DriverImpl};
+
+fn check_types(path: impl AsRef<Path>) -> String {
+    let driver = DriverImpl::new(Config::default());
+    rain_core::check_types(path, &driver)
+        .expect("check types")
+        .into_iter()
+        .map(|mut err| {
+            err.file_name = String::from("<hidden>");
+            err.to_string()
+        })
+        .collect()
+}
+
+/// Anything the checker reports in code that runs fine is a false positive
+#[test]
+fn no_errors_in_working_code() {
+    for path in common::working_code() {
+        let errors = check_types(&path);
+        assert!(errors.is_empty(), "{}\n{errors}", path.display());
+    }
+}
+
+macro_rules! tests {
+    ($($name:ident,)*) => {
+        $(
+        #[test]
+        fn $name() {
+            insta::assert_snapshot!(check_types(concat!("tests/typecheck/", stringify!($name), ".rain")));
+        }
+        )*
+    };
+}
+
+tests! {
+    internal_signatures,
+    record_fields,
+    closure_args,
+    expressions,
+}
@@ -100,16 +100,21 @@ pub enum Node {
     Closure(Closure),
     Block(Block),
     IfCondition(IfCondition),
+    TryCatch(TryCatch),
+    Match(Match),
     FnCall(FnCall),
     Assignment(Assignment),
     BinaryOp(BinaryOp),
     Not(Not),
     Ident(Ident),
     StringLiteral(StringLiteral),
+    FormatString(FormatString),
     IntegerLiteral(IntegerLiteral),
+    FloatLiteral(FloatLiteral),
     SimpleLiteral(SimpleLiteral),
     Record(Record),
     List(List),
+    Comprehension(Comprehension),
 }
 
 impl Node {
@@ -117,16 +122,21 @@ impl Node {
         match self {
             Self::Block(inner) => inner,
             Self::IfCondition(inner) => inner,
+            Self::TryCatch(inner) => inner,
+            Self::Match(inner) => inner,
             Self::FnCall(inner) => inner,
             Self::Assignment(inner) => inner,
             Self::BinaryOp(inner) => inner,
             Self::Not(inner) => inner,
             Self::Ident(inner) => inner,
             Self::StringLiteral(inner) => inner,
+            Self::FormatString(inner) => inner,
             Self::IntegerLiteral(inner) => inner,
+            Self::FloatLiteral(inner) => inner,
             Self::SimpleLiteral(inner) => inner,
             Self::Record(inner) => inner,
             Self::List(inner) => inner,
+            Self::Comprehension(inner) => inner,
             Self::Closure(inner) => inner,
         }
     }
@@ -450,6 +460,47 @@ impl AstNode for StringLiteral {
     }
 }
 
+/// String literal with the `f` prefix, the expressions inside braces are parsed as nodes
+#[derive(Debug)]
+pub struct FormatString {
+    pub token: TokenLocalSpan,
+    pub parts: Vec<FormatStringPart>,
+}
+
+#[derive(Debug)]
+pub enum FormatStringPart {
+    /// Literal text that may still contain escape sequences
+    Text(LocalSpan),
+    Interpolation {
+        lbrace: LocalSpan,
+        expr: NodeId,
+        rbrace: LocalSpan,
+    },
+}
+
+impl From<FormatString> for Node {
+    fn from(inner: FormatString) -> Self {
+        Self::FormatString(inner)
+    }
+}
+
+impl AstNode for FormatString {
+    fn span(&self, _list: &NodeList) -> LocalSpan {
+        self.token.span
+    }
+
+    fn ast_display(&self, f: &mut display::AstFormatter) -> std::fmt::Result {
+        let mut builder = f.node("FormatString");
+        for part in &self.parts {
+            match part {
+                FormatStringPart::Text(span) => builder.child_contents(*span),
+                FormatStringPart::Interpolation { expr, .. } => builder.child(*expr),
+            };
+        }
+        builder.finish()
+    }
+}
+
 #[derive(Debug)]
 pub struct BinaryOp {
     pub left: NodeId,
@@ -570,6 +621,94 @@ impl AstNode for IfCondition {
     }
 }
 
+/// `try { ... } catch err { ... }` catches recoverable errors thrown while evaluating the try block
+#[derive(Debug)]
+pub struct TryCatch {
+    pub try_token: LocalSpan,
+    pub try_block: NodeId,
+    pub catch_token: LocalSpan,
+    pub catch_name: TokenLocalSpan,
+    pub catch_block: NodeId,
+}
+
+impl From<TryCatch> for Node {
+    fn from(inner: TryCatch) -> Self {
+        Self::TryCatch(inner)
+    }
+}
+
+impl AstNode for TryCatch {
+    fn span(&self, list: &NodeList) -> LocalSpan {
+        self.try_token + list.span(self.catch_block)
+    }
+
+    fn ast_display(&self, f: &mut display::AstFormatter) -> std::fmt::Result {
+        f.node("TryCatch")
+            .child(self.try_block)
+            .child_contents(self.catch_name.span)
+            .child(self.catch_block)
+            .finish()
+    }
+}
+
+/// `match value { pattern if guard => expr, ... }` evaluates the first arm whose pattern matches and guard is true
+#[derive(Debug)]
+pub struct Match {
+    pub match_token: LocalSpan,
+    pub value: NodeId,
+    pub lbrace: LocalSpan,
+    pub arms: Vec<MatchArm>,
+    pub rbrace: LocalSpan,
+}
+
+#[derive(Debug)]
+pub struct MatchArm {
+    pub pattern: MatchPattern,
+    pub guard: Option<NodeId>,
+    pub fat_arrow: LocalSpan,
+    pub expr: NodeId,
+    pub comma: Option<LocalSpan>,
+}
+
+#[derive(Debug)]
+pub enum MatchPattern {
+    /// Matches values equal to the result of the expression
+    Value(NodeId),
+    /// Matches values of the name's type and binds them, a named destructure matches records with those keys and `_` binds nothing
+    Name(DeclareName),
+}
+
+impl From<Match> for Node {
+    fn from(inner: Match) -> Self {
+        Self::Match(inner)
+    }
+}
+
+impl AstNode for Match {
+    fn span(&self, _list: &NodeList) -> LocalSpan {
+        self.match_token + self.rbrace
+    }
+
+    fn ast_display(&self, f: &mut display::AstFormatter) -> std::fmt::Result {
+        let mut b = f.node("Match");
+        b.child(self.value);
+        for arm in &self.arms {
+            b.child_fn(|f| {
+                let mut b = f.node("MatchArm");
+                match &arm.pattern {
+                    MatchPattern::Value(nid) => b.child(*nid),
+                    MatchPattern::Name(name) => b.child_fn(|f| name.ast_display(f)),
+                };
+                if let Some(guard) = arm.guard {
+                    b.child_fn(|f| f.node("Guard").child(guard).finish());
+                }
+                b.child(arm.expr).finish()
+            });
+        }
+        b.finish()
+    }
+}
+
 #[derive(Debug)]
 pub struct Assignment {
     pub name: DeclareName,
@@ -655,6 +794,25 @@ impl AstNode for IntegerLiteral {
     }
 }
 
+#[derive(Debug)]
+pub struct FloatLiteral(pub TokenLocalSpan);
+
+impl From<FloatLiteral> for Node {
+    fn from(inner: FloatLiteral) -> Self {
+        Self::FloatLiteral(inner)
+    }
+}
+
+impl AstNode for FloatLiteral {
+    fn span(&self, _list: &NodeList) -> LocalSpan {
+        self.0.span
+    }
+
+    fn ast_display(&self, f: &mut display::AstFormatter) -> std::fmt::Result {
+        f.node("FloatLiteral").child_contents(self.0.span).finish()
+    }
+}
+
 #[derive(Debug)]
 pub struct SimpleLiteral {
     pub tls: TokenLocalSpan,
@@ -749,22 +907,48 @@ impl AstNode for Record {
 
 #[derive(Debug)]
 pub struct RecordField {
-    pub key: TokenLocalSpan,
+    pub key: RecordKey,
     pub equals: LocalSpan,
     pub value: NodeId,
     pub comma: Option<LocalSpan>,
 }
 
+#[derive(Debug)]
+pub enum RecordKey {
+    Ident(TokenLocalSpan),
+    /// Key computed from an expression that must evaluate to a string, `[expr] = value`
+    Computed {
+        lbracket: LocalSpan,
+        expr: NodeId,
+        rbracket: LocalSpan,
+    },
+}
+
+impl RecordKey {
+    pub fn span(&self) -> LocalSpan {
+        match self {
+            Self::Ident(tls) => tls.span,
+            Self::Computed {
+                lbracket, rbracket, ..
+            } => *lbracket + *rbracket,
+        }
+    }
+}
+
 impl AstNode for RecordField {
     fn span(&self, list: &NodeList) -> LocalSpan {
-        self.key.span + self.comma.unwrap_or_else(|| list.span(self.value))
+        self.key.span() + self.comma.unwrap_or_else(|| list.span(self.value))
     }
 
     fn ast_display(&self, f: &mut display::AstFormatter) -> std::fmt::Result {
-        f.node("RecordEntry")
-            .child_contents(self.key.span)
-            .child(self.value)
-            .finish()
+        let mut builder = f.node("RecordEntry");
+        match &self.key {
+            RecordKey::Ident(tls) => builder.child_contents(tls.span),
+            RecordKey::Computed { expr, .. } => {
+                builder.child_fn(|f| f.node("ComputedKey").child(*expr).finish())
+            }
+        };
+        builder.child(self.value).finish()
     }
 }
 
@@ -815,3 +999,68 @@ impl AstNode for ListElement {
         f.node("ListElement").child(self.value).finish()
     }
 }
+
+/// `[value for x in list if condition]` or `{[key] = value for k, v in record if condition}`
+#[derive(Debug)]
+pub struct Comprehension {
+    pub open: LocalSpan,
+    pub body: ComprehensionBody,
+    pub for_token: LocalSpan,
+    /// One name for each list element or a key and value name for each record entry
+    pub bindings: Vec<TokenLocalSpan>,
+    pub in_token: LocalSpan,
+    pub iterable: NodeId,
+    pub condition: Option<NodeId>,
+    pub close: LocalSpan,
+}
+
+#[derive(Debug)]
+pub enum ComprehensionBody {
+    List(NodeId),
+    Record {
+        key: RecordKey,
+        equals: LocalSpan,
+        value: NodeId,
+    },
+}
+
+impl From<Comprehension> for Node {
+    fn from(inner: Comprehension) -> Self {
+        Self::Comprehension(inner)
+    }
+}
+
+impl AstNode for Comprehension {
+    fn span(&self, _list: &NodeList) -> LocalSpan {
+        self.open + self.close
+    }
+
+    fn ast_display(&self, f: &mut display::AstFormatter) -> std::fmt::Result {
+        let mut b = match &self.body {
+            ComprehensionBody::List(value) => {
+                let mut b = f.node("ListComprehension");
+                b.child(*value);
+                b
+            }
+            ComprehensionBody::Record { key, value, .. } => {
+                let mut b = f.node("RecordComprehension");
+                match key {
+                    RecordKey::Ident(tls) => b.child_contents(tls.span),
+                    RecordKey::Computed { expr, .. } => {
+                        b.child_fn(|f| f.node("ComputedKey").child(*expr).finish())
+                    }
+                };
+                b.child(*value);
+                b
+            }
+        };
+        for binding in &self.bindings {
+            b.child_contents(binding.span);
+        }
+        b.child(self.iterable);
+        if let Some(condition) = self.condition {
+            b.child(condition);
+        }
+        b.finish()
+    }
+}
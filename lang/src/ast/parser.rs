@@ -3,15 +3,18 @@ use crate::{
         ArgTypeSpec, DeclareNameListElement, DeclareNamedDestructure, SimpleLiteralKind,
         error::{ParseError, ParseResult},
     },
-    local_span::ErrorLocalSpan,
-    tokens::{Token, TokenLocalSpan, peek::PeekTokenStream},
+    local_span::{ErrorLocalSpan, LocalSpan},
+    tokens::{
+        StringLiteralPrefix, Token, TokenLocalSpan, peek::PeekTokenStream, stream::TokenStream,
+    },
 };
 
 use super::{
-    AlternateCondition, Assignment, BinaryOp, BinaryOperatorKind, Block, Closure, Declare,
-    DeclareName, DeclareNameSingle, FnCall, FnDeclareArg, Ident, IfCondition, IntegerLiteral, List,
-    ListElement, Module, ModuleRoot, Node, NodeId, NodeList, Not, Record, RecordField,
-    StringLiteral,
+    AlternateCondition, Assignment, BinaryOp, BinaryOperatorKind, Block, Closure, Comprehension,
+    ComprehensionBody, Declare, DeclareName, DeclareNameSingle, FloatLiteral, FnCall, FnDeclareArg,
+    FormatString, FormatStringPart, Ident, IfCondition, IntegerLiteral, List, ListElement, Match,
+    MatchArm, MatchPattern, Module, ModuleRoot, Node, NodeId, NodeList, Not, Record, RecordField,
+    RecordKey, StringLiteral, TryCatch,
 };
 
 pub fn parse_module(source: &str) -> ParseResult<Module> {
@@ -36,6 +39,7 @@ pub fn parse_module_tree_sitter(source: &str) -> tree_sitter::Tree {
 }
 
 struct ModuleParser<'src> {
+    source: &'src str,
     nodes: NodeList,
     stream: PeekTokenStream<'src>,
 }
@@ -43,6 +47,7 @@ struct ModuleParser<'src> {
 impl<'src> ModuleParser<'src> {
     pub fn new(s: &'src str) -> Self {
         Self {
+            source: s,
             nodes: NodeList::new(),
             stream: PeekTokenStream::new(s),
         }
@@ -217,13 +222,28 @@ impl<'src> ModuleParser<'src> {
     }
 
     fn parse_assignment(&mut self) -> ParseResult<Assignment> {
+        let name = self.parse_declare_name(&[Token::Colon, Token::Assign])?;
+        let equals_token = self.stream.expect_parse_next(&[Token::Assign])?;
+        let expr = self.parse_expr()?;
+        Ok(Assignment {
+            name,
+            equals_token,
+            expr,
+        })
+    }
+
+    /// Parse a single name or a named destructure, `after_single_name` is what can follow a single name including a colon for its type
+    fn parse_declare_name(
+        &mut self,
+        after_single_name: &'static [Token],
+    ) -> ParseResult<DeclareName> {
         let token = self
             .stream
             .expect_parse_next(&[Token::Ident, Token::LBrace])?;
         let name = match token.token {
             Token::Ident => {
                 let name = token;
-                let peek = self.stream.expect_peek(&[Token::Colon, Token::Assign])?;
+                let peek = self.stream.expect_peek(after_single_name)?;
                 let type_spec = if peek.token == Token::Colon {
                     let colon_token = self.stream.expect_parse_next(&[Token::Colon])?;
                     Some(ArgTypeSpec {
@@ -289,13 +309,7 @@ impl<'src> ModuleParser<'src> {
             }
             _ => unreachable!(),
         };
-        let equals_token = self.stream.expect_parse_next(&[Token::Assign])?;
-        let expr = self.parse_expr()?;
-        Ok(Assignment {
-            name,
-            equals_token,
-            expr,
-        })
+        Ok(name)
     }
 
     fn parse_expr(&mut self) -> ParseResult<NodeId> {
@@ -331,6 +345,10 @@ impl<'src> ModuleParser<'src> {
             Token::Fn => self.parse_fn_declare(t)?,
             Token::Ident => self.push(Ident(t)),
             Token::Number => self.push(IntegerLiteral(t)),
+            Token::Float => self.push(FloatLiteral(t)),
+            Token::DoubleQuoteLiteral(Some(StringLiteralPrefix::Format)) => {
+                self.parse_format_string(t)?
+            }
             Token::DoubleQuoteLiteral(_) => self.push(StringLiteral(t)),
             Token::True => self.push(SimpleLiteralKind::True.with(t)),
             Token::False => self.push(SimpleLiteralKind::False.with(t)),
@@ -344,6 +362,8 @@ impl<'src> ModuleParser<'src> {
                 expr
             }
             Token::If => self.parse_if_condition(t)?,
+            Token::Try => self.parse_try_catch(t)?,
+            Token::Match => self.parse_match(t)?,
             Token::LBrace => self.parse_record(t)?,
             Token::LSqBracket => self.parse_list(t)?,
             Token::Excalmation => {
@@ -442,6 +462,79 @@ impl<'src> ModuleParser<'src> {
         }))
     }
 
+    fn parse_try_catch(&mut self, try_token: TokenLocalSpan) -> ParseResult<NodeId> {
+        debug_assert_eq!(try_token.token, Token::Try);
+        let try_block = self.parse_block()?;
+        let catch_token = self.stream.expect_parse_next(&[Token::Catch])?.span;
+        let catch_name = self.stream.expect_parse_next(&[Token::Ident])?;
+        let catch_block = self.parse_block()?;
+        Ok(self.push(TryCatch {
+            try_token: try_token.span,
+            try_block,
+            catch_token,
+            catch_name,
+            catch_block,
+        }))
+    }
+
+    fn parse_match(&mut self, match_token: TokenLocalSpan) -> ParseResult<NodeId> {
+        debug_assert_eq!(match_token.token, Token::Match);
+        let value = self.parse_expr()?;
+        let lbrace = self.stream.expect_parse_next(&[Token::LBrace])?.span;
+        let mut arms = Vec::new();
+        loop {
+            self.stream.skip_if_newline_or_comment()?;
+            let Some(peek) = self.stream.peek()? else {
+                break;
+            };
+            if peek.token == Token::RBrace {
+                break;
+            }
+            let pattern = match peek.token {
+                Token::Ident | Token::LBrace => MatchPattern::Name(self.parse_declare_name(&[
+                    Token::Colon,
+                    Token::If,
+                    Token::FatArrow,
+                ])?),
+                _ => MatchPattern::Value(self.parse_expr()?),
+            };
+            let mut guard = None;
+            if self
+                .stream
+                .peek()?
+                .is_some_and(|tls| tls.token == Token::If)
+            {
+                let _ = self.stream.parse_next()?;
+                guard = Some(self.parse_expr()?);
+            }
+            let fat_arrow = self.stream.expect_parse_next(&[Token::FatArrow])?.span;
+            let expr = self.parse_expr()?;
+            let mut comma = None;
+            if self
+                .stream
+                .peek()?
+                .is_some_and(|tls| tls.token == Token::Comma)
+            {
+                comma = Some(self.stream.expect_parse_next(&[Token::Comma])?.span);
+            }
+            arms.push(MatchArm {
+                pattern,
+                guard,
+                fat_arrow,
+                expr,
+                comma,
+            });
+        }
+        let rbrace = self.stream.expect_parse_next(&[Token::RBrace])?.span;
+        Ok(self.push(Match {
+            match_token: match_token.span,
+            value,
+            lbrace,
+            arms,
+            rbrace,
+        }))
+    }
+
     fn parse_alternate(&mut self) -> ParseResult<AlternateCondition> {
         let peek = self.stream.expect_peek(&[Token::If, Token::LBrace])?;
         match peek.token {
@@ -456,6 +549,77 @@ impl<'src> ModuleParser<'src> {
         }
     }
 
+    fn parse_format_string(&mut self, token: TokenLocalSpan) -> ParseResult<NodeId> {
+        let content = StringLiteral(token).content_span();
+        let bytes = self.source.as_bytes();
+        let mut parts = Vec::new();
+        let mut text_start = content.start;
+        let mut index = content.start;
+        while index < content.end {
+            let c_next = bytes.get(index + 1);
+            match bytes.get(index) {
+                Some(b'\\') => index += 2,
+                Some(b'{') if c_next == Some(&b'{') => {
+                    // Keep the first brace of the pair as text
+                    parts.push(FormatStringPart::Text(LocalSpan::new(
+                        text_start,
+                        index + 1,
+                    )));
+                    index += 2;
+                    text_start = index;
+                }
+                Some(b'}') if c_next == Some(&b'}') => {
+                    parts.push(FormatStringPart::Text(LocalSpan::new(
+                        text_start,
+                        index + 1,
+                    )));
+                    index += 2;
+                    text_start = index;
+                }
+                Some(b'{') => {
+                    if text_start < index {
+                        parts.push(FormatStringPart::Text(LocalSpan::new(text_start, index)));
+                    }
+                    let lbrace = LocalSpan::byte(index);
+                    let (expr, rbrace) =
+                        self.parse_interpolation(LocalSpan::new(index + 1, content.end))?;
+                    parts.push(FormatStringPart::Interpolation {
+                        lbrace,
+                        expr,
+                        rbrace,
+                    });
+                    index = rbrace.end;
+                    text_start = index;
+                }
+                Some(b'}') => {
+                    return Err(
+                        LocalSpan::byte(index).with_error(ParseError::UnmatchedPair(Token::RBrace))
+                    );
+                }
+                _ => index += 1,
+            }
+        }
+        if text_start < content.end {
+            parts.push(FormatStringPart::Text(LocalSpan::new(
+                text_start,
+                content.end,
+            )));
+        }
+        Ok(self.push(FormatString { token, parts }))
+    }
+
+    /// Parse the expression of a format string interpolation up to and including its closing brace
+    fn parse_interpolation(&mut self, span: LocalSpan) -> ParseResult<(NodeId, LocalSpan)> {
+        let inner = PeekTokenStream::from(TokenStream::new_range(self.source, span));
+        let outer = std::mem::replace(&mut self.stream, inner);
+        let result = self.parse_expr().and_then(|expr| {
+            let rbrace = self.stream.expect_parse_next(&[Token::RBrace])?;
+            Ok((expr, rbrace.span))
+        });
+        self.stream = outer;
+        result
+    }
+
     fn parse_fn_call(&mut self, lhs: NodeId) -> ParseResult<NodeId> {
         let lparen_token = self.stream.expect_parse_next(&[Token::LParen])?;
         let mut args = Vec::new();
@@ -497,11 +661,31 @@ impl<'src> ModuleParser<'src> {
             if peek.token == Token::RBrace {
                 break;
             }
-            let key = self.stream.expect_parse_next(&[Token::Ident])?;
+            let key = self
+                .stream
+                .expect_parse_next(&[Token::Ident, Token::LSqBracket])?;
+            let key = if key.token == Token::LSqBracket {
+                let expr = self.parse_expr()?;
+                let rbracket = self.stream.expect_parse_next(&[Token::RSqBracket])?.span;
+                RecordKey::Computed {
+                    lbracket: key.span,
+                    expr,
+                    rbracket,
+                }
+            } else {
+                RecordKey::Ident(key)
+            };
             let equals = self.stream.expect_parse_next(&[Token::Assign])?.span;
             let value = self.parse_expr()?;
             let mut comma = None;
             if let Some(tls) = self.stream.peek()? {
+                if tls.token == Token::For && fields.is_empty() {
+                    return self.parse_comprehension(
+                        lbrace,
+                        ComprehensionBody::Record { key, equals, value },
+                        &[Token::RBrace],
+                    );
+                }
                 if tls.token == Token::Comma {
                     comma = Some(self.stream.expect_parse_next(&[Token::Comma])?.span);
                 }
@@ -521,6 +705,48 @@ impl<'src> ModuleParser<'src> {
         }))
     }
 
+    fn parse_comprehension(
+        &mut self,
+        open: LocalSpan,
+        body: ComprehensionBody,
+        close: &'static [Token],
+    ) -> ParseResult<NodeId> {
+        let for_token = self.stream.expect_parse_next(&[Token::For])?.span;
+        let mut bindings = vec![self.stream.expect_parse_next(&[Token::Ident])?];
+        if self
+            .stream
+            .peek()?
+            .is_some_and(|tls| tls.token == Token::Comma)
+        {
+            let _ = self.stream.parse_next()?;
+            bindings.push(self.stream.expect_parse_next(&[Token::Ident])?);
+        }
+        let in_token = self.stream.expect_parse_next(&[Token::In])?.span;
+        let iterable = self.parse_expr()?;
+        self.stream.skip_if_newline()?;
+        let mut condition = None;
+        if self
+            .stream
+            .peek()?
+            .is_some_and(|tls| tls.token == Token::If)
+        {
+            let _ = self.stream.parse_next()?;
+            condition = Some(self.parse_expr()?);
+            self.stream.skip_if_newline()?;
+        }
+        let close = self.stream.expect_parse_next(close)?.span;
+        Ok(self.push(Comprehension {
+            open,
+            body,
+            for_token,
+            bindings,
+            in_token,
+            iterable,
+            condition,
+            close,
+        }))
+    }
+
     fn parse_list(&mut self, lbracket: TokenLocalSpan) -> ParseResult<NodeId> {
         let lbracket = lbracket.span;
         let mut elements = Vec::new();
@@ -537,6 +763,13 @@ impl<'src> ModuleParser<'src> {
             let Some(tls) = self.stream.peek()? else {
                 break;
             };
+            if tls.token == Token::For && elements.is_empty() {
+                return self.parse_comprehension(
+                    lbracket,
+                    ComprehensionBody::List(value),
+                    &[Token::RSqBracket],
+                );
+            }
             if tls.token == Token::Comma {
                 let comma = Some(self.stream.expect_parse_next(&[Token::Comma])?.span);
                 elements.push(ListElement { value, comma });
@@ -587,6 +820,7 @@ mod test {
         afs::file::File,
         ast::{error::ParseError, parser::ModuleParser},
         local_span::{ErrorLocalSpan, LocalSpan},
+        tokens::Token,
     };
 
     use super::parse_module;
@@ -616,6 +850,34 @@ mod test {
         insta::assert_snapshot!(parse_display_expr("4"));
     }
 
+    #[test]
+    fn float_literal() {
+        insta::assert_snapshot!(parse_display_expr("4.5e3"));
+    }
+
+    #[test]
+    fn format_string() {
+        insta::assert_snapshot!(parse_display_expr(
+            "f\"{target}-{version.name}.tar.gz {{{foo(\"}\")}}}\""
+        ));
+    }
+
+    #[test]
+    fn format_string_errors() {
+        assert_eq!(
+            ModuleParser::new("f\"a {} b\"").parse_expr(),
+            Err(LocalSpan::byte(5).with_error(ParseError::ExpectedExpression))
+        );
+        assert_eq!(
+            ModuleParser::new("f\"a {b c} d\"").parse_expr(),
+            Err(LocalSpan::byte(7).with_error(ParseError::ExpectedToken(&[Token::RBrace])))
+        );
+        assert_eq!(
+            ModuleParser::new("f\"a } b\"").parse_expr(),
+            Err(LocalSpan::byte(4).with_error(ParseError::UnmatchedPair(Token::RBrace)))
+        );
+    }
+
     #[test]
     fn false_literal() {
         insta::assert_snapshot!(parse_display_expr("false"));
@@ -758,6 +1020,21 @@ mod test {
         insta::assert_snapshot!(parse_display_expr("{\na = b, \n// comment \n c = 4\n}"));
     }
 
+    #[test]
+    fn record_constructor_computed_key() {
+        insta::assert_snapshot!(parse_display_expr("{[a + \"b\"] = 1, c = 2}"));
+    }
+
+    #[test]
+    fn list_comprehension() {
+        insta::assert_snapshot!(parse_display_expr("[f(x) for x in xs if x > 1]"));
+    }
+
+    #[test]
+    fn record_comprehension() {
+        insta::assert_snapshot!(parse_display_expr("{[k + \"_\"] = v * 2 for k, v in r}"));
+    }
+
     #[test]
     fn list_constructor_nested() {
         insta::assert_snapshot!(parse_display_expr("[a, b, 123, [567, d]]"));
@@ -869,4 +1146,18 @@ mod test {
     fn internal() {
         insta::assert_snapshot!(parse_display_expr("internal._print(42)"));
     }
+
+    #[test]
+    fn match_expr() {
+        insta::assert_snapshot!(parse_display_expr(
+            "match v {\n\t\"linux\" => 1,\n\tn: Integer if n > 2 => n\n\t{os, arch: String} => os,\n\t_ => 0,\n}"
+        ));
+    }
+
+    #[test]
+    fn try_catch() {
+        insta::assert_snapshot!(parse_display_expr(
+            "try { fetch(mirror) } catch err { build(err.value) }"
+        ));
+    }
 }
@@ -2,20 +2,31 @@ use std::{
     borrow::Cow,
     collections::HashMap,
     path::{Path, PathBuf},
+    time::Duration,
 };
 
 use crate::{
     afs::{absolute::AbsolutePathBuf, area::FileArea, dir::Dir, entry::FSEntry, file::File},
-    runner::{error::RunnerError, internal::InternalFunction},
+    runner::{
+        dep::{ContentsFilter, Fingerprint},
+        error::RunnerError,
+        internal::InternalFunction,
+    },
 };
 
 pub trait FSTrait {
     /// Resolves file path locally returning an absolute path
     fn resolve_fs_entry(&self, file: &FSEntry) -> PathBuf;
     fn query_fs(&self, entry: &FSEntry) -> Result<FSEntryQueryResult, std::io::Error>;
+    /// Fingerprint an entry so it can be checked for changes later, directory contents are only hashed if `contents` is set
+    fn fingerprint(
+        &self,
+        entry: &FSEntry,
+        contents: Option<&ContentsFilter>,
+    ) -> Result<Fingerprint, std::io::Error>;
 }
 
-pub trait DriverTrait: MonitoringTrait + FSTrait {
+pub trait DriverTrait: MonitoringTrait + FSTrait + Send + Sync {
     fn print(&self, message: String);
     fn escape_bin(&self, name: &str) -> Option<AbsolutePathBuf>;
     fn extract_zip(&self, file: &File) -> Result<FileArea, RunnerError>;
@@ -36,11 +47,13 @@ pub trait DriverTrait: MonitoringTrait + FSTrait {
         args: Vec<String>,
         options: RunOptions,
     ) -> Result<EscapeRunStatus, RunnerError>;
+    /// Download `url` falling back to each of the mirrors in turn, if `options.sha256` is set the download is checked against it
     fn download(
         &self,
         url: &str,
         outname: &str,
         etag: Option<&[u8]>,
+        options: &DownloadOptions,
     ) -> Result<DownloadStatus, RunnerError>;
     fn sha256(&self, file: &File) -> Result<String, RunnerError>;
     fn sha512(&self, file: &File) -> Result<String, RunnerError>;
@@ -57,7 +70,7 @@ pub trait DriverTrait: MonitoringTrait + FSTrait {
         executable: bool,
     ) -> Result<File, RunnerError>;
     fn file_metadata(&self, file: &File) -> Result<FileMetadata, RunnerError>;
-    fn glob(&self, dir: &Dir, pattern: &str) -> Result<Vec<File>, RunnerError>;
+    fn glob(&self, dir: &Dir, options: &GlobOptions) -> Result<Vec<GlobMatch>, RunnerError>;
     fn embed_src(&self) -> Option<Cow<'static, str>>;
     fn host_triple(&self) -> &str;
     fn export_file(&self, src: &File, dst: &FSEntry) -> Result<(), RunnerError>;
@@ -65,8 +78,21 @@ pub trait DriverTrait: MonitoringTrait + FSTrait {
     fn create_tar(&self, dir: &Dir, name: &str) -> Result<File, RunnerError>;
     fn compress_gzip(&self, file: &File, name: &str) -> Result<File, RunnerError>;
     fn get_secret(&self, name: &str) -> Result<String, RunnerError>;
-    fn git_contents(&self, url: &str, commit: &str) -> Result<FileArea, RunnerError>;
-    fn git_lfs_smudge(&self, area: &FileArea) -> Result<FileArea, RunnerError>;
+    /// Shallow checkout of a commit of a remote repo, submodules are only checked out if `submodules` is set
+    fn git_contents(
+        &self,
+        url: &str,
+        commit: &str,
+        submodules: bool,
+    ) -> Result<FileArea, RunnerError>;
+    /// Resolve a branch, tag or other ref of a remote repo to the commit it points at
+    fn git_resolve_ref(&self, url: &str, git_ref: &str) -> Result<String, RunnerError>;
+    /// Copy `area` replacing git lfs pointer files with their objects
+    fn git_lfs_smudge(
+        &self,
+        area: &FileArea,
+        options: &LfsOptions,
+    ) -> Result<FileArea, RunnerError>;
     fn env_var(&self, key: &str) -> Result<Option<String>, RunnerError>;
     fn copy_file(&self, file: &File, name: &str, executable: bool) -> Result<File, RunnerError>;
     fn copy_dir(&self, dir: &Dir, name: &str, include_hidden: bool) -> Result<Dir, RunnerError>;
@@ -79,26 +105,143 @@ pub trait MonitoringTrait {
     fn exit_call(&self, _s: &str) {}
     fn enter_internal_call(&self, _f: &InternalFunction) {}
     fn exit_internal_call(&self, _f: &InternalFunction) {}
+    /// Output from a running command as it is produced, `call` is the description the call was entered with
+    fn output(&self, _call: &str, _stream: OutputStream, _chunk: &[u8]) {}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
 }
 
 pub struct RunOptions {
+    /// Description of the call the command is run in, streamed output is reported against it
+    pub call: String,
     pub inherit_env: bool,
     pub env: HashMap<String, String>,
+    /// Allow network access when running sandboxed
+    pub network: bool,
+    /// Areas referred to by the command, when sandboxed these are the only areas it can read
+    pub input_areas: Vec<FileArea>,
+    /// Kill the command if it is still running after this long
+    pub timeout: Option<Duration>,
+    pub limits: ResourceLimits,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ResourceLimits {
+    /// Maximum size of the command's address space in bytes
+    pub memory: Option<u64>,
+    /// Maximum CPU time of the command in seconds
+    pub cpu: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct GlobOptions {
+    /// Patterns matched against paths relative to the globbed dir, patterns starting with `!` exclude matches
+    pub patterns: Vec<String>,
+    /// Also return directories that match the patterns
+    pub include_dirs: bool,
+    /// Skip hidden files and files ignored by `.gitignore` and `.ignore` files
+    pub respect_gitignore: bool,
+}
+
+#[derive(Debug)]
+pub enum GlobMatch {
+    File(File),
+    Dir(Dir),
 }
 
 pub struct RunStatus {
     pub success: bool,
     pub exit_code: Option<i32>,
     pub area: FileArea,
+    /// Output decoded lossily and truncated to its end if it is large, the exact bytes are in `stdout_file`
     pub stdout: String,
+    /// Output decoded lossily and truncated to its end if it is large, the exact bytes are in `stderr_file`
     pub stderr: String,
+    pub stdout_file: File,
+    pub stderr_file: File,
 }
 
 pub struct EscapeRunStatus {
     pub success: bool,
     pub exit_code: Option<i32>,
+    /// Output decoded lossily and truncated to its end if it is large, the exact bytes are in `stdout_file`
     pub stdout: String,
+    /// Output decoded lossily and truncated to its end if it is large, the exact bytes are in `stderr_file`
     pub stderr: String,
+    pub stdout_file: File,
+    pub stderr_file: File,
+}
+
+#[derive(Default, Clone)]
+pub struct DownloadOptions {
+    /// Expected hex encoded sha256 of the download
+    pub sha256: Option<String>,
+    /// Urls to try in order if the download fails
+    pub mirrors: Vec<String>,
+    /// Extra request headers sent to every url
+    pub headers: Vec<(String, String)>,
+    pub auth: Option<DownloadAuth>,
+    /// Value of the secret named by `auth`
+    pub auth_secret: Option<String>,
+}
+
+impl std::fmt::Debug for DownloadOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DownloadOptions")
+            .field("sha256", &self.sha256)
+            .field("mirrors", &self.mirrors)
+            .field("headers", &self.headers)
+            .field("auth", &self.auth)
+            .field(
+                "auth_secret",
+                &self.auth_secret.as_ref().map(|_| "<redacted>"),
+            )
+            .finish()
+    }
+}
+
+/// Authorization for a download, the secret is only named so this is safe to keep in cache keys
+#[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub enum DownloadAuth {
+    /// `Authorization: Bearer <secret>`
+    Bearer { secret: String },
+    /// `Authorization: Basic` with the secret as the password
+    Basic { username: String, secret: String },
+}
+
+impl DownloadAuth {
+    pub fn secret_name(&self) -> &str {
+        match self {
+            Self::Bearer { secret } | Self::Basic { secret, .. } => secret,
+        }
+    }
+}
+
+#[derive(Default, Clone)]
+pub struct LfsOptions {
+    /// Lfs server to fetch objects from, worked out from the area's repo if not given
+    pub endpoint: Option<String>,
+    /// Authorization for the batch api, object downloads only get the headers the batch api returns
+    pub auth: Option<DownloadAuth>,
+    /// Value of the secret named by `auth`
+    pub auth_secret: Option<String>,
+}
+
+impl std::fmt::Debug for LfsOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LfsOptions")
+            .field("endpoint", &self.endpoint)
+            .field("auth", &self.auth)
+            .field(
+                "auth_secret",
+                &self.auth_secret.as_ref().map(|_| "<redacted>"),
+            )
+            .finish()
+    }
 }
 
 pub struct DownloadStatus {
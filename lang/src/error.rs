@@ -91,6 +91,18 @@ pub struct OwnedResolvedError {
 }
 
 impl OwnedResolvedError {
+    /// Apply `redact` to everything that could contain a runtime value so secrets can be removed before the error is shown
+    pub fn redact(&mut self, redact: impl Fn(&str) -> String) {
+        for s in [
+            &mut self.before,
+            &mut self.contents,
+            &mut self.after,
+            &mut self.err,
+        ] {
+            *s = redact(s);
+        }
+    }
+
     pub fn write_color(&self, writer: &mut impl WriteColor) -> std::io::Result<()> {
         use termcolor::{Color, ColorSpec};
         let Self {
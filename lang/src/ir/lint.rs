@@ -0,0 +1,917 @@
+//! Lints for mistakes in rain sources that evaluation either silently ignores or only hits at runtime
+//!
+//! Each module is linted on its own by walking its declarations with the same scoping rules as the runner. Whether `pub` declarations are used outside their module is worked out afterwards from the fields accessed on static `import("path")` calls across all the modules that were linted.
+
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ast::{
+        AlternateCondition, ArgTypeSpec, Assignment, BinaryOp, BinaryOperatorKind, Block, Closure,
+        Comprehension, ComprehensionBody, DeclareName, FnCall, FormatStringPart, IfCondition,
+        Match, MatchPattern, Node, NodeId, RecordKey, SimpleLiteral, SimpleLiteralKind, TryCatch,
+    },
+    ir::{IrModule, ModuleId, Rir},
+    local_span::{ErrorLocalSpan, LocalSpan},
+    span::ErrorSpan,
+    tokens::StringLiteralPrefix,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Lint {
+    /// Private declaration that nothing refers to
+    UnusedDeclaration,
+    /// Imported module that nothing refers to
+    UnusedImport,
+    /// Binding with the same name as a binding in an outer scope or a declaration
+    ShadowedName,
+    /// Field of a destructure that nothing refers to
+    UnusedDestructure,
+    /// Identifier that does not resolve to anything so will fail when evaluated
+    UnknownIdent,
+    /// `pub` declaration that no other linted module uses
+    UnusedPub,
+    /// Code that can never be evaluated
+    UnreachableCode,
+}
+
+impl Lint {
+    pub const ALL: [Self; 7] = [
+        Self::UnusedDeclaration,
+        Self::UnusedImport,
+        Self::ShadowedName,
+        Self::UnusedDestructure,
+        Self::UnknownIdent,
+        Self::UnusedPub,
+        Self::UnreachableCode,
+    ];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::UnusedDeclaration => "unused_declaration",
+            Self::UnusedImport => "unused_import",
+            Self::ShadowedName => "shadowed_name",
+            Self::UnusedDestructure => "unused_destructure",
+            Self::UnknownIdent => "unknown_ident",
+            Self::UnusedPub => "unused_pub",
+            Self::UnreachableCode => "unreachable_code",
+        }
+    }
+
+    /// Public declarations are the targets `rain exec` runs and the API of libraries, so whether they are used is only checked when asked for
+    pub const fn default_level(self) -> LintLevel {
+        match self {
+            Self::UnknownIdent => LintLevel::Deny,
+            Self::UnusedPub => LintLevel::Allow,
+            Self::UnusedDeclaration
+            | Self::UnusedImport
+            | Self::ShadowedName
+            | Self::UnusedDestructure
+            | Self::UnreachableCode => LintLevel::Warn,
+        }
+    }
+}
+
+impl std::fmt::Display for Lint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Lint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|lint| lint.name() == s)
+            .ok_or_else(|| format!("unknown lint {s:?}"))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LintLevel {
+    Allow,
+    Warn,
+    Deny,
+}
+
+/// Level of each lint, lints that are not set use their [`Lint::default_level`]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct LintConfig {
+    levels: HashMap<Lint, LintLevel>,
+}
+
+impl LintConfig {
+    pub fn set(&mut self, lint: Lint, level: LintLevel) {
+        self.levels.insert(lint, level);
+    }
+
+    pub fn level(&self, lint: Lint) -> LintLevel {
+        self.levels
+            .get(&lint)
+            .copied()
+            .unwrap_or_else(|| lint.default_level())
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("{message} [{lint}]")]
+pub struct LintError {
+    pub lint: Lint,
+    pub level: LintLevel,
+    pub message: String,
+}
+
+/// Lint the `root` module and every module it statically imports
+///
+/// `load_import` is called with the importing module and the path passed to `import` and should insert the imported module into `ir`, modules that can't be loaded are skipped.
+pub fn lint(
+    ir: &Rir,
+    root: ModuleId,
+    config: &LintConfig,
+    mut load_import: impl FnMut(&IrModule, &str) -> Option<ModuleId>,
+) -> Vec<ErrorSpan<LintError>> {
+    let mut errors = Vec::new();
+    let mut reports: HashMap<ModuleId, ModuleReport> = HashMap::new();
+    // Modules in the order they were linted so the lints are reported in a stable order
+    let mut linted = Vec::new();
+    let mut queue = vec![root];
+    while let Some(mid) = queue.pop() {
+        if reports.contains_key(&mid) {
+            continue;
+        }
+        let module = ir.get_module(mid);
+        let mut report = ModuleLinter::new(&module, config).run();
+        errors.extend(report.errors.drain(..).map(|err| err.upgrade(mid)));
+        // Private aliases are only loaded if they are used but re-exports can always be used
+        let paths = report.imports.iter().map(|import| &import.path);
+        for import in paths.chain(report.reexports.values()) {
+            if !report.resolved.contains_key(import) {
+                if let Some(imported) = load_import(&module, import) {
+                    report.resolved.insert(import.clone(), imported);
+                    queue.push(imported);
+                }
+            }
+        }
+        reports.insert(mid, report);
+        linted.push(mid);
+    }
+    if config.level(Lint::UnusedPub) != LintLevel::Allow {
+        errors.extend(unused_pub(ir, &linted, config, &reports));
+    }
+    errors
+}
+
+/// What is used from the `pub` declarations of a module by the other modules
+enum PubUses {
+    All,
+    Names(HashSet<String>),
+}
+
+fn unused_pub(
+    ir: &Rir,
+    linted: &[ModuleId],
+    config: &LintConfig,
+    reports: &HashMap<ModuleId, ModuleReport>,
+) -> Vec<ErrorSpan<LintError>> {
+    let mut uses: HashMap<ModuleId, PubUses> = HashMap::new();
+    // `rain exec` can run anything in the entrypoint and the modules it re-exports
+    if let Some(root) = linted.first() {
+        mark_pub_used(&mut uses, reports, *root, None);
+    }
+    for report in reports.values() {
+        for import in &report.imports {
+            if let Some(mid) = report.resolved.get(&import.path) {
+                mark_pub_used(&mut uses, reports, *mid, import.fields.as_deref());
+            }
+        }
+    }
+    let mut errors = Vec::new();
+    for &mid in linted {
+        let names = match uses.get(&mid) {
+            Some(PubUses::All) => continue,
+            Some(PubUses::Names(names)) => Some(names),
+            None => None,
+        };
+        let module = ir.get_module(mid);
+        for declaration in module.inner().declarations() {
+            if declaration.pub_token.is_none() {
+                continue;
+            }
+            for span in declaration.assignment.name_spans() {
+                let name = span.contents(&module.src);
+                if !names.is_some_and(|names| names.contains(name)) {
+                    errors.push(
+                        span.with_error(LintError {
+                            lint: Lint::UnusedPub,
+                            level: config.level(Lint::UnusedPub),
+                            message: format!("`{name}` is pub but never used outside its module"),
+                        })
+                        .upgrade(mid),
+                    );
+                }
+            }
+        }
+    }
+    errors
+}
+
+/// Mark the declaration at the start of `fields` used following re-exported modules, `None` means the whole module is used
+fn mark_pub_used(
+    uses: &mut HashMap<ModuleId, PubUses>,
+    reports: &HashMap<ModuleId, ModuleReport>,
+    mid: ModuleId,
+    fields: Option<&[String]>,
+) {
+    let Some(report) = reports.get(&mid) else {
+        return;
+    };
+    let Some((name, rest)) = fields.and_then(<[String]>::split_first) else {
+        if matches!(uses.get(&mid), Some(PubUses::All)) {
+            return;
+        }
+        uses.insert(mid, PubUses::All);
+        for path in report.reexports.values() {
+            if let Some(reexported) = report.resolved.get(path) {
+                mark_pub_used(uses, reports, *reexported, None);
+            }
+        }
+        return;
+    };
+    match uses
+        .entry(mid)
+        .or_insert_with(|| PubUses::Names(HashSet::new()))
+    {
+        PubUses::All => return,
+        PubUses::Names(names) => {
+            names.insert(name.clone());
+        }
+    }
+    let reexported = report
+        .reexports
+        .get(name)
+        .and_then(|path| report.resolved.get(path));
+    if let Some(reexported) = reexported {
+        mark_pub_used(uses, reports, *reexported, Some(rest));
+    }
+}
+
+/// Use of a statically imported module
+struct ImportUse {
+    path: String,
+    /// Chain of fields accessed on the module, `None` if the module is used as a value
+    fields: Option<Vec<String>>,
+}
+
+struct ModuleReport {
+    errors: Vec<ErrorLocalSpan<LintError>>,
+    imports: Vec<ImportUse>,
+    /// `pub` declarations that are a static import, by name
+    reexports: HashMap<String, String>,
+    /// Modules the import paths resolved to
+    resolved: HashMap<String, ModuleId>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BindingKind<'a> {
+    Local,
+    /// Field of a destructuring assignment, reported if unused
+    Destructured,
+    /// Module statically imported from the path, reported if unused
+    Import(&'a str),
+}
+
+struct Binding<'a> {
+    name: &'a str,
+    span: LocalSpan,
+    kind: BindingKind<'a>,
+    used: bool,
+}
+
+struct Scope<'a> {
+    /// Closures and comprehensions get a new frame that assignments are bound in, other scopes only hold the names they bind
+    frame: bool,
+    bindings: Vec<Binding<'a>>,
+}
+
+/// What a referenced name resolved to
+enum Resolved<'a> {
+    Value,
+    Import(&'a str),
+}
+
+struct ModuleLinter<'a> {
+    module: &'a IrModule,
+    config: &'a LintConfig,
+    /// Index of the declaration being walked, references from inside a declaration don't count as it being used
+    current: usize,
+    /// Whether each name of each declaration is used
+    used: Vec<Vec<bool>>,
+    /// Enclosing scopes, innermost last
+    scopes: Vec<Scope<'a>>,
+    imports: Vec<ImportUse>,
+    errors: Vec<ErrorLocalSpan<LintError>>,
+}
+
+impl<'a> ModuleLinter<'a> {
+    fn new(module: &'a IrModule, config: &'a LintConfig) -> Self {
+        let used = module
+            .inner()
+            .declarations()
+            .map(|declaration| vec![false; declaration.assignment.name_spans().count()])
+            .collect();
+        Self {
+            module,
+            config,
+            current: 0,
+            used,
+            scopes: Vec::new(),
+            imports: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    fn src(&self) -> &'a str {
+        &self.module.src
+    }
+
+    fn lint(&mut self, span: LocalSpan, lint: Lint, message: String) {
+        let level = self.config.level(lint);
+        if level != LintLevel::Allow {
+            self.errors.push(span.with_error(LintError {
+                lint,
+                level,
+                message,
+            }));
+        }
+    }
+
+    fn run(mut self) -> ModuleReport {
+        let module = self.module;
+        let mut reexports = HashMap::new();
+        for (index, declaration) in module.inner().declarations().enumerate() {
+            self.current = index;
+            self.push_scope(true);
+            for type_spec in declaration.assignment.type_specs().flatten() {
+                self.visit(type_spec.type_expr);
+            }
+            let import = self.static_import(declaration.assignment.expr);
+            match (&declaration.assignment.name, import) {
+                // Uses of the alias are recorded where it is referenced
+                (DeclareName::Single(single), Some(path)) => {
+                    if declaration.pub_token.is_some() {
+                        reexports.insert(
+                            single.name.span.contents(self.src()).to_owned(),
+                            path.to_owned(),
+                        );
+                    }
+                }
+                (DeclareName::NamedDestructure(destructure), Some(path)) => {
+                    for element in &destructure.elements {
+                        self.imports.push(ImportUse {
+                            path: path.to_owned(),
+                            fields: Some(vec![element.name.span.contents(self.src()).to_owned()]),
+                        });
+                    }
+                }
+                (_, None) => self.visit(declaration.assignment.expr),
+            }
+            self.pop_scope();
+        }
+        self.unused_declarations();
+        ModuleReport {
+            errors: self.errors,
+            imports: self.imports,
+            reexports,
+            resolved: HashMap::new(),
+        }
+    }
+
+    fn unused_declarations(&mut self) {
+        let module = self.module;
+        for (declaration, used) in module
+            .inner()
+            .declarations()
+            .zip(std::mem::take(&mut self.used))
+        {
+            if declaration.pub_token.is_some() {
+                continue;
+            }
+            let import = self.static_import(declaration.assignment.expr);
+            for (span, used) in declaration.assignment.name_spans().zip(used) {
+                let name = span.contents(self.src());
+                if used || name.starts_with('_') {
+                    continue;
+                }
+                match (&declaration.assignment.name, import) {
+                    (DeclareName::NamedDestructure(_), _) => self.lint(
+                        span,
+                        Lint::UnusedDestructure,
+                        format!("destructured field `{name}` is never used"),
+                    ),
+                    (DeclareName::Single(_), Some(_)) => {
+                        self.lint(span, Lint::UnusedImport, format!("unused import `{name}`"));
+                    }
+                    (DeclareName::Single(_), None) => self.lint(
+                        span,
+                        Lint::UnusedDeclaration,
+                        format!("`{name}` is never used"),
+                    ),
+                }
+            }
+        }
+    }
+
+    /// The path of `import("path")` if `nid` is one with a literal path
+    fn static_import(&self, nid: NodeId) -> Option<&'a str> {
+        let module = self.module;
+        let Node::FnCall(FnCall { callee, args, .. }) = module.get(nid) else {
+            return None;
+        };
+        let Node::SimpleLiteral(SimpleLiteral {
+            kind: SimpleLiteralKind::Import,
+            ..
+        }) = module.get(*callee)
+        else {
+            return None;
+        };
+        match &args[..] {
+            [arg] => match module.get(*arg) {
+                Node::StringLiteral(literal)
+                    if matches!(literal.prefix(), None | Some(StringLiteralPrefix::Raw)) =>
+                {
+                    Some(literal.content_span().contents(self.src()))
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn pop_scope(&mut self) {
+        let Some(scope) = self.scopes.pop() else {
+            unreachable!("scopes are balanced")
+        };
+        for binding in scope.bindings {
+            if binding.used || binding.name.starts_with('_') {
+                continue;
+            }
+            match binding.kind {
+                BindingKind::Local => (),
+                BindingKind::Destructured => self.lint(
+                    binding.span,
+                    Lint::UnusedDestructure,
+                    format!("destructured field `{}` is never used", binding.name),
+                ),
+                BindingKind::Import(_) => self.lint(
+                    binding.span,
+                    Lint::UnusedImport,
+                    format!("unused import `{}`", binding.name),
+                ),
+            }
+        }
+    }
+
+    fn push_scope(&mut self, frame: bool) {
+        self.scopes.push(Scope {
+            frame,
+            bindings: Vec::new(),
+        });
+    }
+
+    /// Bind `name` in the innermost scope or if `frame` is set the innermost frame like an assignment does
+    fn bind(&mut self, name: &'a str, span: LocalSpan, kind: BindingKind<'a>, frame: bool) {
+        if name == "_" {
+            return;
+        }
+        let Some(target) = self.scopes.iter().rposition(|scope| !frame || scope.frame) else {
+            unreachable!("there is always a frame while walking a declaration")
+        };
+        if !name.starts_with('_') {
+            let outer = self
+                .scopes
+                .iter()
+                .enumerate()
+                .any(|(i, scope)| i != target && scope.bindings.iter().any(|b| b.name == name));
+            if outer {
+                self.lint(
+                    span,
+                    Lint::ShadowedName,
+                    format!("`{name}` shadows a binding in an outer scope"),
+                );
+            } else if self.module.find_declaration_by_name(name).is_some() {
+                self.lint(
+                    span,
+                    Lint::ShadowedName,
+                    format!("`{name}` shadows a declaration"),
+                );
+            }
+        }
+        self.scopes[target].bindings.push(Binding {
+            name,
+            span,
+            kind,
+            used: false,
+        });
+    }
+
+    /// Resolve a referenced name the way the runner does marking what it refers to as used
+    fn resolve(&mut self, name: &str, span: LocalSpan) -> Option<Resolved<'a>> {
+        let binding = self
+            .scopes
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.bindings.iter_mut().rev().find(|b| b.name == name));
+        if let Some(binding) = binding {
+            binding.used = true;
+            return Some(match binding.kind {
+                BindingKind::Import(path) => Resolved::Import(path),
+                BindingKind::Local | BindingKind::Destructured => Resolved::Value,
+            });
+        }
+        let Some(id) = self.module.find_declaration_by_name(name) else {
+            self.lint(
+                span,
+                Lint::UnknownIdent,
+                format!("unknown identifier `{name}`"),
+            );
+            return None;
+        };
+        if id.0 != self.current {
+            self.used[id.0][id.1] = true;
+        }
+        let assignment = &self.module.get_declaration(id).assignment;
+        match (&assignment.name, self.static_import(assignment.expr)) {
+            (DeclareName::Single(_), Some(path)) => Some(Resolved::Import(path)),
+            _ => Some(Resolved::Value),
+        }
+    }
+
+    /// Visit the root of a chain of field accesses returning the import path if it is a statically imported module
+    fn visit_module_root(&mut self, nid: NodeId) -> Option<&'a str> {
+        if let Some(path) = self.static_import(nid) {
+            return Some(path);
+        }
+        let Node::Ident(tls) = self.module.get(nid) else {
+            self.visit(nid);
+            return None;
+        };
+        let name = tls.0.span.contents(self.src());
+        if name == "_" {
+            return None;
+        }
+        match self.resolve(name, tls.0.span)? {
+            Resolved::Import(path) => Some(path),
+            Resolved::Value => None,
+        }
+    }
+
+    fn visit_type_spec(&mut self, type_spec: Option<&ArgTypeSpec>) {
+        if let Some(type_spec) = type_spec {
+            self.visit(type_spec.type_expr);
+        }
+    }
+
+    fn visit(&mut self, nid: NodeId) {
+        let module = self.module;
+        match module.get(nid) {
+            Node::Closure(closure) => self.visit_closure(closure),
+            Node::Block(block) => self.visit_block(block),
+            Node::IfCondition(if_condition) => self.visit_if_condition(if_condition),
+            Node::TryCatch(try_catch) => self.visit_try_catch(try_catch),
+            Node::Match(m) => self.visit_match(m),
+            Node::FnCall(fn_call) => {
+                if let Some(path) = self.static_import(nid) {
+                    self.imports.push(ImportUse {
+                        path: path.to_owned(),
+                        fields: None,
+                    });
+                } else {
+                    self.visit(fn_call.callee);
+                    for arg in &fn_call.args {
+                        self.visit(*arg);
+                    }
+                }
+            }
+            Node::Assignment(assignment) => self.visit_assignment(assignment),
+            Node::BinaryOp(op) if op.op == BinaryOperatorKind::Dot => self.visit_dot(nid, op),
+            Node::BinaryOp(op) => {
+                self.visit(op.left);
+                self.visit(op.right);
+            }
+            Node::Not(not) => self.visit(not.inner),
+            Node::Ident(tls) => {
+                let name = tls.0.span.contents(self.src());
+                // `_` is the previous line's value
+                if name != "_" {
+                    if let Some(Resolved::Import(path)) = self.resolve(name, tls.0.span) {
+                        self.imports.push(ImportUse {
+                            path: path.to_owned(),
+                            fields: None,
+                        });
+                    }
+                }
+            }
+            Node::FormatString(format_string) => {
+                for part in &format_string.parts {
+                    if let FormatStringPart::Interpolation { expr, .. } = part {
+                        self.visit(*expr);
+                    }
+                }
+            }
+            Node::StringLiteral(_)
+            | Node::IntegerLiteral(_)
+            | Node::FloatLiteral(_)
+            | Node::SimpleLiteral(_) => (),
+            Node::Record(record) => {
+                for field in &record.fields {
+                    if let RecordKey::Computed { expr, .. } = &field.key {
+                        self.visit(*expr);
+                    }
+                    self.visit(field.value);
+                }
+            }
+            Node::List(list) => {
+                for element in &list.elements {
+                    self.visit(element.value);
+                }
+            }
+            Node::Comprehension(comprehension) => self.visit_comprehension(comprehension),
+        }
+    }
+
+    fn visit_block(&mut self, block: &'a Block) {
+        let module = self.module;
+        for statement in &block.statements {
+            self.visit(*statement);
+        }
+        let throw = block
+            .statements
+            .iter()
+            .position(|statement| self.is_throw(*statement));
+        let unreachable = throw
+            .and_then(|i| block.statements.get(i + 1..))
+            .unwrap_or_default();
+        if let (Some(first), Some(last)) = (unreachable.first(), unreachable.last()) {
+            self.lint(
+                module.span(*first) + module.span(*last),
+                Lint::UnreachableCode,
+                String::from("unreachable code after throw"),
+            );
+        }
+    }
+
+    fn visit_if_condition(&mut self, if_condition: &'a IfCondition) {
+        let module = self.module;
+        self.visit(if_condition.condition);
+        let unreachable = match (module.get(if_condition.condition), &if_condition.alternate) {
+            (
+                Node::SimpleLiteral(SimpleLiteral {
+                    kind: SimpleLiteralKind::True,
+                    ..
+                }),
+                Some(AlternateCondition::IfElseCondition(nid) | AlternateCondition::ElseBlock(nid)),
+            ) => Some(*nid),
+            (
+                Node::SimpleLiteral(SimpleLiteral {
+                    kind: SimpleLiteralKind::False,
+                    ..
+                }),
+                _,
+            ) => Some(if_condition.then_block),
+            _ => None,
+        };
+        if let Some(unreachable) = unreachable {
+            self.lint(
+                module.span(unreachable),
+                Lint::UnreachableCode,
+                String::from("unreachable branch, the condition is constant"),
+            );
+        }
+        self.visit(if_condition.then_block);
+        if let Some(AlternateCondition::IfElseCondition(nid) | AlternateCondition::ElseBlock(nid)) =
+            if_condition.alternate
+        {
+            self.visit(nid);
+        }
+    }
+
+    /// A call to `internal._throw` never returns
+    fn is_throw(&self, nid: NodeId) -> bool {
+        let module = self.module;
+        let Node::FnCall(fn_call) = module.get(nid) else {
+            return false;
+        };
+        let Node::BinaryOp(BinaryOp {
+            left,
+            op: BinaryOperatorKind::Dot,
+            right,
+            ..
+        }) = module.get(fn_call.callee)
+        else {
+            return false;
+        };
+        matches!(
+            (module.get(*left), module.get(*right)),
+            (
+                Node::SimpleLiteral(SimpleLiteral {
+                    kind: SimpleLiteralKind::Internal,
+                    ..
+                }),
+                Node::Ident(tls),
+            ) if tls.0.span.contents(self.src()) == "_throw"
+        )
+    }
+
+    fn visit_closure(&mut self, closure: &'a Closure) {
+        self.push_scope(true);
+        // Type specs can refer to the args before them
+        for arg in &closure.args {
+            self.visit_type_spec(arg.type_spec.as_ref());
+            self.bind(
+                arg.name.span.contents(self.src()),
+                arg.name.span,
+                BindingKind::Local,
+                false,
+            );
+        }
+        if let Some(return_type) = &closure.return_type {
+            self.visit(return_type.type_expr);
+        }
+        self.visit(closure.block);
+        self.pop_scope();
+    }
+
+    fn visit_assignment(&mut self, assignment: &'a Assignment) {
+        for type_spec in assignment.type_specs() {
+            self.visit_type_spec(type_spec.as_ref());
+        }
+        let import = self.static_import(assignment.expr);
+        if import.is_none() {
+            self.visit(assignment.expr);
+        }
+        match &assignment.name {
+            DeclareName::Single(single) => {
+                let kind = import.map_or(BindingKind::Local, BindingKind::Import);
+                self.bind(
+                    single.name.span.contents(self.src()),
+                    single.name.span,
+                    kind,
+                    true,
+                );
+            }
+            DeclareName::NamedDestructure(destructure) => {
+                for element in &destructure.elements {
+                    let name = element.name.span.contents(self.src());
+                    if let Some(path) = import {
+                        self.imports.push(ImportUse {
+                            path: path.to_owned(),
+                            fields: Some(vec![name.to_owned()]),
+                        });
+                    }
+                    self.bind(name, element.name.span, BindingKind::Destructured, true);
+                }
+            }
+        }
+    }
+
+    fn visit_dot(&mut self, nid: NodeId, op: &BinaryOp) {
+        let module = self.module;
+        let mut fields = Vec::new();
+        let mut root = nid;
+        while let Node::BinaryOp(BinaryOp {
+            left,
+            op: BinaryOperatorKind::Dot,
+            right,
+            ..
+        }) = module.get(root)
+        {
+            let Node::Ident(field) = module.get(*right) else {
+                break;
+            };
+            fields.push(field.0.span.contents(self.src()).to_owned());
+            root = *left;
+        }
+        if fields.is_empty() {
+            // The right side is not a field name so evaluating it fails, but still check it
+            self.visit(op.left);
+            self.visit(op.right);
+            return;
+        }
+        fields.reverse();
+        if let Some(path) = self.visit_module_root(root) {
+            self.imports.push(ImportUse {
+                path: path.to_owned(),
+                fields: Some(fields),
+            });
+        }
+    }
+
+    fn visit_try_catch(&mut self, try_catch: &'a TryCatch) {
+        self.visit(try_catch.try_block);
+        self.push_scope(false);
+        self.bind(
+            try_catch.catch_name.span.contents(self.src()),
+            try_catch.catch_name.span,
+            BindingKind::Local,
+            false,
+        );
+        self.visit(try_catch.catch_block);
+        self.pop_scope();
+    }
+
+    fn visit_match(&mut self, m: &'a Match) {
+        let module = self.module;
+        self.visit(m.value);
+        let catch_all = m.arms.iter().position(|arm| {
+            arm.guard.is_none()
+                && matches!(
+                    &arm.pattern,
+                    MatchPattern::Name(DeclareName::Single(single)) if single.type_spec.is_none()
+                )
+        });
+        let unreachable = catch_all
+            .and_then(|i| m.arms.get(i + 1..))
+            .unwrap_or_default();
+        if let (Some(first), Some(last)) = (unreachable.first(), unreachable.last()) {
+            let start = match &first.pattern {
+                MatchPattern::Value(nid) => module.span(*nid),
+                MatchPattern::Name(DeclareName::Single(single)) => single.name.span,
+                MatchPattern::Name(DeclareName::NamedDestructure(destructure)) => {
+                    destructure.lbrace.span
+                }
+            };
+            self.lint(
+                start + module.span(last.expr),
+                Lint::UnreachableCode,
+                String::from("unreachable match arms after a pattern that matches everything"),
+            );
+        }
+        for arm in &m.arms {
+            self.push_scope(false);
+            match &arm.pattern {
+                MatchPattern::Value(nid) => self.visit(*nid),
+                MatchPattern::Name(DeclareName::Single(single)) => {
+                    self.visit_type_spec(single.type_spec.as_ref());
+                    self.bind(
+                        single.name.span.contents(self.src()),
+                        single.name.span,
+                        BindingKind::Local,
+                        false,
+                    );
+                }
+                MatchPattern::Name(DeclareName::NamedDestructure(destructure)) => {
+                    // The fields are part of what the pattern matches so they don't need to be used
+                    for element in &destructure.elements {
+                        self.visit_type_spec(element.type_spec.as_ref());
+                        self.bind(
+                            element.name.span.contents(self.src()),
+                            element.name.span,
+                            BindingKind::Local,
+                            false,
+                        );
+                    }
+                }
+            }
+            if let Some(guard) = arm.guard {
+                self.visit(guard);
+            }
+            self.visit(arm.expr);
+            self.pop_scope();
+        }
+    }
+
+    fn visit_comprehension(&mut self, comprehension: &'a Comprehension) {
+        self.visit(comprehension.iterable);
+        self.push_scope(true);
+        for binding in &comprehension.bindings {
+            self.bind(
+                binding.span.contents(self.src()),
+                binding.span,
+                BindingKind::Local,
+                false,
+            );
+        }
+        if let Some(condition) = comprehension.condition {
+            self.visit(condition);
+        }
+        match &comprehension.body {
+            ComprehensionBody::List(value) => self.visit(*value),
+            ComprehensionBody::Record { key, value, .. } => {
+                if let RecordKey::Computed { expr, .. } = key {
+                    self.visit(*expr);
+                }
+                self.visit(*value);
+            }
+        }
+        self.pop_scope();
+    }
+}
@@ -1,4 +1,12 @@
-use std::{borrow::Cow, sync::Arc};
+pub mod lint;
+pub mod typecheck;
+
+use std::{
+    borrow::Cow,
+    sync::{Arc, RwLock},
+};
+
+use poison_panic::RwLockExt as _;
 
 use crate::{
     afs::file::File,
@@ -8,9 +16,10 @@ use crate::{
     span::ErrorSpan,
 };
 
+/// Modules are only ever appended so a [`ModuleId`] stays valid and the IR can be shared between threads
 #[derive(Debug, Default)]
 pub struct Rir {
-    modules: Vec<Arc<IrModule>>,
+    modules: RwLock<Vec<Arc<IrModule>>>,
 }
 
 impl Rir {
@@ -19,17 +28,18 @@ impl Rir {
     }
 
     pub fn insert_module(
-        &mut self,
+        &self,
         file: Option<File>,
         src: impl Into<Cow<'static, str>>,
         ast: Result<Module, ErrorLocalSpan<ParseError>>,
     ) -> Result<ModuleId, ErrorSpan<ParseError>> {
-        let id = ModuleId(self.modules.len());
+        let mut modules = self.modules.pwrite();
+        let id = ModuleId(modules.len());
         let (module, res) = match ast {
             Ok(m) => (Some(ParsedIrModule(m)), Ok(id)),
             Err(els) => (None, Err(els.upgrade(id))),
         };
-        self.modules.push(Arc::new(IrModule {
+        modules.push(Arc::new(IrModule {
             id,
             file,
             src: src.into(),
@@ -38,11 +48,12 @@ impl Rir {
         res
     }
 
-    pub fn get_module(&self, module_id: ModuleId) -> &Arc<IrModule> {
-        let Some(m) = self.modules.get(module_id.0) else {
+    pub fn get_module(&self, module_id: ModuleId) -> Arc<IrModule> {
+        let modules = self.modules.pread();
+        let Some(m) = modules.get(module_id.0) else {
             unreachable!("id is always valid")
         };
-        m
+        Arc::clone(m)
     }
 
     pub fn resolve_global_declaration(
@@ -57,11 +68,11 @@ impl Rir {
     }
 
     pub fn len(&self) -> usize {
-        self.modules.len()
+        self.modules.pread().len()
     }
 
     pub fn is_empty(&self) -> bool {
-        self.modules.is_empty()
+        self.modules.pread().is_empty()
     }
 }
 
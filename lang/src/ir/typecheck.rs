@@ -0,0 +1,697 @@
+//! Static type checking of a module without evaluating it
+//!
+//! Types are inferred from literals, internal function signatures and type specs that resolve to a plain runtime type. Anything that can't be known without running the module is left unchecked, so the errors reported are ones evaluation would hit, apart from assignment type specs inside blocks which the runner does not check yet.
+
+use std::{borrow::Cow, collections::HashMap};
+
+use crate::{
+    ast::{
+        AlternateCondition, ArgTypeSpec, Assignment, BinaryOp, BinaryOperatorKind, Closure,
+        Comprehension, ComprehensionBody, DeclareName, FnCall, FormatStringPart, IfCondition,
+        Match, MatchPattern, Node, NodeId, Record, RecordKey, SimpleLiteral, SimpleLiteralKind,
+    },
+    ir::IrModule,
+    local_span::{ErrorLocalSpan, LocalSpan},
+    runner::{error::RunnerError, internal::InternalFunction, value::RainTypeId},
+};
+
+/// Types in `std.types` that are plain runtime types rather than predicates
+const STD_TYPES: &[(&str, RainTypeId)] = &[
+    ("Unit", RainTypeId::Unit),
+    ("Bool", RainTypeId::Boolean),
+    ("Integer", RainTypeId::Integer),
+    ("Float", RainTypeId::Float),
+    ("String", RainTypeId::String),
+    ("AnyRecord", RainTypeId::Record),
+    ("AnyList", RainTypeId::List),
+    ("Area", RainTypeId::FileArea),
+    ("File", RainTypeId::File),
+    ("Dir", RainTypeId::Dir),
+];
+
+/// Check every declaration in `module` returning all the errors found
+pub fn check_module(module: &IrModule) -> Vec<ErrorLocalSpan<RunnerError>> {
+    let mut checker = Checker {
+        module,
+        declarations: HashMap::new(),
+        closures: HashMap::new(),
+        scopes: Vec::new(),
+        errors: Vec::new(),
+    };
+    for index in 0..module.inner().module_root().declarations.len() {
+        checker.declaration_type(index);
+    }
+    checker.errors
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum StaticType {
+    /// Nothing is known about the value
+    Unknown,
+    /// A value of the runtime type
+    Of(RainTypeId),
+    /// A type value that type specs check values are exactly of
+    Type(RainTypeId),
+    InternalFunction(InternalFunction),
+    /// A closure declared at the node
+    Closure(NodeId),
+    /// A record whose fields are known, if it is `complete` there are no other fields
+    Record {
+        fields: Vec<(String, Self)>,
+        complete: bool,
+    },
+    /// The std library module returned by `stdlib(version)`
+    Stdlib,
+    /// The `std.types` module
+    StdTypes,
+}
+
+impl StaticType {
+    fn rain_type_id(&self) -> Option<RainTypeId> {
+        match self {
+            Self::Unknown => None,
+            Self::Of(type_id) => Some(*type_id),
+            Self::Type(_) => Some(RainTypeId::Type),
+            Self::InternalFunction(_) => Some(RainTypeId::InternalFunction),
+            Self::Closure(_) => Some(RainTypeId::Closure),
+            Self::Record { .. } => Some(RainTypeId::Record),
+            Self::Stdlib | Self::StdTypes => Some(RainTypeId::Module),
+        }
+    }
+
+    /// The type of a value that could have come from either branch
+    fn join(self, other: &Self) -> Self {
+        if self == *other {
+            return self;
+        }
+        match (self.rain_type_id(), other.rain_type_id()) {
+            (Some(a), Some(b)) if a == b => Self::Of(a),
+            _ => Self::Unknown,
+        }
+    }
+
+    /// Look up a field like the dot operator, `None` if the field definitely does not exist
+    fn field(&self, name: &str) -> Option<Self> {
+        match self {
+            Self::Record { fields, complete } => match fields.iter().find(|(k, _)| k == name) {
+                Some((_, t)) => Some(t.clone()),
+                None if *complete => None,
+                None => Some(Self::Unknown),
+            },
+            Self::Of(RainTypeId::Internal) => {
+                InternalFunction::evaluate_internal_function_name(name).map(Self::InternalFunction)
+            }
+            Self::Stdlib if name == "types" => Some(Self::StdTypes),
+            Self::StdTypes => Some(
+                STD_TYPES
+                    .iter()
+                    .find(|(n, _)| *n == name)
+                    .map_or(Self::Unknown, |(_, t)| Self::Type(*t)),
+            ),
+            _ => Some(Self::Unknown),
+        }
+    }
+}
+
+struct ClosureType {
+    /// The type each argument's type spec requires if it is known
+    args: Vec<Option<RainTypeId>>,
+    returns: StaticType,
+}
+
+struct Checker<'a> {
+    module: &'a IrModule,
+    /// Inferred type of each declaration's expression, `None` while it is being inferred so cycles are unknown
+    declarations: HashMap<usize, Option<StaticType>>,
+    closures: HashMap<NodeId, ClosureType>,
+    /// Names bound in enclosing closures and blocks, innermost last
+    scopes: Vec<HashMap<&'a str, StaticType>>,
+    errors: Vec<ErrorLocalSpan<RunnerError>>,
+}
+
+impl<'a> Checker<'a> {
+    fn err(&mut self, span: LocalSpan, err: RunnerError) {
+        self.errors.push(span.with_error(err));
+    }
+
+    fn expected_type(&mut self, span: LocalSpan, actual: RainTypeId, expected: &[RainTypeId]) {
+        self.err(
+            span,
+            RunnerError::ExpectedType {
+                actual,
+                expected: Cow::Owned(expected.to_vec()),
+            },
+        );
+    }
+
+    fn src(&self) -> &'a str {
+        &self.module.src
+    }
+
+    fn declaration_type(&mut self, index: usize) -> StaticType {
+        if let Some(t) = self.declarations.get(&index) {
+            return t.clone().unwrap_or(StaticType::Unknown);
+        }
+        self.declarations.insert(index, None);
+        let module = self.module;
+        let declaration = &module.inner().module_root().declarations[index];
+        // Declarations can't see the locals of whatever referred to them
+        let scopes = std::mem::take(&mut self.scopes);
+        let t = self.infer(declaration.assignment.expr);
+        self.check_assignment(&declaration.assignment, &t);
+        self.scopes = scopes;
+        self.declarations.insert(index, Some(t.clone()));
+        t
+    }
+
+    fn resolve_ident(&mut self, name: &str) -> StaticType {
+        if let Some(t) = self.scopes.iter().rev().find_map(|scope| scope.get(name)) {
+            return t.clone();
+        }
+        match self.module.find_declaration_by_name(name) {
+            Some(id) => {
+                let t = self.declaration_type(id.0);
+                match &self.module.get_declaration(id).assignment.name {
+                    DeclareName::Single(_) => t,
+                    DeclareName::NamedDestructure(_) => {
+                        t.field(name).unwrap_or(StaticType::Unknown)
+                    }
+                }
+            }
+            None => StaticType::Unknown,
+        }
+    }
+
+    fn bind(&mut self, name: &'a str, t: StaticType) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name, t);
+        }
+    }
+
+    /// The type values matching the type spec are exactly of, if it is known
+    fn type_spec(&mut self, type_spec: &ArgTypeSpec) -> Option<RainTypeId> {
+        self.type_expr(type_spec.type_expr)
+    }
+
+    fn type_expr(&mut self, nid: NodeId) -> Option<RainTypeId> {
+        match self.infer(nid) {
+            StaticType::Type(type_id) => Some(type_id),
+            StaticType::Unknown
+            | StaticType::Closure(_)
+            | StaticType::Of(RainTypeId::Type | RainTypeId::Closure) => None,
+            t => {
+                if let Some(actual) = t.rain_type_id() {
+                    self.expected_type(
+                        self.module.span(nid),
+                        actual,
+                        &[RainTypeId::Type, RainTypeId::Closure],
+                    );
+                }
+                None
+            }
+        }
+    }
+
+    /// Check `t` is of the type spec's type, returning the narrowest type for the value
+    fn check_type_spec(&mut self, type_spec: Option<&ArgTypeSpec>, t: StaticType) -> StaticType {
+        let Some(type_spec) = type_spec else {
+            return t;
+        };
+        let Some(expected) = self.type_spec(type_spec) else {
+            return t;
+        };
+        match t.rain_type_id() {
+            Some(actual) if actual != expected => {
+                self.expected_type(self.module.span(type_spec.type_expr), actual, &[expected]);
+                StaticType::Of(expected)
+            }
+            Some(_) => t,
+            None => StaticType::Of(expected),
+        }
+    }
+
+    /// Check the names of an assignment against the value and bind them in the current scope
+    fn check_assignment(&mut self, assignment: &'a Assignment, t: &StaticType) {
+        match &assignment.name {
+            DeclareName::Single(single) => {
+                let t = self.check_type_spec(single.type_spec.as_ref(), t.clone());
+                self.bind(single.name.span.contents(self.src()), t);
+            }
+            DeclareName::NamedDestructure(destructure) => {
+                for element in &destructure.elements {
+                    let name = element.name.span.contents(self.src());
+                    let field = self.field(t, element.name.span, name);
+                    let field = self.check_type_spec(element.type_spec.as_ref(), field);
+                    self.bind(name, field);
+                }
+            }
+        }
+    }
+
+    /// Type of the named field of `t`, reporting an error if it can't exist
+    fn field(&mut self, t: &StaticType, span: LocalSpan, name: &str) -> StaticType {
+        match t.rain_type_id() {
+            None | Some(RainTypeId::Module | RainTypeId::Internal | RainTypeId::Record) => {
+                if let Some(field) = t.field(name) {
+                    field
+                } else {
+                    self.err(span, RunnerError::IndexKeyNotFound(name.to_owned()));
+                    StaticType::Unknown
+                }
+            }
+            Some(actual) => {
+                self.expected_type(
+                    span,
+                    actual,
+                    &[RainTypeId::Module, RainTypeId::Internal, RainTypeId::Record],
+                );
+                StaticType::Unknown
+            }
+        }
+    }
+
+    /// Report an error at `span` if `t` is known not to be one of the `expected` types
+    fn expect_type(&mut self, span: LocalSpan, t: &StaticType, expected: &[RainTypeId]) {
+        match t.rain_type_id() {
+            Some(actual) if !expected.contains(&actual) => {
+                self.expected_type(span, actual, expected);
+            }
+            _ => (),
+        }
+    }
+
+    fn expect_bool(&mut self, nid: NodeId, t: &StaticType) {
+        self.expect_type(self.module.span(nid), t, &[RainTypeId::Boolean]);
+    }
+
+    fn infer(&mut self, nid: NodeId) -> StaticType {
+        let module = self.module;
+        match module.get(nid) {
+            Node::Closure(closure) => self.infer_closure(nid, closure),
+            Node::Block(block) => {
+                self.scopes.push(HashMap::new());
+                let mut t = StaticType::Of(RainTypeId::Unit);
+                for statement in &block.statements {
+                    t = self.infer(*statement);
+                }
+                self.scopes.pop();
+                t
+            }
+            Node::IfCondition(if_condition) => self.infer_if_condition(if_condition),
+            Node::TryCatch(try_catch) => {
+                let try_type = self.infer(try_catch.try_block);
+                let caught = StaticType::Record {
+                    fields: vec![
+                        (String::from("value"), StaticType::Unknown),
+                        (String::from("trace"), StaticType::Of(RainTypeId::List)),
+                    ],
+                    complete: true,
+                };
+                self.scopes.push(HashMap::from([(
+                    try_catch.catch_name.span.contents(self.src()),
+                    caught,
+                )]));
+                let catch_type = self.infer(try_catch.catch_block);
+                self.scopes.pop();
+                try_type.join(&catch_type)
+            }
+            Node::Match(m) => self.infer_match(m),
+            Node::FnCall(fn_call) => self.infer_fn_call(fn_call),
+            Node::Assignment(assignment) => {
+                let t = self.infer(assignment.expr);
+                self.check_assignment(assignment, &t);
+                StaticType::Of(RainTypeId::Unit)
+            }
+            Node::BinaryOp(op) => self.infer_binary_op(op),
+            Node::Not(not) => {
+                let t = self.infer(not.inner);
+                self.expect_type(not.exclamation, &t, &[RainTypeId::Boolean]);
+                StaticType::Of(RainTypeId::Boolean)
+            }
+            Node::Ident(tls) => {
+                let name = tls.0.span.contents(self.src());
+                if name == "_" {
+                    // The previous line's value
+                    StaticType::Unknown
+                } else {
+                    self.resolve_ident(name)
+                }
+            }
+            Node::StringLiteral(_) => StaticType::Of(RainTypeId::String),
+            Node::FormatString(format_string) => {
+                for part in &format_string.parts {
+                    if let FormatStringPart::Interpolation { expr, .. } = part {
+                        self.infer(*expr);
+                    }
+                }
+                StaticType::Of(RainTypeId::String)
+            }
+            Node::IntegerLiteral(_) => StaticType::Of(RainTypeId::Integer),
+            Node::FloatLiteral(_) => StaticType::Of(RainTypeId::Float),
+            Node::SimpleLiteral(literal) => match literal.kind {
+                SimpleLiteralKind::True | SimpleLiteralKind::False => {
+                    StaticType::Of(RainTypeId::Boolean)
+                }
+                SimpleLiteralKind::Internal => StaticType::Of(RainTypeId::Internal),
+                SimpleLiteralKind::Import | SimpleLiteralKind::Stdlib => StaticType::Unknown,
+                SimpleLiteralKind::ThisFile => StaticType::Of(RainTypeId::File),
+            },
+            Node::Record(record) => self.infer_record(record),
+            Node::List(list) => {
+                for element in &list.elements {
+                    self.infer(element.value);
+                }
+                StaticType::Of(RainTypeId::List)
+            }
+            Node::Comprehension(comprehension) => self.infer_comprehension(comprehension),
+        }
+    }
+
+    fn infer_closure(&mut self, nid: NodeId, closure: &'a Closure) -> StaticType {
+        let args: Vec<Option<RainTypeId>> = closure
+            .args
+            .iter()
+            .map(|arg| arg.type_spec.as_ref().and_then(|t| self.type_spec(t)))
+            .collect();
+        self.scopes.push(
+            closure
+                .args
+                .iter()
+                .zip(&args)
+                .map(|(arg, t)| {
+                    (
+                        arg.name.span.contents(self.src()),
+                        t.map_or(StaticType::Unknown, StaticType::Of),
+                    )
+                })
+                .collect(),
+        );
+        let body = self.infer(closure.block);
+        let returns = match &closure.return_type {
+            Some(return_type) => match self.type_expr(return_type.type_expr) {
+                Some(expected) => {
+                    self.expect_type(self.module.span(return_type.type_expr), &body, &[expected]);
+                    StaticType::Of(expected)
+                }
+                None => body,
+            },
+            None => body,
+        };
+        self.scopes.pop();
+        self.closures.insert(nid, ClosureType { args, returns });
+        StaticType::Closure(nid)
+    }
+
+    fn infer_if_condition(&mut self, if_condition: &IfCondition) -> StaticType {
+        let condition = self.infer(if_condition.condition);
+        self.expect_bool(if_condition.condition, &condition);
+        let then_type = self.infer(if_condition.then_block);
+        let else_type = match if_condition.alternate {
+            Some(AlternateCondition::IfElseCondition(nid) | AlternateCondition::ElseBlock(nid)) => {
+                self.infer(nid)
+            }
+            None => StaticType::Of(RainTypeId::Unit),
+        };
+        then_type.join(&else_type)
+    }
+
+    fn infer_match(&mut self, m: &'a Match) -> StaticType {
+        let value = self.infer(m.value);
+        let mut result: Option<StaticType> = None;
+        for arm in &m.arms {
+            self.scopes.push(HashMap::new());
+            match &arm.pattern {
+                MatchPattern::Value(nid) => {
+                    self.infer(*nid);
+                }
+                MatchPattern::Name(DeclareName::Single(single)) => {
+                    let t = match &single.type_spec {
+                        Some(type_spec) => self
+                            .type_spec(type_spec)
+                            .map_or_else(|| value.clone(), StaticType::Of),
+                        None => value.clone(),
+                    };
+                    let name = single.name.span.contents(self.src());
+                    if name != "_" {
+                        self.bind(name, t);
+                    }
+                }
+                MatchPattern::Name(DeclareName::NamedDestructure(destructure)) => {
+                    for element in &destructure.elements {
+                        let t = match &element.type_spec {
+                            Some(type_spec) => self
+                                .type_spec(type_spec)
+                                .map_or(StaticType::Unknown, StaticType::Of),
+                            None => StaticType::Unknown,
+                        };
+                        self.bind(element.name.span.contents(self.src()), t);
+                    }
+                }
+            }
+            if let Some(guard) = arm.guard {
+                let t = self.infer(guard);
+                self.expect_bool(guard, &t);
+            }
+            let t = self.infer(arm.expr);
+            self.scopes.pop();
+            result = Some(match result {
+                Some(result) => result.join(&t),
+                None => t,
+            });
+        }
+        result.unwrap_or(StaticType::Unknown)
+    }
+
+    fn infer_fn_call(&mut self, fn_call: &FnCall) -> StaticType {
+        let callee = self.infer(fn_call.callee);
+        let args: Vec<StaticType> = fn_call.args.iter().map(|nid| self.infer(*nid)).collect();
+        let call_span = self.module.span(fn_call.callee) + fn_call.rparen_token.span;
+        if let Node::SimpleLiteral(SimpleLiteral {
+            kind: SimpleLiteralKind::Stdlib,
+            ..
+        }) = self.module.get(fn_call.callee)
+        {
+            return StaticType::Stdlib;
+        }
+        match callee {
+            StaticType::Unknown => StaticType::Unknown,
+            StaticType::InternalFunction(func) => {
+                let signature = func.signature();
+                if !signature.args.contains(&args.len()) {
+                    self.err(
+                        call_span,
+                        RunnerError::IncorrectArgs {
+                            required: signature.args,
+                            actual: args.len(),
+                        },
+                    );
+                    return StaticType::Unknown;
+                }
+                for ((nid, t), expected) in fn_call.args.iter().zip(&args).zip(signature.params) {
+                    if let Some(expected) = expected {
+                        self.expect_type(self.module.span(*nid), t, expected);
+                    }
+                }
+                match (func, args.first()) {
+                    (InternalFunction::GetType, Some(t)) => t
+                        .rain_type_id()
+                        .map_or(StaticType::Unknown, StaticType::Type),
+                    _ => signature
+                        .returns
+                        .map_or(StaticType::Unknown, StaticType::Of),
+                }
+            }
+            StaticType::Closure(closure_nid) => {
+                let Node::Closure(closure) = self.module.get(closure_nid) else {
+                    unreachable!("closure types are always closure nodes")
+                };
+                if closure.args.len() != args.len() {
+                    self.err(
+                        call_span,
+                        RunnerError::IncorrectArgs {
+                            required: closure.args.len()..=closure.args.len(),
+                            actual: args.len(),
+                        },
+                    );
+                    return StaticType::Unknown;
+                }
+                // A closure is always inferred before anything can call it except itself
+                let Some(closure_type) = self.closures.get(&closure_nid) else {
+                    return StaticType::Unknown;
+                };
+                let returns = closure_type.returns.clone();
+                let expected: Vec<Option<RainTypeId>> = closure_type.args.clone();
+                for ((nid, t), expected) in fn_call.args.iter().zip(&args).zip(expected) {
+                    if let Some(expected) = expected {
+                        self.expect_type(self.module.span(*nid), t, &[expected]);
+                    }
+                }
+                returns
+            }
+            t => {
+                if let Some(actual) = t.rain_type_id() {
+                    self.expected_type(
+                        call_span,
+                        actual,
+                        &[RainTypeId::InternalFunction, RainTypeId::Closure],
+                    );
+                }
+                StaticType::Unknown
+            }
+        }
+    }
+
+    fn infer_binary_op(&mut self, op: &BinaryOp) -> StaticType {
+        let left = self.infer(op.left);
+        if op.op == BinaryOperatorKind::Dot {
+            let Node::Ident(tls) = self.module.get(op.right) else {
+                return StaticType::Unknown;
+            };
+            return self.field(&left, tls.0.span, tls.0.span.contents(self.src()));
+        }
+        let right = self.infer(op.right);
+        let (Some(l), Some(r)) = (left.rain_type_id(), right.rain_type_id()) else {
+            return match op.op {
+                BinaryOperatorKind::Equals
+                | BinaryOperatorKind::NotEquals
+                | BinaryOperatorKind::LessThan
+                | BinaryOperatorKind::LessThanEquals
+                | BinaryOperatorKind::GreaterThan
+                | BinaryOperatorKind::GreaterThanEquals
+                | BinaryOperatorKind::LogicalAnd
+                | BinaryOperatorKind::LogicalOr => StaticType::Of(RainTypeId::Boolean),
+                _ => StaticType::Unknown,
+            };
+        };
+        if let Some(t) = binary_op_type(l, op.op, r) {
+            StaticType::Of(t)
+        } else {
+            self.err(
+                op.op_span,
+                RunnerError::Makeshift("binary op invalid for given types".into()),
+            );
+            StaticType::Unknown
+        }
+    }
+
+    fn infer_record(&mut self, record: &Record) -> StaticType {
+        let mut fields = Vec::new();
+        let mut complete = true;
+        for field in &record.fields {
+            match &field.key {
+                RecordKey::Ident(tls) => {
+                    let t = self.infer(field.value);
+                    fields.push((tls.span.contents(self.src()).to_owned(), t));
+                }
+                RecordKey::Computed { expr, .. } => {
+                    let key = self.infer(*expr);
+                    self.expect_type(self.module.span(*expr), &key, &[RainTypeId::String]);
+                    self.infer(field.value);
+                    complete = false;
+                }
+            }
+        }
+        StaticType::Record { fields, complete }
+    }
+
+    fn infer_comprehension(&mut self, comprehension: &'a Comprehension) -> StaticType {
+        let iterable = self.infer(comprehension.iterable);
+        match (iterable.rain_type_id(), comprehension.bindings.len()) {
+            (None, _) | (Some(RainTypeId::List), 1) | (Some(RainTypeId::Record), 2) => {}
+            (Some(RainTypeId::List | RainTypeId::Record), _) => self.err(
+                comprehension.for_token,
+                RunnerError::Makeshift(
+                    "iterating a list binds one name and iterating a record binds a key and a value name"
+                        .into(),
+                ),
+            ),
+            (Some(actual), _) => self.expected_type(
+                self.module.span(comprehension.iterable),
+                actual,
+                &[RainTypeId::List, RainTypeId::Record],
+            ),
+        }
+        let mut bindings: HashMap<&str, StaticType> = comprehension
+            .bindings
+            .iter()
+            .map(|tls| (tls.span.contents(self.src()), StaticType::Unknown))
+            .collect();
+        if let (Some(RainTypeId::Record), [key, _]) =
+            (iterable.rain_type_id(), &comprehension.bindings[..])
+        {
+            bindings.insert(
+                key.span.contents(self.src()),
+                StaticType::Of(RainTypeId::String),
+            );
+        }
+        self.scopes.push(bindings);
+        if let Some(condition) = comprehension.condition {
+            let t = self.infer(condition);
+            self.expect_bool(condition, &t);
+        }
+        let t = match &comprehension.body {
+            ComprehensionBody::List(value) => {
+                self.infer(*value);
+                StaticType::Of(RainTypeId::List)
+            }
+            ComprehensionBody::Record { key, value, .. } => {
+                if let RecordKey::Computed { expr, .. } = key {
+                    self.infer(*expr);
+                }
+                self.infer(*value);
+                StaticType::Of(RainTypeId::Record)
+            }
+        };
+        self.scopes.pop();
+        t
+    }
+}
+
+/// The result type of a binary op that isn't the dot operator, `None` if the runner would error
+fn binary_op_type(
+    left: RainTypeId,
+    op: BinaryOperatorKind,
+    right: RainTypeId,
+) -> Option<RainTypeId> {
+    use BinaryOperatorKind as Op;
+    use RainTypeId as T;
+    let numeric = matches!(
+        (left, right),
+        (T::Float, T::Float | T::Integer) | (T::Integer, T::Float)
+    );
+    match (left, op, right) {
+        (_, Op::Addition | Op::Subtraction | Op::Multiplication | Op::Division, _) if numeric => {
+            Some(T::Float)
+        }
+        (
+            _,
+            Op::Equals
+            | Op::NotEquals
+            | Op::LessThan
+            | Op::LessThanEquals
+            | Op::GreaterThan
+            | Op::GreaterThanEquals,
+            _,
+        ) if numeric => Some(T::Boolean),
+        (T::String, Op::Addition, T::String) => Some(T::String),
+        (T::List, Op::Addition, T::List) => Some(T::List),
+        (
+            T::Integer,
+            Op::Addition | Op::Subtraction | Op::Multiplication | Op::Division,
+            T::Integer,
+        ) => Some(T::Integer),
+        (T::Boolean, Op::LogicalAnd | Op::LogicalOr, T::Boolean)
+        | (
+            T::Integer,
+            Op::LessThan | Op::LessThanEquals | Op::GreaterThan | Op::GreaterThanEquals,
+            T::Integer,
+        )
+        | (T::Integer | T::String | T::Unit | T::Type, Op::Equals | Op::NotEquals, _)
+            if left == right =>
+        {
+            Some(T::Boolean)
+        }
+        (_, Op::Equals | Op::NotEquals, _) if left != right => Some(T::Boolean),
+        _ => None,
+    }
+}
@@ -6,7 +6,7 @@ use crate::{afs::file::File, ir::DeclarationId, runner::dep_list::DepList};
 
 use super::{internal::InternalFunction, value::Value};
 
-pub trait CacheTrait {
+pub trait CacheTrait: Send + Sync {
     fn get(&self, key: &CacheKey) -> Option<CacheEntry>;
     fn put(&self, key: CacheKey, entry: CacheEntry);
     fn put_if_slow(&self, key: CacheKey, entry: CacheEntry);
@@ -41,18 +41,72 @@ pub enum CacheKey {
         closure: super::value::Closure,
         args: Vec<Value>,
     },
+    /// One element of a comprehension, the scope captures the values the comprehension can refer to
+    Comprehension {
+        scope: super::value::Closure,
+        element: Vec<Value>,
+    },
     InternalFunction {
         func: InternalFunction,
         args: Vec<Value>,
     },
     Download {
         url: String,
+        /// Expected sha256 of the download if one was given
+        sha256: Option<String>,
+        headers: Vec<(String, String)>,
+        /// Only names the secret, the value must never be part of the key
+        auth: Option<crate::driver::DownloadAuth>,
+    },
+    /// Contents of the commit a git ref points at, the commit is kept in the entry's etag
+    GitRef {
+        url: String,
+        git_ref: String,
+        submodules: bool,
     },
     Import {
         file: Arc<File>,
     },
 }
 
+impl CacheKey {
+    /// Every string contained in the key, including the values of arguments and captures
+    pub fn find_strings(&self) -> Vec<&str> {
+        match self {
+            Self::Embed | Self::Declaration { .. } | Self::Import { .. } => Vec::new(),
+            Self::CallClosure {
+                closure: scope,
+                args,
+            }
+            | Self::Comprehension {
+                scope,
+                element: args,
+            } => scope
+                .find_strings()
+                .into_iter()
+                .chain(args.iter().flat_map(Value::find_strings))
+                .collect(),
+            Self::InternalFunction { args, .. } => {
+                args.iter().flat_map(Value::find_strings).collect()
+            }
+            Self::Download {
+                url,
+                sha256,
+                headers,
+                auth,
+            } => std::iter::once(url.as_str())
+                .chain(sha256.as_deref())
+                .chain(headers.iter().flat_map(|(k, v)| [k.as_str(), v.as_str()]))
+                .chain(auth.iter().filter_map(|auth| match auth {
+                    crate::driver::DownloadAuth::Bearer { .. } => None,
+                    crate::driver::DownloadAuth::Basic { username, .. } => Some(username.as_str()),
+                }))
+                .collect(),
+            Self::GitRef { url, git_ref, .. } => vec![url.as_str(), git_ref.as_str()],
+        }
+    }
+}
+
 impl Display for CacheKey {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -64,10 +118,19 @@ impl Display for CacheKey {
                 closure.node,
                 display_vec(args)
             )),
+            Self::Comprehension { scope, element } => f.write_fmt(format_args!(
+                "Comprehension({},{:?})({})",
+                scope.module,
+                scope.node,
+                display_vec(element)
+            )),
             Self::InternalFunction { func, args } => {
                 f.write_fmt(format_args!("{func}({})", display_vec(args)))
             }
-            Self::Download { url } => f.write_fmt(format_args!("Download({url})")),
+            Self::Download { url, .. } => f.write_fmt(format_args!("Download({url})")),
+            Self::GitRef { url, git_ref, .. } => {
+                f.write_fmt(format_args!("GitRef({url}, {git_ref})"))
+            }
             Self::Import { file } => f.write_fmt(format_args!("Import({file})")),
         }
     }
@@ -44,6 +44,21 @@ impl<'a> Cx<'a> {
         }
     }
 
+    /// Copy of this context for evaluating a sibling expression independently, it starts with no deps
+    #[must_use]
+    pub fn fork(&self) -> Self {
+        Self {
+            module: self.module,
+            call_depth: self.call_depth,
+            locals: self.locals.clone(),
+            captures: self.captures.clone(),
+            args: self.args.clone(),
+            deps: DepList::new(),
+            previous_line: self.previous_line.clone(),
+            stacktrace: self.stacktrace.clone(),
+        }
+    }
+
     pub fn err(&self, s: impl Into<LocalSpan>, err: RunnerError) -> ErrorTrace<Throwing> {
         s.into()
             .with_module(self.module.id)
@@ -1,21 +1,37 @@
+use std::time::SystemTime;
+
+use sha2::Digest as _;
+
+use crate::{afs::entry::FSEntry, driver::GlobOptions};
+
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub enum Dep {
     /// Marks any calls that depend on this to be uncacheable
     Uncacheable,
-    /// Marks any calls that depend on this to depend on a local area
-    // TODO: Specify the local area
+    /// Marks any calls that depend on this to depend on the local area as a whole
+    ///
+    /// Prefer [`Dep::LocalEntry`] when the exact entries read are known
     LocalArea,
+    /// Marks any calls that depend on this to depend on a local file or directory that had this fingerprint when it was read
+    LocalEntry {
+        entry: FSEntry,
+        fingerprint: Fingerprint,
+    },
     /// Marks any calls that depend on this to depend on the escaped environment
     Escape,
-    /// Marks any calls that depend on this to depend on the secret
-    // TODO: Specify the secret name
-    Secret,
+    /// Marks any calls that depend on this to depend on the secret, only a hash of the value is kept
+    ///
+    /// Entries with this dep stay in memory so the hash is never written to disk or pushed to a remote cache
+    Secret { name: String, value_hash: String },
     /// Marks the call as depending on the calling module
     CallingModule,
     /// This prints so should not be cached
     Print,
-    /// This depends on an environment variable
-    EnvVar,
+    /// This depends on an environment variable, the hash is `None` if the variable was not set
+    EnvVar {
+        name: String,
+        value_hash: Option<String>,
+    },
 }
 
 impl Dep {
@@ -26,11 +42,67 @@ impl Dep {
     pub fn is_intra_run_stable(&self) -> bool {
         match self {
             Self::Uncacheable | Self::CallingModule | Self::Print => false,
-            Self::LocalArea | Self::Escape | Self::Secret | Self::EnvVar => true,
+            Self::LocalArea
+            | Self::LocalEntry { .. }
+            | Self::Escape
+            | Self::Secret { .. }
+            | Self::EnvVar { .. } => true,
         }
     }
 
+    /// Deps that record what they read can be checked again in a later run
     pub fn is_inter_run_stable(&self) -> bool {
-        false
+        matches!(self, Self::LocalEntry { .. } | Self::EnvVar { .. })
+    }
+
+    /// Deps that can be checked again on another machine
+    ///
+    /// Local entries are fingerprinted by modification times that only mean something on the machine that recorded them
+    pub fn is_portable(&self) -> bool {
+        matches!(self, Self::EnvVar { .. })
+    }
+
+    /// Hash of an environment variable or secret value so it can be compared without being stored
+    pub fn value_hash(value: &str) -> String {
+        base16::encode_lower(&sha2::Sha256::digest(value.as_bytes()))
+    }
+}
+
+/// Summary of a local file system entry that changes whenever the entry changes
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Fingerprint {
+    /// Nothing exists at the path
+    Missing,
+    File {
+        size: u64,
+        modified: SystemTime,
+    },
+    /// Only that a directory exists, its contents are not tracked
+    Dir,
+    /// A directory and the entries inside it selected by `filter`, hashed from the relative path, size and modification time of each entry
+    DirContents {
+        filter: ContentsFilter,
+        digest: String,
+    },
+}
+
+impl Fingerprint {
+    /// The directory contents needed to recompute this fingerprint, if any
+    pub fn contents_filter(&self) -> Option<&ContentsFilter> {
+        match self {
+            Self::DirContents { filter, .. } => Some(filter),
+            Self::Missing | Self::File { .. } | Self::Dir => None,
+        }
     }
 }
+
+/// Which entries of a directory are covered by [`Fingerprint::DirContents`]
+///
+/// This mirrors how the directory was read so that changes to entries that were never read, such as build output or ignored files, do not invalidate anything
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum ContentsFilter {
+    /// The files that are copied when the directory is used to create an area
+    Area,
+    /// The entries matched by a glob
+    Glob(GlobOptions),
+}
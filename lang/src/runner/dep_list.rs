@@ -1,4 +1,4 @@
-use crate::{afs::area::FileArea, runner::dep::Dep};
+use crate::runner::dep::Dep;
 
 #[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
 pub struct DepList {
@@ -14,13 +14,6 @@ impl DepList {
         self.inner.push(dep);
     }
 
-    pub fn add_dep_file_area(&mut self, area: &FileArea) {
-        match area {
-            FileArea::Local(_) => self.inner.push(Dep::LocalArea),
-            FileArea::Generated(_) => (),
-        }
-    }
-
     pub fn extend(&mut self, deps: impl Iterator<Item = Dep>) {
         self.inner.extend(deps);
     }
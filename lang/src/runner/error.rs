@@ -1,10 +1,10 @@
-use std::{borrow::Cow, ops::RangeInclusive, string::FromUtf8Error};
+use std::{borrow::Cow, ops::RangeInclusive, string::FromUtf8Error, time::Duration};
 
 use crate::{
     afs::{entry::FSEntry, error::PathError},
     ast::error::ParseError,
     driver::FSEntryQueryResult,
-    error::{ResolvedError, ResolvedSpan},
+    error::{OwnedResolvedError, ResolvedError, ResolvedSpan},
     ir::Rir,
     runner::cx::StacktraceEntry,
     span::ErrorSpan,
@@ -19,11 +19,15 @@ pub struct ErrorTrace<E: std::error::Error> {
 }
 
 impl<E: std::error::Error> ErrorTrace<E> {
-    pub fn resolve_ir<'a>(&'a self, ir: &'a Rir) -> ResolvedError<'a> {
+    pub fn resolve_ir(&self, ir: &Rir) -> OwnedResolvedError {
+        let modules: Vec<_> = self
+            .stacktrace
+            .iter()
+            .map(|s| (ir.get_module(s.m), s.n))
+            .collect();
         let mut trace = Vec::new();
-        for s in &self.stacktrace {
-            let module = ir.get_module(s.m);
-            let span = module.span(s.n);
+        for (module, n) in &modules {
+            let span = module.span(*n);
             let file = module.file().ok();
             let src = &module.src;
             trace.push(ResolvedSpan {
@@ -44,6 +48,7 @@ impl<E: std::error::Error> ErrorTrace<E> {
             err: &self.err_span.err,
             trace,
         }
+        .into_owned()
     }
 
     pub fn convert<T>(self) -> ErrorTrace<T>
@@ -91,6 +96,8 @@ pub enum RunnerError {
     },
     #[error("invalid integer literal")]
     InvalidIntegerLiteral,
+    #[error("invalid float literal")]
+    InvalidFloatLiteral,
     #[error("reached max call depth possibly due to infinite recursion")]
     MaxCallDepth,
     #[error("path error: {0}")]
@@ -104,13 +111,19 @@ pub enum RunnerError {
     #[error("parse error when importing: {0}")]
     ImportParseError(#[from] ParseError),
     #[error("zip error: {0}")]
-    ExtractError(Box<dyn std::error::Error>),
+    ExtractError(Box<dyn std::error::Error + Send + Sync>),
+    #[error("invalid glob pattern: {0}")]
+    InvalidGlobPattern(Box<dyn std::error::Error + Send + Sync>),
+    #[error("invalid regex: {0}")]
+    InvalidRegex(regex::Error),
     #[error("fs query path {0} {1}")]
     FSQuery(FSEntry, FSEntryQueryResult),
     #[error("index out of bounds: {0}")]
     IndexOutOfBounds(RainInteger),
     #[error("index key not found: {0}")]
     IndexKeyNotFound(String),
+    #[error("no match arm matched {0}")]
+    NoMatchingPattern(String),
     #[error("cannot call from embed")]
     EmbedContext,
     #[error("can't escape seal 🦭")]
@@ -119,4 +132,28 @@ pub enum RunnerError {
     FromUtf8Error(#[from] FromUtf8Error),
     #[error("declaration is private")]
     PrivateDeclaration,
+    #[error("cancelled because another branch failed")]
+    Cancelled,
+    #[error("command was killed because it {reason}\nstdout:\n{stdout}\nstderr:\n{stderr}")]
+    RunKilled {
+        reason: KillReason,
+        /// Output up until the command was killed
+        stdout: String,
+        stderr: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KillReason {
+    Timeout(Duration),
+    Cancelled,
+}
+
+impl std::fmt::Display for KillReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Timeout(timeout) => write!(f, "timed out after {timeout:?}"),
+            Self::Cancelled => f.write_str("was cancelled"),
+        }
+    }
 }
@@ -1,7 +1,11 @@
 #![allow(clippy::unnecessary_wraps)]
 
 mod download;
+mod git;
+mod list;
+mod record;
 mod run;
+mod string;
 
 use std::{
     borrow::Cow, hash::Hash, ops::RangeInclusive, path::Path, str::FromStr as _, sync::Arc,
@@ -22,7 +26,7 @@ use crate::{
         path::SealedFilePath,
     },
     ast::NodeId,
-    driver::{DriverTrait, FSEntryQueryResult},
+    driver::{DriverTrait, FSEntryQueryResult, GlobMatch, GlobOptions, LfsOptions},
     local_span::LocalSpan,
     runner::{cache::CacheTrait, dep_list::DepList},
 };
@@ -31,9 +35,9 @@ use super::{
     Result, ResultValue,
     cache::{CacheEntry, CacheKey},
     cx::Cx,
-    dep::Dep,
+    dep::{ContentsFilter, Dep},
     error::{RunnerError, Throwing},
-    value::{RainInteger, RainList, RainRecord, RainTypeId, Value},
+    value::{RainFloat, RainInteger, RainList, RainRecord, RainTypeId, Value},
 };
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
@@ -74,6 +78,19 @@ pub enum InternalFunction {
     HostInfo,
     Import,
     Index,
+    ListAll,
+    ListAny,
+    ListConcat,
+    ListEnumerate,
+    ListFilter,
+    ListFlatten,
+    ListLength,
+    ListMap,
+    ListRange,
+    ListSlice,
+    ListSort,
+    ListUnique,
+    ListZip,
     LocalArea,
     MergeRecords,
     ModuleFile,
@@ -82,7 +99,15 @@ pub enum InternalFunction {
     ParseToml,
     Print,
     ReadFile,
+    RecordDeepMerge,
+    RecordFilter,
+    RecordGet,
+    RecordHasKey,
     RecordKeys,
+    RecordMapValues,
+    RecordRemove,
+    RegexCaptures,
+    RegexMatch,
     Run,
     RustEq,
     SetCacheNever,
@@ -90,6 +115,15 @@ pub enum InternalFunction {
     Sha512,
     SplitString,
     StringContains,
+    StringEndsWith,
+    StringJoin,
+    StringLength,
+    StringReplace,
+    StringSlice,
+    StringStartsWith,
+    StringToLower,
+    StringToUpper,
+    StringTrim,
     Stringify,
     Throw,
     Unit,
@@ -142,6 +176,19 @@ impl InternalFunction {
             "_host_info" => Some(Self::HostInfo),
             "_import" => Some(Self::Import),
             "_index" => Some(Self::Index),
+            "_list_all" => Some(Self::ListAll),
+            "_list_any" => Some(Self::ListAny),
+            "_list_concat" => Some(Self::ListConcat),
+            "_list_enumerate" => Some(Self::ListEnumerate),
+            "_list_filter" => Some(Self::ListFilter),
+            "_list_flatten" => Some(Self::ListFlatten),
+            "_list_length" => Some(Self::ListLength),
+            "_list_map" => Some(Self::ListMap),
+            "_list_range" => Some(Self::ListRange),
+            "_list_slice" => Some(Self::ListSlice),
+            "_list_sort" => Some(Self::ListSort),
+            "_list_unique" => Some(Self::ListUnique),
+            "_list_zip" => Some(Self::ListZip),
             "_local_area" => Some(Self::LocalArea),
             "_merge_records" => Some(Self::MergeRecords),
             "_module_file" => Some(Self::ModuleFile),
@@ -150,7 +197,15 @@ impl InternalFunction {
             "_parse_toml" => Some(Self::ParseToml),
             "_print" => Some(Self::Print),
             "_read_file" => Some(Self::ReadFile),
+            "_record_deep_merge" => Some(Self::RecordDeepMerge),
+            "_record_filter" => Some(Self::RecordFilter),
+            "_record_get" => Some(Self::RecordGet),
+            "_record_has_key" => Some(Self::RecordHasKey),
             "_record_keys" => Some(Self::RecordKeys),
+            "_record_map_values" => Some(Self::RecordMapValues),
+            "_record_remove" => Some(Self::RecordRemove),
+            "_regex_captures" => Some(Self::RegexCaptures),
+            "_regex_match" => Some(Self::RegexMatch),
             "_run" => Some(Self::Run),
             "_rust_eq" => Some(Self::RustEq),
             "_set_cache_never" => Some(Self::SetCacheNever),
@@ -158,6 +213,15 @@ impl InternalFunction {
             "_sha512" => Some(Self::Sha512),
             "_split_string" => Some(Self::SplitString),
             "_string_contains" => Some(Self::StringContains),
+            "_string_ends_with" => Some(Self::StringEndsWith),
+            "_string_join" => Some(Self::StringJoin),
+            "_string_length" => Some(Self::StringLength),
+            "_string_replace" => Some(Self::StringReplace),
+            "_string_slice" => Some(Self::StringSlice),
+            "_string_starts_with" => Some(Self::StringStartsWith),
+            "_string_to_lower" => Some(Self::StringToLower),
+            "_string_to_upper" => Some(Self::StringToUpper),
+            "_string_trim" => Some(Self::StringTrim),
             "_stringify" => Some(Self::Stringify),
             "_throw" => Some(Self::Throw),
             "_unit" => Some(Self::Unit),
@@ -166,6 +230,135 @@ impl InternalFunction {
             _ => None,
         }
     }
+
+    /// Whether local directories passed to this function are read as a whole, rather than just used to find entries inside them
+    fn reads_dir_contents(self) -> bool {
+        matches!(
+            self,
+            Self::Run | Self::CreateTar | Self::CopyDir | Self::GitLfsSmudge
+        )
+    }
+
+    /// The arguments accepted and value returned, used to check calls without running them
+    #[expect(clippy::too_many_lines)]
+    pub fn signature(self) -> InternalSignature {
+        use RainTypeId as T;
+        const ANY: Option<&[RainTypeId]> = None;
+        const STRING: Option<&[RainTypeId]> = Some(&[T::String]);
+        const INTEGER: Option<&[RainTypeId]> = Some(&[T::Integer]);
+        const BOOL: Option<&[RainTypeId]> = Some(&[T::Boolean]);
+        const FILE: Option<&[RainTypeId]> = Some(&[T::File]);
+        const DIR: Option<&[RainTypeId]> = Some(&[T::Dir]);
+        const AREA: Option<&[RainTypeId]> = Some(&[T::FileArea]);
+        const DIR_OR_AREA: Option<&[RainTypeId]> = Some(&[T::Dir, T::FileArea]);
+        const LIST: Option<&[RainTypeId]> = Some(&[T::List]);
+        const RECORD: Option<&[RainTypeId]> = Some(&[T::Record]);
+        const CALLABLE: Option<&[RainTypeId]> = Some(&[T::Closure, T::InternalFunction]);
+        let sig = |args: RangeInclusive<usize>,
+                   params: &'static [Option<&'static [RainTypeId]>],
+                   returns: Option<RainTypeId>| InternalSignature {
+            args,
+            params,
+            returns,
+        };
+        match self {
+            Self::Print => sig(0..=usize::MAX, &[], Some(T::Unit)),
+            Self::Debug | Self::Throw => sig(1..=1, &[ANY], None),
+            Self::GetType => sig(1..=1, &[ANY], Some(T::Type)),
+            Self::Stringify => sig(1..=1, &[ANY], Some(T::String)),
+            Self::RustEq => sig(2..=2, &[ANY, ANY], Some(T::Boolean)),
+            Self::Unit | Self::SetCacheNever | Self::ClearCallingCacheDeps => {
+                sig(0..=0, &[], Some(T::Unit))
+            }
+            Self::ModuleFile => sig(0..=0, &[], Some(T::File)),
+            Self::Embed => sig(0..=0, &[], Some(T::Module)),
+            Self::HostInfo => sig(0..=0, &[], Some(T::Record)),
+            Self::GetFile => sig(1..=2, &[ANY, STRING], Some(T::File)),
+            Self::GetDir => sig(1..=2, &[ANY, STRING], Some(T::Dir)),
+            Self::Import => sig(1..=1, &[FILE], Some(T::Module)),
+            Self::GetArea | Self::ExtractZip | Self::ExtractTar => {
+                sig(1..=1, &[FILE], Some(T::FileArea))
+            }
+            Self::Sha256 | Self::Sha512 | Self::ReadFile | Self::FileName => {
+                sig(1..=1, &[FILE], Some(T::String))
+            }
+            Self::FileMetadata => sig(1..=1, &[FILE], Some(T::Record)),
+            Self::ExtractGzip | Self::ExtractXz | Self::ExtractZstd | Self::CompressGzip => {
+                sig(2..=2, &[FILE, STRING], Some(T::File))
+            }
+            Self::CompressZstd => sig(3..=3, &[FILE, STRING, INTEGER], Some(T::File)),
+            Self::CreateFile => sig(3..=3, &[STRING, STRING, BOOL], Some(T::File)),
+            Self::CopyFile => sig(3..=3, &[FILE, STRING, BOOL], Some(T::File)),
+            Self::CopyDir => sig(2..=2, &[DIR, STRING], Some(T::Dir)),
+            Self::CreateTar => sig(2..=2, &[DIR_OR_AREA, STRING], Some(T::File)),
+            Self::CreateArea => sig(2..=2, &[LIST, BOOL], Some(T::FileArea)),
+            Self::CreateWriteArea => sig(1..=1, &[LIST], Some(T::FileArea)),
+            Self::LocalArea => sig(1..=1, &[STRING], Some(T::FileArea)),
+            Self::Glob => sig(
+                1..=3,
+                &[DIR_OR_AREA, Some(&[T::String, T::List]), RECORD],
+                Some(T::List),
+            ),
+            Self::ExportToLocal => sig(2..=3, &[ANY, DIR_OR_AREA, STRING], None),
+            Self::CheckExportToLocal => sig(2..=3, &[FILE, DIR, STRING], None),
+            Self::EscapeHard => sig(1..=1, &[STRING], Some(T::EscapeFile)),
+            Self::EscapeRun => sig(4..=4, &[ANY, ANY, ANY, ANY], Some(T::Record)),
+            Self::Run => sig(4..=5, &[ANY, ANY, ANY, ANY, RECORD], Some(T::Record)),
+            Self::Download => sig(1..=2, &[STRING, RECORD], None),
+            Self::GitContents => sig(2..=3, &[STRING, STRING, ANY], Some(T::FileArea)),
+            Self::GitLfsSmudge => sig(1..=2, &[AREA, RECORD], Some(T::FileArea)),
+            Self::GetSecret => sig(1..=1, &[STRING], Some(T::String)),
+            Self::BytesToString => sig(1..=1, &[LIST], Some(T::String)),
+            Self::EscapeBin | Self::EnvVar | Self::ParseToml | Self::ParseJSON => {
+                sig(1..=1, &[STRING], None)
+            }
+            Self::ParseTargetTriple => sig(1..=1, &[STRING], Some(T::Record)),
+            Self::Index => sig(2..=2, &[ANY, Some(&[T::String, T::Integer])], None),
+            Self::Fold => sig(3..=3, &[ANY, LIST, CALLABLE], None),
+            Self::MergeRecords => sig(2..=2, &[RECORD, RECORD], Some(T::Record)),
+            Self::RecordKeys => sig(1..=1, &[RECORD], Some(T::List)),
+            Self::RecordHasKey => sig(2..=2, &[RECORD, STRING], Some(T::Boolean)),
+            Self::RecordRemove => sig(1..=usize::MAX, &[RECORD], Some(T::Record)),
+            Self::RecordGet => sig(3..=3, &[RECORD, STRING, ANY], None),
+            Self::RecordMapValues | Self::RecordFilter => {
+                sig(2..=2, &[RECORD, CALLABLE], Some(T::Record))
+            }
+            Self::RecordDeepMerge => sig(3..=3, &[RECORD, RECORD, STRING], Some(T::Record)),
+            Self::ListLength => sig(1..=1, &[LIST], Some(T::Integer)),
+            Self::ListRange => sig(2..=2, &[INTEGER, INTEGER], Some(T::List)),
+            Self::ListSlice => sig(2..=3, &[LIST, INTEGER, INTEGER], Some(T::List)),
+            Self::ListConcat => sig(0..=usize::MAX, &[], Some(T::List)),
+            Self::ListFlatten | Self::ListUnique | Self::ListEnumerate => {
+                sig(1..=1, &[LIST], Some(T::List))
+            }
+            Self::ListMap | Self::ListFilter => sig(2..=2, &[LIST, CALLABLE], Some(T::List)),
+            Self::ListAny | Self::ListAll => sig(1..=2, &[LIST, CALLABLE], Some(T::Boolean)),
+            Self::ListSort => sig(1..=2, &[LIST, CALLABLE], Some(T::List)),
+            Self::ListZip => sig(2..=2, &[LIST, LIST], Some(T::List)),
+            Self::SplitString => sig(2..=2, &[STRING, STRING], Some(T::List)),
+            Self::StringContains | Self::StringStartsWith | Self::StringEndsWith => {
+                sig(2..=2, &[STRING, STRING], Some(T::Boolean))
+            }
+            Self::StringJoin => sig(2..=2, &[LIST, STRING], Some(T::String)),
+            Self::StringLength => sig(1..=1, &[STRING], Some(T::Integer)),
+            Self::StringTrim | Self::StringToUpper | Self::StringToLower => {
+                sig(1..=1, &[STRING], Some(T::String))
+            }
+            Self::StringReplace => sig(3..=3, &[STRING, STRING, STRING], Some(T::String)),
+            Self::StringSlice => sig(2..=3, &[STRING, INTEGER, INTEGER], Some(T::String)),
+            Self::RegexMatch => sig(2..=2, &[STRING, STRING], Some(T::Boolean)),
+            Self::RegexCaptures => sig(2..=2, &[STRING, STRING], None),
+        }
+    }
+}
+
+/// Shape of an internal function call, see [`InternalFunction::signature`]
+pub struct InternalSignature {
+    pub args: RangeInclusive<usize>,
+    /// Types accepted by each positional argument, `None` accepts anything and arguments past the end are unchecked
+    pub params: &'static [Option<&'static [RainTypeId]>],
+    /// The type always returned if there is one
+    pub returns: Option<RainTypeId>,
 }
 
 macro_rules! single_arg {
@@ -278,7 +471,8 @@ pub struct InternalCx<'a, 'b, 'c, Driver, Cache> {
 }
 
 impl<Driver: DriverTrait, Cache: CacheTrait> InternalCx<'_, '_, '_, Driver, Cache> {
-    pub fn call_internal_function(self) -> ResultValue {
+    pub fn call_internal_function(mut self) -> ResultValue {
+        self.add_dep_local_file_args()?;
         match self.func {
             InternalFunction::Print => self.print(),
             InternalFunction::Debug => self.debug(),
@@ -337,6 +531,36 @@ impl<Driver: DriverTrait, Cache: CacheTrait> InternalCx<'_, '_, '_, Driver, Cach
             InternalFunction::ExtractZstd => self.extract_zstd(),
             InternalFunction::FileName => self.file_name(),
             InternalFunction::CopyDir => self.copy_dir(),
+            InternalFunction::StringTrim => self.string_trim(),
+            InternalFunction::StringReplace => self.string_replace(),
+            InternalFunction::StringStartsWith => self.string_starts_with(),
+            InternalFunction::StringEndsWith => self.string_ends_with(),
+            InternalFunction::StringJoin => self.string_join(),
+            InternalFunction::StringToUpper => self.string_to_upper(),
+            InternalFunction::StringToLower => self.string_to_lower(),
+            InternalFunction::StringSlice => self.string_slice(),
+            InternalFunction::StringLength => self.string_length(),
+            InternalFunction::RegexMatch => self.regex_match(),
+            InternalFunction::RegexCaptures => self.regex_captures(),
+            InternalFunction::ListAll => self.list_all(),
+            InternalFunction::ListAny => self.list_any(),
+            InternalFunction::ListConcat => self.list_concat(),
+            InternalFunction::ListEnumerate => self.list_enumerate(),
+            InternalFunction::ListFilter => self.list_filter(),
+            InternalFunction::ListFlatten => self.list_flatten(),
+            InternalFunction::ListLength => self.list_length(),
+            InternalFunction::ListMap => self.list_map(),
+            InternalFunction::ListRange => self.list_range(),
+            InternalFunction::ListSlice => self.list_slice(),
+            InternalFunction::ListSort => self.list_sort(),
+            InternalFunction::ListUnique => self.list_unique(),
+            InternalFunction::ListZip => self.list_zip(),
+            InternalFunction::RecordDeepMerge => self.record_deep_merge(),
+            InternalFunction::RecordFilter => self.record_filter(),
+            InternalFunction::RecordGet => self.record_get(),
+            InternalFunction::RecordHasKey => self.record_has_key(),
+            InternalFunction::RecordMapValues => self.record_map_values(),
+            InternalFunction::RecordRemove => self.record_remove(),
         }
     }
 
@@ -364,6 +588,127 @@ impl<Driver: DriverTrait, Cache: CacheTrait> InternalCx<'_, '_, '_, Driver, Cach
         ))
     }
 
+    fn expect_string<'v>(&self, (nid, value): &'v (NodeId, Value)) -> Result<&'v str> {
+        let Value::String(s) = value else {
+            return Err(self.cx.nid_err(
+                *nid,
+                RunnerError::ExpectedType {
+                    actual: value.rain_type_id(),
+                    expected: Cow::Borrowed(&[RainTypeId::String]),
+                },
+            ));
+        };
+        Ok(s)
+    }
+
+    fn expect_bool(&self, nid: NodeId, value: &Value) -> Result<bool> {
+        let Value::Boolean(b) = value else {
+            return Err(self.cx.nid_err(
+                nid,
+                RunnerError::ExpectedType {
+                    actual: value.rain_type_id(),
+                    expected: Cow::Borrowed(&[RainTypeId::Boolean]),
+                },
+            ));
+        };
+        Ok(*b)
+    }
+
+    /// Call a function argument of this internal function with `args`
+    fn call_callback(
+        &mut self,
+        (func_nid, func_value): &(NodeId, Value),
+        args: Vec<Value>,
+    ) -> ResultValue {
+        self.runner.call_function(
+            self.cx,
+            self.nid,
+            func_value,
+            self.call_span,
+            args.into_iter().map(|v| (*func_nid, v)).collect(),
+        )
+    }
+
+    fn call_predicate(&mut self, func: &(NodeId, Value), args: Vec<Value>) -> Result<bool> {
+        let result = self.call_callback(func, args)?;
+        self.expect_bool(func.0, &result)
+    }
+
+    /// Range from the `start` and optional `end` index arguments of a slice of something `length` long
+    fn expect_slice_range(
+        &self,
+        start: &(NodeId, Value),
+        end: Option<&(NodeId, Value)>,
+        length: usize,
+    ) -> Result<std::ops::Range<usize>> {
+        let index = |(nid, value): &(NodeId, Value)| {
+            let Value::Integer(index) = value else {
+                return Err(self.cx.nid_err(
+                    *nid,
+                    RunnerError::ExpectedType {
+                        actual: value.rain_type_id(),
+                        expected: Cow::Borrowed(&[RainTypeId::Integer]),
+                    },
+                ));
+            };
+            match usize::try_from(&index.0) {
+                Ok(i) if i <= length => Ok(i),
+                _ => Err(self
+                    .cx
+                    .nid_err(*nid, RunnerError::IndexOutOfBounds(index.as_ref().clone()))),
+            }
+        };
+        let start = index(start)?;
+        let end = end.map_or(Ok(length), index)?;
+        if start > end {
+            return Err(self.cx.nid_err(
+                self.nid,
+                RunnerError::Makeshift(
+                    format!("slice start {start} is after slice end {end}").into(),
+                ),
+            ));
+        }
+        Ok(start..end)
+    }
+
+    /// Dep on the fingerprint of `entry` if it is local so cached results are invalidated when it changes
+    fn local_entry_dep(
+        &self,
+        entry: &FSEntry,
+        contents: Option<&ContentsFilter>,
+    ) -> Result<Option<Dep>> {
+        if !entry.area.is_local() {
+            return Ok(None);
+        }
+        let fingerprint = self
+            .runner
+            .driver
+            .fingerprint(entry, contents)
+            .map_err(|err| self.cx.nid_err(self.nid, RunnerError::AreaIOError(err)))?;
+        Ok(Some(Dep::LocalEntry {
+            entry: entry.clone(),
+            fingerprint,
+        }))
+    }
+
+    /// Local files passed to internal functions are assumed to be read by them, local directories only by functions that read them as a whole
+    fn add_dep_local_file_args(&mut self) -> Result<()> {
+        let reads_dirs = self.func.reads_dir_contents();
+        for (_, value) in &self.arg_values {
+            for file in value.find_local_files() {
+                let dep = self.local_entry_dep(file.inner(), None)?;
+                self.deps.extend(dep.into_iter());
+            }
+            if reads_dirs {
+                for dir in value.find_local_dirs() {
+                    let dep = self.local_entry_dep(&dir, Some(&ContentsFilter::Area))?;
+                    self.deps.extend(dep.into_iter());
+                }
+            }
+        }
+        Ok(())
+    }
+
     fn expect_dir_or_area(&self, arg_nid: NodeId, arg_value: &Value) -> Result<Arc<Dir>> {
         match arg_value {
             Value::FileArea(file_area) => Ok(Arc::new(Dir::root(file_area.as_ref().clone()))),
@@ -399,7 +744,7 @@ impl<Driver: DriverTrait, Cache: CacheTrait> InternalCx<'_, '_, '_, Driver, Cach
                 }
             })
             .collect();
-        self.runner.driver.print(args.join(" "));
+        self.runner.print(args.join(" "));
         Ok(Value::Unit)
     }
 
@@ -413,7 +758,6 @@ impl<Driver: DriverTrait, Cache: CacheTrait> InternalCx<'_, '_, '_, Driver, Cach
                     .module
                     .file()
                     .map_err(|err| self.cx.nid_err(self.nid, err))?;
-                self.deps.add_dep_file_area(file.area());
                 self.deps.push(Dep::CallingModule);
                 let file_path = file
                     .path()
@@ -433,7 +777,6 @@ impl<Driver: DriverTrait, Cache: CacheTrait> InternalCx<'_, '_, '_, Driver, Cach
                 let path = expect_type!(self, String, (path_nid, path_value));
                 match parent_value {
                     Value::FileArea(area) => {
-                        self.deps.add_dep_file_area(area);
                         let file_path = SealedFilePath::new(path)
                             .map_err(|err| self.cx.nid_err(*path_nid, err.into()))?;
                         Ok(FSEntry {
@@ -443,7 +786,6 @@ impl<Driver: DriverTrait, Cache: CacheTrait> InternalCx<'_, '_, '_, Driver, Cach
                     }
                     Value::Dir(dir) => {
                         let area = dir.area();
-                        self.deps.add_dep_file_area(area);
                         let base_path = dir.path();
                         let path = base_path
                             .join(path)
@@ -468,6 +810,8 @@ impl<Driver: DriverTrait, Cache: CacheTrait> InternalCx<'_, '_, '_, Driver, Cach
 
     fn get_file(mut self) -> ResultValue {
         let entry = self.file_area_resolve_path()?;
+        let dep = self.local_entry_dep(&entry, None)?;
+        self.deps.extend(dep.into_iter());
         match self
             .runner
             .driver
@@ -487,6 +831,8 @@ impl<Driver: DriverTrait, Cache: CacheTrait> InternalCx<'_, '_, '_, Driver, Cach
 
     fn get_dir(mut self) -> ResultValue {
         let entry = self.file_area_resolve_path()?;
+        let dep = self.local_entry_dep(&entry, None)?;
+        self.deps.extend(dep.into_iter());
         match self
             .runner
             .driver
@@ -676,7 +1022,7 @@ impl<Driver: DriverTrait, Cache: CacheTrait> InternalCx<'_, '_, '_, Driver, Cach
             match v {
                 toml::Value::String(s) => Value::String(Arc::new(s)),
                 toml::Value::Integer(n) => Value::Integer(Arc::new(RainInteger(BigInt::from(n)))),
-                toml::Value::Float(f) => Value::String(Arc::new(f.to_string())),
+                toml::Value::Float(f) => Value::Float(RainFloat(f)),
                 toml::Value::Boolean(b) => Value::Boolean(b),
                 toml::Value::Datetime(datetime) => Value::String(Arc::new(datetime.to_string())),
                 toml::Value::Array(vec) => Value::List(Arc::new(RainList(
@@ -704,8 +1050,8 @@ impl<Driver: DriverTrait, Cache: CacheTrait> InternalCx<'_, '_, '_, Driver, Cach
                 serde_json::Value::Null => Value::Unit,
                 serde_json::Value::String(s) => Value::String(Arc::new(s)),
                 serde_json::Value::Number(n) => {
-                    if let Some(float) = n.as_f64() {
-                        Value::String(Arc::new(float.to_string()))
+                    if n.is_f64() {
+                        Value::Float(RainFloat(n.as_f64().unwrap_or(f64::NAN)))
                     } else {
                         Value::Integer(Arc::new(RainInteger(
                             n.as_i64()
@@ -755,7 +1101,8 @@ impl<Driver: DriverTrait, Cache: CacheTrait> InternalCx<'_, '_, '_, Driver, Cach
             })
             .collect::<Result<Vec<&FSEntry>, _>>()?;
         for entry in &dirs {
-            self.deps.add_dep_file_area(&entry.area);
+            let dep = self.local_entry_dep(entry, Some(&ContentsFilter::Area))?;
+            self.deps.extend(dep.into_iter());
         }
         let merged_area = self
             .runner
@@ -824,7 +1171,7 @@ impl<Driver: DriverTrait, Cache: CacheTrait> InternalCx<'_, '_, '_, Driver, Cach
         } else {
             format!("{value}")
         };
-        self.runner.driver.print(p);
+        self.runner.print(p);
         Ok(value.clone())
     }
 
@@ -1194,62 +1541,91 @@ impl<Driver: DriverTrait, Cache: CacheTrait> InternalCx<'_, '_, '_, Driver, Cach
     }
 
     fn glob(self) -> ResultValue {
-        match &self.arg_values[..] {
-            [(dir_nid, dir_value)] => {
-                let d = match dir_value {
-                    Value::Dir(d) => d.as_ref(),
-                    Value::FileArea(a) => &Dir::root((**a).clone()),
-                    _ => {
-                        return Err(self.cx.nid_err(
-                            *dir_nid,
-                            RunnerError::ExpectedType {
-                                actual: dir_value.rain_type_id(),
-                                expected: Cow::Borrowed(&[RainTypeId::Dir, RainTypeId::FileArea]),
-                            },
-                        ));
-                    }
-                };
-                self.deps.add_dep_file_area(d.area());
-                let files = self
-                    .runner
-                    .driver
-                    .glob(d, "**/*")
-                    .map_err(|err| self.cx.nid_err(self.nid, err))?;
-                let files: Vec<Value> = files
-                    .into_iter()
-                    .map(|f| Value::File(Arc::new(f)))
-                    .collect();
-                Ok(Value::List(Arc::new(RainList(files))))
+        let ((dir_nid, dir_value), pattern, options) = match &self.arg_values[..] {
+            [(dir_nid, dir_value)] => ((*dir_nid, dir_value), None, None),
+            [(dir_nid, dir_value), pattern] => ((*dir_nid, dir_value), Some(pattern), None),
+            [(dir_nid, dir_value), pattern, options] => {
+                ((*dir_nid, dir_value), Some(pattern), Some(options))
+            }
+            _ => return self.incorrect_args(1..=3),
+        };
+        let d = match dir_value {
+            Value::Dir(d) => d.as_ref(),
+            Value::FileArea(a) => &Dir::root((**a).clone()),
+            _ => {
+                return Err(self.cx.nid_err(
+                    dir_nid,
+                    RunnerError::ExpectedType {
+                        actual: dir_value.rain_type_id(),
+                        expected: Cow::Borrowed(&[RainTypeId::Dir, RainTypeId::FileArea]),
+                    },
+                ));
+            }
+        };
+        let patterns = match pattern {
+            None => vec!["**".to_owned()],
+            Some((_, Value::String(pattern))) => vec![pattern.to_string()],
+            Some((pattern_nid, Value::List(patterns))) => patterns
+                .0
+                .iter()
+                .map(|pattern| match pattern {
+                    Value::String(pattern) => Ok(pattern.to_string()),
+                    _ => Err(self.cx.nid_err(
+                        *pattern_nid,
+                        RunnerError::ExpectedType {
+                            actual: pattern.rain_type_id(),
+                            expected: Cow::Borrowed(&[RainTypeId::String]),
+                        },
+                    )),
+                })
+                .collect::<Result<Vec<String>>>()?,
+            Some((pattern_nid, pattern_value)) => {
+                return Err(self.cx.nid_err(
+                    *pattern_nid,
+                    RunnerError::ExpectedType {
+                        actual: pattern_value.rain_type_id(),
+                        expected: Cow::Borrowed(&[RainTypeId::String, RainTypeId::List]),
+                    },
+                ));
             }
-            [(dir_nid, dir_value), (pattern_nid, pattern_value)] => {
-                let d = match dir_value {
-                    Value::Dir(d) => d.as_ref(),
-                    Value::FileArea(a) => &Dir::root((**a).clone()),
+        };
+        let mut glob_options = GlobOptions {
+            patterns,
+            include_dirs: false,
+            respect_gitignore: true,
+        };
+        if let Some((options_nid, options_value)) = options {
+            let options = expect_type!(self, Record, (*options_nid, options_value));
+            for (key, value) in &options.0 {
+                let option = match key.as_str() {
+                    "dirs" => &mut glob_options.include_dirs,
+                    "gitignore" => &mut glob_options.respect_gitignore,
                     _ => {
                         return Err(self.cx.nid_err(
-                            *dir_nid,
-                            RunnerError::ExpectedType {
-                                actual: dir_value.rain_type_id(),
-                                expected: Cow::Borrowed(&[RainTypeId::Dir, RainTypeId::FileArea]),
-                            },
+                            *options_nid,
+                            RunnerError::Makeshift(format!("unknown glob option {key}").into()),
                         ));
                     }
                 };
-                let pattern = expect_type!(self, String, (pattern_nid, pattern_value));
-                self.deps.add_dep_file_area(d.area());
-                let files = self
-                    .runner
-                    .driver
-                    .glob(d, pattern)
-                    .map_err(|err| self.cx.nid_err(self.nid, err))?;
-                let files: Vec<Value> = files
-                    .into_iter()
-                    .map(|f| Value::File(Arc::new(f)))
-                    .collect();
-                Ok(Value::List(Arc::new(RainList(files))))
+                *option = *expect_type!(self, Boolean, (*options_nid, value));
             }
-            _ => self.incorrect_args(1..=2),
         }
+        let dep =
+            self.local_entry_dep(d.inner(), Some(&ContentsFilter::Glob(glob_options.clone())))?;
+        self.deps.extend(dep.into_iter());
+        let matches = self
+            .runner
+            .driver
+            .glob(d, &glob_options)
+            .map_err(|err| self.cx.nid_err(self.nid, err))?;
+        let matches: Vec<Value> = matches
+            .into_iter()
+            .map(|m| match m {
+                GlobMatch::File(f) => Value::File(Arc::new(f)),
+                GlobMatch::Dir(d) => Value::Dir(Arc::new(d)),
+            })
+            .collect();
+        Ok(Value::List(Arc::new(RainList(matches))))
     }
 
     fn stringify(self) -> ResultValue {
@@ -1278,6 +1654,7 @@ impl<Driver: DriverTrait, Cache: CacheTrait> InternalCx<'_, '_, '_, Driver, Cach
             ))),
             Value::EscapeFile(f) => Ok(Value::String(Arc::new(format!("{}", f.0.display())))),
             Value::Integer(i) => Ok(Value::String(Arc::new(i.to_string()))),
+            Value::Float(f) => Ok(Value::String(Arc::new(f.to_string()))),
             Value::Boolean(b) => Ok(Value::String(Arc::new(b.to_string()))),
             _ => Err(self.cx.nid_err(
                 nid,
@@ -1288,6 +1665,7 @@ impl<Driver: DriverTrait, Cache: CacheTrait> InternalCx<'_, '_, '_, Driver, Cach
                         RainTypeId::Dir,
                         RainTypeId::EscapeFile,
                         RainTypeId::Integer,
+                        RainTypeId::Float,
                         RainTypeId::Boolean,
                     ]),
                 },
@@ -1360,12 +1738,15 @@ impl<Driver: DriverTrait, Cache: CacheTrait> InternalCx<'_, '_, '_, Driver, Cach
 
     fn get_secret(self) -> ResultValue {
         let name = expect_type!(self, String, single_arg!(self));
-        self.deps.push(Dep::Secret);
         let secret = self
             .runner
             .driver
             .get_secret(name)
             .map_err(|err| self.cx.nid_err(self.nid, err))?;
+        self.deps.push(Dep::Secret {
+            name: name.as_ref().clone(),
+            value_hash: Dep::value_hash(&secret),
+        });
         Ok(Value::String(Arc::new(secret)))
     }
 
@@ -1427,37 +1808,73 @@ impl<Driver: DriverTrait, Cache: CacheTrait> InternalCx<'_, '_, '_, Driver, Cach
         Ok(Value::Record(Arc::new(RainRecord(out))))
     }
 
-    fn git_contents(self) -> ResultValue {
-        let (url, commit) = two_args!(self);
-        let url = expect_type!(self, String, url);
-        let commit = expect_type!(self, String, commit);
-        let area = self
-            .runner
-            .driver
-            .git_contents(url, commit)
-            .map_err(|err| self.cx.nid_err(self.nid, err))?;
-        Ok(Value::FileArea(Arc::new(area)))
-    }
-
     fn git_lfs_smudge(self) -> ResultValue {
-        let area = expect_type!(self, FileArea, single_arg!(self));
+        let (area, mut options) = match &self.arg_values[..] {
+            [(area_nid, area)] => ((*area_nid, area), LfsOptions::default()),
+            [(area_nid, area), (options_nid, options)] => {
+                ((*area_nid, area), self.lfs_options(*options_nid, options)?)
+            }
+            _ => return self.incorrect_args(1..=2),
+        };
+        let area = expect_type!(self, FileArea, area);
+        if let Some((secret, dep)) = self.resolve_auth_secret(options.auth.as_ref())? {
+            options.auth_secret = Some(secret);
+            self.deps.push(dep);
+        }
         let new_area = self
             .runner
             .driver
-            .git_lfs_smudge(area)
+            .git_lfs_smudge(area, &options)
             .map_err(|err| self.cx.nid_err(self.nid, err))?;
         Ok(Value::FileArea(Arc::new(new_area)))
     }
 
+    /// `{endpoint = "https://host/repo.git/info/lfs", auth = {bearer = "SECRET_NAME"}}`, auth takes the same form as for downloads
+    fn lfs_options(&self, options_nid: NodeId, options_value: &Value) -> Result<LfsOptions> {
+        let expected_type = |value: &Value, expected: &'static [RainTypeId]| {
+            self.cx.nid_err(
+                options_nid,
+                RunnerError::ExpectedType {
+                    actual: value.rain_type_id(),
+                    expected: Cow::Borrowed(expected),
+                },
+            )
+        };
+        let Value::Record(record) = options_value else {
+            return Err(expected_type(options_value, &[RainTypeId::Record]));
+        };
+        let mut options = LfsOptions::default();
+        for (key, value) in &record.0 {
+            match (key.as_str(), value) {
+                ("endpoint", Value::String(s)) => options.endpoint = Some(s.to_string()),
+                ("auth", Value::Record(auth)) => {
+                    options.auth = Some(self.download_auth(options_nid, auth)?);
+                }
+                ("endpoint", _) => return Err(expected_type(value, &[RainTypeId::String])),
+                ("auth", _) => return Err(expected_type(value, &[RainTypeId::Record])),
+                _ => {
+                    return Err(self.cx.nid_err(
+                        options_nid,
+                        RunnerError::Makeshift(format!("unknown git lfs option {key}").into()),
+                    ));
+                }
+            }
+        }
+        Ok(options)
+    }
+
     fn env_var(self) -> ResultValue {
-        self.deps.push(Dep::EnvVar);
         let var_name = expect_type!(self, String, single_arg!(self));
-        if let Some(value) = self
+        let value = self
             .runner
             .driver
             .env_var(var_name)
-            .map_err(|err| self.cx.nid_err(self.nid, err))?
-        {
+            .map_err(|err| self.cx.nid_err(self.nid, err))?;
+        self.deps.push(Dep::EnvVar {
+            name: var_name.as_ref().clone(),
+            value_hash: value.as_deref().map(Dep::value_hash),
+        });
+        if let Some(value) = value {
             Ok(Value::String(Arc::new(value)))
         } else {
             Ok(Value::Unit)
@@ -1,13 +1,15 @@
-use std::{sync::Arc, time::Instant};
+use std::{borrow::Cow, sync::Arc, time::Instant};
 
 use chrono::Utc;
 use indexmap::IndexMap;
 
-use crate::driver::{DownloadStatus, DriverTrait};
+use crate::ast::NodeId;
+use crate::driver::{DownloadAuth, DownloadOptions, DownloadStatus, DriverTrait};
 use crate::runner::cache::CacheTrait;
+use crate::runner::dep::Dep;
 use crate::runner::dep_list::DepList;
 use crate::runner::{
-    ResultValue,
+    Result, ResultValue,
     cache::{CacheEntry, CacheKey},
     error::RunnerError,
     value::{RainInteger, RainRecord, RainTypeId, Value},
@@ -15,95 +17,247 @@ use crate::runner::{
 
 use super::{InternalCx, enter_call};
 
+/// Headers that carry credentials, these are rejected in `headers` because their values would be stored in cache keys
+const SENSITIVE_HEADERS: &[&str] = &["Authorization", "Proxy-Authorization", "Cookie"];
+
 impl<Driver: DriverTrait, Cache: CacheTrait> InternalCx<'_, '_, '_, Driver, Cache> {
     pub fn download(self) -> ResultValue {
-        match &self.arg_values[..] {
-            [(url_nid, url_value)] => {
-                let start = Instant::now();
-                let Value::String(url) = url_value else {
-                    return Err(self.cx.nid_err(
-                        *url_nid,
-                        RunnerError::ExpectedType {
-                            actual: url_value.rain_type_id(),
-                            expected: std::borrow::Cow::Borrowed(&[RainTypeId::String]),
-                        },
-                    ));
-                };
-                let cache_key = CacheKey::Download {
-                    url: url.to_string(),
-                };
-                let call_description = format!("Download {url}");
-                let _call = enter_call(self.runner.driver, call_description);
-                let cache_entry = self.runner.cache.get(&cache_key);
-                if let Some(cache_entry) = &cache_entry {
-                    if let Some(expires) = cache_entry.expires {
-                        if expires > Utc::now() || self.runner.offline {
-                            log::debug!("Download cache hit, not expired");
-                            return Ok(cache_entry.value.clone());
-                        }
-                    } else {
-                        log::debug!("Download cache hit, no expiry");
-                        return Ok(cache_entry.value.clone());
+        let (url, options) = match &self.arg_values[..] {
+            [url] => (url, DownloadOptions::default()),
+            [url, (options_nid, options_value)] => {
+                (url, self.download_options(*options_nid, options_value)?)
+            }
+            _ => return self.incorrect_args(1..=2),
+        };
+        let start = Instant::now();
+        let url = self.expect_string(url)?.to_owned();
+        let mut options = options;
+        let mut deps = DepList::new();
+        if let Some((secret, dep)) = self.resolve_auth_secret(options.auth.as_ref())? {
+            options.auth_secret = Some(secret);
+            self.deps.push(dep.clone());
+            deps.push(dep);
+        }
+        let cache_key = CacheKey::Download {
+            url: url.clone(),
+            sha256: options.sha256.clone(),
+            headers: options.headers.clone(),
+            auth: options.auth.clone(),
+        };
+        let call_description = format!("Download {url}");
+        let _call = enter_call(self.runner.driver, call_description);
+        let cache_entry = self.runner.cache.get(&cache_key);
+        if let Some(cache_entry) = &cache_entry {
+            if let Some(expires) = cache_entry.expires {
+                if expires > Utc::now() || self.runner.offline {
+                    log::debug!("Download cache hit, not expired");
+                    return Ok(cache_entry.value.clone());
+                }
+            } else {
+                log::debug!("Download cache hit, no expiry");
+                return Ok(cache_entry.value.clone());
+            }
+        }
+        if self.runner.offline {
+            return Err(self.cx.nid_err(
+                self.nid,
+                RunnerError::Makeshift("offline mode: cannot download item is not in cache".into()),
+            ));
+        }
+        log::debug!("Download cache miss");
+        let etag: Option<&[u8]> = cache_entry.as_ref().and_then(|e| e.etag.as_deref());
+        let DownloadStatus {
+            ok,
+            status_code,
+            file,
+            etag,
+        } = self
+            .runner
+            .driver
+            .download(&url, "download", etag, &options)
+            .map_err(|err| self.cx.nid_err(self.nid, err))?;
+        if !ok && status_code == Some(304) {
+            // Etag matched we can use our cached value!
+            if let Some(mut cache_entry) = cache_entry {
+                log::debug!("Download cache etag hit");
+                // TODO: Maybe we shouldn't have an expiry on this?
+                cache_entry.expires = Some(Utc::now() + chrono::TimeDelta::days(30));
+                let value = cache_entry.value.clone();
+                self.runner.cache.put(cache_key, cache_entry);
+                return Ok(value);
+            }
+        }
+        let mut m = IndexMap::new();
+        m.insert("ok".to_owned(), Value::Boolean(ok));
+        m.insert(
+            "status_code".to_owned(),
+            Value::Integer(Arc::new(RainInteger(
+                status_code.unwrap_or_default().into(),
+            ))),
+        );
+        if let Some(file) = file {
+            m.insert("file".to_owned(), Value::File(Arc::new(file)));
+        } else {
+            m.insert("file".to_owned(), Value::Unit);
+        }
+        let out = Value::Record(Arc::new(RainRecord(m)));
+        // A download that matched its hash can never change so doesn't need to expire
+        let expires = if ok && options.sha256.is_some() {
+            None
+        } else {
+            Some(Utc::now() + chrono::TimeDelta::hours(1))
+        };
+        self.runner.cache.put(
+            cache_key,
+            CacheEntry {
+                execution_time: start.elapsed(),
+                etag,
+                expires,
+                deps,
+                value: out.clone(),
+            },
+        );
+        Ok(out)
+    }
+
+    /// Look up the secret named by the auth option along with a dep on it so cache entries using it are dropped if it changes
+    pub(super) fn resolve_auth_secret(
+        &self,
+        auth: Option<&DownloadAuth>,
+    ) -> Result<Option<(String, Dep)>> {
+        let Some(auth) = auth else {
+            return Ok(None);
+        };
+        let name = auth.secret_name();
+        let secret = self
+            .runner
+            .driver
+            .get_secret(name)
+            .map_err(|err| self.cx.nid_err(self.nid, err))?;
+        let dep = Dep::Secret {
+            name: name.to_owned(),
+            value_hash: Dep::value_hash(&secret),
+        };
+        Ok(Some((secret, dep)))
+    }
+
+    fn download_options(
+        &self,
+        options_nid: NodeId,
+        options_value: &Value,
+    ) -> Result<DownloadOptions> {
+        let Value::Record(record) = options_value else {
+            return Err(self.cx.nid_err(
+                options_nid,
+                RunnerError::ExpectedType {
+                    actual: options_value.rain_type_id(),
+                    expected: Cow::Borrowed(&[RainTypeId::Record]),
+                },
+            ));
+        };
+        let expected_type = |value: &Value, expected: &'static [RainTypeId]| {
+            self.cx.nid_err(
+                options_nid,
+                RunnerError::ExpectedType {
+                    actual: value.rain_type_id(),
+                    expected: Cow::Borrowed(expected),
+                },
+            )
+        };
+        let mut options = DownloadOptions::default();
+        for (key, value) in &record.0 {
+            match (key.as_str(), value) {
+                ("sha256", Value::String(s)) => {
+                    if s.len() != 64 || !s.bytes().all(|b| b.is_ascii_hexdigit()) {
+                        return Err(self.cx.nid_err(
+                            options_nid,
+                            RunnerError::Makeshift(format!("invalid sha256 {s}").into()),
+                        ));
                     }
+                    options.sha256 = Some(s.to_ascii_lowercase());
                 }
-                if self.runner.offline {
-                    return Err(self.cx.nid_err(
-                        self.nid,
-                        RunnerError::Makeshift(
-                            "offline mode: cannot download item is not in cache".into(),
-                        ),
-                    ));
+                ("mirrors", Value::List(list)) => {
+                    for mirror in &list.0 {
+                        let Value::String(mirror) = mirror else {
+                            return Err(expected_type(mirror, &[RainTypeId::String]));
+                        };
+                        options.mirrors.push(mirror.to_string());
+                    }
                 }
-                log::debug!("Download cache miss");
-                let etag: Option<&[u8]> = cache_entry.as_ref().and_then(|e| e.etag.as_deref());
-                let DownloadStatus {
-                    ok,
-                    status_code,
-                    file,
-                    etag,
-                } = self
-                    .runner
-                    .driver
-                    .download(url, "download", etag)
-                    .map_err(|err| self.cx.nid_err(self.nid, err))?;
-                if !ok && status_code == Some(304) {
-                    // Etag matched we can use our cached value!
-                    if let Some(mut cache_entry) = cache_entry {
-                        log::debug!("Download cache etag hit");
-                        // TODO: Maybe we shouldn't have an expiry on this?
-                        cache_entry.expires = Some(Utc::now() + chrono::TimeDelta::days(30));
-                        let value = cache_entry.value.clone();
-                        self.runner.cache.put(cache_key, cache_entry);
-                        return Ok(value);
+                ("headers", Value::Record(headers)) => {
+                    for (name, value) in &headers.0 {
+                        if SENSITIVE_HEADERS
+                            .iter()
+                            .any(|h| h.eq_ignore_ascii_case(name))
+                        {
+                            return Err(self.cx.nid_err(
+                                options_nid,
+                                RunnerError::Makeshift(
+                                    format!("{name} header not allowed in headers, use auth with a secret instead").into(),
+                                ),
+                            ));
+                        }
+                        let Value::String(value) = value else {
+                            return Err(expected_type(value, &[RainTypeId::String]));
+                        };
+                        options.headers.push((name.clone(), value.to_string()));
                     }
                 }
-                let mut m = IndexMap::new();
-                m.insert("ok".to_owned(), Value::Boolean(ok));
-                m.insert(
-                    "status_code".to_owned(),
-                    Value::Integer(Arc::new(RainInteger(
-                        status_code.unwrap_or_default().into(),
-                    ))),
-                );
-                if let Some(file) = file {
-                    m.insert("file".to_owned(), Value::File(Arc::new(file)));
-                } else {
-                    m.insert("file".to_owned(), Value::Unit);
+                ("auth", Value::Record(auth)) => {
+                    options.auth = Some(self.download_auth(options_nid, auth)?);
+                }
+                ("sha256", _) => return Err(expected_type(value, &[RainTypeId::String])),
+                ("mirrors", _) => return Err(expected_type(value, &[RainTypeId::List])),
+                ("headers" | "auth", _) => {
+                    return Err(expected_type(value, &[RainTypeId::Record]));
+                }
+                _ => {
+                    return Err(self.cx.nid_err(
+                        options_nid,
+                        RunnerError::Makeshift(format!("unknown download option {key}").into()),
+                    ));
                 }
-                let out = Value::Record(Arc::new(RainRecord(m)));
-                self.runner.cache.put(
-                    cache_key,
-                    CacheEntry {
-                        execution_time: start.elapsed(),
-                        etag,
-                        expires: Some(Utc::now() + chrono::TimeDelta::hours(1)),
-                        deps: DepList::new(),
-                        value: out.clone(),
-                    },
-                );
-                Ok(out)
             }
-            _ => self.incorrect_args(2..=2),
+        }
+        Ok(options)
+    }
+
+    /// Either `{bearer = "SECRET_NAME"}` or `{username = "user", password = "SECRET_NAME"}`, only secret names are accepted so secret values never end up in cache keys
+    pub(super) fn download_auth(
+        &self,
+        options_nid: NodeId,
+        auth: &RainRecord,
+    ) -> Result<DownloadAuth> {
+        let field = |key: &str| match auth.0.get(key) {
+            None => Ok(None),
+            Some(Value::String(s)) => Ok(Some(s.to_string())),
+            Some(value) => Err(self.cx.nid_err(
+                options_nid,
+                RunnerError::ExpectedType {
+                    actual: value.rain_type_id(),
+                    expected: Cow::Borrowed(&[RainTypeId::String]),
+                },
+            )),
+        };
+        if let Some(key) = auth
+            .0
+            .keys()
+            .find(|k| !matches!(k.as_str(), "bearer" | "username" | "password"))
+        {
+            return Err(self.cx.nid_err(
+                options_nid,
+                RunnerError::Makeshift(format!("unknown auth option {key}").into()),
+            ));
+        }
+        match (field("bearer")?, field("username")?, field("password")?) {
+            (Some(secret), None, None) => Ok(DownloadAuth::Bearer { secret }),
+            (None, Some(username), Some(secret)) => Ok(DownloadAuth::Basic { username, secret }),
+            _ => Err(self.cx.nid_err(
+                options_nid,
+                RunnerError::Makeshift(
+                    "auth must be either a bearer secret or a username and password secret".into(),
+                ),
+            )),
         }
     }
 }
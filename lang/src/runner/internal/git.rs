@@ -0,0 +1,150 @@
+use std::{borrow::Cow, sync::Arc, time::Instant};
+
+use chrono::Utc;
+
+use crate::{
+    ast::NodeId,
+    driver::DriverTrait,
+    runner::{
+        Result, ResultValue,
+        cache::{CacheEntry, CacheKey, CacheTrait},
+        dep::Dep,
+        dep_list::DepList,
+        error::RunnerError,
+        value::{RainTypeId, Value},
+    },
+};
+
+use super::{InternalCx, enter_call};
+
+impl<Driver: DriverTrait, Cache: CacheTrait> InternalCx<'_, '_, '_, Driver, Cache> {
+    pub fn git_contents(self) -> ResultValue {
+        let (url, git_ref, options) = match &self.arg_values[..] {
+            [url, git_ref] => (url, git_ref, None),
+            [url, git_ref, options] => (url, git_ref, Some(options)),
+            _ => return self.incorrect_args(2..=3),
+        };
+        let url = self.expect_string(url)?;
+        let git_ref = self.expect_string(git_ref)?;
+        let submodules = match options {
+            Some((options_nid, options_value)) => {
+                self.git_submodules_option(*options_nid, options_value)?
+            }
+            None => false,
+        };
+        if is_commit_hash(git_ref) {
+            let area = self
+                .runner
+                .driver
+                .git_contents(url, git_ref, submodules)
+                .map_err(|err| self.cx.nid_err(self.nid, err))?;
+            return Ok(Value::FileArea(Arc::new(area)));
+        }
+        let (url, git_ref) = (url.to_owned(), git_ref.to_owned());
+        self.git_ref_contents(&url, &git_ref, submodules)
+    }
+
+    /// Contents of the commit a ref points at
+    ///
+    /// The commit is recorded in the cache entry and rechecked once it expires, if it hasn't moved the cached contents are reused
+    fn git_ref_contents(self, url: &str, git_ref: &str, submodules: bool) -> ResultValue {
+        // The ref can move so calls can't be cached by their args, the ref cache entry below takes their place
+        self.deps.push(Dep::Uncacheable);
+        let start = Instant::now();
+        let cache_key = CacheKey::GitRef {
+            url: url.to_owned(),
+            git_ref: git_ref.to_owned(),
+            submodules,
+        };
+        let _call = enter_call(self.runner.driver, format!("Git {url} {git_ref}"));
+        let cache_entry = self.runner.cache.get(&cache_key);
+        if let Some(cache_entry) = &cache_entry {
+            if cache_entry
+                .expires
+                .is_none_or(|expires| expires > Utc::now())
+                || self.runner.offline
+            {
+                log::debug!("Git ref cache hit, not expired");
+                return Ok(cache_entry.value.clone());
+            }
+        }
+        if self.runner.offline {
+            return Err(self.cx.nid_err(
+                self.nid,
+                RunnerError::Makeshift(
+                    "offline mode: cannot resolve git ref it is not in cache".into(),
+                ),
+            ));
+        }
+        let commit = self
+            .runner
+            .driver
+            .git_resolve_ref(url, git_ref)
+            .map_err(|err| self.cx.nid_err(self.nid, err))?;
+        if let Some(mut cache_entry) = cache_entry {
+            if cache_entry.etag.as_deref() == Some(commit.as_bytes()) {
+                log::debug!("Git ref has not moved from {commit}");
+                cache_entry.expires = Some(Utc::now() + chrono::TimeDelta::hours(1));
+                let value = cache_entry.value.clone();
+                self.runner.cache.put(cache_key, cache_entry);
+                return Ok(value);
+            }
+        }
+        let area = self
+            .runner
+            .driver
+            .git_contents(url, &commit, submodules)
+            .map_err(|err| self.cx.nid_err(self.nid, err))?;
+        let out = Value::FileArea(Arc::new(area));
+        self.runner.cache.put(
+            cache_key,
+            CacheEntry {
+                execution_time: start.elapsed(),
+                etag: Some(commit.into_bytes()),
+                expires: Some(Utc::now() + chrono::TimeDelta::hours(1)),
+                deps: DepList::new(),
+                value: out.clone(),
+            },
+        );
+        Ok(out)
+    }
+
+    fn git_submodules_option(&self, options_nid: NodeId, options_value: &Value) -> Result<bool> {
+        let Value::Record(options) = options_value else {
+            return Err(self.cx.nid_err(
+                options_nid,
+                RunnerError::ExpectedType {
+                    actual: options_value.rain_type_id(),
+                    expected: Cow::Borrowed(&[RainTypeId::Record]),
+                },
+            ));
+        };
+        let mut submodules = false;
+        for (key, value) in &options.0 {
+            match (key.as_str(), value) {
+                ("submodules", Value::Boolean(b)) => submodules = *b,
+                ("submodules", _) => {
+                    return Err(self.cx.nid_err(
+                        options_nid,
+                        RunnerError::ExpectedType {
+                            actual: value.rain_type_id(),
+                            expected: Cow::Borrowed(&[RainTypeId::Boolean]),
+                        },
+                    ));
+                }
+                _ => {
+                    return Err(self.cx.nid_err(
+                        options_nid,
+                        RunnerError::Makeshift(format!("unknown git option {key}").into()),
+                    ));
+                }
+            }
+        }
+        Ok(submodules)
+    }
+}
+
+/// Full commit hashes are used as they are, anything else is resolved as a ref
+fn is_commit_hash(s: &str) -> bool {
+    s.len() == 40 && s.bytes().all(|b| b.is_ascii_hexdigit())
+}
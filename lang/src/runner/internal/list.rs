@@ -0,0 +1,301 @@
+use std::{borrow::Cow, cmp::Ordering, collections::HashSet, sync::Arc};
+
+use num_bigint::BigInt;
+use num_traits::FromPrimitive as _;
+
+use crate::{
+    ast::NodeId,
+    driver::DriverTrait,
+    runner::{
+        Result, ResultValue,
+        cache::CacheTrait,
+        error::RunnerError,
+        value::{RainInteger, RainList, RainTypeId, Value},
+    },
+};
+
+use super::InternalCx;
+
+impl<Driver: DriverTrait, Cache: CacheTrait> InternalCx<'_, '_, '_, Driver, Cache> {
+    pub fn list_length(self) -> ResultValue {
+        let [list] = &self.arg_values[..] else {
+            return self.incorrect_args(1..=1);
+        };
+        let list = self.expect_list(list)?;
+        Ok(integer_value(BigInt::from(list.0.len())))
+    }
+
+    /// Integers from `start` up to but not including `end`
+    pub fn list_range(self) -> ResultValue {
+        let [(start_nid, start_value), (end_nid, end_value)] = &self.arg_values[..] else {
+            return self.incorrect_args(2..=2);
+        };
+        let (Value::Integer(start), Value::Integer(end)) = (start_value, end_value) else {
+            let (nid, value) = if matches!(start_value, Value::Integer(_)) {
+                (end_nid, end_value)
+            } else {
+                (start_nid, start_value)
+            };
+            return Err(self.cx.nid_err(
+                *nid,
+                RunnerError::ExpectedType {
+                    actual: value.rain_type_id(),
+                    expected: Cow::Borrowed(&[RainTypeId::Integer]),
+                },
+            ));
+        };
+        let mut out = Vec::new();
+        let mut i = start.0.clone();
+        while i < end.0 {
+            out.push(integer_value(i.clone()));
+            i += 1;
+        }
+        Ok(list_value(out))
+    }
+
+    /// Elements between the `start` and `end` indices, `end` defaults to the length
+    pub fn list_slice(self) -> ResultValue {
+        let (list, start, end) = match &self.arg_values[..] {
+            [list, start] => (list, start, None),
+            [list, start, end] => (list, start, Some(end)),
+            _ => return self.incorrect_args(2..=3),
+        };
+        let list = self.expect_list(list)?;
+        let range = self.expect_slice_range(start, end, list.0.len())?;
+        Ok(list_value(list.0[range].to_vec()))
+    }
+
+    pub fn list_concat(self) -> ResultValue {
+        let mut out = Vec::new();
+        for list in &self.arg_values {
+            out.extend(self.expect_list(list)?.0.iter().cloned());
+        }
+        Ok(list_value(out))
+    }
+
+    /// Concatenate a list of lists
+    pub fn list_flatten(self) -> ResultValue {
+        let [list] = &self.arg_values[..] else {
+            return self.incorrect_args(1..=1);
+        };
+        let nid = list.0;
+        let mut out = Vec::new();
+        for inner in &self.expect_list(list)?.0 {
+            out.extend(self.expect_list(&(nid, inner.clone()))?.0.iter().cloned());
+        }
+        Ok(list_value(out))
+    }
+
+    pub fn list_map(mut self) -> ResultValue {
+        let [list, func] = &self.arg_values[..] else {
+            return self.incorrect_args(2..=2);
+        };
+        let list = self.expect_list(list)?;
+        let func = func.clone();
+        let mut out = Vec::with_capacity(list.0.len());
+        for item in &list.0 {
+            out.push(self.call_callback(&func, vec![item.clone()])?);
+        }
+        Ok(list_value(out))
+    }
+
+    pub fn list_filter(mut self) -> ResultValue {
+        let [list, func] = &self.arg_values[..] else {
+            return self.incorrect_args(2..=2);
+        };
+        let list = self.expect_list(list)?;
+        let func = func.clone();
+        let mut out = Vec::new();
+        for item in &list.0 {
+            if self.call_predicate(&func, vec![item.clone()])? {
+                out.push(item.clone());
+            }
+        }
+        Ok(list_value(out))
+    }
+
+    pub fn list_any(self) -> ResultValue {
+        self.list_any_all(true)
+    }
+
+    pub fn list_all(self) -> ResultValue {
+        self.list_any_all(false)
+    }
+
+    /// Short circuits on the first element that is `stop_on`, the elements are booleans unless a predicate is given
+    fn list_any_all(mut self, stop_on: bool) -> ResultValue {
+        let (list, func) = match &self.arg_values[..] {
+            [list] => (list, None),
+            [list, func] => (list, Some(func.clone())),
+            _ => return self.incorrect_args(1..=2),
+        };
+        let nid = list.0;
+        let list = self.expect_list(list)?;
+        for item in &list.0 {
+            let b = match &func {
+                Some(func) => self.call_predicate(func, vec![item.clone()])?,
+                None => self.expect_bool(nid, item)?,
+            };
+            if b == stop_on {
+                return Ok(Value::Boolean(stop_on));
+            }
+        }
+        Ok(Value::Boolean(!stop_on))
+    }
+
+    /// Stable sort by the elements or by the result of the key function
+    pub fn list_sort(mut self) -> ResultValue {
+        let (list, key) = match &self.arg_values[..] {
+            [list] => (list, None),
+            [list, key] => (list, Some(key.clone())),
+            _ => return self.incorrect_args(1..=2),
+        };
+        let list = self.expect_list(list)?;
+        let mut keyed = Vec::with_capacity(list.0.len());
+        for item in &list.0 {
+            let k = match &key {
+                Some(key) => self.call_callback(key, vec![item.clone()])?,
+                None => item.clone(),
+            };
+            keyed.push((k, item.clone()));
+        }
+        // Sorting with an order that isn't total can panic so check every key can be compared first
+        if let Some((first, _)) = keyed.first() {
+            for (k, _) in &keyed {
+                if matches!(k, Value::Float(f) if f.0.is_nan()) {
+                    return Err(self.cx.nid_err(
+                        self.nid,
+                        RunnerError::Makeshift("cannot sort by NaN".into()),
+                    ));
+                }
+                if sort_kind(k).is_none() || sort_kind(k) != sort_kind(first) {
+                    return Err(self.cx.nid_err(
+                        self.nid,
+                        RunnerError::Makeshift(
+                            format!(
+                                "cannot compare {} and {}",
+                                first.rain_type_id(),
+                                k.rain_type_id()
+                            )
+                            .into(),
+                        ),
+                    ));
+                }
+            }
+        }
+        keyed.sort_by(|(a, _), (b, _)| compare_keys(a, b));
+        Ok(list_value(keyed.into_iter().map(|(_, v)| v).collect()))
+    }
+
+    /// Remove duplicates keeping the first occurrence
+    pub fn list_unique(self) -> ResultValue {
+        let [list] = &self.arg_values[..] else {
+            return self.incorrect_args(1..=1);
+        };
+        let list = self.expect_list(list)?;
+        let mut seen = HashSet::new();
+        Ok(list_value(
+            list.0.iter().filter(|v| seen.insert(*v)).cloned().collect(),
+        ))
+    }
+
+    /// Pairs of elements at the same index, stops at the end of the shorter list
+    pub fn list_zip(self) -> ResultValue {
+        let [a, b] = &self.arg_values[..] else {
+            return self.incorrect_args(2..=2);
+        };
+        let a = self.expect_list(a)?;
+        let b = self.expect_list(b)?;
+        Ok(list_value(
+            a.0.iter()
+                .zip(&b.0)
+                .map(|(a, b)| list_value(vec![a.clone(), b.clone()]))
+                .collect(),
+        ))
+    }
+
+    /// Pairs of the index and element
+    pub fn list_enumerate(self) -> ResultValue {
+        let [list] = &self.arg_values[..] else {
+            return self.incorrect_args(1..=1);
+        };
+        let list = self.expect_list(list)?;
+        Ok(list_value(
+            list.0
+                .iter()
+                .enumerate()
+                .map(|(i, v)| list_value(vec![integer_value(BigInt::from(i)), v.clone()]))
+                .collect(),
+        ))
+    }
+
+    fn expect_list(&self, (nid, value): &(NodeId, Value)) -> Result<Arc<RainList>> {
+        let Value::List(list) = value else {
+            return Err(self.cx.nid_err(
+                *nid,
+                RunnerError::ExpectedType {
+                    actual: value.rain_type_id(),
+                    expected: Cow::Borrowed(&[RainTypeId::List]),
+                },
+            ));
+        };
+        Ok(Arc::clone(list))
+    }
+}
+
+/// Ordering of values of the same type, integers and floats can be compared with each other
+/// Kinds of sort keys that can be compared with each other
+#[derive(PartialEq, Eq)]
+enum SortKind {
+    Number,
+    String,
+    Boolean,
+}
+
+fn sort_kind(v: &Value) -> Option<SortKind> {
+    match v {
+        Value::Integer(_) | Value::Float(_) => Some(SortKind::Number),
+        Value::String(_) => Some(SortKind::String),
+        Value::Boolean(_) => Some(SortKind::Boolean),
+        _ => None,
+    }
+}
+
+/// Total order of keys of the same [`SortKind`] that aren't NaN, integers and floats are compared exactly
+fn compare_keys(a: &Value, b: &Value) -> Ordering {
+    match (a, b) {
+        (Value::Integer(a), Value::Integer(b)) => a.0.cmp(&b.0),
+        (Value::Float(a), Value::Float(b)) => a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal),
+        (Value::Integer(a), Value::Float(b)) => compare_int_float(&a.0, b.0),
+        (Value::Float(a), Value::Integer(b)) => compare_int_float(&b.0, a.0).reverse(),
+        (Value::String(a), Value::String(b)) => a.cmp(b),
+        (Value::Boolean(a), Value::Boolean(b)) => a.cmp(b),
+        _ => Ordering::Equal,
+    }
+}
+
+/// Compare without rounding the integer to a float, going through [`f64`] would make large integers equal to floats they aren't
+fn compare_int_float(i: &BigInt, f: f64) -> Ordering {
+    let floor = f.floor();
+    let Some(floor_int) = BigInt::from_f64(floor) else {
+        // Only infinities have no integer floor
+        return if f > 0.0 {
+            Ordering::Less
+        } else {
+            Ordering::Greater
+        };
+    };
+    i.cmp(&floor_int).then(if f > floor {
+        Ordering::Less
+    } else {
+        Ordering::Equal
+    })
+}
+
+fn integer_value(i: BigInt) -> Value {
+    Value::Integer(Arc::new(RainInteger(i)))
+}
+
+fn list_value(values: Vec<Value>) -> Value {
+    Value::List(Arc::new(RainList(values)))
+}
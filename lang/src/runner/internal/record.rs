@@ -0,0 +1,150 @@
+use std::{borrow::Cow, sync::Arc};
+
+use indexmap::IndexMap;
+
+use crate::{
+    ast::NodeId,
+    driver::DriverTrait,
+    runner::{
+        Result, ResultValue,
+        cache::CacheTrait,
+        error::RunnerError,
+        value::{RainList, RainRecord, RainTypeId, Value},
+    },
+};
+
+use super::InternalCx;
+
+/// How conflicting values are resolved when deep merging records
+#[derive(Clone, Copy)]
+enum MergeStrategy {
+    /// Values from the right record replace values from the left
+    Override,
+    /// Values from the left record are kept
+    Keep,
+    /// Lists are concatenated, other values from the right record replace values from the left
+    Append,
+}
+
+impl<Driver: DriverTrait, Cache: CacheTrait> InternalCx<'_, '_, '_, Driver, Cache> {
+    pub fn record_has_key(self) -> ResultValue {
+        let [record, key] = &self.arg_values[..] else {
+            return self.incorrect_args(2..=2);
+        };
+        let record = self.expect_record(record)?;
+        let key = self.expect_string(key)?;
+        Ok(Value::Boolean(record.0.contains_key(key)))
+    }
+
+    /// Record without the given keys, keys that are not present are ignored
+    pub fn record_remove(self) -> ResultValue {
+        let [record, keys @ ..] = &self.arg_values[..] else {
+            return self.incorrect_args(1..=usize::MAX);
+        };
+        let mut out = self.expect_record(record)?.as_ref().clone();
+        for key in keys {
+            out.0.shift_remove(self.expect_string(key)?);
+        }
+        Ok(Value::Record(Arc::new(out)))
+    }
+
+    /// Value for `key` or `fallback` if the record does not have it
+    pub fn record_get(self) -> ResultValue {
+        let [record, key, (_, fallback)] = &self.arg_values[..] else {
+            return self.incorrect_args(3..=3);
+        };
+        let record = self.expect_record(record)?;
+        let key = self.expect_string(key)?;
+        Ok(record.0.get(key).unwrap_or(fallback).clone())
+    }
+
+    pub fn record_map_values(mut self) -> ResultValue {
+        let [record, func] = &self.arg_values[..] else {
+            return self.incorrect_args(2..=2);
+        };
+        let record = self.expect_record(record)?;
+        let func = func.clone();
+        let mut out = IndexMap::with_capacity(record.0.len());
+        for (k, v) in &record.0 {
+            out.insert(k.clone(), self.call_callback(&func, vec![v.clone()])?);
+        }
+        Ok(Value::Record(Arc::new(RainRecord(out))))
+    }
+
+    /// Entries for which the predicate called with the key and value returns true
+    pub fn record_filter(mut self) -> ResultValue {
+        let [record, func] = &self.arg_values[..] else {
+            return self.incorrect_args(2..=2);
+        };
+        let record = self.expect_record(record)?;
+        let func = func.clone();
+        let mut out = IndexMap::new();
+        for (k, v) in &record.0 {
+            let key = Value::String(Arc::new(k.clone()));
+            if self.call_predicate(&func, vec![key, v.clone()])? {
+                out.insert(k.clone(), v.clone());
+            }
+        }
+        Ok(Value::Record(Arc::new(RainRecord(out))))
+    }
+
+    /// Recursively merge nested records resolving other conflicts with the strategy
+    pub fn record_deep_merge(self) -> ResultValue {
+        let [a, b, strategy] = &self.arg_values[..] else {
+            return self.incorrect_args(3..=3);
+        };
+        let a = self.expect_record(a)?;
+        let b = self.expect_record(b)?;
+        let strategy = match self.expect_string(strategy)? {
+            "override" => MergeStrategy::Override,
+            "keep" => MergeStrategy::Keep,
+            "append" => MergeStrategy::Append,
+            s => {
+                return Err(self.cx.nid_err(
+                    strategy.0,
+                    RunnerError::Makeshift(
+                        format!(
+                            "unknown merge strategy {s:?}, expected \"override\", \"keep\" or \"append\""
+                        )
+                        .into(),
+                    ),
+                ));
+            }
+        };
+        Ok(Value::Record(Arc::new(deep_merge(&a, &b, strategy))))
+    }
+
+    fn expect_record(&self, (nid, value): &(NodeId, Value)) -> Result<Arc<RainRecord>> {
+        let Value::Record(record) = value else {
+            return Err(self.cx.nid_err(
+                *nid,
+                RunnerError::ExpectedType {
+                    actual: value.rain_type_id(),
+                    expected: Cow::Borrowed(&[RainTypeId::Record]),
+                },
+            ));
+        };
+        Ok(Arc::clone(record))
+    }
+}
+
+fn deep_merge(a: &RainRecord, b: &RainRecord, strategy: MergeStrategy) -> RainRecord {
+    let mut out = a.clone();
+    for (k, right) in &b.0 {
+        let Some(left) = out.0.get_mut(k) else {
+            out.0.insert(k.clone(), right.clone());
+            continue;
+        };
+        *left = match (&*left, right, strategy) {
+            (Value::Record(l), Value::Record(r), _) => {
+                Value::Record(Arc::new(deep_merge(l, r, strategy)))
+            }
+            (Value::List(l), Value::List(r), MergeStrategy::Append) => Value::List(Arc::new(
+                RainList(l.0.iter().chain(&r.0).cloned().collect()),
+            )),
+            (_, _, MergeStrategy::Keep) => continue,
+            (_, right, _) => right.clone(),
+        };
+    }
+    out
+}
@@ -1,13 +1,13 @@
 #![allow(clippy::unnecessary_wraps, clippy::needless_pass_by_value)]
 
-use std::{borrow::Cow, collections::HashMap, sync::Arc};
+use std::{borrow::Cow, collections::HashMap, sync::Arc, time::Duration};
 
 use indexmap::IndexMap;
 
 use crate::{
     afs::{dir::Dir, entry::FSEntryTrait as _},
     ast::NodeId,
-    driver::{DriverTrait, RunOptions},
+    driver::{DriverTrait, ResourceLimits, RunOptions},
     runner::{cache::CacheTrait, dep::Dep},
 };
 
@@ -19,102 +19,197 @@ use crate::runner::{
 
 use super::{InternalCx, enter_call};
 
+/// Options given to `_run` in its optional options record
+#[derive(Default)]
+struct RecordRunOptions {
+    network: bool,
+    timeout: Option<Duration>,
+    limits: ResourceLimits,
+}
+
 impl<Driver: DriverTrait, Cache: CacheTrait> InternalCx<'_, '_, '_, Driver, Cache> {
+    #[expect(clippy::too_many_lines)]
     pub fn run(self) -> ResultValue {
-        match &self.arg_values[..] {
-            [
-                (area_nid, area_value),
-                (file_nid, file_value),
-                (args_nid, args_value),
-                (env_nid, env_value),
-            ] => {
-                let overlay_area = match area_value {
-                    Value::Unit => None,
-                    Value::FileArea(area) => Some(area.as_ref()),
-                    _ => Err(self.cx.nid_err(
-                        *area_nid,
-                        RunnerError::ExpectedType {
-                            actual: area_value.rain_type_id(),
-                            expected: Cow::Borrowed(&[RainTypeId::FileArea, RainTypeId::Unit]),
-                        },
-                    ))?,
-                };
-                let bin = match file_value {
-                    Value::File(file) => &self.runner.driver.resolve_fs_entry(file.inner()),
-                    Value::EscapeFile(escaped_file) => escaped_file.0.as_path(),
-                    _ => {
-                        return Err(self.cx.nid_err(
-                            *file_nid,
-                            RunnerError::ExpectedType {
-                                actual: file_value.rain_type_id(),
-                                expected: Cow::Borrowed(&[
-                                    RainTypeId::File,
-                                    RainTypeId::EscapeFile,
-                                ]),
-                            },
-                        ));
-                    }
-                };
-                let Value::List(args) = args_value else {
+        let (
+            (area_nid, area_value),
+            (file_nid, file_value),
+            (args_nid, args_value),
+            (env_nid, env_value),
+            options,
+        ) = match &self.arg_values[..] {
+            [area, file, args, env] => (area, file, args, env, None),
+            [area, file, args, env, options] => (area, file, args, env, Some(options)),
+            _ => return self.incorrect_args(4..=5),
+        };
+        let RecordRunOptions {
+            network,
+            timeout,
+            limits,
+        } = match options {
+            Some((options_nid, options_value)) => self.run_options(*options_nid, options_value)?,
+            None => RecordRunOptions::default(),
+        };
+        let input_areas = [area_value, file_value, args_value, env_value]
+            .iter()
+            .flat_map(|v| v.find_areas())
+            .cloned()
+            .collect();
+        let overlay_area = match area_value {
+            Value::Unit => None,
+            Value::FileArea(area) => Some(area.as_ref()),
+            _ => Err(self.cx.nid_err(
+                *area_nid,
+                RunnerError::ExpectedType {
+                    actual: area_value.rain_type_id(),
+                    expected: Cow::Borrowed(&[RainTypeId::FileArea, RainTypeId::Unit]),
+                },
+            ))?,
+        };
+        let bin = match file_value {
+            Value::File(file) => &self.runner.driver.resolve_fs_entry(file.inner()),
+            Value::EscapeFile(escaped_file) => escaped_file.0.as_path(),
+            _ => {
+                return Err(self.cx.nid_err(
+                    *file_nid,
+                    RunnerError::ExpectedType {
+                        actual: file_value.rain_type_id(),
+                        expected: Cow::Borrowed(&[RainTypeId::File, RainTypeId::EscapeFile]),
+                    },
+                ));
+            }
+        };
+        let Value::List(args) = args_value else {
+            return Err(self.cx.nid_err(
+                *args_nid,
+                RunnerError::ExpectedType {
+                    actual: args_value.rain_type_id(),
+                    expected: Cow::Borrowed(&[RainTypeId::List]),
+                },
+            ));
+        };
+        let args = args
+            .0
+            .iter()
+            .map(|value| self.stringify_args(*args_nid, value))
+            .collect::<Result<Vec<String>>>()?;
+        let Value::Record(env) = env_value else {
+            return Err(self.cx.nid_err(
+                *env_nid,
+                RunnerError::ExpectedType {
+                    actual: env_value.rain_type_id(),
+                    expected: Cow::Borrowed(&[RainTypeId::List]),
+                },
+            ));
+        };
+        let env = env
+            .0
+            .iter()
+            .map(|(key, value)| self.stringify_env(*env_nid, key, value))
+            .collect::<Result<HashMap<String, String>>>()?;
+
+        let display_args = args.join(" ");
+        let call = format!("Run {} {display_args}", bin.display());
+        let _call = enter_call(self.runner.driver, call.clone());
+        let status = self
+            .runner
+            .driver
+            .run(
+                overlay_area,
+                bin,
+                args,
+                RunOptions {
+                    call,
+                    inherit_env: false,
+                    env,
+                    network,
+                    input_areas,
+                    timeout,
+                    limits,
+                },
+            )
+            .map_err(|err| self.cx.nid_err(self.nid, err))?;
+        let mut m = IndexMap::new();
+        m.insert("success".to_owned(), Value::Boolean(status.success));
+        m.insert(
+            "exit_code".to_owned(),
+            Value::Integer(Arc::new(RainInteger(status.exit_code.unwrap_or(-1).into()))),
+        );
+        m.insert("area".to_owned(), Value::FileArea(Arc::new(status.area)));
+        m.insert("stdout".to_owned(), Value::String(Arc::new(status.stdout)));
+        m.insert("stderr".to_owned(), Value::String(Arc::new(status.stderr)));
+        m.insert(
+            "stdout_file".to_owned(),
+            Value::File(Arc::new(status.stdout_file)),
+        );
+        m.insert(
+            "stderr_file".to_owned(),
+            Value::File(Arc::new(status.stderr_file)),
+        );
+        Ok(Value::Record(Arc::new(RainRecord(m))))
+    }
+
+    fn run_options(&self, options_nid: NodeId, options_value: &Value) -> Result<RecordRunOptions> {
+        let Value::Record(options) = options_value else {
+            return Err(self.cx.nid_err(
+                options_nid,
+                RunnerError::ExpectedType {
+                    actual: options_value.rain_type_id(),
+                    expected: Cow::Borrowed(&[RainTypeId::Record]),
+                },
+            ));
+        };
+        let mut run_options = RecordRunOptions::default();
+        for (key, value) in &options.0 {
+            match (key.as_str(), value) {
+                ("network", Value::Boolean(b)) => run_options.network = *b,
+                ("timeout", Value::Integer(i)) => {
+                    let secs = self.run_option_u64(options_nid, key, i)?;
+                    run_options.timeout = Some(Duration::from_secs(secs));
+                }
+                ("memory_limit", Value::Integer(i)) => {
+                    run_options.limits.memory = Some(self.run_option_u64(options_nid, key, i)?);
+                }
+                ("cpu_limit", Value::Integer(i)) => {
+                    run_options.limits.cpu = Some(self.run_option_u64(options_nid, key, i)?);
+                }
+                ("network", _) => {
                     return Err(self.cx.nid_err(
-                        *args_nid,
+                        options_nid,
                         RunnerError::ExpectedType {
-                            actual: args_value.rain_type_id(),
-                            expected: Cow::Borrowed(&[RainTypeId::List]),
+                            actual: value.rain_type_id(),
+                            expected: Cow::Borrowed(&[RainTypeId::Boolean]),
                         },
                     ));
-                };
-                let args = args
-                    .0
-                    .iter()
-                    .map(|value| self.stringify_args(*args_nid, value))
-                    .collect::<Result<Vec<String>>>()?;
-                let Value::Record(env) = env_value else {
+                }
+                ("timeout" | "memory_limit" | "cpu_limit", _) => {
                     return Err(self.cx.nid_err(
-                        *env_nid,
+                        options_nid,
                         RunnerError::ExpectedType {
-                            actual: env_value.rain_type_id(),
-                            expected: Cow::Borrowed(&[RainTypeId::List]),
+                            actual: value.rain_type_id(),
+                            expected: Cow::Borrowed(&[RainTypeId::Integer]),
                         },
                     ));
-                };
-                let env = env
-                    .0
-                    .iter()
-                    .map(|(key, value)| self.stringify_env(*env_nid, key, value))
-                    .collect::<Result<HashMap<String, String>>>()?;
-
-                let display_args = args.join(" ");
-                let _call = enter_call(
-                    self.runner.driver,
-                    format!("Run {} {display_args}", bin.display()),
-                );
-                let status = self
-                    .runner
-                    .driver
-                    .run(
-                        overlay_area,
-                        bin,
-                        args,
-                        RunOptions {
-                            inherit_env: false,
-                            env,
-                        },
-                    )
-                    .map_err(|err| self.cx.nid_err(self.nid, err))?;
-                let mut m = IndexMap::new();
-                m.insert("success".to_owned(), Value::Boolean(status.success));
-                m.insert(
-                    "exit_code".to_owned(),
-                    Value::Integer(Arc::new(RainInteger(status.exit_code.unwrap_or(-1).into()))),
-                );
-                m.insert("area".to_owned(), Value::FileArea(Arc::new(status.area)));
-                m.insert("stdout".to_owned(), Value::String(Arc::new(status.stdout)));
-                m.insert("stderr".to_owned(), Value::String(Arc::new(status.stderr)));
-                Ok(Value::Record(Arc::new(RainRecord(m))))
+                }
+                _ => {
+                    return Err(self.cx.nid_err(
+                        options_nid,
+                        RunnerError::Makeshift(format!("unknown run option {key}").into()),
+                    ));
+                }
             }
-            _ => self.incorrect_args(4..=4),
         }
+        Ok(run_options)
+    }
+
+    fn run_option_u64(&self, options_nid: NodeId, key: &str, i: &RainInteger) -> Result<u64> {
+        (&i.0).try_into().map_err(|_| {
+            self.cx.nid_err(
+                options_nid,
+                RunnerError::Makeshift(
+                    format!("run option {key} must be a positive integer").into(),
+                ),
+            )
+        })
     }
 
     pub fn escape_run(self) -> ResultValue {
@@ -173,10 +268,8 @@ impl<Driver: DriverTrait, Cache: CacheTrait> InternalCx<'_, '_, '_, Driver, Cach
                     .map(|(key, value)| self.stringify_env(*env_nid, key, value))
                     .collect::<Result<HashMap<String, String>>>()?;
                 let display_args = args.join(" ");
-                let _call = enter_call(
-                    self.runner.driver,
-                    format!("Run {} {display_args}", bin.display()),
-                );
+                let call = format!("Run {} {display_args}", bin.display());
+                let _call = enter_call(self.runner.driver, call.clone());
                 let status = self
                     .runner
                     .driver
@@ -185,8 +278,13 @@ impl<Driver: DriverTrait, Cache: CacheTrait> InternalCx<'_, '_, '_, Driver, Cach
                         bin,
                         args,
                         RunOptions {
+                            call,
                             inherit_env: true,
                             env,
+                            network: true,
+                            input_areas: Vec::new(),
+                            timeout: None,
+                            limits: ResourceLimits::default(),
                         },
                     )
                     .map_err(|err| self.cx.nid_err(self.nid, err))?;
@@ -198,6 +296,14 @@ impl<Driver: DriverTrait, Cache: CacheTrait> InternalCx<'_, '_, '_, Driver, Cach
                 );
                 m.insert("stdout".to_owned(), Value::String(Arc::new(status.stdout)));
                 m.insert("stderr".to_owned(), Value::String(Arc::new(status.stderr)));
+                m.insert(
+                    "stdout_file".to_owned(),
+                    Value::File(Arc::new(status.stdout_file)),
+                );
+                m.insert(
+                    "stderr_file".to_owned(),
+                    Value::File(Arc::new(status.stderr_file)),
+                );
                 Ok(Value::Record(Arc::new(RainRecord(m))))
             }
             _ => self.incorrect_args(4..=4),
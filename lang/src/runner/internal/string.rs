@@ -0,0 +1,165 @@
+use std::{borrow::Cow, sync::Arc};
+
+use num_bigint::BigInt;
+
+use crate::{
+    ast::NodeId,
+    driver::DriverTrait,
+    runner::{
+        Result, ResultValue,
+        cache::CacheTrait,
+        error::RunnerError,
+        value::{RainInteger, RainList, RainTypeId, Value},
+    },
+};
+
+use super::InternalCx;
+
+impl<Driver: DriverTrait, Cache: CacheTrait> InternalCx<'_, '_, '_, Driver, Cache> {
+    pub fn string_trim(self) -> ResultValue {
+        let [s] = &self.arg_values[..] else {
+            return self.incorrect_args(1..=1);
+        };
+        Ok(string_value(self.expect_string(s)?.trim()))
+    }
+
+    pub fn string_replace(self) -> ResultValue {
+        let [s, from, to] = &self.arg_values[..] else {
+            return self.incorrect_args(3..=3);
+        };
+        let s = self.expect_string(s)?;
+        let from = self.expect_string(from)?;
+        let to = self.expect_string(to)?;
+        Ok(string_value(&s.replace(from, to)))
+    }
+
+    pub fn string_starts_with(self) -> ResultValue {
+        let [s, prefix] = &self.arg_values[..] else {
+            return self.incorrect_args(2..=2);
+        };
+        let s = self.expect_string(s)?;
+        let prefix = self.expect_string(prefix)?;
+        Ok(Value::Boolean(s.starts_with(prefix)))
+    }
+
+    pub fn string_ends_with(self) -> ResultValue {
+        let [s, suffix] = &self.arg_values[..] else {
+            return self.incorrect_args(2..=2);
+        };
+        let s = self.expect_string(s)?;
+        let suffix = self.expect_string(suffix)?;
+        Ok(Value::Boolean(s.ends_with(suffix)))
+    }
+
+    pub fn string_join(self) -> ResultValue {
+        let [(list_nid, list_value), sep] = &self.arg_values[..] else {
+            return self.incorrect_args(2..=2);
+        };
+        let sep = self.expect_string(sep)?;
+        let Value::List(list) = list_value else {
+            return Err(self.cx.nid_err(
+                *list_nid,
+                RunnerError::ExpectedType {
+                    actual: list_value.rain_type_id(),
+                    expected: Cow::Borrowed(&[RainTypeId::List]),
+                },
+            ));
+        };
+        let mut out = String::new();
+        for (i, v) in list.0.iter().enumerate() {
+            let Value::String(part) = v else {
+                return Err(self.cx.nid_err(
+                    *list_nid,
+                    RunnerError::ExpectedType {
+                        actual: v.rain_type_id(),
+                        expected: Cow::Borrowed(&[RainTypeId::String]),
+                    },
+                ));
+            };
+            if i > 0 {
+                out.push_str(sep);
+            }
+            out.push_str(part);
+        }
+        Ok(Value::String(Arc::new(out)))
+    }
+
+    pub fn string_to_upper(self) -> ResultValue {
+        let [s] = &self.arg_values[..] else {
+            return self.incorrect_args(1..=1);
+        };
+        Ok(string_value(&self.expect_string(s)?.to_uppercase()))
+    }
+
+    pub fn string_to_lower(self) -> ResultValue {
+        let [s] = &self.arg_values[..] else {
+            return self.incorrect_args(1..=1);
+        };
+        Ok(string_value(&self.expect_string(s)?.to_lowercase()))
+    }
+
+    /// Length in characters rather than bytes
+    pub fn string_length(self) -> ResultValue {
+        let [s] = &self.arg_values[..] else {
+            return self.incorrect_args(1..=1);
+        };
+        let length = self.expect_string(s)?.chars().count();
+        Ok(Value::Integer(Arc::new(RainInteger(BigInt::from(length)))))
+    }
+
+    /// Substring between the `start` and `end` character indices, `end` defaults to the length
+    pub fn string_slice(self) -> ResultValue {
+        let (s, start, end) = match &self.arg_values[..] {
+            [s, start] => (s, start, None),
+            [s, start, end] => (s, start, Some(end)),
+            _ => return self.incorrect_args(2..=3),
+        };
+        let s = self.expect_string(s)?;
+        let range = self.expect_slice_range(start, end, s.chars().count())?;
+        Ok(string_value(
+            &s.chars()
+                .skip(range.start)
+                .take(range.len())
+                .collect::<String>(),
+        ))
+    }
+
+    pub fn regex_match(self) -> ResultValue {
+        let [s, pattern] = &self.arg_values[..] else {
+            return self.incorrect_args(2..=2);
+        };
+        let s = self.expect_string(s)?;
+        let regex = self.expect_regex(pattern)?;
+        Ok(Value::Boolean(regex.is_match(s)))
+    }
+
+    /// Capture groups of the first match, the first element is the whole match and groups that did not participate are unit
+    ///
+    /// Returns unit if there is no match
+    pub fn regex_captures(self) -> ResultValue {
+        let [s, pattern] = &self.arg_values[..] else {
+            return self.incorrect_args(2..=2);
+        };
+        let s = self.expect_string(s)?;
+        let regex = self.expect_regex(pattern)?;
+        let Some(captures) = regex.captures(s) else {
+            return Ok(Value::Unit);
+        };
+        Ok(Value::List(Arc::new(RainList(
+            captures
+                .iter()
+                .map(|m| m.map_or(Value::Unit, |m| string_value(m.as_str())))
+                .collect(),
+        ))))
+    }
+
+    fn expect_regex(&self, pattern: &(NodeId, Value)) -> Result<regex::Regex> {
+        let nid = pattern.0;
+        regex::Regex::new(self.expect_string(pattern)?)
+            .map_err(|err| self.cx.nid_err(nid, RunnerError::InvalidRegex(err)))
+    }
+}
+
+fn string_value(s: &str) -> Value {
+    Value::String(Arc::new(s.to_owned()))
+}
@@ -4,6 +4,7 @@ pub mod dep;
 pub mod dep_list;
 pub mod error;
 pub mod internal;
+pub mod parallel;
 pub mod value;
 
 use std::{
@@ -17,12 +18,14 @@ use error::{ErrorTrace, RunnerError, Throwing};
 use indexmap::IndexMap;
 use internal::InternalFunction;
 use regex::Regex;
-use value::{RainInteger, RainList, RainRecord, RainTypeId, Value};
+use value::{RainFloat, RainInteger, RainList, RainRecord, RainTypeId, Value};
 
 use crate::{
     ast::{
-        AlternateCondition, BinaryOp, BinaryOperatorKind, DeclareName, FnCall, IfCondition, Node,
-        NodeId, Not, SimpleLiteral, SimpleLiteralKind,
+        AlternateCondition, Assignment, BinaryOp, BinaryOperatorKind, Comprehension,
+        ComprehensionBody, DeclareName, FnCall, FormatString, FormatStringPart, IfCondition, Match,
+        MatchArm, MatchPattern, Node, NodeId, Not, Record, RecordKey, SimpleLiteral,
+        SimpleLiteralKind, TryCatch,
     },
     driver::DriverTrait,
     ir::{DeclarationId, Rir},
@@ -31,6 +34,7 @@ use crate::{
         cache::{CacheKey, CacheTrait},
         cx::{Cx, StacktraceEntry},
         dep_list::DepList,
+        parallel::{CancelToken, PrintSink, WorkerPool},
         value::Closure,
     },
 };
@@ -39,16 +43,20 @@ type ResultValue = Result<Value>;
 type Result<T, E = ErrorTrace<Throwing>> = core::result::Result<T, E>;
 
 pub struct Runner<'a, Driver, Cache> {
-    pub ir: &'a mut Rir,
+    pub ir: &'a Rir,
     pub cache: &'a Cache,
     pub driver: &'a Driver,
     pub offline: bool,
     pub seal: bool,
     pub max_call_depth: usize,
+    pub workers: Arc<WorkerPool>,
+    print_sink: PrintSink,
+    /// Set for runners evaluating one of several parallel branches
+    cancel: Option<Arc<CancelToken>>,
 }
 
 impl<'a, Driver: DriverTrait, Cache: CacheTrait> Runner<'a, Driver, Cache> {
-    pub fn new(rir: &'a mut Rir, cache: &'a Cache, driver: &'a Driver) -> Self {
+    pub fn new(rir: &'a Rir, cache: &'a Cache, driver: &'a Driver) -> Self {
         Self {
             ir: rir,
             cache,
@@ -56,16 +64,23 @@ impl<'a, Driver: DriverTrait, Cache: CacheTrait> Runner<'a, Driver, Cache> {
             offline: false,
             seal: false,
             max_call_depth: 250,
+            workers: Arc::new(WorkerPool::sequential()),
+            print_sink: PrintSink::Driver,
+            cancel: None,
         }
     }
 
+    fn print(&self, message: String) {
+        self.print_sink.print(self.driver, message);
+    }
+
     pub fn evaluate_and_call(&mut self, id: DeclarationId, args: &[String]) -> ResultValue {
-        let m = Arc::clone(self.ir.get_module(id.module_id()));
+        let m = self.ir.get_module(id.module_id());
         let mut initial_cx = Cx::new(&m, 0, HashMap::new(), Vec::new());
         let v = self.evaluate_declaration(&mut initial_cx, id)?;
         match v {
             Value::Closure(closure) => {
-                let m = Arc::clone(self.ir.get_module(closure.module));
+                let m = self.ir.get_module(closure.module);
                 let Node::Closure(closure_declare) = m.get(closure.node) else {
                     unreachable!()
                 };
@@ -102,7 +117,7 @@ impl<'a, Driver: DriverTrait, Cache: CacheTrait> Runner<'a, Driver, Cache> {
     }
 
     pub fn evaluate_declaration(&mut self, cx: &mut Cx, id: DeclarationId) -> ResultValue {
-        let m = &Arc::clone(self.ir.get_module(id.module_id()));
+        let m = &self.ir.get_module(id.module_id());
         let declaration = m.get_declaration(id.local_id());
         if id.module_id() != cx.module.id && declaration.pub_token.is_none() {
             let span = m.get_declaration_name_span(id.local_id());
@@ -172,55 +187,34 @@ impl<'a, Driver: DriverTrait, Cache: CacheTrait> Runner<'a, Driver, Cache> {
 
     #[expect(clippy::too_many_lines)]
     fn evaluate_node(&mut self, cx: &mut Cx, nid: NodeId) -> ResultValue {
+        self.check_cancelled(cx, nid)?;
         match cx.module.get(nid) {
-            Node::Closure(_) => {
-                let mut captures = HashMap::<String, Value>::new();
-                for (k, v) in &cx.args {
-                    captures.insert(k.to_string(), v.clone());
-                }
-                for (k, v) in &cx.locals {
-                    captures.insert(k.to_string(), v.clone());
-                }
-                Ok(Value::Closure(Closure {
-                    captures: Arc::new(captures),
-                    module: cx.module.id,
-                    node: nid,
-                }))
-            }
+            Node::Closure(_) => Ok(Value::Closure(capture_scope(cx, nid))),
             Node::Block(block) => {
-                for nid in &block.statements {
+                let mut statements = &block.statements[..];
+                while let Some((nid, rest)) = statements.split_first() {
+                    let independent = self.independent_assignments(cx, statements);
+                    if independent > 1 {
+                        let (assignments, rest) = statements.split_at(independent);
+                        self.evaluate_independent_assignments(cx, assignments)?;
+                        cx.previous_line = Some(Value::Unit);
+                        statements = rest;
+                        continue;
+                    }
                     let v = self.evaluate_node(cx, *nid)?;
                     cx.previous_line = Some(v);
+                    statements = rest;
                 }
                 Ok(cx.previous_line.clone().unwrap_or(Value::Unit))
             }
             Node::IfCondition(if_condition) => self.evaluate_if_condition(cx, if_condition),
+            Node::TryCatch(try_catch) => self.evaluate_try_catch(cx, try_catch),
+            Node::Match(m) => self.evaluate_match(cx, m),
             Node::FnCall(fn_call) => self.evaluate_fn_call(cx, nid, fn_call),
             Node::Assignment(assignment) => {
                 let v = self.evaluate_node(cx, assignment.expr)?;
-                match &assignment.name {
-                    DeclareName::Single(declare_name_single) => {
-                        let name = declare_name_single.name.span.contents(&cx.module.src);
-                        // TODO: Type check
-                        cx.locals.insert(name, v);
-                        Ok(Value::Unit)
-                    }
-                    DeclareName::NamedDestructure(_) => {
-                        for name_span in assignment.name_spans() {
-                            let name = name_span.contents(&cx.module.src);
-                            let Some(value) = self.evaluate_named_index(cx, &v, name_span, name)?
-                            else {
-                                return Err(cx.nid_err(
-                                    assignment.expr,
-                                    RunnerError::IndexKeyNotFound(name.to_owned()),
-                                ));
-                            };
-                            // TODO: Type check
-                            cx.locals.insert(name, value);
-                        }
-                        Ok(Value::Unit)
-                    }
-                }
+                self.assign(cx, assignment, v)?;
+                Ok(Value::Unit)
             }
             Node::BinaryOp(binary_op) => self.evaluate_binary_op(cx, binary_op),
             Node::Ident(tls) => self
@@ -252,9 +246,7 @@ impl<'a, Driver: DriverTrait, Cache: CacheTrait> Runner<'a, Driver, Cache> {
             }) => self.this_file_sugar(cx, nid, tls.span),
             Node::StringLiteral(lit) => match lit.prefix() {
                 Some(crate::tokens::StringLiteralPrefix::Format) => {
-                    log::info!("{lit:?}");
-                    let contents = lit.content_span().contents(&cx.module.src);
-                    todo!("format strings not implemented: {contents}")
+                    unreachable!("format strings are parsed as FormatString")
                 }
                 Some(crate::tokens::StringLiteralPrefix::Raw) => {
                     let contents = lit.content_span().contents(&cx.module.src);
@@ -267,6 +259,7 @@ impl<'a, Driver: DriverTrait, Cache: CacheTrait> Runner<'a, Driver, Cache> {
                     ))))
                 }
             },
+            Node::FormatString(format_string) => self.evaluate_format_string(cx, format_string),
             Node::IntegerLiteral(tls) => Ok(Value::Integer(Arc::new(RainInteger(
                 tls.0
                     .span
@@ -274,21 +267,20 @@ impl<'a, Driver: DriverTrait, Cache: CacheTrait> Runner<'a, Driver, Cache> {
                     .parse::<num_bigint::BigInt>()
                     .map_err(|_| cx.err(tls.0, RunnerError::InvalidIntegerLiteral))?,
             )))),
-            Node::Record(record) => {
-                let mut builder = IndexMap::new();
-                for e in &record.fields {
-                    builder.insert(
-                        e.key.span.contents(&cx.module.src).to_owned(),
-                        self.evaluate_node(cx, e.value)?,
-                    );
-                }
-                Ok(Value::Record(Arc::new(RainRecord(builder))))
+            Node::FloatLiteral(tls) => Ok(Value::Float(RainFloat(
+                tls.0
+                    .span
+                    .contents(&cx.module.src)
+                    .parse::<f64>()
+                    .map_err(|_| cx.err(tls.0, RunnerError::InvalidFloatLiteral))?,
+            ))),
+            Node::Record(record) => self.evaluate_record(cx, record),
+            Node::Comprehension(comprehension) => {
+                self.evaluate_comprehension(cx, nid, comprehension)
             }
             Node::List(list) => {
-                let mut builder = Vec::new();
-                for e in &list.elements {
-                    builder.push(self.evaluate_node(cx, e.value)?);
-                }
+                let nids: Vec<NodeId> = list.elements.iter().map(|e| e.value).collect();
+                let builder = self.evaluate_nodes(cx, &nids)?;
                 Ok(Value::List(Arc::new(RainList(builder))))
             }
             Node::Not(Not { exclamation, inner }) => {
@@ -307,6 +299,55 @@ impl<'a, Driver: DriverTrait, Cache: CacheTrait> Runner<'a, Driver, Cache> {
         }
     }
 
+    fn assign(&mut self, cx: &mut Cx, assignment: &Assignment, v: Value) -> Result<()> {
+        match &assignment.name {
+            DeclareName::Single(declare_name_single) => {
+                let name = declare_name_single.name.span.contents(&cx.module.src);
+                // TODO: Type check
+                cx.locals.insert(name, v);
+                Ok(())
+            }
+            DeclareName::NamedDestructure(_) => {
+                for name_span in assignment.name_spans() {
+                    let name = name_span.contents(&cx.module.src);
+                    let Some(value) = self.evaluate_named_index(cx, &v, name_span, name)? else {
+                        return Err(cx.nid_err(
+                            assignment.expr,
+                            RunnerError::IndexKeyNotFound(name.to_owned()),
+                        ));
+                    };
+                    // TODO: Type check
+                    cx.locals.insert(name, value);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Evaluate assignments that don't depend on each other together then bind them in order
+    fn evaluate_independent_assignments(
+        &mut self,
+        cx: &mut Cx,
+        statements: &[NodeId],
+    ) -> Result<()> {
+        let module = cx.module;
+        let assignments: Vec<&Assignment> = statements
+            .iter()
+            .map(|nid| {
+                let Node::Assignment(assignment) = module.get(*nid) else {
+                    unreachable!("independent statements are always assignments")
+                };
+                assignment
+            })
+            .collect();
+        let exprs: Vec<NodeId> = assignments.iter().map(|a| a.expr).collect();
+        let values = self.evaluate_nodes(cx, &exprs)?;
+        for (assignment, v) in assignments.into_iter().zip(values) {
+            self.assign(cx, assignment, v)?;
+        }
+        Ok(())
+    }
+
     fn resolve_ident(&mut self, cx: &mut Cx, ident: &str) -> Result<Option<Value>> {
         if ident == "_" {
             return Ok(cx.previous_line.clone());
@@ -362,7 +403,7 @@ impl<'a, Driver: DriverTrait, Cache: CacheTrait> Runner<'a, Driver, Cache> {
         match &function_value {
             Value::Closure(closure) => {
                 let arg_values: Vec<_> = arg_values.into_iter().map(|(_, v)| v).collect();
-                let m = &Arc::clone(self.ir.get_module(closure.module));
+                let m = &self.ir.get_module(closure.module);
                 let Node::Closure(closure_declare) = m.get(closure.node) else {
                     unreachable!()
                 };
@@ -508,19 +549,33 @@ impl<'a, Driver: DriverTrait, Cache: CacheTrait> Runner<'a, Driver, Cache> {
         type_spec_nid: NodeId,
         type_spec_value: &Value,
     ) -> Result<(), ErrorTrace<Throwing>> {
+        if self.type_matches(cx, v, type_spec_nid, type_spec_value)? {
+            return Ok(());
+        }
+        let expected = match type_spec_value {
+            Value::Type(expected_type) => vec![*expected_type],
+            // FIXME: We have no way to know what types would work here :(
+            _ => vec![],
+        };
+        Err(cx.nid_err(
+            type_spec_nid,
+            RunnerError::ExpectedType {
+                actual: v.rain_type_id(),
+                expected: std::borrow::Cow::Owned(expected),
+            },
+        ))
+    }
+
+    /// Whether `v` is of the type given by a type spec, it is an error if the type spec is not a type or a closure returning a bool
+    fn type_matches(
+        &mut self,
+        cx: &mut Cx<'_>,
+        v: &Value,
+        type_spec_nid: NodeId,
+        type_spec_value: &Value,
+    ) -> Result<bool> {
         match type_spec_value {
-            Value::Type(expected_type) => {
-                if v.rain_type_id() != *expected_type {
-                    return Err(cx.nid_err(
-                        type_spec_nid,
-                        RunnerError::ExpectedType {
-                            actual: v.rain_type_id(),
-                            expected: std::borrow::Cow::Owned(vec![*expected_type]),
-                        },
-                    ));
-                }
-                Ok(())
-            }
+            Value::Type(expected_type) => Ok(v.rain_type_id() == *expected_type),
             Value::Closure(_) => {
                 let result = self.call_function(
                     cx,
@@ -530,19 +585,7 @@ impl<'a, Driver: DriverTrait, Cache: CacheTrait> Runner<'a, Driver, Cache> {
                     vec![(type_spec_nid, v.clone())],
                 )?;
                 match result {
-                    Value::Boolean(ok) => {
-                        if !ok {
-                            return Err(cx.nid_err(
-                                type_spec_nid,
-                                RunnerError::ExpectedType {
-                                    actual: v.rain_type_id(),
-                                    // FIXME: We have no way to know what types would work here :(
-                                    expected: std::borrow::Cow::Owned(vec![]),
-                                },
-                            ));
-                        }
-                        Ok(())
-                    }
+                    Value::Boolean(ok) => Ok(ok),
                     _ => Err(cx.nid_err(
                         type_spec_nid,
                         RunnerError::ExpectedType {
@@ -569,83 +612,12 @@ impl<'a, Driver: DriverTrait, Cache: CacheTrait> Runner<'a, Driver, Cache> {
             return self.evaluate_dot_operator(cx, op, &left);
         }
         let right = self.evaluate_node(cx, op.right)?;
-
-        match (left, op.op, right) {
-            (Value::String(left), BinaryOperatorKind::Addition, Value::String(right)) => {
-                Ok(Value::String(Arc::new(left.to_string() + &**right)))
-            }
-            (Value::List(left), BinaryOperatorKind::Addition, Value::List(right)) => {
-                let mut v = left.0.clone();
-                v.append(&mut right.0.clone());
-                Ok(Value::List(Arc::new(RainList(v))))
-            }
-            (Value::Integer(left), BinaryOperatorKind::Addition, Value::Integer(right)) => {
-                Ok(Value::Integer(Arc::new(RainInteger(&left.0 + &right.0))))
-            }
-            (Value::Integer(left), BinaryOperatorKind::Subtraction, Value::Integer(right)) => {
-                Ok(Value::Integer(Arc::new(RainInteger(&left.0 - &right.0))))
-            }
-            (Value::Integer(left), BinaryOperatorKind::Multiplication, Value::Integer(right)) => {
-                Ok(Value::Integer(Arc::new(RainInteger(&left.0 * &right.0))))
-            }
-            (Value::Integer(left), BinaryOperatorKind::Division, Value::Integer(right)) => {
-                Ok(Value::Integer(Arc::new(RainInteger(&left.0 / &right.0))))
-            }
-            (Value::Boolean(left), BinaryOperatorKind::LogicalAnd, Value::Boolean(right)) => {
-                Ok(Value::Boolean(left && right))
-            }
-            (Value::Boolean(left), BinaryOperatorKind::LogicalOr, Value::Boolean(right)) => {
-                Ok(Value::Boolean(left || right))
-            }
-            (Value::Integer(left), BinaryOperatorKind::Equals, Value::Integer(right)) => {
-                Ok(Value::Boolean(left.0 == right.0))
-            }
-            (Value::Integer(left), BinaryOperatorKind::NotEquals, Value::Integer(right)) => {
-                Ok(Value::Boolean(left.0 != right.0))
-            }
-            (Value::String(left), BinaryOperatorKind::Equals, Value::String(right)) => {
-                Ok(Value::Boolean(left == right))
-            }
-            (Value::String(left), BinaryOperatorKind::NotEquals, Value::String(right)) => {
-                Ok(Value::Boolean(left != right))
-            }
-            (Value::Integer(left), BinaryOperatorKind::LessThan, Value::Integer(right)) => {
-                Ok(Value::Boolean(left.0 < right.0))
-            }
-            (Value::Integer(left), BinaryOperatorKind::GreaterThan, Value::Integer(right)) => {
-                Ok(Value::Boolean(left.0 > right.0))
-            }
-            (Value::Integer(left), BinaryOperatorKind::LessThanEquals, Value::Integer(right)) => {
-                Ok(Value::Boolean(left.0 <= right.0))
-            }
-            (
-                Value::Integer(left),
-                BinaryOperatorKind::GreaterThanEquals,
-                Value::Integer(right),
-            ) => Ok(Value::Boolean(left.0 >= right.0)),
-            (Value::Unit, BinaryOperatorKind::Equals, Value::Unit) => Ok(Value::Boolean(true)),
-            (Value::Unit, BinaryOperatorKind::NotEquals, Value::Unit) => Ok(Value::Boolean(false)),
-            (left, BinaryOperatorKind::Equals, right)
-                if left.rain_type_id() != right.rain_type_id() =>
-            {
-                Ok(Value::Boolean(false))
-            }
-            (left, BinaryOperatorKind::NotEquals, right)
-                if left.rain_type_id() != right.rain_type_id() =>
-            {
-                Ok(Value::Boolean(true))
-            }
-            (Value::Type(left), BinaryOperatorKind::Equals, Value::Type(right)) => {
-                Ok(Value::Boolean(left == right))
-            }
-            (Value::Type(left), BinaryOperatorKind::NotEquals, Value::Type(right)) => {
-                Ok(Value::Boolean(left != right))
-            }
-            _ => Err(cx.err(
+        binary_op(left, op.op, right).ok_or_else(|| {
+            cx.err(
                 op.op_span,
                 RunnerError::Makeshift("binary op invalid for given types".into()),
-            )),
-        }
+            )
+        })
     }
 
     fn evaluate_named_index(
@@ -719,6 +691,156 @@ impl<'a, Driver: DriverTrait, Cache: CacheTrait> Runner<'a, Driver, Cache> {
         }
     }
 
+    /// Recoverable errors from the try block are bound to the catch name as a record of the thrown value and the trace
+    fn evaluate_try_catch(&mut self, cx: &mut Cx, try_catch: &TryCatch) -> ResultValue {
+        let err = match self.evaluate_node(cx, try_catch.try_block) {
+            Err(err) if matches!(err.err_span.err, Throwing::Recoverable(_)) => err,
+            result => return result,
+        };
+        let resolved = err.resolve_ir(self.ir);
+        let Throwing::Recoverable(value) = err.err_span.err else {
+            unreachable!("only recoverable errors are caught")
+        };
+        let trace = resolved
+            .trace
+            .into_iter()
+            .chain(std::iter::once((
+                resolved.file_name,
+                resolved.line,
+                resolved.col,
+            )))
+            .map(|(file, line, column)| {
+                let mut entry = IndexMap::new();
+                entry.insert("file".to_owned(), Value::String(Arc::new(file)));
+                entry.insert(
+                    "line".to_owned(),
+                    Value::Integer(Arc::new(RainInteger(line.into()))),
+                );
+                entry.insert(
+                    "column".to_owned(),
+                    Value::Integer(Arc::new(RainInteger(column.into()))),
+                );
+                Value::Record(Arc::new(RainRecord(entry)))
+            })
+            .collect();
+        let mut caught = IndexMap::new();
+        caught.insert("value".to_owned(), value);
+        caught.insert("trace".to_owned(), Value::List(Arc::new(RainList(trace))));
+        let name = try_catch.catch_name.span.contents(&cx.module.src);
+        let shadowed = cx
+            .locals
+            .insert(name, Value::Record(Arc::new(RainRecord(caught))));
+        let result = self.evaluate_node(cx, try_catch.catch_block);
+        match shadowed {
+            Some(v) => cx.locals.insert(name, v),
+            None => cx.locals.remove(name),
+        };
+        result
+    }
+
+    fn evaluate_match(&mut self, cx: &mut Cx, m: &Match) -> ResultValue {
+        let value = self.evaluate_node(cx, m.value)?;
+        for arm in &m.arms {
+            let Some(bindings) = self.match_pattern(cx, &arm.pattern, &value)? else {
+                continue;
+            };
+            let shadowed: Vec<_> = bindings
+                .into_iter()
+                .map(|(name, v)| (name, cx.locals.insert(name, v)))
+                .collect();
+            let result = self.evaluate_match_arm(cx, arm);
+            for (name, previous) in shadowed.into_iter().rev() {
+                match previous {
+                    Some(v) => cx.locals.insert(name, v),
+                    None => cx.locals.remove(name),
+                };
+            }
+            if let Some(v) = result? {
+                return Ok(v);
+            }
+        }
+        Err(cx.nid_err(m.value, RunnerError::NoMatchingPattern(value.to_string())))
+    }
+
+    /// Names bound by the pattern if it matches `value`
+    fn match_pattern<'c>(
+        &mut self,
+        cx: &mut Cx<'c>,
+        pattern: &MatchPattern,
+        value: &Value,
+    ) -> Result<Option<Vec<(&'c str, Value)>>> {
+        let module = cx.module;
+        let mut bindings = Vec::new();
+        match pattern {
+            MatchPattern::Value(nid) => {
+                let pattern_value = self.evaluate_node(cx, *nid)?;
+                // Compare like `==` so numbers match across integers and floats, falling back to structural equality for types `==` doesn't support
+                let matches = match binary_op(
+                    value.clone(),
+                    BinaryOperatorKind::Equals,
+                    pattern_value.clone(),
+                ) {
+                    Some(Value::Boolean(equal)) => equal,
+                    _ => pattern_value == *value,
+                };
+                if !matches {
+                    return Ok(None);
+                }
+            }
+            MatchPattern::Name(DeclareName::Single(single)) => {
+                if let Some(type_spec) = &single.type_spec {
+                    let type_spec_value = self.evaluate_node(cx, type_spec.type_expr)?;
+                    if !self.type_matches(cx, value, type_spec.type_expr, &type_spec_value)? {
+                        return Ok(None);
+                    }
+                }
+                let name = single.name.span.contents(&module.src);
+                if name != "_" {
+                    bindings.push((name, value.clone()));
+                }
+            }
+            MatchPattern::Name(DeclareName::NamedDestructure(destructure)) => {
+                let Value::Record(record) = value else {
+                    return Ok(None);
+                };
+                for element in &destructure.elements {
+                    let name = element.name.span.contents(&module.src);
+                    let Some(v) = record.0.get(name) else {
+                        return Ok(None);
+                    };
+                    if let Some(type_spec) = &element.type_spec {
+                        let type_spec_value = self.evaluate_node(cx, type_spec.type_expr)?;
+                        if !self.type_matches(cx, v, type_spec.type_expr, &type_spec_value)? {
+                            return Ok(None);
+                        }
+                    }
+                    bindings.push((name, v.clone()));
+                }
+            }
+        }
+        Ok(Some(bindings))
+    }
+
+    /// Evaluate the arm if its guard passes with the pattern's names bound
+    fn evaluate_match_arm(&mut self, cx: &mut Cx, arm: &MatchArm) -> Result<Option<Value>> {
+        if let Some(guard) = arm.guard {
+            match self.evaluate_node(cx, guard)? {
+                Value::Boolean(true) => {}
+                Value::Boolean(false) => return Ok(None),
+                v => {
+                    return Err(cx.nid_err(
+                        guard,
+                        RunnerError::ExpectedType {
+                            actual: v.rain_type_id(),
+                            expected: std::borrow::Cow::Borrowed(&[RainTypeId::Boolean]),
+                        },
+                    ));
+                }
+            }
+        }
+        Ok(Some(self.evaluate_node(cx, arm.expr)?))
+    }
+
     fn import_sugar(&mut self, cx: &mut Cx, nid: NodeId, call_span: LocalSpan) -> ResultValue {
         let embed_value = self.call_function(
             cx,
@@ -785,6 +907,224 @@ impl<'a, Driver: DriverTrait, Cache: CacheTrait> Runner<'a, Driver, Cache> {
         self.evaluate_declaration(cx, did)
     }
 
+    fn evaluate_record(&mut self, cx: &mut Cx, record: &Record) -> ResultValue {
+        let key_nids: Vec<NodeId> = record
+            .fields
+            .iter()
+            .filter_map(|e| match e.key {
+                RecordKey::Ident(_) => None,
+                RecordKey::Computed { expr, .. } => Some(expr),
+            })
+            .collect();
+        let mut key_values = self.evaluate_nodes(cx, &key_nids)?.into_iter();
+        let mut keys = Vec::with_capacity(record.fields.len());
+        for e in &record.fields {
+            match &e.key {
+                RecordKey::Ident(tls) => {
+                    keys.push(tls.span.contents(&cx.module.src).to_owned());
+                }
+                RecordKey::Computed { expr, .. } => {
+                    let Some(v) = key_values.next() else {
+                        unreachable!("a value is evaluated for every computed key")
+                    };
+                    keys.push(expect_record_key(cx, *expr, v)?);
+                }
+            }
+        }
+        let nids: Vec<NodeId> = record.fields.iter().map(|e| e.value).collect();
+        let values = self.evaluate_nodes(cx, &nids)?;
+        let builder: IndexMap<String, Value> = keys.into_iter().zip(values).collect();
+        Ok(Value::Record(Arc::new(RainRecord(builder))))
+    }
+
+    fn evaluate_comprehension(
+        &mut self,
+        cx: &mut Cx,
+        nid: NodeId,
+        comprehension: &Comprehension,
+    ) -> ResultValue {
+        let module = cx.module;
+        let names: Vec<&str> = comprehension
+            .bindings
+            .iter()
+            .map(|tls| tls.span.contents(&module.src))
+            .collect();
+        let iterable = self.evaluate_node(cx, comprehension.iterable)?;
+        let elements: Vec<Vec<Value>> = match (&iterable, names.len()) {
+            (Value::List(list), 1) => list.0.iter().map(|v| vec![v.clone()]).collect(),
+            (Value::Record(record), 2) => record
+                .0
+                .iter()
+                .map(|(k, v)| vec![Value::String(Arc::new(k.clone())), v.clone()])
+                .collect(),
+            (Value::List(_) | Value::Record(_), _) => {
+                return Err(cx.err(
+                    comprehension.for_token,
+                    RunnerError::Makeshift(
+                        "iterating a list binds one name and iterating a record binds a key and a value name"
+                            .into(),
+                    ),
+                ));
+            }
+            _ => {
+                return Err(cx.nid_err(
+                    comprehension.iterable,
+                    RunnerError::ExpectedType {
+                        actual: iterable.rain_type_id(),
+                        expected: std::borrow::Cow::Borrowed(&[
+                            RainTypeId::List,
+                            RainTypeId::Record,
+                        ]),
+                    },
+                ));
+            }
+        };
+        let scope = capture_scope(cx, nid);
+        let mut produced = Vec::new();
+        for element in elements {
+            produced.push(self.evaluate_comprehension_element(
+                cx,
+                nid,
+                comprehension,
+                &scope,
+                &names,
+                element,
+            )?);
+        }
+        let produced = produced.into_iter().flatten();
+        match comprehension.body {
+            ComprehensionBody::List(_) => Ok(Value::List(Arc::new(RainList(produced.collect())))),
+            ComprehensionBody::Record { .. } => {
+                let mut builder = IndexMap::new();
+                for entry in produced {
+                    let Value::List(entry) = entry else {
+                        unreachable!("record comprehensions produce key value pairs")
+                    };
+                    let [Value::String(k), v] = &entry.0[..] else {
+                        unreachable!("record comprehensions produce key value pairs")
+                    };
+                    builder.insert(k.as_ref().clone(), v.clone());
+                }
+                Ok(Value::Record(Arc::new(RainRecord(builder))))
+            }
+        }
+    }
+
+    /// Evaluate a comprehension for one element with the names bound to `element`
+    ///
+    /// Like a closure call the result is cached by the captured scope and element, it is none when the condition filters the element out
+    fn evaluate_comprehension_element<'c>(
+        &mut self,
+        cx: &mut Cx<'c>,
+        nid: NodeId,
+        comprehension: &Comprehension,
+        scope: &Closure,
+        names: &[&'c str],
+        element: Vec<Value>,
+    ) -> Result<Option<Value>> {
+        let cache_key = CacheKey::Comprehension {
+            scope: scope.clone(),
+            element: element.clone(),
+        };
+        if let Some(entry) = self.cache.get(&cache_key) {
+            // Elements are cached as a list, anything else can't have come from here so is treated as a miss
+            if let Value::List(cached) = entry.value {
+                cx.propagate_deps(entry.deps);
+                return Ok(cached.0.first().cloned());
+            }
+            log::warn!("comprehension element cached as a non list value, evaluating again");
+        }
+        let start = Instant::now();
+        let mut element_cx = cx.callee(
+            cx.module,
+            names.iter().copied().zip(element).collect(),
+            &scope.captures,
+            StacktraceEntry {
+                m: cx.module.id,
+                n: nid,
+            },
+        );
+        let keep = match comprehension.condition {
+            Some(condition) => match self.evaluate_node(&mut element_cx, condition)? {
+                Value::Boolean(b) => b,
+                v => {
+                    return Err(element_cx.nid_err(
+                        condition,
+                        RunnerError::ExpectedType {
+                            actual: v.rain_type_id(),
+                            expected: std::borrow::Cow::Borrowed(&[RainTypeId::Boolean]),
+                        },
+                    ));
+                }
+            },
+            None => true,
+        };
+        let result = match &comprehension.body {
+            _ if !keep => None,
+            ComprehensionBody::List(value) => Some(self.evaluate_node(&mut element_cx, *value)?),
+            ComprehensionBody::Record { key, value, .. } => {
+                let key = match key {
+                    RecordKey::Ident(tls) => tls.span.contents(&cx.module.src).to_owned(),
+                    RecordKey::Computed { expr, .. } => {
+                        let v = self.evaluate_node(&mut element_cx, *expr)?;
+                        expect_record_key(&element_cx, *expr, v)?
+                    }
+                };
+                let value = self.evaluate_node(&mut element_cx, *value)?;
+                Some(Value::List(Arc::new(RainList(vec![
+                    Value::String(Arc::new(key)),
+                    value,
+                ]))))
+            }
+        };
+        self.cache.put_if_slow(
+            cache_key,
+            CacheEntry {
+                execution_time: start.elapsed(),
+                expires: None,
+                etag: None,
+                deps: element_cx.deps.clone(),
+                value: Value::List(Arc::new(RainList(result.iter().cloned().collect()))),
+            },
+        );
+        cx.propagate_deps(element_cx.deps);
+        Ok(result)
+    }
+
+    fn evaluate_format_string(&mut self, cx: &mut Cx, format_string: &FormatString) -> ResultValue {
+        let mut out = String::new();
+        for part in &format_string.parts {
+            match part {
+                FormatStringPart::Text(span) => {
+                    out.push_str(&EscapeReplacer::replace_all(span.contents(&cx.module.src)));
+                }
+                FormatStringPart::Interpolation { expr, .. } => {
+                    match self.evaluate_node(cx, *expr)? {
+                        Value::String(s) => out.push_str(&s),
+                        v @ (Value::Integer(_) | Value::Float(_) | Value::Boolean(_)) => {
+                            out.push_str(&v.to_string());
+                        }
+                        v => {
+                            return Err(cx.nid_err(
+                                *expr,
+                                RunnerError::ExpectedType {
+                                    actual: v.rain_type_id(),
+                                    expected: std::borrow::Cow::Borrowed(&[
+                                        RainTypeId::String,
+                                        RainTypeId::Integer,
+                                        RainTypeId::Float,
+                                        RainTypeId::Boolean,
+                                    ]),
+                                },
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+        Ok(Value::String(Arc::new(out)))
+    }
+
     fn this_file_sugar(&mut self, cx: &mut Cx, nid: NodeId, call_span: LocalSpan) -> ResultValue {
         self.call_function(
             cx,
@@ -796,6 +1136,36 @@ impl<'a, Driver: DriverTrait, Cache: CacheTrait> Runner<'a, Driver, Cache> {
     }
 }
 
+/// Closure over the args and locals in scope of `node`
+fn capture_scope(cx: &Cx, node: NodeId) -> Closure {
+    let mut captures = HashMap::<String, Value>::new();
+    for (k, v) in &cx.args {
+        captures.insert(k.to_string(), v.clone());
+    }
+    for (k, v) in &cx.locals {
+        captures.insert(k.to_string(), v.clone());
+    }
+    Closure {
+        captures: Arc::new(captures),
+        module: cx.module.id,
+        node,
+    }
+}
+
+/// Computed record keys must evaluate to strings
+fn expect_record_key(cx: &Cx, expr: NodeId, v: Value) -> Result<String> {
+    let Value::String(s) = v else {
+        return Err(cx.nid_err(
+            expr,
+            RunnerError::ExpectedType {
+                actual: v.rain_type_id(),
+                expected: std::borrow::Cow::Borrowed(&[RainTypeId::String]),
+            },
+        ));
+    };
+    Ok(s.as_ref().clone())
+}
+
 struct EscapeReplacer;
 
 impl EscapeReplacer {
@@ -825,3 +1195,106 @@ impl regex::Replacer for EscapeReplacer {
         dst.push_str(replaced);
     }
 }
+
+/// Apply a binary operator other than dot to two values, `None` if the operator doesn't apply to their types
+fn binary_op(left: Value, op: BinaryOperatorKind, right: Value) -> Option<Value> {
+    if let Some(value) = float_binary_op(&left, op, &right) {
+        return Some(value);
+    }
+    match (left, op, right) {
+        (Value::String(left), BinaryOperatorKind::Addition, Value::String(right)) => {
+            Some(Value::String(Arc::new(left.to_string() + &**right)))
+        }
+        (Value::List(left), BinaryOperatorKind::Addition, Value::List(right)) => {
+            let mut v = left.0.clone();
+            v.append(&mut right.0.clone());
+            Some(Value::List(Arc::new(RainList(v))))
+        }
+        (Value::Integer(left), BinaryOperatorKind::Addition, Value::Integer(right)) => {
+            Some(Value::Integer(Arc::new(RainInteger(&left.0 + &right.0))))
+        }
+        (Value::Integer(left), BinaryOperatorKind::Subtraction, Value::Integer(right)) => {
+            Some(Value::Integer(Arc::new(RainInteger(&left.0 - &right.0))))
+        }
+        (Value::Integer(left), BinaryOperatorKind::Multiplication, Value::Integer(right)) => {
+            Some(Value::Integer(Arc::new(RainInteger(&left.0 * &right.0))))
+        }
+        (Value::Integer(left), BinaryOperatorKind::Division, Value::Integer(right)) => {
+            Some(Value::Integer(Arc::new(RainInteger(&left.0 / &right.0))))
+        }
+        (Value::Boolean(left), BinaryOperatorKind::LogicalAnd, Value::Boolean(right)) => {
+            Some(Value::Boolean(left && right))
+        }
+        (Value::Boolean(left), BinaryOperatorKind::LogicalOr, Value::Boolean(right)) => {
+            Some(Value::Boolean(left || right))
+        }
+        (Value::Integer(left), BinaryOperatorKind::Equals, Value::Integer(right)) => {
+            Some(Value::Boolean(left.0 == right.0))
+        }
+        (Value::Integer(left), BinaryOperatorKind::NotEquals, Value::Integer(right)) => {
+            Some(Value::Boolean(left.0 != right.0))
+        }
+        (Value::String(left), BinaryOperatorKind::Equals, Value::String(right)) => {
+            Some(Value::Boolean(left == right))
+        }
+        (Value::String(left), BinaryOperatorKind::NotEquals, Value::String(right)) => {
+            Some(Value::Boolean(left != right))
+        }
+        (Value::Integer(left), BinaryOperatorKind::LessThan, Value::Integer(right)) => {
+            Some(Value::Boolean(left.0 < right.0))
+        }
+        (Value::Integer(left), BinaryOperatorKind::GreaterThan, Value::Integer(right)) => {
+            Some(Value::Boolean(left.0 > right.0))
+        }
+        (Value::Integer(left), BinaryOperatorKind::LessThanEquals, Value::Integer(right)) => {
+            Some(Value::Boolean(left.0 <= right.0))
+        }
+        (Value::Integer(left), BinaryOperatorKind::GreaterThanEquals, Value::Integer(right)) => {
+            Some(Value::Boolean(left.0 >= right.0))
+        }
+        (Value::Unit, BinaryOperatorKind::Equals, Value::Unit) => Some(Value::Boolean(true)),
+        (Value::Unit, BinaryOperatorKind::NotEquals, Value::Unit) => Some(Value::Boolean(false)),
+        (left, BinaryOperatorKind::Equals, right)
+            if left.rain_type_id() != right.rain_type_id() =>
+        {
+            Some(Value::Boolean(false))
+        }
+        (left, BinaryOperatorKind::NotEquals, right)
+            if left.rain_type_id() != right.rain_type_id() =>
+        {
+            Some(Value::Boolean(true))
+        }
+        (Value::Type(left), BinaryOperatorKind::Equals, Value::Type(right)) => {
+            Some(Value::Boolean(left == right))
+        }
+        (Value::Type(left), BinaryOperatorKind::NotEquals, Value::Type(right)) => {
+            Some(Value::Boolean(left != right))
+        }
+        _ => None,
+    }
+}
+
+/// Arithmetic and comparisons where either side is a float, an integer on the other side is converted to a float
+#[expect(clippy::float_cmp)]
+fn float_binary_op(left: &Value, op: BinaryOperatorKind, right: &Value) -> Option<Value> {
+    let (left, right) = match (left, right) {
+        (Value::Float(left), Value::Float(right)) => (left.0, right.0),
+        (Value::Float(left), Value::Integer(right)) => (left.0, right.to_f64()),
+        (Value::Integer(left), Value::Float(right)) => (left.to_f64(), right.0),
+        _ => return None,
+    };
+    let value = match op {
+        BinaryOperatorKind::Addition => Value::Float(RainFloat(left + right)),
+        BinaryOperatorKind::Subtraction => Value::Float(RainFloat(left - right)),
+        BinaryOperatorKind::Multiplication => Value::Float(RainFloat(left * right)),
+        BinaryOperatorKind::Division => Value::Float(RainFloat(left / right)),
+        BinaryOperatorKind::Equals => Value::Boolean(left == right),
+        BinaryOperatorKind::NotEquals => Value::Boolean(left != right),
+        BinaryOperatorKind::LessThan => Value::Boolean(left < right),
+        BinaryOperatorKind::GreaterThan => Value::Boolean(left > right),
+        BinaryOperatorKind::LessThanEquals => Value::Boolean(left <= right),
+        BinaryOperatorKind::GreaterThanEquals => Value::Boolean(left >= right),
+        _ => return None,
+    };
+    Some(value)
+}
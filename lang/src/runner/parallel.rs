@@ -0,0 +1,385 @@
+use std::{
+    collections::HashSet,
+    sync::{
+        Arc, Mutex, PoisonError,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+};
+
+use crate::{
+    ast::{
+        AlternateCondition, ComprehensionBody, DeclareName, FormatStringPart, MatchPattern, Node,
+        NodeId, RecordKey,
+    },
+    driver::DriverTrait,
+    ir::IrModule,
+    runner::{
+        Result, Runner,
+        cache::CacheTrait,
+        cx::Cx,
+        dep_list::DepList,
+        error::{RunnerError, Throwing},
+        value::Value,
+    },
+};
+
+/// Stack size for worker threads, evaluation recurses deeply so the default 2 MiB is not enough
+const WORKER_STACK_SIZE: usize = 16 * 1024 * 1024;
+
+/// Limits how many extra threads a run can use to evaluate independent expressions
+///
+/// Shared between a runner and every runner forked from it, when no slot is free the work is done on the current thread instead
+#[derive(Debug)]
+pub struct WorkerPool {
+    available: AtomicUsize,
+}
+
+impl WorkerPool {
+    /// A pool for `jobs` threads in total including the thread that started the run
+    pub fn new(jobs: usize) -> Self {
+        Self {
+            available: AtomicUsize::new(jobs.saturating_sub(1)),
+        }
+    }
+
+    pub fn sequential() -> Self {
+        Self::new(1)
+    }
+
+    fn try_acquire(self: &Arc<Self>) -> Option<WorkerSlot> {
+        self.available
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| n.checked_sub(1))
+            .ok()
+            .map(|_| WorkerSlot(Arc::clone(self)))
+    }
+}
+
+struct WorkerSlot(Arc<WorkerPool>);
+
+impl Drop for WorkerSlot {
+    fn drop(&mut self) {
+        self.0.available.fetch_add(1, Ordering::AcqRel);
+    }
+}
+
+/// Set when a branch fails so that its siblings stop at the next node instead of running to completion
+///
+/// Nested parallel evaluations chain to the token of the branch they run in so cancelling an outer branch also stops them
+#[derive(Debug, Default)]
+pub(super) struct CancelToken {
+    cancelled: AtomicBool,
+    parent: Option<Arc<Self>>,
+}
+
+impl CancelToken {
+    fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Release);
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Acquire)
+            || self.parent.as_ref().is_some_and(|p| p.is_cancelled())
+    }
+}
+
+/// Where a runner's prints go
+#[derive(Debug, Clone, Default)]
+pub(super) enum PrintSink {
+    #[default]
+    Driver,
+    /// One of several branches evaluated in parallel
+    Branch {
+        order: Arc<PrintOrder>,
+        index: usize,
+    },
+}
+
+impl PrintSink {
+    pub(super) fn print(&self, driver: &impl DriverTrait, message: String) {
+        match self {
+            Self::Driver => driver.print(message),
+            Self::Branch { order, index } => {
+                let mut state = order.state.lock().unwrap_or_else(PoisonError::into_inner);
+                if state.current == *index {
+                    order.parent.print(driver, message);
+                } else {
+                    state.pending[*index].push(message);
+                }
+            }
+        }
+    }
+
+    fn finish(&self, driver: &impl DriverTrait) {
+        let Self::Branch { order, index } = self else {
+            return;
+        };
+        let mut state = order.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state.finished[*index] = true;
+        while state.current < state.finished.len() && state.finished[state.current] {
+            state.current += 1;
+            let current = state.current;
+            if let Some(pending) = state.pending.get_mut(current) {
+                for message in std::mem::take(pending) {
+                    order.parent.print(driver, message);
+                }
+            }
+        }
+    }
+}
+
+/// Keeps the prints of parallel branches in node order
+///
+/// The earliest unfinished branch prints straight through, later branches buffer until every branch before them has finished
+#[derive(Debug)]
+pub(super) struct PrintOrder {
+    parent: PrintSink,
+    state: Mutex<PrintOrderState>,
+}
+
+#[derive(Debug)]
+struct PrintOrderState {
+    current: usize,
+    pending: Vec<Vec<String>>,
+    finished: Vec<bool>,
+}
+
+struct Branch {
+    result: Result<Value>,
+    deps: DepList,
+}
+
+impl<Driver: DriverTrait, Cache: CacheTrait> Runner<'_, Driver, Cache> {
+    /// A runner sharing everything with this one except where its prints go and how it is cancelled
+    fn fork(&self, print_sink: PrintSink, cancel: &Arc<CancelToken>) -> Self {
+        Self {
+            ir: self.ir,
+            cache: self.cache,
+            driver: self.driver,
+            offline: self.offline,
+            seal: self.seal,
+            max_call_depth: self.max_call_depth,
+            workers: Arc::clone(&self.workers),
+            print_sink,
+            cancel: Some(Arc::clone(cancel)),
+        }
+    }
+
+    /// Error if a sibling branch has failed, checked before evaluating each node
+    pub(super) fn check_cancelled(&self, cx: &Cx, nid: NodeId) -> Result<()> {
+        if self.cancel.as_ref().is_some_and(|c| c.is_cancelled()) {
+            return Err(cx.nid_err(nid, RunnerError::Cancelled));
+        }
+        Ok(())
+    }
+
+    fn run_branch(mut self, mut cx: Cx, nid: NodeId) -> Branch {
+        let result = self.evaluate_node(&mut cx, nid);
+        if result.is_err() {
+            if let Some(cancel) = &self.cancel {
+                cancel.cancel();
+            }
+        }
+        self.print_sink.finish(self.driver);
+        Branch {
+            result,
+            deps: cx.deps,
+        }
+    }
+
+    /// Evaluate each node in order, spreading them over the worker pool if there are free workers
+    ///
+    /// Deps are merged in node order once every node has finished and prints are kept in node order so the outcome does not depend on scheduling.
+    /// If a node fails the rest are cancelled and the first error that is not a cancellation is returned.
+    pub(super) fn evaluate_nodes(&mut self, cx: &mut Cx, nids: &[NodeId]) -> Result<Vec<Value>> {
+        if nids.len() < 2 || self.workers.available.load(Ordering::Acquire) == 0 {
+            return nids
+                .iter()
+                .map(|nid| self.evaluate_node(cx, *nid))
+                .collect();
+        }
+        let cancel = Arc::new(CancelToken {
+            cancelled: AtomicBool::new(false),
+            parent: self.cancel.clone(),
+        });
+        let order = Arc::new(PrintOrder {
+            parent: self.print_sink.clone(),
+            state: Mutex::new(PrintOrderState {
+                current: 0,
+                pending: vec![Vec::new(); nids.len()],
+                finished: vec![false; nids.len()],
+            }),
+        });
+        let this = &*self;
+        let branches = std::thread::scope(|s| {
+            let mut handles = Vec::with_capacity(nids.len());
+            for (index, &nid) in nids.iter().enumerate() {
+                let print_sink = PrintSink::Branch {
+                    order: Arc::clone(&order),
+                    index,
+                };
+                let spawned = this.workers.try_acquire().and_then(|slot| {
+                    let runner = this.fork(print_sink.clone(), &cancel);
+                    let branch_cx = cx.fork();
+                    std::thread::Builder::new()
+                        .name(String::from("rain worker"))
+                        .stack_size(WORKER_STACK_SIZE)
+                        .spawn_scoped(s, move || {
+                            let _slot = slot;
+                            runner.run_branch(branch_cx, nid)
+                        })
+                        .inspect_err(|err| log::warn!("failed to spawn worker thread: {err}"))
+                        .ok()
+                });
+                match spawned {
+                    Some(handle) => handles.push(Ok(handle)),
+                    None => handles.push(Err(this
+                        .fork(print_sink, &cancel)
+                        .run_branch(cx.fork(), nid))),
+                }
+            }
+            handles
+                .into_iter()
+                .map(|handle| match handle {
+                    Ok(handle) => handle
+                        .join()
+                        .unwrap_or_else(|err| std::panic::resume_unwind(err)),
+                    Err(branch) => branch,
+                })
+                .collect::<Vec<Branch>>()
+        });
+        let mut values = Vec::with_capacity(branches.len());
+        let mut errors = Vec::new();
+        for branch in branches {
+            cx.deps.extend(branch.deps.into_iter());
+            match branch.result {
+                Ok(v) => values.push(v),
+                Err(err) => errors.push(err),
+            }
+        }
+        if errors.is_empty() {
+            return Ok(values);
+        }
+        let index = errors
+            .iter()
+            .position(|err| {
+                !matches!(
+                    err.err_span.err,
+                    Throwing::Unrecoverable(RunnerError::Cancelled)
+                )
+            })
+            .unwrap_or(0);
+        Err(errors.swap_remove(index))
+    }
+
+    /// Number of assignments at the start of `statements` that do not refer to anything assigned before them in the same run
+    ///
+    /// Those assignments can be evaluated in parallel with each other
+    pub(super) fn independent_assignments(&self, cx: &Cx, statements: &[NodeId]) -> usize {
+        if self.workers.available.load(Ordering::Acquire) == 0 {
+            return 0;
+        }
+        let mut assigned: HashSet<&str> = HashSet::from(["_"]);
+        let mut count = 0;
+        for nid in statements {
+            let Node::Assignment(assignment) = cx.module.get(*nid) else {
+                break;
+            };
+            if mentions_any(cx.module, assignment.expr, &assigned) {
+                break;
+            }
+            assigned.extend(assignment.names(&cx.module.src));
+            count += 1;
+        }
+        count
+    }
+}
+
+/// Whether any identifier in the subtree of `nid` is one of `names`
+///
+/// This is purely syntactic so it will over approximate, for example a closure argument shadowing a name still counts
+fn mentions_any(module: &IrModule, nid: NodeId, names: &HashSet<&str>) -> bool {
+    let mentions = |nid| mentions_any(module, nid, names);
+    match module.get(nid) {
+        Node::Ident(tls) => names.contains(tls.0.span.contents(&module.src)),
+        Node::Closure(closure) => {
+            closure
+                .args
+                .iter()
+                .filter_map(|a| a.type_spec.as_ref())
+                .any(|t| mentions(t.type_expr))
+                || closure
+                    .return_type
+                    .as_ref()
+                    .is_some_and(|t| mentions(t.type_expr))
+                || mentions(closure.block)
+        }
+        Node::Block(block) => block.statements.iter().any(|nid| mentions(*nid)),
+        Node::IfCondition(if_condition) => {
+            mentions(if_condition.condition)
+                || mentions(if_condition.then_block)
+                || match if_condition.alternate {
+                    Some(
+                        AlternateCondition::IfElseCondition(nid)
+                        | AlternateCondition::ElseBlock(nid),
+                    ) => mentions(nid),
+                    None => false,
+                }
+        }
+        Node::TryCatch(try_catch) => {
+            mentions(try_catch.try_block) || mentions(try_catch.catch_block)
+        }
+        Node::Match(m) => {
+            mentions(m.value)
+                || m.arms.iter().any(|arm| {
+                    let pattern_mentions = match &arm.pattern {
+                        MatchPattern::Value(nid) => mentions(*nid),
+                        MatchPattern::Name(DeclareName::Single(single)) => single
+                            .type_spec
+                            .as_ref()
+                            .is_some_and(|t| mentions(t.type_expr)),
+                        MatchPattern::Name(DeclareName::NamedDestructure(destructure)) => {
+                            destructure.elements.iter().any(|e| {
+                                e.type_spec.as_ref().is_some_and(|t| mentions(t.type_expr))
+                            })
+                        }
+                    };
+                    pattern_mentions || arm.guard.is_some_and(mentions) || mentions(arm.expr)
+                })
+        }
+        Node::FnCall(fn_call) => {
+            mentions(fn_call.callee) || fn_call.args.iter().any(|nid| mentions(*nid))
+        }
+        Node::Assignment(assignment) => {
+            mentions(assignment.expr)
+                || assignment
+                    .type_specs()
+                    .flatten()
+                    .any(|t| mentions(t.type_expr))
+        }
+        Node::BinaryOp(binary_op) => mentions(binary_op.left) || mentions(binary_op.right),
+        Node::Not(not) => mentions(not.inner),
+        Node::Record(record) => record.fields.iter().any(|f| {
+            mentions(f.value) || matches!(f.key, RecordKey::Computed { expr, .. } if mentions(expr))
+        }),
+        Node::List(list) => list.elements.iter().any(|e| mentions(e.value)),
+        Node::Comprehension(comprehension) => {
+            mentions(comprehension.iterable)
+                || comprehension.condition.is_some_and(mentions)
+                || match &comprehension.body {
+                    ComprehensionBody::List(value) => mentions(*value),
+                    ComprehensionBody::Record { key, value, .. } => {
+                        mentions(*value)
+                            || matches!(key, RecordKey::Computed { expr, .. } if mentions(*expr))
+                    }
+                }
+        }
+        Node::FormatString(format_string) => format_string.parts.iter().any(|p| match p {
+            FormatStringPart::Text(_) => false,
+            FormatStringPart::Interpolation { expr, .. } => mentions(*expr),
+        }),
+        Node::SimpleLiteral(_)
+        | Node::StringLiteral(_)
+        | Node::IntegerLiteral(_)
+        | Node::FloatLiteral(_) => false,
+    }
+}
@@ -9,7 +9,11 @@ use indexmap::IndexMap;
 
 use crate::{
     afs::{
-        absolute::AbsolutePathBuf, area::FileArea, dir::Dir, entry::FSEntryTrait as _, file::File,
+        absolute::AbsolutePathBuf,
+        area::FileArea,
+        dir::Dir,
+        entry::{FSEntry, FSEntryTrait as _},
+        file::File,
     },
     ast::NodeId,
     ir::ModuleId,
@@ -22,6 +26,7 @@ pub enum Value {
     Unit,
     Boolean(bool),
     Integer(Arc<RainInteger>),
+    Float(RainFloat),
     String(Arc<String>),
     Module(ModuleId),
     FileArea(Arc<FileArea>),
@@ -42,6 +47,7 @@ impl Display for Value {
             Self::Unit => f.write_str("unit"),
             Self::Boolean(b) => Display::fmt(&b, f),
             Self::Integer(rain_integer) => Display::fmt(&rain_integer, f),
+            Self::Float(rain_float) => Display::fmt(rain_float, f),
             Self::String(s) => Debug::fmt(s, f),
             Self::Module(module_id) => Display::fmt(module_id, f),
             Self::FileArea(file_area) => Display::fmt(file_area, f),
@@ -63,6 +69,7 @@ pub enum RainTypeId {
     Unit,
     Boolean,
     Integer,
+    Float,
     String,
     Module,
     FileArea,
@@ -83,6 +90,7 @@ impl std::fmt::Display for RainTypeId {
             Self::Unit => "Unit",
             Self::Boolean => "Boolean",
             Self::Integer => "Integer",
+            Self::Float => "Float",
             Self::String => "String",
             Self::Module => "Module",
             Self::FileArea => "FileArea",
@@ -114,6 +122,38 @@ impl From<i32> for RainInteger {
     }
 }
 
+impl RainInteger {
+    /// Nearest float, integers too large for a float become infinite
+    pub fn to_f64(&self) -> f64 {
+        num_traits::ToPrimitive::to_f64(&self.0).unwrap_or(f64::NAN)
+    }
+}
+
+/// Equality and hashing compare the bits of the float so values can be used as cache keys, use [`f64`] comparisons for the language's operators
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+pub struct RainFloat(pub f64);
+
+impl PartialEq for RainFloat {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.to_bits() == other.0.to_bits()
+    }
+}
+
+impl Eq for RainFloat {}
+
+impl std::hash::Hash for RainFloat {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.to_bits().hash(state);
+    }
+}
+
+impl Display for RainFloat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Debug always includes a fraction or exponent so the output reads back as a float
+        Debug::fmt(&self.0, f)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct RainList(pub Vec<Value>);
 
@@ -175,6 +215,7 @@ impl Value {
             Self::Unit => RainTypeId::Unit,
             Self::Boolean(_) => RainTypeId::Boolean,
             Self::Integer(_) => RainTypeId::Integer,
+            Self::Float(_) => RainTypeId::Float,
             Self::String(_) => RainTypeId::String,
             Self::Module(_) => RainTypeId::Module,
             Self::FileArea(_) => RainTypeId::FileArea,
@@ -195,6 +236,7 @@ impl Value {
             Self::Unit
             | Self::Boolean(_)
             | Self::Integer(_)
+            | Self::Float(_)
             | Self::String(_)
             | Self::Module(_)
             | Self::EscapeFile(_)
@@ -209,6 +251,52 @@ impl Value {
             Self::Record(record) => record.0.iter().flat_map(|(_, v)| v.find_areas()).collect(),
         }
     }
+
+    /// Every string contained in this value, including record keys and closure captures
+    pub fn find_strings(&self) -> Vec<&str> {
+        match self {
+            Self::String(s) => vec![s.as_str()],
+            Self::List(list) => list.0.iter().flat_map(Self::find_strings).collect(),
+            Self::Record(record) => record
+                .0
+                .iter()
+                .flat_map(|(k, v)| std::iter::once(k.as_str()).chain(v.find_strings()))
+                .collect(),
+            Self::Closure(closure) => closure.find_strings(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Every file in a local area contained in this value
+    pub fn find_local_files(&self) -> Vec<&File> {
+        match self {
+            Self::File(f) if f.area().is_local() => vec![f],
+            Self::List(list) => list.0.iter().flat_map(|v| v.find_local_files()).collect(),
+            Self::Record(record) => record
+                .0
+                .iter()
+                .flat_map(|(_, v)| v.find_local_files())
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Every directory in a local area contained in this value, including the roots of local areas
+    pub fn find_local_dirs(&self) -> Vec<FSEntry> {
+        match self {
+            Self::Dir(d) if d.area().is_local() => vec![d.inner().clone()],
+            Self::FileArea(area) if area.is_local() => {
+                vec![Dir::root(area.as_ref().clone()).inner().clone()]
+            }
+            Self::List(list) => list.0.iter().flat_map(Self::find_local_dirs).collect(),
+            Self::Record(record) => record
+                .0
+                .iter()
+                .flat_map(|(_, v)| v.find_local_dirs())
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -218,6 +306,16 @@ pub struct Closure {
     pub node: NodeId,
 }
 
+impl Closure {
+    /// Every string contained in the captured values
+    pub fn find_strings(&self) -> Vec<&str> {
+        self.captures
+            .values()
+            .flat_map(Value::find_strings)
+            .collect()
+    }
+}
+
 impl std::fmt::Display for Closure {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.write_fmt(format_args!("Closure<{}, {:?}>", self.module, self.node))
@@ -1,5 +1,5 @@
 use crate::{
-    error::{ResolvedError, ResolvedSpan},
+    error::{OwnedResolvedError, ResolvedError, ResolvedSpan},
     ir::{ModuleId, Rir},
     local_span::LocalSpan,
     runner::{cx::StacktraceEntry, error::ErrorTrace},
@@ -31,7 +31,7 @@ impl<E: std::error::Error> ErrorSpan<E> {
         }
     }
 
-    pub fn resolve_ir<'a>(&'a self, ir: &'a Rir) -> ResolvedError<'a> {
+    pub fn resolve_ir(&self, ir: &Rir) -> OwnedResolvedError {
         let module = ir.get_module(self.span.module);
         let file = module.file().ok();
         let src = &module.src;
@@ -43,6 +43,7 @@ impl<E: std::error::Error> ErrorSpan<E> {
                 call_span: self.span.span,
             }],
         }
+        .into_owned()
     }
 
     pub fn convert<T>(self) -> ErrorSpan<T>
@@ -9,6 +9,8 @@ use crate::local_span::LocalSpan;
 pub enum Token {
     Ident,
     Number,
+    /// Number with a fractional part or exponent
+    Float,
     SingleQuoteLiteral(Option<StringLiteralPrefix>),
     DoubleQuoteLiteral(Option<StringLiteralPrefix>),
     Comment,
@@ -54,6 +56,7 @@ pub enum Token {
     LessEq,
     GreaterEq,
     ReturnType,
+    FatArrow,
 
     // Keywords that may be used in the future
     Reserved,
@@ -63,6 +66,11 @@ pub enum Token {
     Pub,
     If,
     Else,
+    Try,
+    Catch,
+    For,
+    In,
+    Match,
     True,
     False,
     Internal,
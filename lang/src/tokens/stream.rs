@@ -16,6 +16,15 @@ impl<'a> TokenStream<'a> {
             last_parsed_span: None,
         }
     }
+
+    /// Stream over the tokens in `span` of `source`, the produced spans are relative to the whole of `source`
+    pub fn new_range(source: &'a str, span: LocalSpan) -> Self {
+        Self {
+            source: &source[..span.end],
+            index: span.start,
+            last_parsed_span: None,
+        }
+    }
 }
 
 impl TokenStream<'_> {
@@ -71,6 +80,7 @@ impl TokenStream<'_> {
                 (b'&', Some(b'&')) => self.inc2(Token::LogicalAnd),
                 (b'&', _) => self.inc(Token::Ampersand),
                 (b'=', Some(b'=')) => self.inc2(Token::Equals),
+                (b'=', Some(b'>')) => self.inc2(Token::FatArrow),
                 (b'=', _) => self.inc(Token::Assign),
                 (b'!', Some(b'=')) => self.inc2(Token::NotEquals),
                 (b'!', _) => self.inc(Token::Excalmation),
@@ -164,16 +174,22 @@ impl TokenStream<'_> {
             "let" => Token::Let,
             "if" => Token::If,
             "else" => Token::Else,
+            "try" => Token::Try,
+            "catch" => Token::Catch,
+            "for" => Token::For,
+            "in" => Token::In,
+            "match" => Token::Match,
             "true" => Token::True,
             "false" => Token::False,
             "internal" => Token::Internal,
             "import" => Token::Import,
             "stdlib" => Token::Stdlib,
             "this_file" => Token::ThisFile,
-            "throw" | "try" | "type" | "for" | "in" | "while" | "match" | "async" | "await"
-            | "default" | "struct" | "trait" | "break" | "continue" | "return" | "yield"
-            | "enum" | "union" | "safe" | "unsafe" | "macro" | "const" | "var" | "interface"
-            | "abstract" | "alias" | "super" => Token::Reserved,
+            "throw" | "type" | "while" | "async" | "await" | "default" | "struct" | "trait"
+            | "break" | "continue" | "return" | "yield" | "enum" | "union" | "safe" | "unsafe"
+            | "macro" | "const" | "var" | "interface" | "abstract" | "alias" | "super" => {
+                Token::Reserved
+            }
             _ => Token::Ident,
         };
         TokenLocalSpan {
@@ -185,19 +201,39 @@ impl TokenStream<'_> {
     fn number(&mut self) -> TokenLocalSpan {
         let start = self.index;
         self.index += 1;
-        while let Some(c) = self.source.as_bytes().get(self.index) {
-            match c {
-                b'0'..=b'9' => {}
-                _ => break,
-            }
+        self.digits();
+        let mut token = Token::Number;
+        let bytes = self.source.as_bytes();
+        // The fraction needs a digit after the dot so `1.` is still a number followed by a dot
+        if let (Some(b'.'), Some(b'0'..=b'9')) = (bytes.get(self.index), bytes.get(self.index + 1))
+        {
             self.index += 1;
+            self.digits();
+            token = Token::Float;
+        }
+        if let Some(b'e' | b'E') = bytes.get(self.index) {
+            let exponent_start = match bytes.get(self.index + 1) {
+                Some(b'+' | b'-') => self.index + 2,
+                _ => self.index + 1,
+            };
+            if let Some(b'0'..=b'9') = bytes.get(exponent_start) {
+                self.index = exponent_start;
+                self.digits();
+                token = Token::Float;
+            }
         }
         TokenLocalSpan {
-            token: Token::Number,
+            token,
             span: LocalSpan::new(start, self.index),
         }
     }
 
+    fn digits(&mut self) {
+        while let Some(b'0'..=b'9') = self.source.as_bytes().get(self.index) {
+            self.index += 1;
+        }
+    }
+
     fn single_quote_literal(&mut self) -> Result<TokenLocalSpan, ErrorLocalSpan<TokenError>> {
         let start = self.index;
         let prefix_symbol = self.source.as_bytes().get(self.index).copied();
@@ -273,8 +309,10 @@ impl TokenStream<'_> {
         };
         self.index += 1;
         let mut escape = false;
+        // Depth of braces inside a format string, anything nested is an expression
+        let mut depth = 0usize;
         loop {
-            let Some(c) = self.source.as_bytes().get(self.index) else {
+            let Some(&c) = self.source.as_bytes().get(self.index) else {
                 return Err(
                     LocalSpan::new(start, self.index).with_error(TokenError::UnclosedDoubleQuote)
                 );
@@ -288,10 +326,30 @@ impl TokenStream<'_> {
                 escape = false;
                 continue;
             }
+            let c_next = self.source.as_bytes().get(self.index + 1);
             match c {
-                b'\\' if prefix != Some(StringLiteralPrefix::Raw) => {
+                b'\\' if depth == 0 && prefix != Some(StringLiteralPrefix::Raw) => {
                     escape = true;
                 }
+                b'{' if prefix == Some(StringLiteralPrefix::Format) => {
+                    if depth == 0 && c_next == Some(&b'{') {
+                        // Escaped brace
+                        self.index += 1;
+                    } else {
+                        depth += 1;
+                    }
+                }
+                b'}' if depth > 0 => {
+                    depth -= 1;
+                }
+                b'"' if depth > 0 => {
+                    self.double_quote_literal()?;
+                    continue;
+                }
+                b'a'..=b'z' if depth > 0 && c_next == Some(&b'"') => {
+                    self.double_quote_literal()?;
+                    continue;
+                }
                 b'"' => {
                     self.index += 1;
                     break;
@@ -65,7 +65,7 @@ fn symbols() {
 #[test]
 fn compound_symbols() {
     assert_tokens!(
-        "==&&!=||<= >=->",
+        "==&&!=||<= >=->=>",
         Token::Equals,
         Token::LogicalAnd,
         Token::NotEquals,
@@ -73,6 +73,7 @@ fn compound_symbols() {
         Token::LessEq,
         Token::GreaterEq,
         Token::ReturnType,
+        Token::FatArrow,
     );
 }
 
@@ -147,15 +148,30 @@ fn double_quote_literal() {
     assert_tokens!("\"he\\\\ni\"", Token::DoubleQuoteLiteral(None));
 }
 
+#[test]
+fn format_string_literal() {
+    let format = Token::DoubleQuoteLiteral(Some(crate::tokens::StringLiteralPrefix::Format));
+    assert_tokens!("f\"{a.b(\"}\")}\"", format);
+    assert_tokens!("f\"{f\"{1}\"}\" 2", format, Token::Number);
+    assert_tokens!("f\"{{\" \"{\"", format, Token::DoubleQuoteLiteral(None));
+    assert_tokens!("f\"{ {a = 1}.a }\"", format);
+    assert!(str_tokens("f\"{\"}\"").is_err());
+}
+
 #[test]
 fn keywords() {
     assert_tokens!(
-        "fn let pub if else true false internal",
+        "fn let pub if else try catch for in match true false internal",
         Token::Fn,
         Token::Let,
         Token::Pub,
         Token::If,
         Token::Else,
+        Token::Try,
+        Token::Catch,
+        Token::For,
+        Token::In,
+        Token::Match,
         Token::True,
         Token::False,
         Token::Internal
@@ -173,6 +189,21 @@ fn number() {
     );
 }
 
+#[test]
+fn float() {
+    assert_tokens!(
+        "1.5 0.25 1e10 2.5E-3 6e+2",
+        Token::Float,
+        Token::Float,
+        Token::Float,
+        Token::Float,
+        Token::Float,
+    );
+    assert_tokens!("1.", Token::Number, Token::Dot);
+    assert_tokens!("1.a", Token::Number, Token::Dot, Token::Ident);
+    assert_tokens!("1e", Token::Number, Token::Ident);
+}
+
 #[test]
 fn illegal_chars() {
     for (s, c) in [("`", b'`'), ("\r", b'\r'), ("\0", b'\0')] {
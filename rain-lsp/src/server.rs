@@ -6,6 +6,8 @@ use lsp_types::{
     PublishDiagnosticsParams, TextDocumentSyncKind,
 };
 
+use rain_lang::ir::lint::{LintConfig, LintLevel};
+
 use crate::{
     comms::Comms,
     json_rpc::{self, Notification, Request},
@@ -14,6 +16,8 @@ use crate::{
 pub struct Server {
     comms: Comms,
     text_documents: HashMap<String, TextDocument>,
+    /// Set from the `lints` initialization option
+    lint_config: LintConfig,
 }
 
 struct TextDocument {
@@ -24,29 +28,77 @@ struct TextDocument {
 }
 
 impl TextDocument {
-    fn publish_diagnostics(&self) -> Notification<PublishDiagnosticsParams> {
+    fn publish_diagnostics(
+        &self,
+        lint_config: &LintConfig,
+    ) -> Notification<PublishDiagnosticsParams> {
         Notification::new(
             "textDocument/publishDiagnostics",
             Some(PublishDiagnosticsParams {
                 uri: self.uri.clone(),
                 version: Some(self.version),
-                diagnostics: self.diagnostics().collect(),
+                diagnostics: self.diagnostics(lint_config),
             }),
         )
     }
 
-    fn diagnostics(&self) -> impl Iterator<Item = Diagnostic> {
-        tree_errors(&self.tree).map(|node| Diagnostic {
-            range: convert_range_to_lsp(node.range()),
-            severity: Some(DiagnosticSeverity::ERROR),
-            code: None,
-            code_description: None,
-            source: None,
-            message: node.to_sexp(),
-            related_information: None,
-            tags: None,
-            data: None,
-        })
+    fn diagnostics(&self, lint_config: &LintConfig) -> Vec<Diagnostic> {
+        let mut diagnostics: Vec<Diagnostic> = tree_errors(&self.tree)
+            .map(|node| error_diagnostic(convert_range_to_lsp(node.range()), node.to_sexp()))
+            .collect();
+        if diagnostics.is_empty() {
+            // The rain parser is stricter than tree sitter, e.g. about the expressions inside format strings
+            let module = rain_lang::ast::parser::parse_module(&self.source);
+            let ir = rain_lang::ir::Rir::new();
+            match ir.insert_module(None, self.source.clone(), module) {
+                Ok(id) => {
+                    diagnostics.extend(
+                        rain_lang::ir::typecheck::check_module(&ir.get_module(id))
+                            .into_iter()
+                            .map(|err| {
+                                error_diagnostic(
+                                    convert_span_to_lsp(&self.source, err.span),
+                                    err.err.to_string(),
+                                )
+                            }),
+                    );
+                    // Imports aren't followed so only lints within this document are reported
+                    diagnostics.extend(
+                        rain_lang::ir::lint::lint(&ir, id, lint_config, |_, _| None)
+                            .into_iter()
+                            .map(|err| {
+                                let mut diagnostic = error_diagnostic(
+                                    convert_span_to_lsp(&self.source, err.span.span),
+                                    err.err.to_string(),
+                                );
+                                if err.err.level == LintLevel::Warn {
+                                    diagnostic.severity = Some(DiagnosticSeverity::WARNING);
+                                }
+                                diagnostic
+                            }),
+                    );
+                }
+                Err(err) => diagnostics.push(error_diagnostic(
+                    convert_span_to_lsp(&self.source, err.span.span),
+                    err.err.to_string(),
+                )),
+            }
+        }
+        diagnostics
+    }
+}
+
+fn error_diagnostic(range: lsp_types::Range, message: String) -> Diagnostic {
+    Diagnostic {
+        range,
+        severity: Some(DiagnosticSeverity::ERROR),
+        code: None,
+        code_description: None,
+        source: None,
+        message,
+        related_information: None,
+        tags: None,
+        data: None,
     }
 }
 
@@ -67,6 +119,7 @@ impl Server {
         Self {
             comms,
             text_documents: HashMap::new(),
+            lint_config: LintConfig::default(),
         }
     }
 
@@ -74,6 +127,17 @@ impl Server {
         let initialize = self
             .comms
             .receive_message::<json_rpc::Request<lsp_types::InitializeParams>>();
+        if let Some(lints) = initialize
+            .params
+            .as_ref()
+            .and_then(|params| params.initialization_options.as_ref())
+            .and_then(|options| options.get("lints"))
+        {
+            match serde_json::from_value(lints.clone()) {
+                Ok(lint_config) => self.lint_config = lint_config,
+                Err(err) => eprintln!("invalid lints initialization option: {err}"),
+            }
+        }
         self.comms
             .send_message(&initialize.ok_response(lsp_types::InitializeResult {
                 capabilities: lsp_types::ServerCapabilities {
@@ -158,7 +222,7 @@ impl Server {
             tree,
         };
         self.comms
-            .send_message(&text_document.publish_diagnostics());
+            .send_message(&text_document.publish_diagnostics(&self.lint_config));
         self.text_documents
             .insert(text_document.uri.to_string(), text_document);
     }
@@ -178,7 +242,7 @@ impl Server {
             tree,
         };
         self.comms
-            .send_message(&text_document.publish_diagnostics());
+            .send_message(&text_document.publish_diagnostics(&self.lint_config));
     }
 
     fn handle_hover(&mut self, message: Request<HoverParams>) {
@@ -231,6 +295,23 @@ fn convert_range_to_lsp(range: tree_sitter::Range) -> lsp_types::Range {
     }
 }
 
+fn convert_span_to_lsp(src: &str, span: rain_lang::local_span::LocalSpan) -> lsp_types::Range {
+    lsp_types::Range {
+        start: convert_offset_to_lsp(src, span.start),
+        end: convert_offset_to_lsp(src, span.end),
+    }
+}
+
+/// Convert a byte offset to a position, like tree sitter the column is in bytes
+fn convert_offset_to_lsp(src: &str, offset: usize) -> lsp_types::Position {
+    let before = &src[..offset.min(src.len())];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    convert_point_to_lsp(tree_sitter::Point {
+        row: before.matches('\n').count(),
+        column: before.len() - line_start,
+    })
+}
+
 fn convert_point_to_lsp(start_point: tree_sitter::Point) -> lsp_types::Position {
     lsp_types::Position {
         line: start_point.row.try_into().unwrap(),
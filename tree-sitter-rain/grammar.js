@@ -4,7 +4,10 @@
 export default grammar({
   name: "rain",
 
-  conflicts: ($) => [[$.record_literal, $.declare_named_destructure]],
+  conflicts: ($) => [
+    [$.record_literal, $.declare_named_destructure],
+    [$.expr, $.declare_single_name],
+  ],
 
   rules: {
     source_file: ($) => repeat(choice($.line_comment, $.declaration)),
@@ -54,8 +57,12 @@ export default grammar({
         $.unary_expr,
         $.binary_expr,
         $.if_condition,
+        $.try_catch,
+        $.match_expr,
         $.list_literal,
+        $.list_comprehension,
         $.record_literal,
+        $.record_comprehension,
         $.internal,
         $.raw_string_literal,
         $.format_string_literal,
@@ -90,6 +97,25 @@ export default grammar({
         repeat(seq("else", "if", $.expr, $.block)),
         optional(seq("else", $.block)),
       ),
+    try_catch: ($) => seq("try", $.block, "catch", $.identifier, $.block),
+    match_expr: ($) =>
+      seq(
+        "match",
+        $.expr,
+        "{",
+        seq(
+          repeat(choice($.line_comment, seq($.match_arm, ","))),
+          optional(seq($.match_arm, repeat($.line_comment))),
+        ),
+        "}",
+      ),
+    match_arm: ($) =>
+      seq(
+        choice($.declare_name, $.expr),
+        optional(seq("if", $.expr)),
+        "=>",
+        $.expr,
+      ),
     list_literal: ($) =>
       seq(
         "[",
@@ -108,7 +134,20 @@ export default grammar({
         ),
         "}",
       ),
-    record_element: ($) => seq($.identifier, "=", $.expr),
+    record_element: ($) =>
+      seq(choice($.identifier, $.computed_key), "=", $.expr),
+    computed_key: ($) => seq("[", $.expr, "]"),
+    list_comprehension: ($) => seq("[", $.expr, $.for_clause, "]"),
+    record_comprehension: ($) => seq("{", $.record_element, $.for_clause, "}"),
+    for_clause: ($) =>
+      seq(
+        "for",
+        $.identifier,
+        optional(seq(",", $.identifier)),
+        "in",
+        $.expr,
+        optional(seq("if", $.expr)),
+      ),
     fn_call: ($) => prec(9, seq($.expr, $.arg_list)),
     arg_list: ($) =>
       seq("(", optional(seq($.expr, repeat(seq(",", $.expr)))), ")"),
@@ -122,16 +161,23 @@ export default grammar({
         ),
         '"',
       ),
-    format_string_literal: () =>
+    format_string_literal: ($) =>
       seq(
         'f"',
         repeat(
-          choice(/[^"\n\\]/u, seq("\\", choice("\\", '"', "n", "r", "t", "0"))),
+          choice(
+            /[^"{}\n\\]/u,
+            "{{",
+            "}}",
+            seq("\\", choice("\\", '"', "n", "r", "t", "0")),
+            $.interpolation,
+          ),
         ),
         '"',
       ),
+    interpolation: ($) => seq("{", $.expr, "}"),
     raw_string_literal: () => /r"[^"]*"/,
-    number_literal: () => /\d+/,
+    number_literal: () => /\d+(\.\d+)?([eE][+-]?\d+)?/,
     bool_literal: () => choice("true", "false"),
 
     identifier: () => /[a-zA-Z_\P{ASCII}][a-zA-Z0-9_\P{ASCII}]*/u,
@@ -1,6 +1,7 @@
 (internal) @variable.builtin
 (raw_string_literal) @string
 (format_string_literal) @string
+(interpolation "{" @punctuation.special "}" @punctuation.special)
 (string_literal) @string
 (number_literal) @constant.numeric
 (bool_literal) @constant.builtin.boolean
@@ -11,6 +12,13 @@
 
 "if" @keyword.control.conditional
 "else" @keyword.control.conditional
+"match" @keyword.control.conditional
+
+"try" @keyword.control.exception
+"catch" @keyword.control.exception
+
+"for" @keyword.control.repeat
+"in" @keyword.control.repeat
 
 "=" @operator
 "!" @operator
@@ -27,6 +35,7 @@
 "-" @operator
 "/" @operator
 "->" @operator
+"=>" @operator
 
 "," @punctuation.delimiter
 